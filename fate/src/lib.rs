@@ -1,3 +1,20 @@
+//! Fight And Travel (Game) Engine.
+//!
+//! Each sub-crate is re-exported as a module, and these modules are the intended import paths:
+//!
+//! - `fate::math` for all math types (vectors, matrices, quaternions, `Transform`, `Rect`, `Aabb`, colors...).
+//!   It re-exports `vek`, which should not be imported directly;
+//! - `fate::gx` for OpenGL wrappers;
+//! - `fate::img` for image loading and saving;
+//! - `fate::font` for font loading and glyph atlases;
+//! - `fate::mt` for tasks and threading;
+//! - `fate::lab` for miscellaneous utilities;
+//! - `fate::main_loop` for the main loop;
+//! - `fate::dmap` for maps indexed by generational keys.
+//!
+//! The `prelude` re-exports the most common math types, and the extension traits `fate::math` adds on top of
+//! them (e.g `QuaternionFromMatrix`, `LookAt`, `Vec4Swizzle` or `IntoReprC`).
+
 #![doc(html_root_url = "https://docs.rs/fate/0.1.0")]
 #![doc(test(attr(deny(warnings))))]
 #![feature(use_extern_macros)]
@@ -11,3 +28,13 @@ pub extern crate fate_lab as lab;
 pub extern crate fate_main_loop as main_loop;
 pub extern crate fate_math as math;
 pub extern crate fate_mt as mt;
+
+pub mod prelude {
+    pub use math::{
+        Vec2, Vec3, Vec4, Extent2, Extent3,
+        Mat2, Mat3, Mat4, Quaternion, Transform,
+        Rect, Aabr, Aabb,
+        Rgb, Rgba,
+        QuaternionFromMatrix, QuaternionSlerp, QuaternionEuler, ReversedZProjection, LookAt, Mat4IntoTransform, AffineInverse, Vec2Swizzle, Vec3Swizzle, Vec4Swizzle, IntoReprC, IntoReprSimd,
+    };
+}
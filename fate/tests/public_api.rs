@@ -0,0 +1,25 @@
+// Imports every advertised path, so that accidentally removing one breaks the build.
+
+extern crate fate;
+
+#[allow(unused_imports)]
+use fate::{font, gx, dmap, img, lab, main_loop, math, mt, check_gl};
+use fate::math::{Vec2, Vec3, Vec4, Extent2, Mat4, Quaternion, Transform, Rect, Aabr, Aabb, Rgb, Rgba};
+
+#[test]
+fn prelude_types_are_the_math_types() {
+    use fate::prelude;
+
+    let v: Vec3<f32> = prelude::Vec3::zero();
+    let _: prelude::Vec2<f32> = Vec2::zero();
+    let _: prelude::Vec4<f32> = Vec4::zero();
+    let _: prelude::Extent2<u32> = Extent2::one();
+    let _: prelude::Mat4<f32> = Mat4::identity();
+    let _: prelude::Quaternion<f32> = Quaternion::identity();
+    let _: prelude::Transform<f32, f32, f32> = Transform { position: v, .. Transform::default() };
+    let _: prelude::Rect<i32, u32> = Rect::new(0, 0, 1, 1);
+    let _: prelude::Aabr<f32> = Aabr { min: Vec2::zero(), max: Vec2::one() };
+    let _: prelude::Aabb<f32> = Aabb { min: v, max: Vec3::one() };
+    let _: prelude::Rgb<u8> = Rgb::black();
+    let _: prelude::Rgba<f32> = Rgba::white();
+}
@@ -8,6 +8,8 @@ use super::Font;
 pub struct Atlas {
     pub img: ImgVec<u8>,
     pub glyphs: HashMap<char, AtlasGlyphInfo>,
+    /// Horizontal kerning for pairs of glyphs in this atlas; pairs that have none are not stored.
+    pub kerning_px: HashMap<(char, char), i16>,
     pen: Vec2<usize>,
     biggest_height_in_line: usize,
 }
@@ -30,6 +32,7 @@ impl Atlas {
         Self {
             img: ImgVec::new(vec!(0_u8; tex_side * tex_side), tex_side, tex_side),
             glyphs: HashMap::new(),
+            kerning_px: HashMap::new(),
             pen: Vec2::zero(),
             biggest_height_in_line: 0,
         }
@@ -85,6 +88,18 @@ impl Atlas {
         let old = self.glyphs.insert(c, gi);
         assert!(old.is_none());
 
+        // Only the pairs involving the new glyph are new. A pair whose kerning can't be
+        // retrieved is treated as having none.
+        if font.has_kerning() {
+            let pairs = self.glyphs.keys().flat_map(|&other| if other == c { vec![(c, c)] } else { vec![(other, c), (c, other)] });
+            for pair in pairs {
+                let k = font.kerning_px(pair.0, pair.1).map(|k| k.x).unwrap_or(0);
+                if k != 0 {
+                    self.kerning_px.insert(pair, k as _);
+                }
+            }
+        }
+
         self.pen.x += bmp_w + 1;
     }
 }
@@ -299,6 +299,20 @@ impl Font {
     pub fn max_horizontal_advance_px(&self) -> i32 {
         i32_from_26_6(self.ft_size_metrics().max_advance)
     }
+    /// How much to move the pen by, in addition to `left`'s advance, when `right` follows `left`.
+    /// This is usually negative, and zero for fonts that have no kerning.
+    pub fn kerning_px(&self, left: char, right: char) -> Result<Vec2<i32>, Error> {
+        if !self.has_kerning() {
+            return Ok(Vec2::zero());
+        }
+        let mut v = FT_Vector { x: 0, y: 0 };
+        unsafe {
+            let left = FT_Get_Char_Index(self.ft_face, left as _);
+            let right = FT_Get_Char_Index(self.ft_face, right as _);
+            ft_result(FT_Get_Kerning(self.ft_face, left, right, /*FT_Kerning_Mode::*/FT_KERNING_DEFAULT as _, &mut v))?;
+        }
+        Ok(Vec2::new(i32_from_26_6(v.x), i32_from_26_6(v.y)))
+    }
     pub fn glyph(&self, c: char) -> GlyphLoader {
         GlyphLoader {
             font: self,
@@ -1,5 +1,5 @@
-use fate::math::{Mat4, Vec3, Extent2, FrustumPlanes, Vec2, Rect};
-use xform::Xform;
+use fate::math::{Mat4, Vec3, Vec4, Extent2, FrustumPlanes, Vec2, Rect, Lerp, Ray3, CullingFrustum, ReversedZProjection};
+use xform::{Xform, Xform64};
 
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum CameraProjectionMode {
@@ -7,12 +7,57 @@ pub enum CameraProjectionMode {
     Ortho,
 }
 
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum CameraDepthMode {
+    /// Depth in [-1, 1], with `near` at -1 and `far` at 1. `far` may be infinite for perspective projections,
+    /// in which case reversed-Z is used instead (see `Camera::effective_depth_mode()`).
+    Standard,
+    /// Depth in [0, 1], with `near` at 1. Perspective projections put the far plane at infinity, so `far`
+    /// is only used for culling.
+    ReversedZ,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Camera {
     pub projection_mode: CameraProjectionMode,
     pub fov_y_radians: f32,
     pub near: f32,
     pub far: f32,
+    pub depth_mode: CameraDepthMode,
+    /// Only instances whose layer mask intersects this one are seen by this camera.
+    pub layer_mask: u32,
+}
+
+impl Camera {
+    pub const ALL_LAYERS: u32 = !0;
+
+    pub fn sees_layers(&self, layer_mask: u32) -> bool {
+        self.layer_mask & layer_mask != 0
+    }
+    /// The depth mode that is actually used: perspective projections with an infinite far plane are always
+    /// reversed-Z, since standard depth would have no precision left for distant geometry.
+    /// The GL depth state must be set up for this mode, not `depth_mode`.
+    pub fn effective_depth_mode(&self) -> CameraDepthMode {
+        match self.projection_mode {
+            CameraProjectionMode::Perspective if self.far.is_infinite() => CameraDepthMode::ReversedZ,
+            _ => self.depth_mode,
+        }
+    }
+}
+
+/// For smooth camera blends. The projection mode switches halfway through.
+impl Lerp<f32> for Camera {
+    type Output = Self;
+    fn lerp_unclamped(from: Self, to: Self, factor: f32) -> Self {
+        Self {
+            projection_mode: if factor < 0.5 { from.projection_mode } else { to.projection_mode },
+            layer_mask: if factor < 0.5 { from.layer_mask } else { to.layer_mask },
+            depth_mode: if factor < 0.5 { from.depth_mode } else { to.depth_mode },
+            fov_y_radians: f32::lerp_unclamped(from.fov_y_radians, to.fov_y_radians, factor),
+            near: f32::lerp_unclamped(from.near, to.near, factor),
+            far: f32::lerp_unclamped(from.far, to.far, factor),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -29,6 +74,19 @@ pub fn aspect_ratio(size: Extent2<u32>) -> f32 {
     w as f32 / h as f32
 }
 
+/// Maps pixel coordinates (origin at the top-left, Y going down) to NDC.
+pub fn pixel_ortho_matrix(size: Extent2<u32>) -> Mat4<f32> {
+    let Extent2 { w, h } = size.map(|x| x as f32);
+    Mat4::orthographic_lh_no(FrustumPlanes {
+        left: 0.,
+        right: w,
+        top: 0.,
+        bottom: h,
+        near: -1.,
+        far: 1.,
+    })
+}
+
 impl View {
     // !!! Must be normalized
     pub fn up_vector_for_lookat(&self) -> Vec3<f32> {
@@ -52,24 +110,76 @@ impl View {
         self.xform.view_matrix_with_up(self.up_vector_for_lookat())
     } 
     pub fn proj_matrix(&self) -> Mat4<f32> {
-        match self.camera.projection_mode {
-            CameraProjectionMode::Perspective => {
+        match (self.camera.projection_mode, self.camera.effective_depth_mode()) {
+            (CameraProjectionMode::Perspective, CameraDepthMode::Standard) => {
                 Mat4::perspective_lh_no(self.camera.fov_y_radians, self.aspect_ratio(), self.camera.near, self.camera.far)
             },
-            CameraProjectionMode::Ortho => {
+            (CameraProjectionMode::Perspective, CameraDepthMode::ReversedZ) => {
+                Mat4::perspective_lh_reversed_z_infinite(self.camera.fov_y_radians, self.aspect_ratio(), self.camera.near)
+            },
+            (CameraProjectionMode::Ortho, CameraDepthMode::Standard) => {
                 Mat4::orthographic_lh_no(self.ortho_frustum_planes())
             },
+            (CameraProjectionMode::Ortho, CameraDepthMode::ReversedZ) => {
+                let planes = self.ortho_frustum_planes();
+                Mat4::orthographic_lh_zo(FrustumPlanes { near: planes.far, far: planes.near, .. planes })
+            },
+        }
+    }
+    /// World-space planes of the view's frustum, as `(nx, ny, nz, d)` facing inwards (see `CullingFrustum`), in the
+    /// order left, right, bottom, top, near, far.
+    /// With an infinite far plane, the last one lets everything through.
+    pub fn frustum_planes(&self) -> [Vec4<f32>; 6] {
+        let viewproj = self.proj_matrix() * self.view_matrix();
+        match self.camera.effective_depth_mode() {
+            CameraDepthMode::Standard => CullingFrustum::from_mat4_no(viewproj).planes,
+            CameraDepthMode::ReversedZ => CullingFrustum::from_mat4_zo(viewproj).planes,
         }
     }
+    /// Sorts `items` such that the farthest from the eye comes first, which is the order in which
+    /// translucent geometry has to be drawn.
+    pub fn sort_back_to_front<T, F>(&self, items: &mut [T], position: F) where F: Fn(&T) -> Vec3<f32> {
+        let eye = self.xform.position;
+        items.sort_by(|a, b| {
+            let da = eye.distance_squared(position(a));
+            let db = eye.distance_squared(position(b));
+            db.partial_cmp(&da).unwrap_or(::std::cmp::Ordering::Equal)
+        });
+    }
     pub fn viewport_to_world(&self, p: Vec2<i32>, z: f32) -> Vec3<f32> {
         let y = self.viewport.h as i32 - p.y;
         let v = Vec3::new(p.x as f32 + 0.5, y as f32 + 0.5, 0.);
-        let mut w = Mat4::viewport_to_world_no(v, self.view_matrix(), self.proj_matrix(), self.viewport.map(|p| p as f32, |e| e as f32));
+        let viewport = self.viewport.map(|p| p as f32, |e| e as f32);
+        let mut w = match self.camera.effective_depth_mode() {
+            CameraDepthMode::Standard => Mat4::viewport_to_world_no(v, self.view_matrix(), self.proj_matrix(), viewport),
+            CameraDepthMode::ReversedZ => Mat4::viewport_to_world_zo(v, self.view_matrix(), self.proj_matrix(), viewport),
+        };
         w.z = z;
         w
     }
+    /// The ray that starts on the near plane at `ndc` and goes away from the eye, for picking.
+    /// The direction is normalized.
+    #[allow(dead_code)]
+    pub fn world_ray_at_ndc(&self, ndc: Vec2<f32>) -> Ray3<f32> {
+        // The second point is between near and far, because the far plane may be at infinity
+        let (near_z, mid_z) = match self.camera.effective_depth_mode() {
+            CameraDepthMode::Standard => (-1., 0.),
+            CameraDepthMode::ReversedZ => (1., 0.5),
+        };
+        let inv_viewproj = (self.proj_matrix() * self.view_matrix()).inverted();
+        let unproject = |z| {
+            let p = inv_viewproj * Vec4::new(ndc.x, ndc.y, z, 1.);
+            Vec3::from(p) / p.w
+        };
+        let origin = unproject(near_z);
+        Ray3::new(origin, (unproject(mid_z) - origin).normalized())
+    }
     pub fn world_to_viewport(&self, o: Vec3<f32>) -> (Vec2<i32>, f32) {
-        let v = Mat4::world_to_viewport_no(o, self.view_matrix(), self.proj_matrix(), self.viewport.map(|p| p as f32, |e| e as f32));
+        let viewport = self.viewport.map(|p| p as f32, |e| e as f32);
+        let v = match self.camera.effective_depth_mode() {
+            CameraDepthMode::Standard => Mat4::world_to_viewport_no(o, self.view_matrix(), self.proj_matrix(), viewport),
+            CameraDepthMode::ReversedZ => Mat4::world_to_viewport_zo(o, self.view_matrix(), self.proj_matrix(), viewport),
+        };
         let (mut z, mut v) = (v.z, Vec2::from(v.map(|x| x.round() as i32)));
         if z.abs() <= 0.0001 {
             z = 0.;
@@ -77,12 +187,21 @@ impl View {
         v.y = self.viewport.h as i32 - v.y;
         (v, z)
     }
-    pub fn viewport_to_ugly_ndc(&self, mut p: Vec2<i32>) -> Vec3<f32> {
-        let vp_size = self.viewport.extent().map(|x| x as f32);
-        p.y = self.viewport.h as i32 - p.y;
-        let t = p.map(|x| x as f32) / vp_size;
-        let t = (t - 0.5) * 2.;
-        t.into()
+    /// Model-view matrix for camera-relative rendering: `eye` is the camera's double-precision
+    /// position (`self.xform.position` is ignored), and translations are resolved in f64 before
+    /// anything is converted to f32.
+    pub fn relative_model_view_matrix(&self, eye: Vec3<f64>, object: &Xform64) -> Mat4<f32> {
+        let view = View { xform: Xform { position: Vec3::zero(), .. self.xform }, .. *self };
+        view.view_matrix() * object.relative_to(eye).model_matrix()
+    }
+    pub fn pixel_ortho_matrix(&self) -> Mat4<f32> {
+        pixel_ortho_matrix(self.viewport.extent())
+    }
+    pub fn viewport_to_ugly_ndc(&self, p: Vec2<i32>) -> Vec3<f32> {
+        let p = p.map(|x| x as f32);
+        let mut t = Vec3::from(self.pixel_ortho_matrix() * Vec4::new(p.x, p.y, 0., 1.));
+        t.z = 0.;
+        t
     }
     pub fn viewport_to_pretty_ndc(&self, p: Vec2<i32>) -> Vec3<f32> {
         let FrustumPlanes { left, right, top, bottom, .. } = self.ortho_frustum_planes();
@@ -91,3 +210,117 @@ impl View {
         self.viewport_to_ugly_ndc(p) * Vec3::new(right, top, 0.)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn pixel_rect_to_ndc_corners() {
+        let m = pixel_ortho_matrix(Extent2::new(800, 600));
+        let rect = Rect::<f32, f32>::new(200., 150., 400., 300.);
+        let corners = [
+            (Vec2::new(rect.x, rect.y), Vec2::new(-0.5, 0.5)),
+            (Vec2::new(rect.x + rect.w, rect.y), Vec2::new(0.5, 0.5)),
+            (Vec2::new(rect.x, rect.y + rect.h), Vec2::new(-0.5, -0.5)),
+            (Vec2::new(rect.x + rect.w, rect.y + rect.h), Vec2::new(0.5, -0.5)),
+        ];
+        for &(px, expected) in corners.iter() {
+            let ndc = m * Vec4::new(px.x, px.y, 0., 1.);
+            assert_relative_eq!(ndc.x, expected.x);
+            assert_relative_eq!(ndc.y, expected.y);
+            assert_relative_eq!(ndc.w, 1.);
+        }
+        let origin = m * Vec4::new(0., 0., 0., 1.);
+        assert_relative_eq!(origin.x, -1.);
+        assert_relative_eq!(origin.y, 1.);
+    }
+    #[test]
+    fn relative_model_view_is_stable_far_from_origin() {
+        let view = View {
+            xform: Xform::default(),
+            camera: Camera { projection_mode: CameraProjectionMode::Perspective, fov_y_radians: 1., near: 0.1, far: 10000., depth_mode: CameraDepthMode::Standard, layer_mask: Camera::ALL_LAYERS, },
+            viewport: Rect::new(0, 0, 800, 600),
+        };
+        let offset = Vec3::new(1.5_f64, -0.25, 3.);
+        let near_origin = view.relative_model_view_matrix(Vec3::zero(), &Xform64 { position: offset, .. Xform64::default() });
+
+        let far_eye = Vec3::new(123456789.125_f64, 5000000.5, -98765432.75);
+        for i in 0..4 {
+            let eye = far_eye + Vec3::broadcast(i as f64 * 0.001);
+            let object = Xform64 { position: eye + offset, .. Xform64::default() };
+            let far = view.relative_model_view_matrix(eye, &object);
+            for (a, b) in far.into_col_array().iter().zip(near_origin.into_col_array().iter()) {
+                assert_relative_eq!(*a, *b, epsilon = 0.00001);
+            }
+        }
+    }
+    #[test]
+    fn world_rays_start_on_the_near_plane() {
+        for &(depth_mode, far) in &[(CameraDepthMode::Standard, 100.), (CameraDepthMode::Standard, ::std::f32::INFINITY), (CameraDepthMode::ReversedZ, 100.)] {
+            let view = View {
+                xform: Xform::default(),
+                camera: Camera { projection_mode: CameraProjectionMode::Perspective, fov_y_radians: 1., near: 0.1, far, depth_mode, layer_mask: Camera::ALL_LAYERS, },
+                viewport: Rect::new(0, 0, 800, 600),
+            };
+            let ray = view.world_ray_at_ndc(Vec2::zero());
+            assert_relative_eq!(ray.origin.z, 0.1, epsilon = 0.0001);
+            assert_relative_eq!(ray.direction.z, 1., epsilon = 0.0001);
+            // Off-center rays diverge from the eye
+            let ray = view.world_ray_at_ndc(Vec2::new(1., 0.));
+            assert_relative_eq!(ray.origin.x / ray.origin.z, ray.direction.x / ray.direction.z, epsilon = 0.0001);
+            assert_relative_eq!(ray.direction.x / ray.direction.z, view.aspect_ratio() * (0.5_f32).tan(), epsilon = 0.0001);
+        }
+    }
+    #[test]
+    fn infinite_far_plane_uses_reversed_z() {
+        let view = View {
+            xform: Xform::default(),
+            camera: Camera { projection_mode: CameraProjectionMode::Perspective, fov_y_radians: 1., near: 0.1, far: ::std::f32::INFINITY, depth_mode: CameraDepthMode::Standard, layer_mask: Camera::ALL_LAYERS, },
+            viewport: Rect::new(0, 0, 800, 600),
+        };
+        assert_eq!(view.camera.effective_depth_mode(), CameraDepthMode::ReversedZ);
+        let proj = view.proj_matrix();
+        assert!(proj.into_col_array().iter().all(|x| x.is_finite()));
+        let depth = |z| {
+            let clip = proj * Vec4::new(0., 0., z, 1.);
+            clip.z / clip.w
+        };
+        assert_relative_eq!(depth(0.1), 1., epsilon = 0.0001);
+        assert!(depth(1e6) > 0. && depth(1e6) < 0.01);
+
+        let finite = Camera { far: 100., .. view.camera };
+        assert_eq!(finite.effective_depth_mode(), CameraDepthMode::Standard);
+        let ortho = Camera { projection_mode: CameraProjectionMode::Ortho, .. view.camera };
+        assert_eq!(ortho.effective_depth_mode(), CameraDepthMode::Standard);
+    }
+    #[test]
+    fn camera_blend_lerps_planes() {
+        let from = Camera { projection_mode: CameraProjectionMode::Perspective, fov_y_radians: 1., near: 0.1, far: 100., depth_mode: CameraDepthMode::Standard, layer_mask: 1, };
+        let to = Camera { projection_mode: CameraProjectionMode::Ortho, fov_y_radians: 2., near: 0.3, far: 300., depth_mode: CameraDepthMode::ReversedZ, layer_mask: 2, };
+        let mid = Camera::lerp(from, to, 0.25);
+        assert_eq!(mid.projection_mode, CameraProjectionMode::Perspective);
+        assert_relative_eq!(mid.fov_y_radians, 1.25);
+        assert_relative_eq!(mid.far, 150.);
+        assert_eq!(Camera::lerp(from, to, 0.75).projection_mode, CameraProjectionMode::Ortho);
+        assert_eq!(Camera::lerp(from, to, 0.75).depth_mode, CameraDepthMode::ReversedZ);
+    }
+    #[test]
+    fn reversed_z_maps_near_to_one_and_far_towards_zero() {
+        let view = View {
+            xform: Xform::default(),
+            camera: Camera { projection_mode: CameraProjectionMode::Perspective, fov_y_radians: 1., near: 0.1, far: 100., depth_mode: CameraDepthMode::ReversedZ, layer_mask: Camera::ALL_LAYERS, },
+            viewport: Rect::new(0, 0, 800, 600),
+        };
+        fn depth(view: &View, z: f32) -> f32 {
+            let clip = view.proj_matrix() * Vec4::new(0., 0., z, 1.);
+            clip.z / clip.w
+        }
+        assert_relative_eq!(depth(&view, 0.1), 1.);
+        assert!(depth(&view, 1e7) < 1e-7);
+        assert_eq!((view.proj_matrix() * Vec4::new(0., 0., 1., 0.)).z, 0.);
+
+        let view = View { camera: Camera { projection_mode: CameraProjectionMode::Ortho, .. view.camera }, .. view };
+        assert_relative_eq!(depth(&view, 0.1), 1.);
+        assert_relative_eq!(depth(&view, 100.), 0.);
+    }
+}
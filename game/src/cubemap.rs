@@ -1,4 +1,5 @@
-use fate::math::{Extent2};
+use fate::math::{Extent2, Vec3};
+use fate::gx::gl::{self, types::GLenum};
 use gpu::GpuTextureInternalFormat;
 
 #[derive(Debug, Default, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -29,6 +30,53 @@ pub enum CubemapFace {
 }
 
 impl CubemapFace {
+    pub fn all() -> [Self; 6] {
+        [
+            CubemapFace::PositiveX,
+            CubemapFace::NegativeX,
+            CubemapFace::PositiveY,
+            CubemapFace::NegativeY,
+            CubemapFace::PositiveZ,
+            CubemapFace::NegativeZ,
+        ]
+    }
+    pub fn to_gl_enum(&self) -> GLenum {
+        gl::TEXTURE_CUBE_MAP_POSITIVE_X + *self as GLenum
+    }
+    /// The face's outward normal.
+    pub fn direction(&self) -> Vec3<f32> {
+        match *self {
+            CubemapFace::PositiveX => Vec3::unit_x(),
+            CubemapFace::NegativeX => -Vec3::unit_x(),
+            CubemapFace::PositiveY => Vec3::unit_y(),
+            CubemapFace::NegativeY => -Vec3::unit_y(),
+            CubemapFace::PositiveZ => Vec3::unit_z(),
+            CubemapFace::NegativeZ => -Vec3::unit_z(),
+        }
+    }
+    /// The direction in which the `s` texture coordinate increases on the face, as per the GL convention.
+    pub fn right(&self) -> Vec3<f32> {
+        match *self {
+            CubemapFace::PositiveX => -Vec3::unit_z(),
+            CubemapFace::NegativeX => Vec3::unit_z(),
+            CubemapFace::PositiveY => Vec3::unit_x(),
+            CubemapFace::NegativeY => Vec3::unit_x(),
+            CubemapFace::PositiveZ => Vec3::unit_x(),
+            CubemapFace::NegativeZ => -Vec3::unit_x(),
+        }
+    }
+    /// The direction in which the `t` texture coordinate decreases on the face, as per the GL convention.
+    /// In other words, this points towards the first row of the face's image.
+    pub fn up(&self) -> Vec3<f32> {
+        match *self {
+            CubemapFace::PositiveX => Vec3::unit_y(),
+            CubemapFace::NegativeX => Vec3::unit_y(),
+            CubemapFace::PositiveY => -Vec3::unit_z(),
+            CubemapFace::NegativeY => Vec3::unit_z(),
+            CubemapFace::PositiveZ => Vec3::unit_y(),
+            CubemapFace::NegativeZ => Vec3::unit_y(),
+        }
+    }
     pub const TERRAGEN_SUFFIXES: [&'static str; 6] = [ "ft", "bk", "up", "dn", "rt", "lf" ];
     pub fn try_from_terragen_suffix(suffix: &str) -> Option<Self> {
         Some(match suffix {
@@ -66,4 +114,42 @@ impl CubemapArrayInfo {
         let bits = self.nb_cubemaps as usize * 6 * size.product() * self.internal_format.pixel_bits().expect("This internal format has no defined pixel size");
         (bits + 7) / 8
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Face selection and texture coordinates for a direction, straight from table 8.19 of the GL 4.5 spec.
+    fn gl_face_and_st(d: Vec3<f32>) -> (GLenum, f32, f32) {
+        let a = d.map(f32::abs);
+        let (face, sc, tc, ma) = if a.x >= a.y && a.x >= a.z {
+            if d.x > 0. { (gl::TEXTURE_CUBE_MAP_POSITIVE_X, -d.z, -d.y, a.x) } else { (gl::TEXTURE_CUBE_MAP_NEGATIVE_X, d.z, -d.y, a.x) }
+        } else if a.y >= a.z {
+            if d.y > 0. { (gl::TEXTURE_CUBE_MAP_POSITIVE_Y, d.x, d.z, a.y) } else { (gl::TEXTURE_CUBE_MAP_NEGATIVE_Y, d.x, -d.z, a.y) }
+        } else {
+            if d.z > 0. { (gl::TEXTURE_CUBE_MAP_POSITIVE_Z, d.x, -d.y, a.z) } else { (gl::TEXTURE_CUBE_MAP_NEGATIVE_Z, -d.x, -d.y, a.z) }
+        };
+        (face, (sc / ma + 1.) / 2., (tc / ma + 1.) / 2.)
+    }
+
+    #[test]
+    fn faces_match_gl_cubemap_convention() {
+        for face in CubemapFace::all().iter() {
+            let d = face.direction();
+            assert_eq!(gl_face_and_st(d), (face.to_gl_enum(), 0.5, 0.5));
+            assert_eq!(gl_face_and_st(d + face.right() * 0.5), (face.to_gl_enum(), 0.75, 0.5));
+            assert_eq!(gl_face_and_st(d + face.up() * 0.5), (face.to_gl_enum(), 0.5, 0.25));
+            assert_eq!(face.right().cross(face.up()).dot(d).abs(), 1.);
+        }
+    }
+    #[test]
+    fn all_faces_are_in_gl_order() {
+        let faces = CubemapFace::all();
+        for (i, face) in faces.iter().enumerate() {
+            assert_eq!(*face as usize, i);
+            assert_eq!(face.to_gl_enum(), gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as GLenum);
+        }
+        assert_eq!(faces[5].to_gl_enum(), gl::TEXTURE_CUBE_MAP_NEGATIVE_Z);
+    }
+}
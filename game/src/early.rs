@@ -44,9 +44,21 @@ pub fn setup_panic_hook() {
     }));
 }
 
-pub fn setup_env() {
+/// Runs before the logger is set up, so that it sees the environment; problems are returned to be logged afterwards.
+pub fn setup_env() -> Result<(), String> {
     //env::set_var("RUST_LOG", "info");
     env::set_var("RUST_BACKTRACE", "full");
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--data-path" {
+            match args.next() {
+                Some(path) => env::set_var(::resources::DATA_PATH_ENV_VAR, path),
+                None => return Err("`--data-path` expects a directory".to_owned()),
+            }
+        }
+    }
+    Ok(())
 }
 
 pub fn setup_log() {
@@ -1,3 +1,5 @@
+use std::ops::{BitOr, BitAnd, Not};
+use std::collections::VecDeque;
 use system::*;
 use dmc::device::{MouseButton, Key, ButtonState};
 
@@ -17,11 +19,31 @@ pub enum Event {
     KeyboardFocusGained,
     KeyboardFocusLost,
     CanvasResized(u32, u32),
+    WindowMinimized,
+    WindowMaximized,
+    /// Back from being minimized or maximized.
+    WindowRestored,
+    /// The ratio of physical to logical pixels changed, e.g the window moved to a HiDPI display.
+    /// The logical canvas size stays the same, but there are now more (or less) pixels to draw to.
+    WindowScaleFactorChanged { new_scale: f32 },
     KeyboardKeyPressed(Key),
     KeyboardKeyReleased(Key),
     KeyboardTextChar(char),
     KeyboardKeyPressedRaw(Key),
     KeyboardKeyReleasedRaw(Key),
+    /// `id` identifies a finger for the whole began/moved/ended sequence; `position` is in pixels, like `MouseMotion`.
+    TouchBegan { id: u64, position: Vec2<f32>, pressure: f32 },
+    TouchMoved { id: u64, position: Vec2<f32>, pressure: f32 },
+    TouchEnded { id: u64, position: Vec2<f32>, pressure: f32 },
+    /// The system took the touch away (e.g palm rejection); it won't be ended, and gestures involving it should be reset.
+    TouchCancelled { id: u64 },
+    /// All files of a single drop, at once; `position` is in pixels, like `MouseMotion`.
+    FilesDropped { paths: Vec<PathBuf>, position: Vec2<u32> },
+    /// The IME's text being composed, for previewing it inline; `cursor` is in `char`s. An empty `text` ends the preview.
+    TextInputCompositionUpdated { text: String, cursor: usize },
+    /// Text the user finished typing through an IME, possibly several `char`s at once. It is not also sent as `KeyboardTextChar`s.
+    /// Platforms that can't tell composed text apart from typed text (e.g SDL2) send all text input this way.
+    TextInputCompositionCommitted { text: String },
 }
 
 impl Event {
@@ -41,12 +63,178 @@ impl Event {
             Event::MouseButtonPressedRaw(btn) => sys.on_mouse_button_raw(g, btn, ButtonState::Down),
             Event::MouseButtonReleasedRaw(btn) => sys.on_mouse_button_raw(g, btn, ButtonState::Up),
             Event::CanvasResized(w, h) => sys.on_canvas_resized(g, Extent2 { w, h }),
+            Event::WindowMinimized => sys.on_window_minimized(g),
+            Event::WindowMaximized => sys.on_window_maximized(g),
+            Event::WindowRestored => sys.on_window_restored(g),
+            Event::WindowScaleFactorChanged { new_scale } => sys.on_window_scale_factor_changed(g, new_scale),
             Event::KeyboardKeyPressed(key) => sys.on_key(g, key, ButtonState::Down),
             Event::KeyboardKeyReleased(key) => sys.on_key(g, key, ButtonState::Up),
             Event::KeyboardTextChar(char) => sys.on_text_char(g, char),
             Event::KeyboardKeyPressedRaw(key) => sys.on_key_raw(g, key, ButtonState::Down),
             Event::KeyboardKeyReleasedRaw(key) => sys.on_key_raw(g, key, ButtonState::Up),
+            Event::TouchBegan { id, position, pressure } => sys.on_touch_began(g, id, position, pressure),
+            Event::TouchMoved { id, position, pressure } => sys.on_touch_moved(g, id, position, pressure),
+            Event::TouchEnded { id, position, pressure } => sys.on_touch_ended(g, id, position, pressure),
+            Event::TouchCancelled { id } => sys.on_touch_cancelled(g, id),
+            Event::FilesDropped { ref paths, position } => sys.on_files_dropped(g, paths, position),
+            Event::TextInputCompositionUpdated { ref text, cursor } => sys.on_text_composition_updated(g, text, cursor),
+            Event::TextInputCompositionCommitted { ref text } => sys.on_text_composition_committed(g, text),
         }
     }
 }
 
+
+/// A set of event types, with one bit per `Event` variant, e.g `EventTypeMask::MOUSE_MOTION | EventTypeMask::MOUSE_MOTION_RAW`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EventTypeMask(u64);
+
+macro_rules! event_type_masks {
+    ($($NAME:ident = $bit:expr => $pat:pat,)+) => {
+        impl EventTypeMask {
+            $(pub const $NAME: Self = EventTypeMask(1 << $bit);)+
+        }
+        impl Event {
+            pub fn type_mask(&self) -> EventTypeMask {
+                match *self {
+                    $($pat => EventTypeMask::$NAME,)+
+                }
+            }
+        }
+    };
+}
+
+event_type_masks!{
+    QUIT = 0 => Event::Quit,
+    MOUSE_MOTION = 1 => Event::MouseMotion(..),
+    MOUSE_SCROLL = 2 => Event::MouseScroll(..),
+    MOUSE_BUTTON_PRESSED = 3 => Event::MouseButtonPressed(_),
+    MOUSE_BUTTON_RELEASED = 4 => Event::MouseButtonReleased(_),
+    MOUSE_MOTION_RAW = 5 => Event::MouseMotionRaw(..),
+    MOUSE_SCROLL_RAW = 6 => Event::MouseScrollRaw(..),
+    MOUSE_BUTTON_PRESSED_RAW = 7 => Event::MouseButtonPressedRaw(_),
+    MOUSE_BUTTON_RELEASED_RAW = 8 => Event::MouseButtonReleasedRaw(_),
+    MOUSE_ENTER = 9 => Event::MouseEnter,
+    MOUSE_LEAVE = 10 => Event::MouseLeave,
+    KEYBOARD_FOCUS_GAINED = 11 => Event::KeyboardFocusGained,
+    KEYBOARD_FOCUS_LOST = 12 => Event::KeyboardFocusLost,
+    CANVAS_RESIZED = 13 => Event::CanvasResized(..),
+    WINDOW_MINIMIZED = 14 => Event::WindowMinimized,
+    WINDOW_MAXIMIZED = 15 => Event::WindowMaximized,
+    WINDOW_RESTORED = 16 => Event::WindowRestored,
+    KEYBOARD_KEY_PRESSED = 17 => Event::KeyboardKeyPressed(_),
+    KEYBOARD_KEY_RELEASED = 18 => Event::KeyboardKeyReleased(_),
+    KEYBOARD_TEXT_CHAR = 19 => Event::KeyboardTextChar(_),
+    KEYBOARD_KEY_PRESSED_RAW = 20 => Event::KeyboardKeyPressedRaw(_),
+    KEYBOARD_KEY_RELEASED_RAW = 21 => Event::KeyboardKeyReleasedRaw(_),
+    TOUCH_BEGAN = 22 => Event::TouchBegan { .. },
+    TOUCH_MOVED = 23 => Event::TouchMoved { .. },
+    TOUCH_ENDED = 24 => Event::TouchEnded { .. },
+    TOUCH_CANCELLED = 25 => Event::TouchCancelled { .. },
+    FILES_DROPPED = 26 => Event::FilesDropped { .. },
+    TEXT_INPUT_COMPOSITION_UPDATED = 27 => Event::TextInputCompositionUpdated { .. },
+    TEXT_INPUT_COMPOSITION_COMMITTED = 28 => Event::TextInputCompositionCommitted { .. },
+    WINDOW_SCALE_FACTOR_CHANGED = 29 => Event::WindowScaleFactorChanged { .. },
+}
+
+impl EventTypeMask {
+    pub const NONE: Self = EventTypeMask(0);
+    pub const ALL: Self = EventTypeMask(!0);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for EventTypeMask {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self { EventTypeMask(self.0 | other.0) }
+}
+impl BitAnd for EventTypeMask {
+    type Output = Self;
+    fn bitand(self, other: Self) -> Self { EventTypeMask(self.0 & other.0) }
+}
+impl Not for EventTypeMask {
+    type Output = Self;
+    fn not(self) -> Self { EventTypeMask(!self.0) }
+}
+
+
+/// Events waiting to be polled, in order. Like SDL2's `SDL_EventState`, filtered event types are still queued:
+/// `poll()` skips them until the filter allows them again, but `drain_all()` returns them regardless.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventQueue {
+    events: VecDeque<Event>,
+    filter: EventTypeMask,
+}
+
+impl Default for EventQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventQueue {
+    pub fn new() -> Self {
+        Self {
+            events: VecDeque::with_capacity(8),
+            filter: EventTypeMask::ALL,
+        }
+    }
+    pub fn filter(&self) -> EventTypeMask {
+        self.filter
+    }
+    pub fn set_filter(&mut self, allowed: EventTypeMask) {
+        self.filter = allowed;
+    }
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+    pub fn push_back(&mut self, ev: Event) {
+        self.events.push_back(ev);
+    }
+    /// The oldest event whose type is allowed by the filter, if any.
+    pub fn poll(&mut self) -> Option<Event> {
+        let filter = self.filter;
+        let i = self.events.iter().position(|ev| filter.contains(ev.type_mask()))?;
+        self.events.remove(i)
+    }
+    /// All events, in order, including the filtered ones.
+    pub fn drain_all(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filtered_events_stay_queued() {
+        let mut queue = EventQueue::new();
+        queue.set_filter(!(EventTypeMask::MOUSE_MOTION | EventTypeMask::MOUSE_MOTION_RAW));
+        queue.push_back(Event::MouseMotion(1., 2.));
+        queue.push_back(Event::MouseButtonPressed(MouseButton::Left));
+        queue.push_back(Event::MouseMotionRaw(1., 2.));
+        queue.push_back(Event::Quit);
+
+        assert_eq!(queue.poll(), Some(Event::MouseButtonPressed(MouseButton::Left)));
+        assert_eq!(queue.poll(), Some(Event::Quit));
+        assert_eq!(queue.poll(), None);
+        assert!(!queue.is_empty());
+
+        queue.set_filter(EventTypeMask::ALL);
+        assert_eq!(queue.poll(), Some(Event::MouseMotion(1., 2.)));
+        assert_eq!(queue.poll(), Some(Event::MouseMotionRaw(1., 2.)));
+        assert!(queue.is_empty());
+    }
+    #[test]
+    fn drain_all_ignores_the_filter() {
+        let mut queue = EventQueue::new();
+        queue.set_filter(EventTypeMask::NONE);
+        queue.push_back(Event::MouseMotion(1., 2.));
+        queue.push_back(Event::KeyboardFocusLost);
+        assert_eq!(queue.poll(), None);
+        assert_eq!(queue.drain_all(), vec![Event::MouseMotion(1., 2.), Event::KeyboardFocusLost]);
+        assert!(queue.is_empty());
+    }
+}
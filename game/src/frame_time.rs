@@ -7,6 +7,8 @@ pub struct FrameTimeManager {
     current_frame_start: Instant,
     max_len: usize,
     average_frame_time: Duration,
+    max_dt: Duration,
+    is_dt_clamped: bool,
 }
 
 impl FrameTimeManager {
@@ -17,15 +19,29 @@ impl FrameTimeManager {
             current_frame_start: Instant::now(), // Should actually be in begin_main_loop_iteration()
             max_len,
             average_frame_time: Duration::default(),
+            max_dt: Duration::from_millis(250),
+            is_dt_clamped: false,
         }
     }
+    /// Frame times greater than this are clamped, so that a single hitch doesn't make animations jump.
+    pub fn max_dt(&self) -> Duration {
+        self.max_dt
+    }
+    pub fn set_max_dt(&mut self, max_dt: Duration) {
+        self.max_dt = max_dt;
+    }
     pub fn begin_main_loop_iteration(&mut self) {
         self.current_frame_start = Instant::now();
     }
     pub fn end_main_loop_iteration  (&mut self) {
         let current_frame_end = Instant::now();
-        self.previous_frame_times.push_back(current_frame_end - self.current_frame_start);
+        let frame_time = current_frame_end - self.current_frame_start;
         self.current_frame_start = current_frame_end;
+        self.push_frame_time(frame_time);
+    }
+    pub fn push_frame_time(&mut self, frame_time: Duration) {
+        self.is_dt_clamped = frame_time > self.max_dt;
+        self.previous_frame_times.push_back(if self.is_dt_clamped { self.max_dt } else { frame_time });
 
         while self.previous_frame_times.len() > self.max_len {
             self.previous_frame_times.pop_front();
@@ -45,5 +61,35 @@ impl FrameTimeManager {
     pub fn smooth_dt(&self) -> Duration {
         self.average_frame_time
     }
+    /// Was the last frame time greater than `max_dt()` ?
+    pub fn is_dt_clamped(&self) -> bool {
+        self.is_dt_clamped
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_spike_is_clamped() {
+        let mut m = FrameTimeManager::with_max_len(4);
+        m.set_max_dt(Duration::from_millis(100));
+        for _ in 0..3 {
+            m.push_frame_time(Duration::from_millis(16));
+        }
+        assert!(!m.is_dt_clamped());
+        assert_eq!(m.dt(), Duration::from_millis(16));
+
+        m.push_frame_time(Duration::from_secs(3));
+        assert!(m.is_dt_clamped());
+        assert_eq!(m.dt(), Duration::from_millis(100));
+        assert_eq!(m.smooth_dt(), Duration::from_millis((16 * 3 + 100) / 4));
+
+        m.push_frame_time(Duration::from_millis(16));
+        assert!(!m.is_dt_clamped());
+        assert_eq!(m.dt(), Duration::from_millis(16));
+    }
 }
 
@@ -0,0 +1,189 @@
+use fate::math::{Mat4, Vec3, Vec4, Aabb, FrustumPlanes, Lerp, AabbSetOps, CullingFrustum, Containment};
+use camera::{View, Camera, CameraDepthMode};
+
+/// A view frustum, as its eight corners in world space.
+///
+/// Corners 0..4 are on the near plane, corners 4..8 on the far plane, in the same order
+/// (bottom-left, bottom-right, top-left, top-right).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frustum {
+    pub corners: [Vec3<f32>; 8],
+}
+
+impl Frustum {
+    /// `inv_viewproj` maps NDC (with Z in [-1, 1], as the `_no` projections produce) to world space.
+    pub fn from_inverse_viewproj(inv_viewproj: Mat4<f32>) -> Self {
+        let mut corners = [Vec3::zero(); 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let x = if i & 1 == 0 { -1. } else { 1. };
+            let y = if i & 2 == 0 { -1. } else { 1. };
+            let z = if i & 4 == 0 { -1. } else { 1. };
+            let p = inv_viewproj * Vec4::new(x, y, z, 1.);
+            *corner = Vec3::from(p) / p.w;
+        }
+        Self { corners }
+    }
+    /// With reversed-Z, the far plane is at infinity, so the corners are computed at `far` instead.
+    /// Returns `None` if `far` itself is infinite, since the far corners would then be at infinity too.
+    pub fn from_view(view: &View) -> Option<Self> {
+        if !view.camera.far.is_finite() {
+            return None;
+        }
+        let view = View { camera: Camera { depth_mode: CameraDepthMode::Standard, .. view.camera }, .. *view };
+        Some(Self::from_inverse_viewproj((view.proj_matrix() * view.view_matrix()).inverted()))
+    }
+    pub fn center(&self) -> Vec3<f32> {
+        self.corners.iter().fold(Vec3::zero(), |sum, c| sum + *c) / 8.
+    }
+    /// The part of the frustum between `start` and `end`, which are fractions of the distance between
+    /// the near and far planes.
+    pub fn slice(&self, start: f32, end: f32) -> Self {
+        let mut corners = [Vec3::zero(); 8];
+        for i in 0..4 {
+            let (near, far) = (self.corners[i], self.corners[i + 4]);
+            corners[i] = Vec3::lerp_unclamped(near, far, start);
+            corners[i + 4] = Vec3::lerp_unclamped(near, far, end);
+        }
+        Self { corners }
+    }
+    /// Splits the frustum into `nb_cascades` slices by depth.
+    ///
+    /// `lambda` blends between a uniform split (0) and a logarithmic split (1), the latter giving
+    /// more resolution close to the eye.
+    pub fn split_cascades(&self, near: f32, far: f32, nb_cascades: usize, lambda: f32) -> Vec<Self> {
+        cascade_split_fractions(near, far, nb_cascades, lambda).windows(2).map(|w| self.slice(w[0], w[1])).collect()
+    }
+    /// Returns a light view-projection matrix whose orthographic box tightly encloses this frustum,
+    /// as seen from a directional light shining along `light_dir`.
+    pub fn fit_ortho_to_light(&self, light_dir: Vec3<f32>) -> Mat4<f32> {
+        let light_dir = light_dir.normalized();
+        let center = self.center();
+        let up = if light_dir.cross(Vec3::up()).magnitude_squared() < 0.0001 { Vec3::unit_z() } else { Vec3::up() };
+        let light_view = Mat4::look_at(center, center + light_dir, up);
+
+        let mut min = Vec3::broadcast(::std::f32::INFINITY);
+        let mut max = Vec3::broadcast(::std::f32::NEG_INFINITY);
+        for c in self.corners.iter() {
+            let p = Vec3::from(light_view * Vec4::from_point(*c));
+            min = Vec3::partial_min(min, p);
+            max = Vec3::partial_max(max, p);
+        }
+        let proj = Mat4::orthographic_lh_no(FrustumPlanes {
+            left: min.x,
+            right: max.x,
+            bottom: min.y,
+            top: max.y,
+            near: min.z,
+            far: max.z,
+        });
+        proj * light_view
+    }
+}
+
+/// The half-space test: `aabb` is outside if it's entirely behind one of the `planes`, such as the ones
+/// from `View::frustum_planes()`.
+///
+/// Boxes near the frustum's edges may be kept while being outside, which is fine for culling.
+pub fn aabb_outside_frustum(aabb: &Aabb<f32>, planes: &[Vec4<f32>; 6]) -> bool {
+    CullingFrustum { planes: *planes }.intersects_aabb(aabb) == Containment::Outside
+}
+
+/// The box that bounds `aabb` once transformed by `m`, e.g to put a mesh's bounds in world space.
+pub fn transformed_aabb(aabb: &Aabb<f32>, m: Mat4<f32>) -> Aabb<f32> {
+    let corners = (0..8).map(|i| {
+        let x = if i & 1 == 0 { aabb.min.x } else { aabb.max.x };
+        let y = if i & 2 == 0 { aabb.min.y } else { aabb.max.y };
+        let z = if i & 4 == 0 { aabb.min.z } else { aabb.max.z };
+        Vec3::from(m * Vec4::new(x, y, z, 1.))
+    });
+    Aabb::from_points(corners).unwrap()
+}
+
+/// Fractions of `[near, far]` at which cascades start and end; there are `nb_cascades + 1` of them,
+/// starting at 0 and ending at 1.
+pub fn cascade_split_fractions(near: f32, far: f32, nb_cascades: usize, lambda: f32) -> Vec<f32> {
+    assert!(nb_cascades > 0);
+    assert!(0. < near && near < far);
+    (0 ..= nb_cascades).map(|i| {
+        let t = i as f32 / nb_cascades as f32;
+        let log = near * (far / near).powf(t);
+        let uniform = near + (far - near) * t;
+        let d = f32::lerp_unclamped(uniform, log, lambda);
+        (d - near) / (far - near)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fate::math::Rect;
+    use camera::CameraProjectionMode;
+    use xform::Xform;
+
+    fn test_view() -> View {
+        View {
+            xform: Xform { position: Vec3::new(1., 2., -5.), .. Xform::default() },
+            camera: Camera {
+                projection_mode: CameraProjectionMode::Perspective,
+                fov_y_radians: 60_f32.to_radians(),
+                near: 0.1,
+                far: 50.,
+                depth_mode: CameraDepthMode::Standard,
+                layer_mask: Camera::ALL_LAYERS,
+            },
+            viewport: Rect::new(0, 0, 800, 600),
+        }
+    }
+
+    #[test]
+    fn fitted_ortho_box_contains_frustum_corners() {
+        let frustum = Frustum::from_view(&test_view()).unwrap();
+        for light_dir in [Vec3::new(1., -1., 0.5), Vec3::new(0., -1., 0.), Vec3::new(-0.3, -0.2, 1.)].iter() {
+            let m = frustum.fit_ortho_to_light(*light_dir);
+            for c in frustum.corners.iter() {
+                let p = m * Vec4::from_point(*c);
+                for x in [p.x, p.y, p.z].iter() {
+                    assert!(x.abs() <= 1.0001, "{:?} is outside the light's box", p);
+                }
+            }
+        }
+    }
+    #[test]
+    fn cascades_cover_the_whole_depth_range() {
+        let f = cascade_split_fractions(0.1, 50., 4, 0.5);
+        assert_eq!(f.len(), 5);
+        assert_relative_eq!(f[0], 0.);
+        assert_relative_eq!(f[4], 1., epsilon = 0.0001);
+        assert!(f.windows(2).all(|w| w[0] < w[1]));
+
+        let frustum = Frustum::from_view(&test_view()).unwrap();
+        let cascades = frustum.split_cascades(0.1, 50., 4, 0.5);
+        assert_eq!(cascades.len(), 4);
+        assert_relative_eq!(cascades[0].corners[0], frustum.corners[0], epsilon = 0.0001);
+        assert_relative_eq!(cascades[3].corners[7], frustum.corners[7], epsilon = 0.001);
+    }
+    #[test]
+    fn infinite_far_plane_has_no_corners() {
+        let view = test_view();
+        let view = View { camera: Camera { far: ::std::f32::INFINITY, .. view.camera }, .. view };
+        assert_eq!(Frustum::from_view(&view), None);
+    }
+    #[test]
+    fn boxes_outside_the_view_are_culled() {
+        let planes = test_view().frustum_planes();
+        let unit_box_at = |x, y, z| Aabb { min: Vec3::new(x - 0.5, y - 0.5, z - 0.5), max: Vec3::new(x + 0.5, y + 0.5, z + 0.5) };
+        assert!(!aabb_outside_frustum(&unit_box_at(1., 2., 5.), &planes));
+        assert!(!aabb_outside_frustum(&unit_box_at(1., 2., 44.8), &planes)); // Straddles the far plane
+        assert!(aabb_outside_frustum(&unit_box_at(1., 2., -10.), &planes)); // Behind the eye
+        assert!(aabb_outside_frustum(&unit_box_at(1., 2., 60.), &planes));
+        assert!(aabb_outside_frustum(&unit_box_at(100., 2., 5.), &planes));
+    }
+    #[test]
+    fn transformed_aabb_bounds_all_corners() {
+        let aabb = Aabb { min: Vec3::new(0., 0., 0.), max: Vec3::new(1., 2., 3.) };
+        let m = Mat4::translation_3d(Vec3::new(10., 0., 0.)) * Mat4::rotation_z(::std::f32::consts::PI / 2.);
+        let t = transformed_aabb(&aabb, m);
+        assert_relative_eq!(t.min, Vec3::new(8., 0., 0.), epsilon = 0.0001);
+        assert_relative_eq!(t.max, Vec3::new(10., 1., 3.), epsilon = 0.0001);
+    }
+}
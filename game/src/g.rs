@@ -11,15 +11,16 @@ use frame_time::FrameTimeManager;
 use message::Message;
 use input::Input;
 use resources::Resources;
-use gpu::{GpuCmd, CpuSubImage2D, GpuTextureFilter};
-use mouse_cursor::MouseCursor;
+use gpu::{GpuCmd, CpuSubImage2D, GpuTextureFilter, GpuTextureFilters, GpuMemoryKind, GpuMemoryStats, GpuMemoryTracker};
+use mouse_cursor::{MouseCursor, CursorImage, CustomCursorId};
+use platform::FullscreenMode;
 use viewport::{ViewportDB, ViewportVisitor, LeafViewport};
 use cubemap::{CubemapArrayInfo, CubemapArrayID, CubemapFace, CubemapSelector};
 use texture2d::{Texture2DArrayInfo, Texture2DArrayID};
 use mesh::{MeshID, MeshInfo};
 use material::{MaterialID, Material};
 use light::Light;
-use camera::{Camera, CameraProjectionMode};
+use camera::{Camera, CameraProjectionMode, CameraDepthMode};
 use xform::Xform;
 use eid::EID;
 
@@ -43,11 +44,22 @@ pub struct G {
     //
 
     gpu_cmd_queue: VecDeque<GpuCmd>,
+    gpu_memory: GpuMemoryTracker,
 
     // "singletons"
     pub is_mouse_cursor_visible: bool,
     pub mouse_cursor: MouseCursor,
+    custom_mouse_cursors: Vec<CursorImage>,
+    /// Applied by the main loop before drawing; if the platform refuses, it is reset to the previous mode.
+    pub fullscreen_mode: FullscreenMode,
+    /// From 0 (invisible) to 1 (opaque, the default). Applied like `fullscreen_mode`.
+    pub window_opacity: f32,
+    /// Whether the window has a title bar and borders. Applied like `fullscreen_mode`.
+    pub is_window_decorated: bool,
+    pending_clipboard_text: Option<String>,
+    pending_window_position: Option<Vec2<i32>>,
     clear_color: Rgba<f32>,
+    skybox_filters: GpuTextureFilters,
     viewport_db: ViewportDB,
 
     /*
@@ -75,10 +87,10 @@ pub struct G {
 }
 
 impl G {
-    pub fn new(canvas_size: Extent2<u32>, mt: Arc<mt::SharedThreadContext>) -> Self {
+    pub fn new(canvas_size: Extent2<u32>, canvas_scale_factor: f32, mt: Arc<mt::SharedThreadContext>, res: Resources) -> Self {
         let camera = EID(0);
         let root_viewport = LeafViewport {
-            clear_color: Rgba::blue(),
+            clear_color: Some(Rgba::blue()),
             skybox_cubemap_selector: None,
             camera,
         };
@@ -89,12 +101,20 @@ impl G {
             pending_messages: VecDeque::new(),
             fps_stats_history: VecDeque::new(),
             mt,
-            input: Input::new(canvas_size),
-            res: Resources::new().unwrap(),
+            input: Input::new(canvas_size, canvas_scale_factor),
+            res,
             gpu_cmd_queue: VecDeque::with_capacity(1024),
+            gpu_memory: GpuMemoryTracker::with_budget(512 * 1024 * 1024),
             clear_color: Rgba::new(0., 1., 1., 1.),
+            skybox_filters: GpuTextureFilters::default(),
             mouse_cursor: MouseCursor::default(),
+            custom_mouse_cursors: Vec::new(),
             is_mouse_cursor_visible: true,
+            fullscreen_mode: FullscreenMode::default(),
+            window_opacity: 1.,
+            is_window_decorated: true,
+            pending_clipboard_text: None,
+            pending_window_position: None,
             viewport_db: ViewportDB::new(root_viewport),
             cubemap_arrays: array![None; CubemapArrayID::MAX],
             texture2d_arrays: array![None; Texture2DArrayID::MAX],
@@ -106,6 +126,7 @@ impl G {
             //instances: HashMap::new(),
         };
         g.gpu_cmd_queue.push_back(GpuCmd::ClearColorEdit);
+        g.gpu_cmd_queue.push_back(GpuCmd::SkyboxFiltersEdit);
         g.eid_set_xform(camera, Xform {
             position: Vec3::new(0., 0., -5.),
             .. Default::default()
@@ -115,6 +136,8 @@ impl G {
             fov_y_radians: 60_f32.to_radians(),
             near: 0.001,
             far: 10000.,
+            depth_mode: CameraDepthMode::ReversedZ,
+            layer_mask: Camera::ALL_LAYERS,
         });
         g
     }
@@ -139,6 +162,15 @@ impl G {
     pub fn clear_color(&self) -> Rgba<f32> {
         self.clear_color
     }
+    pub fn skybox_filters(&self) -> GpuTextureFilters {
+        self.skybox_filters
+    }
+    pub fn skybox_set_filters(&mut self, filters: GpuTextureFilters) {
+        if filters != self.skybox_filters {
+            self.skybox_filters = filters;
+            self.gpu_cmd_queue.push_back(GpuCmd::SkyboxFiltersEdit);
+        }
+    }
 
     pub fn eid_xform(&self, eid: EID) -> Option<&Xform> {
         self.xforms.get(&eid)
@@ -177,15 +209,56 @@ impl G {
         self.viewport_db().visit(Rect { x: 0, y: 0, w, h }, f);
     }
 
-    pub fn cubemap_array_create(&mut self, id: CubemapArrayID, info: CubemapArrayInfo) {
+    /// The cursor is created by the platform before the next frame is drawn; assign it to `mouse_cursor` to use it.
+    #[allow(dead_code)]
+    pub fn add_custom_mouse_cursor(&mut self, image: CursorImage) -> MouseCursor {
+        self.custom_mouse_cursors.push(image);
+        MouseCursor::Custom(CustomCursorId(self.custom_mouse_cursors.len() - 1))
+    }
+    pub fn custom_mouse_cursors(&self) -> &[CursorImage] {
+        &self.custom_mouse_cursors
+    }
+    /// Copied to the system clipboard by the main loop before the next frame is drawn; only the last text set
+    /// before then is copied. Failures are logged.
+    pub fn set_clipboard_text(&mut self, text: String) {
+        self.pending_clipboard_text = Some(text);
+    }
+    pub fn take_pending_clipboard_text(&mut self) -> Option<String> {
+        self.pending_clipboard_text.take()
+    }
+    /// Moves the window's top-left corner, in desktop coordinates, before the next frame is drawn
+    /// (`platform::CENTERED_WINDOW_POSITION` centers it). Failures are logged.
+    pub fn set_window_position(&mut self, position: Vec2<i32>) {
+        self.pending_window_position = Some(position);
+    }
+    pub fn take_pending_window_position(&mut self) -> Option<Vec2<i32>> {
+        self.pending_window_position.take()
+    }
+
+    pub fn gpu_memory_stats(&self) -> GpuMemoryStats {
+        self.gpu_memory.stats()
+    }
+    pub fn gpu_buffer_memory_alloc(&mut self, size: usize) -> Result<(), String> {
+        self.gpu_memory.alloc(GpuMemoryKind::Buffer, size)
+    }
+    #[allow(dead_code)]
+    pub fn gpu_buffer_memory_free(&mut self, size: usize) {
+        self.gpu_memory.free(GpuMemoryKind::Buffer, size)
+    }
+
+    pub fn cubemap_array_create(&mut self, id: CubemapArrayID, info: CubemapArrayInfo) -> Result<(), String> {
         assert!(self.cubemap_array_info(id).is_none());
+        self.gpu_memory.alloc(GpuMemoryKind::Texture, info.memory_usage())?;
         self.cubemap_arrays[id.0 as usize] = Some(info);
-        self.gpu_cmd_queue.push_back(GpuCmd::CubemapArrayCreate(id))
+        self.gpu_cmd_queue.push_back(GpuCmd::CubemapArrayCreate(id));
+        Ok(())
     }
     pub fn cubemap_array_delete(&mut self, id: CubemapArrayID) -> Option<CubemapArrayInfo> {
         assert!(self.cubemap_array_info(id).is_some());
         self.gpu_cmd_queue.push_back(GpuCmd::CubemapArrayDelete(id));
-        self.cubemap_arrays[id.0 as usize].take()
+        let info = self.cubemap_arrays[id.0 as usize].take();
+        self.gpu_memory.free(GpuMemoryKind::Texture, info.unwrap().memory_usage());
+        info
     }
     pub fn cubemap_array_info(&self, array: CubemapArrayID) -> Option<&CubemapArrayInfo> {
         self.cubemap_arrays[array.0 as usize].as_ref()
@@ -208,16 +281,31 @@ impl G {
         assert!(self.cubemap_array_info(id).is_some());
         self.gpu_cmd_queue.push_back(GpuCmd::CubemapArraySetMagFilter(id, filter))
     }
+    /// Like setting the min and mag filters, but also allows filtering between mipmap levels.
+    pub fn cubemap_array_set_filters(&mut self, id: CubemapArrayID, filters: GpuTextureFilters) {
+        assert!(self.cubemap_array_info(id).is_some());
+        self.gpu_cmd_queue.push_back(GpuCmd::CubemapArraySetFilters(id, filters))
+    }
+    /// Fills all levels but the first from it. All six faces of every cubemap in the array should have
+    /// been uploaded by then, since they are all filtered at once.
+    pub fn cubemap_array_generate_mipmaps(&mut self, id: CubemapArrayID) {
+        assert!(self.cubemap_array_info(id).unwrap().nb_levels > 1);
+        self.gpu_cmd_queue.push_back(GpuCmd::CubemapArrayGenerateMipmaps(id))
+    }
 
-    pub fn texture2d_array_create(&mut self, id: Texture2DArrayID, info: Texture2DArrayInfo) {
+    pub fn texture2d_array_create(&mut self, id: Texture2DArrayID, info: Texture2DArrayInfo) -> Result<(), String> {
         assert!(self.texture2d_array_info(id).is_none());
+        self.gpu_memory.alloc(GpuMemoryKind::Texture, info.memory_usage())?;
         self.texture2d_arrays[id.0 as usize] = Some(info);
-        self.gpu_cmd_queue.push_back(GpuCmd::Texture2DArrayCreate(id))
+        self.gpu_cmd_queue.push_back(GpuCmd::Texture2DArrayCreate(id));
+        Ok(())
     }
     pub fn texture2d_array_delete(&mut self, id: Texture2DArrayID) -> Option<Texture2DArrayInfo> {
         assert!(self.texture2d_array_info(id).is_some());
         self.gpu_cmd_queue.push_back(GpuCmd::Texture2DArrayDelete(id));
-        self.texture2d_arrays[id.0 as usize].take()
+        let info = self.texture2d_arrays[id.0 as usize].take();
+        self.gpu_memory.free(GpuMemoryKind::Texture, info.unwrap().memory_usage());
+        info
     }
     pub fn texture2d_array_info(&self, array: Texture2DArrayID) -> Option<&Texture2DArrayInfo> {
         self.texture2d_arrays[array.0 as usize].as_ref()
@@ -240,6 +328,21 @@ impl G {
         assert!(self.texture2d_array_info(id).is_some());
         self.gpu_cmd_queue.push_back(GpuCmd::Texture2DArraySetMagFilter(id, filter))
     }
+    /// Like setting the min and mag filters, but also allows filtering between mipmap levels.
+    pub fn texture2d_array_set_filters(&mut self, id: Texture2DArrayID, filters: GpuTextureFilters) {
+        assert!(self.texture2d_array_info(id).is_some());
+        self.gpu_cmd_queue.push_back(GpuCmd::Texture2DArraySetFilters(id, filters))
+    }
+    /// Fills all levels but the first from it, in every slot.
+    pub fn texture2d_array_generate_mipmaps(&mut self, id: Texture2DArrayID) {
+        assert!(self.texture2d_array_info(id).unwrap().nb_levels > 1);
+        self.gpu_cmd_queue.push_back(GpuCmd::Texture2DArrayGenerateMipmaps(id))
+    }
+    /// Clamped to what the GPU supports; ignored (with a warning) if it doesn't support anisotropic filtering.
+    pub fn texture2d_array_set_anisotropy(&mut self, id: Texture2DArrayID, level: f32) {
+        assert!(self.texture2d_array_info(id).is_some());
+        self.gpu_cmd_queue.push_back(GpuCmd::Texture2DArraySetAnisotropy(id, level))
+    }
 
 
     /*
@@ -7,24 +7,60 @@ use viewport::ViewportNode;
 use eid::EID;
 use cubemap::{CubemapSelector, CubemapArrayID, CubemapArrayInfo, CubemapFace};
 use texture2d::{Texture2DArrayID, Texture2DArrayInfo};
-use gpu::{GpuTextureInternalFormat, CpuSubImage2D, CpuImgFormat, CpuImgPixelType, CpuPixels, GpuTextureFilter};
+use gpu::{format_mem, GpuTextureInternalFormat, CpuSubImage2D, CpuMipmapChain2D, CpuImgFormat, CpuImgPixelType, CpuPixels, GpuTextureFilter, GpuTextureFilters};
 use system::*;
 
 mod cubemap {
     use super::*;
     pub const RGB8_1L_1X1: CubemapArrayID = CubemapArrayID(0);
-    pub const RGB8_1L_1024X1024: CubemapArrayID = CubemapArrayID(1);
+    pub const RGB8_11L_1024X1024: CubemapArrayID = CubemapArrayID(1);
 }
 
 mod texture2d {
     use super::*;
     pub const RGB8_1L_1X1: Texture2DArrayID = Texture2DArrayID(0);
     pub const RGB8_1L_1024X1024: Texture2DArrayID = Texture2DArrayID(1);
-    pub const RGB8_1L_256X256: Texture2DArrayID = Texture2DArrayID(2);
+    pub const RGB8_9L_256X256: Texture2DArrayID = Texture2DArrayID(2);
 }
 
 
-type ImgFuture = mt::Future<mt::Then<mt::ReadFile, mt::Async<io::Result<img::Result<(img::Metadata, img::AnyImage)>>>>>;
+type ImgFuture = mt::Future<mt::Then<mt::ReadFile, mt::Async<io::Result<img::Result<LoadedImage>>>>>;
+
+/// Images at least this large are converted and mipmapped off the draw thread.
+const OFF_THREAD_PREPARE_MIN_PIXELS: u32 = 512 * 512;
+
+#[derive(Debug)]
+enum LoadedImage {
+    Decoded(img::AnyImage),
+    Prepared(CpuMipmapChain2D),
+}
+
+impl LoadedImage {
+    fn into_sub_images(self) -> Vec<CpuSubImage2D> {
+        match self {
+            LoadedImage::Decoded(img) => vec![CpuSubImage2D::from_any_image(img)],
+            LoadedImage::Prepared(chain) => chain.into_sub_images(),
+        }
+    }
+}
+
+/// Images that aren't prepared off-thread only come with level 0, so the GPU generates the other levels once
+/// the whole array is uploaded.
+fn needs_gpu_mipmaps(nb_levels: u32, size: Extent2<u32>) -> bool {
+    nb_levels > 1 && size.product() < OFF_THREAD_PREPARE_MIN_PIXELS
+}
+
+/// If `prepare` is `Some((internal_format, nb_levels))`, the task also converts the image and
+/// generates its mipmaps, so that the draw thread only has to upload them.
+fn schedule_image_load(g: &G, path: &PathBuf, prepare: Option<(GpuTextureInternalFormat, u32)>) -> ImgFuture {
+    use self::mt::TaskExt;
+    g.mt.schedule(mt::ReadFile::new(path).then(move |result: io::Result<Vec<u8>>| {
+        mt::Async::new(move || result.map(|data| img::load_from_memory(data).map(|(_, img)| match prepare {
+            None => LoadedImage::Decoded(img),
+            Some((internal_format, nb_levels)) => LoadedImage::Prepared(CpuMipmapChain2D::from_any_image(img, internal_format, nb_levels)),
+        })))
+    }))
+}
 
 #[derive(Debug)]
 struct CubemapFaceRequest {
@@ -49,50 +85,33 @@ pub struct Gameplay {
     texture2d_requests: Vec<Texture2DRequest>,
 }
 
-fn format_mem(b: usize) -> String {
-    let kb = b / 1024;
-    if kb == 0 { return format!("{} b", b); }
-    let mib = kb / 1024;
-    if mib == 0 { return format!("{} Kb", kb); }
-    let gib = mib / 1024;
-    if gib == 0 { return format!("{} MiB", mib); }
-    
-    format!("{} GiB", gib)
-}
-
 impl Gameplay {
     pub fn new(g: &mut G) -> Self {
         {
             let mut leaf = g.viewport_db_mut().root_node().value.unwrap_leaf().borrow_mut();
-            leaf.skybox_cubemap_selector = Some(CubemapSelector { array_id: cubemap::RGB8_1L_1024X1024, cubemap: 0, });
+            leaf.skybox_cubemap_selector = Some(CubemapSelector { array_id: cubemap::RGB8_11L_1024X1024, cubemap: 0, });
         }
 
         let cubemap_array_infos = [
             (cubemap::RGB8_1L_1X1, CubemapArrayInfo { nb_levels: 1, internal_format: GpuTextureInternalFormat::RGB8, size: Extent2::one(), nb_cubemaps: 16, }),
-            (cubemap::RGB8_1L_1024X1024, CubemapArrayInfo { nb_levels: 1, internal_format: GpuTextureInternalFormat::RGB8, size: Extent2::broadcast(1024), nb_cubemaps: 6, }),
+            (cubemap::RGB8_11L_1024X1024, CubemapArrayInfo { nb_levels: 11, internal_format: GpuTextureInternalFormat::RGB8, size: Extent2::broadcast(1024), nb_cubemaps: 6, }),
         ];
         let texture2d_array_infos = [
             (texture2d::RGB8_1L_1X1, Texture2DArrayInfo { nb_levels: 1, internal_format: GpuTextureInternalFormat::RGB8, size: Extent2::one(), nb_slots: 2, }),
-            (texture2d::RGB8_1L_256X256, Texture2DArrayInfo { nb_levels: 1, internal_format: GpuTextureInternalFormat::RGB8, size: Extent2::broadcast(256), nb_slots: 3, }),
+            (texture2d::RGB8_9L_256X256, Texture2DArrayInfo { nb_levels: 9, internal_format: GpuTextureInternalFormat::RGB8, size: Extent2::broadcast(256), nb_slots: 3, }),
             (texture2d::RGB8_1L_1024X1024, Texture2DArrayInfo { nb_levels: 1, internal_format: GpuTextureInternalFormat::RGB8, size: Extent2::broadcast(1024), nb_slots: 2, }),
         ];
 
 
-        let mut tex_mem = 0;
-
         for (array_id, info) in cubemap_array_infos.iter() {
-            tex_mem += info.memory_usage();
             info!("Memory usage of {:?}: {}", array_id, format_mem(info.memory_usage()));
-            g.cubemap_array_create(*array_id, *info);
+            g.cubemap_array_create(*array_id, *info).unwrap();
         }
         for (array_id, info) in texture2d_array_infos.iter() {
-            tex_mem += info.memory_usage();
             info!("Memory usage of {:?}: {}", array_id, format_mem(info.memory_usage()));
-            g.texture2d_array_create(*array_id, *info);
+            g.texture2d_array_create(*array_id, *info).unwrap();
         }
 
-        // Use max. 512 Mib total on the GPU
-        let max_mem = 512 * 1024 * 1024;
         // Max. 2 Mib of scratch space (misc unpredictable allocations)
         let scratch_mem = 2 * 1024 * 1024;
 
@@ -100,19 +119,19 @@ impl Gameplay {
         let max_chunks = 3*3*3;
         let chunk_mem = 8 * 1024 * 1024;
 
-        info!("tex_mem         : {}", format_mem(tex_mem));
+        // Reserve them up front, so that running out of budget shows up here rather than mid-game.
+        g.gpu_buffer_memory_alloc(scratch_mem + max_chunks * chunk_mem).unwrap();
+
+        let stats = g.gpu_memory_stats();
+        info!("tex_mem         : {}", format_mem(stats.textures));
         info!("scratch_mem     : {}", format_mem(scratch_mem));
         info!("total_chunks_mem: {}", format_mem(max_chunks * chunk_mem));
-        info!("max_mem         : {}", format_mem(max_mem));
-        assert!(tex_mem + scratch_mem + max_chunks * chunk_mem <= max_mem);
+        info!("max_mem         : {}", format_mem(stats.budget));
 
         fn pixel(rgb: Rgb<u8>) -> CpuSubImage2D {
             CpuSubImage2D::from_rgb_u8_pixel(rgb)
         }
 
-        // TODO:
-        // GL_TEXTURE_MAX_ANISOTROPY GL_MAX_TEXTURE_MAX_ANISOTROPY GL_LINEAR_MIPMAP_LINEAR
-        // ARB_texture_filter_anisotropic EXT_texture_filter_anisotropic
         g.cubemap_array_clear(cubemap::RGB8_1L_1X1, 0, Rgba::magenta());
 
         g.cubemap_array_set_min_filter(cubemap::RGB8_1L_1X1, GpuTextureFilter::Nearest);
@@ -160,20 +179,20 @@ impl Gameplay {
         g.cubemap_array_sub_image_2d(cubemap::RGB8_1L_1X1, 5, CubemapFace::PositiveZ, pixel(Rgb::new(255, 255, 255)));
         g.cubemap_array_sub_image_2d(cubemap::RGB8_1L_1X1, 5, CubemapFace::NegativeZ, pixel(Rgb::new(255, 255, 255)));
 
-        g.cubemap_array_clear(cubemap::RGB8_1L_1024X1024, 0, Rgba::magenta());
+        g.cubemap_array_clear(cubemap::RGB8_11L_1024X1024, 0, Rgba::magenta());
 
-        g.cubemap_array_set_min_filter(cubemap::RGB8_1L_1024X1024, GpuTextureFilter::Linear);
-        g.cubemap_array_set_mag_filter(cubemap::RGB8_1L_1024X1024, GpuTextureFilter::Linear);
+        let trilinear = GpuTextureFilters { mip: Some(GpuTextureFilter::Linear), .. GpuTextureFilters::default() };
+        g.cubemap_array_set_filters(cubemap::RGB8_11L_1024X1024, trilinear);
 
 
         g.texture2d_array_clear(texture2d::RGB8_1L_1X1, 0, Rgba::cyan());
-        g.texture2d_array_clear(texture2d::RGB8_1L_256X256, 0, Rgba::cyan());
+        g.texture2d_array_clear(texture2d::RGB8_9L_256X256, 0, Rgba::cyan());
         g.texture2d_array_clear(texture2d::RGB8_1L_1024X1024, 0, Rgba::cyan());
 
         g.texture2d_array_set_min_filter(texture2d::RGB8_1L_1X1, GpuTextureFilter::Nearest);
         g.texture2d_array_set_mag_filter(texture2d::RGB8_1L_1X1, GpuTextureFilter::Nearest);
-        g.texture2d_array_set_min_filter(texture2d::RGB8_1L_256X256, GpuTextureFilter::Linear);
-        g.texture2d_array_set_min_filter(texture2d::RGB8_1L_256X256, GpuTextureFilter::Linear);
+        g.texture2d_array_set_filters(texture2d::RGB8_9L_256X256, trilinear);
+        g.texture2d_array_set_anisotropy(texture2d::RGB8_9L_256X256, 16.);
         g.texture2d_array_set_min_filter(texture2d::RGB8_1L_1024X1024, GpuTextureFilter::Linear);
         g.texture2d_array_set_mag_filter(texture2d::RGB8_1L_1024X1024, GpuTextureFilter::Linear);
 
@@ -183,15 +202,22 @@ impl Gameplay {
 
         // Upload cubemap textures (async)
         
-        let dir = g.res.data_path().join(PathBuf::from("art/3rdparty/mayhem"));
+        let dir = PathBuf::from("art/3rdparty/mayhem");
         let suffixes = CubemapFace::TERRAGEN_SUFFIXES;
         let extension = "jpg";
         let mut cubemap_face_requests = vec![];
         for (cubemap_index, name) in ["grouse", "aqua4", "h2s", "flame"].iter().enumerate() {
             for suffix in suffixes.iter() {
+                let path = match g.res.resolve(dir.join(format!("{}_{}.{}", name, suffix, extension))) {
+                    Some(path) => path,
+                    None => {
+                        warn!("Skipping the `{}` face of cubemap {} (`{}`) in array {:?}", suffix, cubemap_index, name, cubemap::RGB8_11L_1024X1024);
+                        continue;
+                    },
+                };
                 cubemap_face_requests.push(CubemapFaceRequest {
-                    path: dir.join(format!("{}_{}.{}", name, suffix, extension)),
-                    array_id: cubemap::RGB8_1L_1024X1024,
+                    path,
+                    array_id: cubemap::RGB8_11L_1024X1024,
                     cubemap_index: cubemap_index as _,
                     face: CubemapFace::try_from_terragen_suffix(suffix).unwrap(),
                     future: None,
@@ -199,31 +225,34 @@ impl Gameplay {
             }
         }
 
-        let dir = g.res.data_path().join(PathBuf::from("art/tex2d"));
+        let dir = PathBuf::from("art/tex2d");
         let mut texture2d_requests = vec![];
         for (i, name) in ["maze.png", "plasma.png", "checkerboard.png"].iter().enumerate() {
+            let path = match g.res.resolve(dir.join(name)) {
+                Some(path) => path,
+                None => {
+                    warn!("Skipping slot {} (`{}`) of texture array {:?}", i, name, texture2d::RGB8_9L_256X256);
+                    continue;
+                },
+            };
             texture2d_requests.push(Texture2DRequest {
-                path: dir.join(name),
-                array_id: texture2d::RGB8_1L_256X256,
+                path,
+                array_id: texture2d::RGB8_9L_256X256,
                 slot: i as _,
                 future: None,
             });
         }
 
         for req in cubemap_face_requests.iter_mut() {
-            use self::mt::TaskExt;
-            let future = g.mt.schedule(mt::ReadFile::new(&req.path).then(|result: io::Result<Vec<u8>>| {
-                mt::Async::new(move || result.map(|data| img::load_from_memory(data)))
-            }));
-            req.future = Some(future);
+            let info = *g.cubemap_array_info(req.array_id).unwrap();
+            let prepare = if info.size.product() >= OFF_THREAD_PREPARE_MIN_PIXELS { Some((info.internal_format, info.nb_levels)) } else { None };
+            req.future = Some(schedule_image_load(g, &req.path, prepare));
         }
 
         for req in texture2d_requests.iter_mut() {
-            use self::mt::TaskExt;
-            let future = g.mt.schedule(mt::ReadFile::new(&req.path).then(|result: io::Result<Vec<u8>>| {
-                mt::Async::new(move || result.map(|data| img::load_from_memory(data)))
-            }));
-            req.future = Some(future);
+            let info = *g.texture2d_array_info(req.array_id).unwrap();
+            let prepare = if info.size.product() >= OFF_THREAD_PREPARE_MIN_PIXELS { Some((info.internal_format, info.nb_levels)) } else { None };
+            req.future = Some(schedule_image_load(g, &req.path, prepare));
         }
 
         // TODO: Upload font atlas
@@ -259,9 +288,17 @@ impl Gameplay {
                 Some(i) => {
                     let mut req = self.cubemap_face_requests.remove(i);
                     match req.future.take().unwrap().wait() {
-                        Ok(Ok((_, img))) => {
-                            g.cubemap_array_sub_image_2d(req.array_id, req.cubemap_index as _, req.face, CpuSubImage2D::from_any_image(img));
+                        Ok(Ok(img)) => {
+                            for sub_image in img.into_sub_images() {
+                                g.cubemap_array_sub_image_2d(req.array_id, req.cubemap_index as _, req.face, sub_image);
+                            }
                             info!("Loaded `{}`", req.path.display());
+                            // Only once all faces of all cubemaps are there
+                            let info = *g.cubemap_array_info(req.array_id).unwrap();
+                            let is_last = self.cubemap_face_requests.iter().all(|r| r.array_id != req.array_id);
+                            if is_last && needs_gpu_mipmaps(info.nb_levels, info.size) {
+                                g.cubemap_array_generate_mipmaps(req.array_id);
+                            }
                         },
                         _ => unimplemented!{},
                     }
@@ -292,9 +329,16 @@ impl Gameplay {
                 Some(i) => {
                     let mut req = self.texture2d_requests.remove(i);
                     match req.future.take().unwrap().wait() {
-                        Ok(Ok((_, img))) => {
-                            g.texture2d_array_sub_image_2d(req.array_id, req.slot as _, CpuSubImage2D::from_any_image(img));
+                        Ok(Ok(img)) => {
+                            for sub_image in img.into_sub_images() {
+                                g.texture2d_array_sub_image_2d(req.array_id, req.slot as _, sub_image);
+                            }
                             info!("Loaded `{}`", req.path.display());
+                            let info = *g.texture2d_array_info(req.array_id).unwrap();
+                            let is_last = self.texture2d_requests.iter().all(|r| r.array_id != req.array_id);
+                            if is_last && needs_gpu_mipmaps(info.nb_levels, info.size) {
+                                g.texture2d_array_generate_mipmaps(req.array_id);
+                            }
                         },
                         _ => unimplemented!{},
                     }
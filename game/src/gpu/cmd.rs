@@ -1,7 +1,7 @@
 use fate::math::Rgba;
 use cubemap::{CubemapArrayID, CubemapFace};
 use texture2d::Texture2DArrayID;
-use super::{CpuSubImage2D, GpuTextureFilter};
+use super::{CpuSubImage2D, GpuTextureFilter, GpuTextureFilters};
 
 /// Commands for the rendering backend to update the on-GPU data.
 ///
@@ -10,17 +10,23 @@ use super::{CpuSubImage2D, GpuTextureFilter};
 #[derive(Debug, Clone, PartialEq)]
 pub enum GpuCmd {
     ClearColorEdit,
+    SkyboxFiltersEdit,
     CubemapArrayCreate(CubemapArrayID),
     CubemapArrayDelete(CubemapArrayID),
     CubemapArrayClear(CubemapArrayID, u32, Rgba<f32>), // id, level, color
     CubemapArraySubImage2D(CubemapArrayID, usize, CubemapFace, CpuSubImage2D),
     CubemapArraySetMinFilter(CubemapArrayID, GpuTextureFilter),
     CubemapArraySetMagFilter(CubemapArrayID, GpuTextureFilter),
+    CubemapArraySetFilters(CubemapArrayID, GpuTextureFilters),
+    CubemapArrayGenerateMipmaps(CubemapArrayID),
     Texture2DArrayCreate(Texture2DArrayID),
     Texture2DArrayDelete(Texture2DArrayID),
     Texture2DArrayClear(Texture2DArrayID, u32, Rgba<f32>), // id, level, color
     Texture2DArraySubImage2D(Texture2DArrayID, usize, CpuSubImage2D),
     Texture2DArraySetMinFilter(Texture2DArrayID, GpuTextureFilter),
     Texture2DArraySetMagFilter(Texture2DArrayID, GpuTextureFilter),
+    Texture2DArraySetFilters(Texture2DArrayID, GpuTextureFilters),
+    Texture2DArrayGenerateMipmaps(Texture2DArrayID),
+    Texture2DArraySetAnisotropy(Texture2DArrayID, f32),
 }
 
@@ -1,5 +1,7 @@
-use fate::gx::gl;
-use fate::math::Rgb;
+use fate::gx::gl::{self, types::*};
+use std::rc::Rc;
+use std::ops::Range;
+use fate::math::{Rgb, Extent2};
 use fate::img;
 
 // TODO: Also move enums to GX
@@ -18,6 +20,10 @@ impl CpuPixels {
     pub fn from_vec<T>(v: Vec<T>) -> Self {
         CpuPixels(Box::new(into_bytes_vec(v)))
     }
+    /// Shares a range of `data`, e.g a single level of a `CpuMipmapChain2D`.
+    pub fn from_shared(data: Rc<Vec<u8>>, range: Range<usize>) -> Self {
+        CpuPixels(Box::new(SharedPixels { data, range }))
+    }
     pub fn as_slice(&self) -> &[u8] {
         self.0.as_ref().as_ref()
     }
@@ -47,6 +53,17 @@ impl Clone for CpuPixels {
     }
 }
 
+struct SharedPixels {
+    data: Rc<Vec<u8>>,
+    range: Range<usize>,
+}
+
+impl AsRef<[u8]> for SharedPixels {
+    fn as_ref(&self) -> &[u8] {
+        &self.data[self.range.clone()]
+    }
+}
+
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CpuSubImage3D {
@@ -134,6 +151,144 @@ impl CpuSubImage2D {
 }
 
 
+/// All levels of a 2D image, tightly packed into a single buffer, in the layout expected by some
+/// internal format.
+///
+/// This is meant to be prepared off the draw thread, so that only the upload is left to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuMipmapChain2D {
+    /// Size of level 0.
+    pub size: Extent2<u32>,
+    pub format: CpuImgFormat,
+    pub type_: CpuImgPixelType,
+    pub data: Vec<u8>,
+    /// Offset in bytes of each level into `data`.
+    pub level_offsets: Vec<usize>,
+}
+
+impl CpuMipmapChain2D {
+    /// Converts `img` to the pixel layout of `internal_format` (with the same rules as GL uses for
+    /// missing components) then generates up to `nb_levels` levels with a box filter.
+    ///
+    /// If `internal_format` isn't an unsigned normalized 8-bit format, the pixels are kept as-is
+    /// and only level 0 is produced.
+    pub fn from_any_image(img: img::AnyImage, internal_format: GpuTextureInternalFormat, nb_levels: u32) -> Self {
+        let src = CpuSubImage2D::from_any_image(img);
+        let size = Extent2::new(src.w, src.h);
+        let src_nb_channels = u8_nb_channels(src.format).unwrap();
+
+        let (format, nb_channels) = match u8_format_for(internal_format) {
+            Some(x) => x,
+            None => return Self {
+                size,
+                format: src.format,
+                type_: src.type_,
+                data: src.data.as_slice().to_vec(),
+                level_offsets: vec![0],
+            },
+        };
+
+        let mut data = convert_u8_pixels(src.data.as_slice(), src_nb_channels, nb_channels);
+        let mut level_offsets = vec![0];
+        let mut level_size = size;
+        while (level_offsets.len() as u32) < nb_levels && (level_size.w > 1 || level_size.h > 1) {
+            let offset = *level_offsets.last().unwrap();
+            let next = downsample_u8_pixels(&data[offset..], level_size, nb_channels);
+            level_offsets.push(data.len());
+            data.extend_from_slice(&next);
+            level_size = level_size.map(|x| (x / 2).max(1));
+        }
+
+        Self { size, format, type_: CpuImgPixelType::U8, data, level_offsets }
+    }
+    pub fn nb_levels(&self) -> u32 {
+        self.level_offsets.len() as _
+    }
+    pub fn level_size(&self, level: u32) -> Extent2<u32> {
+        assert!(level < self.nb_levels());
+        self.size.map(|x| (x >> level).max(1))
+    }
+    pub fn level_range(&self, level: u32) -> Range<usize> {
+        assert!(level < self.nb_levels());
+        let start = self.level_offsets[level as usize];
+        let end = self.level_offsets.get(level as usize + 1).cloned().unwrap_or(self.data.len());
+        start .. end
+    }
+    pub fn level_data(&self, level: u32) -> &[u8] {
+        &self.data[self.level_range(level)]
+    }
+    /// One sub-image per level, all sharing the same buffer.
+    pub fn into_sub_images(self) -> Vec<CpuSubImage2D> {
+        let ranges: Vec<_> = (0 .. self.nb_levels()).map(|level| (level, self.level_size(level), self.level_range(level))).collect();
+        let (format, type_) = (self.format, self.type_);
+        let data = Rc::new(self.data);
+        ranges.into_iter().map(|(level, size, range)| CpuSubImage2D {
+            level,
+            x: 0,
+            y: 0,
+            w: size.w,
+            h: size.h,
+            format,
+            type_,
+            data: CpuPixels::from_shared(data.clone(), range),
+        }).collect()
+    }
+}
+
+fn u8_nb_channels(format: CpuImgFormat) -> Option<usize> {
+    match format {
+        CpuImgFormat::R => Some(1),
+        CpuImgFormat::RG => Some(2),
+        CpuImgFormat::RGB => Some(3),
+        CpuImgFormat::RGBA => Some(4),
+        _ => None,
+    }
+}
+
+fn u8_format_for(internal_format: GpuTextureInternalFormat) -> Option<(CpuImgFormat, usize)> {
+    use self::GpuTextureInternalFormat as F;
+    match internal_format {
+        F::R8 => Some((CpuImgFormat::R, 1)),
+        F::RG8 => Some((CpuImgFormat::RG, 2)),
+        F::RGB8 | F::SRGB8 => Some((CpuImgFormat::RGB, 3)),
+        F::RGBA8 | F::SRGBA8 => Some((CpuImgFormat::RGBA, 4)),
+        _ => None,
+    }
+}
+
+// Missing color components are 0, and missing alpha is 255, as GL does.
+fn convert_u8_pixels(src: &[u8], src_nb_channels: usize, dst_nb_channels: usize) -> Vec<u8> {
+    if src_nb_channels == dst_nb_channels {
+        return src.to_vec();
+    }
+    let mut dst = Vec::with_capacity(src.len() / src_nb_channels * dst_nb_channels);
+    for pixel in src.chunks(src_nb_channels) {
+        let mut rgba = [0, 0, 0, 255];
+        rgba[..src_nb_channels].copy_from_slice(pixel);
+        dst.extend_from_slice(&rgba[..dst_nb_channels]);
+    }
+    dst
+}
+
+fn downsample_u8_pixels(src: &[u8], size: Extent2<u32>, nb_channels: usize) -> Vec<u8> {
+    let (w, h) = (size.w as usize, size.h as usize);
+    let (dw, dh) = ((w / 2).max(1), (h / 2).max(1));
+    let mut dst = Vec::with_capacity(dw * dh * nb_channels);
+    for y in 0 .. dh {
+        let (y0, y1) = ((2 * y).min(h - 1), (2 * y + 1).min(h - 1));
+        for x in 0 .. dw {
+            let (x0, x1) = ((2 * x).min(w - 1), (2 * x + 1).min(w - 1));
+            for c in 0 .. nb_channels {
+                let texel = |x: usize, y: usize| src[(y * w + x) * nb_channels + c] as u32;
+                let sum = texel(x0, y0) + texel(x1, y0) + texel(x0, y1) + texel(x1, y1);
+                dst.push(((sum + 2) / 4) as u8);
+            }
+        }
+    }
+    dst
+}
+
+
 #[allow(non_camel_case_types)]
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
@@ -330,3 +485,88 @@ pub enum GpuTextureFilter {
     Nearest = gl::NEAREST,
     // TODO: Others???
 }
+
+/// Complete filtering state, as stored by sampler objects.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct GpuTextureFilters {
+    pub min: GpuTextureFilter,
+    pub mag: GpuTextureFilter,
+    /// Filtering between mipmap levels. If `None`, only the base level is sampled.
+    pub mip: Option<GpuTextureFilter>,
+}
+
+impl Default for GpuTextureFilters {
+    fn default() -> Self {
+        Self {
+            min: GpuTextureFilter::Linear,
+            mag: GpuTextureFilter::Linear,
+            mip: None,
+        }
+    }
+}
+
+impl GpuTextureFilters {
+    pub fn gl_min_filter(&self) -> GLenum {
+        match (self.min, self.mip) {
+            (min, None) => min as _,
+            (GpuTextureFilter::Nearest, Some(GpuTextureFilter::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+            (GpuTextureFilter::Nearest, Some(GpuTextureFilter::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+            (GpuTextureFilter::Linear, Some(GpuTextureFilter::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+            (GpuTextureFilter::Linear, Some(GpuTextureFilter::Linear)) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+    pub fn gl_mag_filter(&self) -> GLenum {
+        self.mag as _
+    }
+    /// Returns the `(pname, param)` pairs that need to be sent to GL to go from `self` to `new`.
+    pub fn gl_changed_params(&self, new: &Self) -> Vec<(GLenum, GLenum)> {
+        let mut params = vec![];
+        if self.gl_min_filter() != new.gl_min_filter() {
+            params.push((gl::TEXTURE_MIN_FILTER, new.gl_min_filter()));
+        }
+        if self.gl_mag_filter() != new.gl_mag_filter() {
+            params.push((gl::TEXTURE_MAG_FILTER, new.gl_mag_filter()));
+        }
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn off_thread_level_0_matches_on_thread_upload() {
+        let rgb: Vec<_> = (0 .. 4 * 4).map(|i| img::Rgb { data: [i as u8, 2 * i as u8, 255 - i as u8] }).collect();
+        let img = || img::AnyImage::Rgb8(img::ImgVec::new(rgb.clone(), 4, 4));
+
+        let on_thread = CpuSubImage2D::from_any_image(img());
+        let off_thread = CpuMipmapChain2D::from_any_image(img(), GpuTextureInternalFormat::RGB8, 8);
+        assert_eq!(off_thread.format, on_thread.format);
+        assert_eq!(off_thread.level_data(0), on_thread.data.as_slice());
+        assert_eq!(off_thread.nb_levels(), 3);
+        assert_eq!(off_thread.level_size(2), Extent2::one());
+        assert_eq!(off_thread.level_data(2).len(), 3);
+
+        let levels = off_thread.clone().into_sub_images();
+        assert_eq!(levels[0], on_thread);
+        assert_eq!(levels[1].data.as_slice(), off_thread.level_data(1));
+    }
+    #[test]
+    fn missing_components_are_filled_as_gl_does() {
+        let gray = vec![img::Luma { data: [7] }, img::Luma { data: [9] }];
+        let chain = CpuMipmapChain2D::from_any_image(img::AnyImage::Gray8(img::ImgVec::new(gray, 2, 1)), GpuTextureInternalFormat::RGBA8, 1);
+        assert_eq!(chain.format, CpuImgFormat::RGBA);
+        assert_eq!(chain.data, vec![7, 0, 0, 255, 9, 0, 0, 255]);
+    }
+    #[test]
+    fn only_changed_filters_are_sent() {
+        let old = GpuTextureFilters::default();
+        assert!(old.gl_changed_params(&old).is_empty());
+
+        let new = GpuTextureFilters { mag: GpuTextureFilter::Nearest, .. old };
+        assert_eq!(old.gl_changed_params(&new), vec![(gl::TEXTURE_MAG_FILTER, gl::NEAREST)]);
+
+        let new = GpuTextureFilters { mip: Some(GpuTextureFilter::Linear), .. old };
+        assert_eq!(old.gl_changed_params(&new), vec![(gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR)]);
+    }
+}
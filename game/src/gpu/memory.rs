@@ -0,0 +1,109 @@
+/// What kind of GPU resource an allocation is for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GpuMemoryKind {
+    Texture,
+    Buffer,
+}
+
+/// A snapshot of tracked GPU memory, in bytes.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct GpuMemoryStats {
+    pub textures: usize,
+    pub buffers: usize,
+    pub budget: usize,
+}
+
+impl GpuMemoryStats {
+    pub fn used(&self) -> usize {
+        self.textures + self.buffers
+    }
+    pub fn available(&self) -> usize {
+        self.budget.saturating_sub(self.used())
+    }
+}
+
+/// Sums tracked GPU allocations, and refuses the ones that would exceed the budget.
+///
+/// This only knows about what it's told; the driver may allocate more behind our back.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GpuMemoryTracker {
+    stats: GpuMemoryStats,
+}
+
+impl GpuMemoryTracker {
+    pub fn with_budget(budget: usize) -> Self {
+        Self { stats: GpuMemoryStats { budget, .. Default::default() } }
+    }
+    pub fn stats(&self) -> GpuMemoryStats {
+        self.stats
+    }
+    pub fn alloc(&mut self, kind: GpuMemoryKind, size: usize) -> Result<(), String> {
+        if size > self.stats.available() {
+            return Err(format!("Refusing to allocate {} for {:?}: {} of {} are already in use", format_mem(size), kind, format_mem(self.stats.used()), format_mem(self.stats.budget)));
+        }
+        *self.usage_mut(kind) += size;
+        Ok(())
+    }
+    pub fn free(&mut self, kind: GpuMemoryKind, size: usize) {
+        let usage = self.usage_mut(kind);
+        assert!(size <= *usage, "Freeing more {:?} memory than was allocated", kind);
+        *usage -= size;
+    }
+    fn usage_mut(&mut self, kind: GpuMemoryKind) -> &mut usize {
+        match kind {
+            GpuMemoryKind::Texture => &mut self.stats.textures,
+            GpuMemoryKind::Buffer => &mut self.stats.buffers,
+        }
+    }
+}
+
+pub fn format_mem(b: usize) -> String {
+    let kb = b / 1024;
+    if kb == 0 { return format!("{} b", b); }
+    let mib = kb / 1024;
+    if mib == 0 { return format!("{} Kb", kb); }
+    let gib = mib / 1024;
+    if gib == 0 { return format!("{} MiB", mib); }
+
+    format!("{} GiB", gib)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fate::math::Extent2;
+    use cubemap::CubemapArrayInfo;
+    use texture2d::Texture2DArrayInfo;
+    use gpu::GpuTextureInternalFormat;
+
+    #[test]
+    fn creating_and_freeing_arrays_updates_usage() {
+        let cubemaps = CubemapArrayInfo { nb_levels: 1, internal_format: GpuTextureInternalFormat::RGB8, size: Extent2::broadcast(1024), nb_cubemaps: 6, };
+        let textures = Texture2DArrayInfo { nb_levels: 1, internal_format: GpuTextureInternalFormat::RGB8, size: Extent2::broadcast(256), nb_slots: 3, };
+        assert_eq!(cubemaps.memory_usage(), 6 * 6 * 1024 * 1024 * 3);
+        assert_eq!(textures.memory_usage(), 3 * 256 * 256 * 3);
+
+        let mut tracker = GpuMemoryTracker::with_budget(512 * 1024 * 1024);
+        tracker.alloc(GpuMemoryKind::Texture, cubemaps.memory_usage()).unwrap();
+        assert_eq!(tracker.stats().textures, cubemaps.memory_usage());
+        tracker.alloc(GpuMemoryKind::Texture, textures.memory_usage()).unwrap();
+        tracker.alloc(GpuMemoryKind::Buffer, 1024).unwrap();
+        assert_eq!(tracker.stats().used(), cubemaps.memory_usage() + textures.memory_usage() + 1024);
+
+        tracker.free(GpuMemoryKind::Texture, cubemaps.memory_usage());
+        assert_eq!(tracker.stats().textures, textures.memory_usage());
+        tracker.free(GpuMemoryKind::Texture, textures.memory_usage());
+        tracker.free(GpuMemoryKind::Buffer, 1024);
+        assert_eq!(tracker.stats().used(), 0);
+    }
+    #[test]
+    fn over_budget_allocations_are_refused() {
+        let mut tracker = GpuMemoryTracker::with_budget(1000);
+        tracker.alloc(GpuMemoryKind::Buffer, 600).unwrap();
+        assert!(tracker.alloc(GpuMemoryKind::Texture, 401).is_err());
+        assert_eq!(tracker.stats(), GpuMemoryStats { textures: 0, buffers: 600, budget: 1000 });
+        tracker.alloc(GpuMemoryKind::Texture, 400).unwrap();
+        assert_eq!(tracker.stats().available(), 0);
+    }
+}
@@ -3,5 +3,7 @@ pub use self::cmd::GpuCmd;
 pub mod end_frame;
 pub use self::end_frame::GpuEndFrame;
 pub mod img;
-pub use self::img::{GpuTextureInternalFormat, CpuImgPixelType, CpuImgFormat, CpuSubImage3D, CpuSubImage2D, CpuPixels, GpuTextureFilter};
+pub mod memory;
+pub use self::memory::{GpuMemoryKind, GpuMemoryStats, GpuMemoryTracker, format_mem};
+pub use self::img::{GpuTextureInternalFormat, CpuImgPixelType, CpuImgFormat, CpuSubImage3D, CpuSubImage2D, CpuMipmapChain2D, CpuPixels, GpuTextureFilter, GpuTextureFilters};
 
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 use std::mem;
 use dmc::device::{MouseButton, ButtonState, Keysym, KeyState};
-use fate::math::{Vec2, Vec3};
+use fate::math::{Vec2, Vec3, Rect};
+use event::{Event, EventTypeMask};
 use system::*;
 
 #[derive(Debug, Default, Clone, PartialEq)]
@@ -12,16 +13,27 @@ pub struct Input {
 	mouse_displacement: Vec2<f64>,
     is_mouse_inside: bool,
     has_keyboard_focus: bool,
+    gates_unfocused_input: bool,
+    is_text_input_active: bool,
+    text_input_rect: Option<Rect<i32, u32>>,
+    muted_event_types: EventTypeMask,
+    is_mouse_motion_coalesced: bool,
     quit_requested: bool,
     previous_canvas_size: Extent2<u32>,
     canvas_size: Extent2<u32>,
+    canvas_scale_factor: f32,
 }
 
 impl Input {
-    pub fn new(canvas_size: Extent2<u32>) -> Self {
+    /// The window is assumed to have keyboard focus, since platforms only report focus changes.
+    pub fn new(canvas_size: Extent2<u32>, canvas_scale_factor: f32) -> Self {
         Self {
             previous_canvas_size: canvas_size,
             canvas_size,
+            canvas_scale_factor,
+            has_keyboard_focus: true,
+            gates_unfocused_input: true,
+            is_mouse_motion_coalesced: true,
             .. Self::default()
         }
     }
@@ -37,18 +49,97 @@ impl Input {
     pub fn mouse_displacement(&self) -> Vec2<f64> {
         self.mouse_displacement
     }
-    pub fn is_mouse_inside(&self) -> bool {
+    pub fn is_mouse_over_canvas(&self) -> bool {
         self.is_mouse_inside
     }
     pub fn has_keyboard_focus(&self) -> bool {
         self.has_keyboard_focus
     }
+    /// If true (the default), keyboard, scroll and raw mouse events are not dispatched while the window doesn't have keyboard focus.
+    /// Mouse motion and buttons still are, so that hovering and clicking into the window keep working.
+    pub fn gates_unfocused_input(&self) -> bool {
+        self.gates_unfocused_input
+    }
+    pub fn set_gates_unfocused_input(&mut self, gates: bool) {
+        self.gates_unfocused_input = gates;
+    }
+    /// Text input (and the IME) is off by default, so that typing doesn't trigger hotkeys.
+    /// Systems that need text (e.g a console or a text field) turn it on while they have focus.
+    pub fn start_text_input(&mut self) {
+        self.is_text_input_active = true;
+    }
+    pub fn stop_text_input(&mut self) {
+        self.is_text_input_active = false;
+    }
+    pub fn is_text_input_active(&self) -> bool {
+        self.is_text_input_active
+    }
+    /// Hints where the text being typed is, in canvas pixels, so that the IME's candidate window can appear next to it.
+    pub fn set_text_input_rect(&mut self, rect: Rect<i32, u32>) {
+        self.text_input_rect = Some(rect);
+    }
+    pub fn text_input_rect(&self) -> Option<Rect<i32, u32>> {
+        self.text_input_rect
+    }
+    /// Only events of these types are polled from the platform (all of them by default); the others stay queued
+    /// until the filter allows them again. For instance, mouse motion can be silenced during loading screens.
+    #[allow(dead_code)]
+    pub fn set_event_filter(&mut self, allowed: EventTypeMask) {
+        self.muted_event_types = !allowed;
+    }
+    pub fn event_filter(&self) -> EventTypeMask {
+        !self.muted_event_types
+    }
+    /// If true (the default), each run of mouse motion events polled in a frame is merged into a single one,
+    /// see `platform::coalesce_mouse_motion()`. Turn it off to get every intermediate position, e.g for drawing.
+    pub fn is_mouse_motion_coalesced(&self) -> bool {
+        self.is_mouse_motion_coalesced
+    }
+    pub fn set_mouse_motion_coalesced(&mut self, coalesced: bool) {
+        self.is_mouse_motion_coalesced = coalesced;
+    }
+    pub fn is_suppressed(&self, ev: &Event) -> bool {
+        match *ev {
+            Event::KeyboardTextChar(_)
+            | Event::TextInputCompositionUpdated { .. }
+            | Event::TextInputCompositionCommitted { .. } => if !self.is_text_input_active {
+                return true;
+            },
+            _ => (),
+        }
+        if !self.gates_unfocused_input || self.has_keyboard_focus {
+            return false;
+        }
+        match *ev {
+            Event::KeyboardKeyPressed(_)
+            | Event::KeyboardKeyReleased(_)
+            | Event::KeyboardTextChar(_)
+            | Event::TextInputCompositionUpdated { .. }
+            | Event::TextInputCompositionCommitted { .. }
+            | Event::KeyboardKeyPressedRaw(_)
+            | Event::KeyboardKeyReleasedRaw(_)
+            | Event::MouseScroll(..)
+            | Event::MouseMotionRaw(..)
+            | Event::MouseScrollRaw(..)
+            | Event::MouseButtonPressedRaw(_)
+            | Event::MouseButtonReleasedRaw(_) => true,
+            _ => false,
+        }
+    }
     pub fn canvas_size(&self) -> Extent2<u32> {
         self.canvas_size
     }
     pub fn previous_canvas_size(&self) -> Extent2<u32> {
         self.previous_canvas_size
     }
+    /// Physical pixels per logical pixel, e.g 2 on Retina displays. Mouse positions and `canvas_size()` are in
+    /// logical pixels, but rendering is done in physical pixels.
+    pub fn canvas_scale_factor(&self) -> f32 {
+        self.canvas_scale_factor
+    }
+    pub fn canvas_physical_size(&self) -> Extent2<u32> {
+        self.canvas_size.map(|x| (x as f32 * self.canvas_scale_factor).round() as u32)
+    }
     pub fn quit_requested(&self) -> bool {
         self.quit_requested
     }
@@ -89,6 +180,9 @@ impl System for InputUpdater {
             g.input.previous_canvas_size = mem::replace(&mut g.input.canvas_size, size);
         }
     }
+    fn on_window_scale_factor_changed(&mut self, g: &mut G, new_scale: f32) {
+        g.input.canvas_scale_factor = new_scale;
+    }
     fn on_mouse_enter(&mut self, g: &mut G) {
         g.input.is_mouse_inside = true;
     }
@@ -102,6 +196,9 @@ impl System for InputUpdater {
     }
     fn on_keyboard_focus_lost(&mut self, g: &mut G) {
         g.input.has_keyboard_focus = false;
+        // We won't be told about releases, so don't leave anything held down (e.g the camera would keep moving).
+        g.input.keys.clear();
+        g.input.mouse_buttons.clear();
     }
     fn on_mouse_motion(&mut self, g: &mut G, pos: Vec2<f64>) {
         if g.input.mouse_position != Some(pos) {
@@ -123,3 +220,49 @@ impl System for InputUpdater {
     fn on_key_raw(&mut self, _g: &mut G, _key: Key, _state: KeyState) {}
     fn on_text_char(&mut self, _g: &mut G, _char: char) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unfocused_keyboard_events_are_suppressed() {
+        let mut input = Input::new(Extent2::new(800, 600), 1.);
+        input.has_keyboard_focus = false;
+        input.start_text_input();
+        let ev = Event::KeyboardTextChar('z');
+        assert!(input.is_suppressed(&ev));
+        assert!(!input.is_suppressed(&Event::KeyboardFocusGained));
+        assert!(!input.is_suppressed(&Event::MouseButtonPressed(MouseButton::Left)));
+
+        input.has_keyboard_focus = true;
+        assert!(!input.is_suppressed(&ev));
+
+        input.has_keyboard_focus = false;
+        input.set_gates_unfocused_input(false);
+        assert!(!input.is_suppressed(&ev));
+    }
+    #[test]
+    fn text_chars_are_only_delivered_during_text_input() {
+        let mut input = Input::new(Extent2::new(800, 600), 1.);
+        let ev = Event::KeyboardTextChar('z');
+        assert!(input.is_suppressed(&ev));
+        input.start_text_input();
+        assert!(!input.is_suppressed(&ev));
+        input.stop_text_input();
+        assert!(input.is_suppressed(&ev));
+    }
+    #[test]
+    fn compositions_are_only_delivered_during_text_input() {
+        let mut input = Input::new(Extent2::new(800, 600), 1.);
+        let updated = Event::TextInputCompositionUpdated { text: "にほ".to_owned(), cursor: 2 };
+        let committed = Event::TextInputCompositionCommitted { text: "日本".to_owned() };
+        assert!(input.is_suppressed(&updated));
+        assert!(input.is_suppressed(&committed));
+        input.start_text_input();
+        assert!(!input.is_suppressed(&updated));
+        assert!(!input.is_suppressed(&committed));
+        input.has_keyboard_focus = false;
+        assert!(input.is_suppressed(&committed));
+    }
+}
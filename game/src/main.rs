@@ -73,12 +73,16 @@ pub mod light;
 pub mod material;
 pub mod eid;
 pub mod camera;
+pub mod frustum;
 pub mod xform;
 
 fn main() {
+    let env_result = early::setup_env();
     early::setup_log();
     early::setup_panic_hook();
-    early::setup_env();
+    if let Err(e) = env_result {
+        warn!("{}", e);
+    }
     fate::main_loop::run(&mut main_game::MainGame::new())
 }
 
@@ -7,17 +7,19 @@ use fate::main_loop::{MainSystem, Tick as MainLoopTick, Draw as MainLoopDraw};
 use fate::lab::duration_ext::DurationExt;
 use fate::lab::fps::{FpsManager, FpsCounter};
 use fate::mt;
+use fate::math::Rect;
 
 use g::G;
 use system::{System, Tick, Draw};
-use platform::{self, Platform, DmcPlatform, Sdl2Platform};
+use platform::{self, Platform, DmcPlatform, Sdl2Platform, FullscreenMode};
 use quit::{Quit, Quitter};
 use input::InputUpdater;
-use event::Event;
+use resources::Resources;
+use event::{Event, EventTypeMask};
 use r_gl45::{self, GLSystem};
 use gpu::GpuEndFrame;
 use gameplay::Gameplay;
-use mouse_cursor::MouseCursor;
+use mouse_cursor::{MouseCursor, CustomCursorId};
 use viewport::ViewportInputHandler;
 
 
@@ -25,7 +27,14 @@ use viewport::ViewportInputHandler;
 pub struct MainGame {
     platform: Box<Platform>,
     mouse_cursor: MouseCursor,
+    nb_custom_mouse_cursors: usize,
     is_mouse_cursor_visible: bool,
+    fullscreen_mode: FullscreenMode,
+    window_opacity: f32,
+    is_window_decorated: bool,
+    is_text_input_active: bool,
+    text_input_rect: Option<Rect<i32, u32>>,
+    event_filter: EventTypeMask,
     g: RefCell<G>,
     event_queue: VecDeque<Event>,
     systems: Vec<Box<System>>,
@@ -41,24 +50,39 @@ impl MainGame {
         info!("Using GL pixel format settings: {:#?}", platform_settings.gl_pixel_format_settings);
         info!("Using GL context settings: {:#?}", platform_settings.gl_context_settings);
 
-        let mut platform = match env::var("platform").as_ref().map(String::as_str) {
+        let platform = match env::var("platform").as_ref().map(String::as_str) {
             Ok("sdl2") => Box::new(Sdl2Platform::new(&platform_settings)) as Box<Platform>,
             _ => Box::new(DmcPlatform::new(&platform_settings)) as Box<Platform>,
         };
 
-        r_gl45::gl_setup::gl_setup(platform.as_ref());
+        if let Err(e) = r_gl45::gl_setup::gl_setup(platform.as_ref()) {
+            error!("{}", e);
+            panic!("Unsupported OpenGL context:\n{}", e);
+        }
 
-        let canvas_size = platform.canvas_size();
-        let (mt, threads) = mt::spawn_threads(3);
-        let mut g = G::new(canvas_size, mt.clone());
-        let systems: Vec<Box<System>> = vec![
+        let res = Resources::new().unwrap();
+
+        let mut game = Self::with_platform(platform, res, |g| vec![
             Box::new(InputUpdater::new()),
             Box::new(Quitter::default()),
             Box::new(ViewportInputHandler::new()),
-            Box::new(Gameplay::new(&mut g)),
-            Box::new(GLSystem::new()),
+            Box::new(Gameplay::new(g)),
+            Box::new(GLSystem::new(g)),
             Box::new(GpuEndFrame::new()),
-        ];
+        ]);
+        // The window was created that way, so there's nothing to apply.
+        game.is_window_decorated = platform_settings.decorated;
+        game.g.get_mut().is_window_decorated = platform_settings.decorated;
+        game
+    }
+    /// Creates the game on top of any platform and resources; `make_systems` is given the freshly created `G`.
+    pub fn with_platform<F>(mut platform: Box<Platform>, res: Resources, make_systems: F) -> Self
+        where F: FnOnce(&mut G) -> Vec<Box<System>>
+    {
+        let canvas_size = platform.canvas_size();
+        let (mt, threads) = mt::spawn_threads(3);
+        let mut g = G::new(canvas_size, platform.canvas_scale_factor(), mt.clone(), res);
+        let systems = make_systems(&mut g);
         let fps_manager = FpsManager {
             fps_counter: FpsCounter::with_interval(Duration::from_secs(1)),
             desired_fps_ceil: 64.,
@@ -66,11 +90,19 @@ impl MainGame {
         };
 
         platform.show_window();
+        platform.stop_text_input(); // Some platforms (e.g SDL2) start with text input enabled
  
         Self {
             platform,
             mouse_cursor: MouseCursor::default(),
+            nb_custom_mouse_cursors: 0,
             is_mouse_cursor_visible: true,
+            fullscreen_mode: FullscreenMode::default(),
+            window_opacity: 1.,
+            is_window_decorated: true,
+            is_text_input_active: false,
+            text_input_rect: None,
+            event_filter: EventTypeMask::ALL,
             g: RefCell::new(g),
             event_queue: VecDeque::with_capacity(2047),
             systems,
@@ -136,10 +168,21 @@ impl MainSystem for MainGame {
     }
     fn pump_events(&mut self) {
         self.pump_messages();
+        let event_filter = self.g.borrow().input.event_filter();
+        if self.event_filter != event_filter {
+            self.event_filter = event_filter;
+            self.platform.set_event_filter(event_filter);
+        }
         while let Some(ev) = self.poll_event() {
             self.event_queue.push_back(ev);
         }
+        if self.g.borrow().input.is_mouse_motion_coalesced() {
+            platform::coalesce_mouse_motion(&mut self.event_queue);
+        }
         while let Some(ev) = self.event_queue.pop_front() {
+            if self.g.borrow().input.is_suppressed(&ev) {
+                continue;
+            }
             for sys in self.systems.iter_mut() {
                 ev.dispatch(sys.as_mut(), &mut self.g.borrow_mut());
             }
@@ -173,8 +216,16 @@ impl MainSystem for MainGame {
             dt: dt_as_duration.to_f64_seconds() as _,
             smooth_dt: smooth_dt_as_duration.to_f64_seconds() as _,
             tick_progress: draw.tick_progress,
+            is_dt_clamped: g.frame_time_manager.is_dt_clamped(),
         };
 
+        while self.nb_custom_mouse_cursors < g.custom_mouse_cursors().len() {
+            let id = CustomCursorId(self.nb_custom_mouse_cursors);
+            if let Err(e) = self.platform.create_custom_mouse_cursor(id, &g.custom_mouse_cursors()[id.0]) {
+                error!("Could not create custom mouse cursor {:?}: {}", id, e);
+            }
+            self.nb_custom_mouse_cursors += 1;
+        }
         if self.mouse_cursor != g.mouse_cursor {
             self.mouse_cursor = g.mouse_cursor;
             self.platform.set_mouse_cursor(&g.mouse_cursor);
@@ -183,6 +234,57 @@ impl MainSystem for MainGame {
             self.is_mouse_cursor_visible = g.is_mouse_cursor_visible;
             self.platform.set_mouse_cursor_visible(g.is_mouse_cursor_visible);
         }
+        if self.fullscreen_mode != g.fullscreen_mode {
+            match self.platform.set_fullscreen(g.fullscreen_mode) {
+                Ok(()) => self.fullscreen_mode = g.fullscreen_mode,
+                Err(e) => {
+                    error!("Could not switch to {:?}: {}", g.fullscreen_mode, e);
+                    g.fullscreen_mode = self.fullscreen_mode;
+                },
+            }
+        }
+        if self.window_opacity != g.window_opacity {
+            match self.platform.set_window_opacity(g.window_opacity) {
+                Ok(()) => self.window_opacity = g.window_opacity,
+                Err(e) => {
+                    error!("Could not set the window opacity to {}: {}", g.window_opacity, e);
+                    g.window_opacity = self.window_opacity;
+                },
+            }
+        }
+        if self.is_window_decorated != g.is_window_decorated {
+            match self.platform.set_window_decorated(g.is_window_decorated) {
+                Ok(()) => self.is_window_decorated = g.is_window_decorated,
+                Err(e) => {
+                    error!("Could not {} window decorations: {}", if g.is_window_decorated { "add" } else { "remove" }, e);
+                    g.is_window_decorated = self.is_window_decorated;
+                },
+            }
+        }
+        if let Some(position) = g.take_pending_window_position() {
+            if let Err(e) = self.platform.set_window_position(position) {
+                error!("Could not move the window to {:?}: {}", position, e);
+            }
+        }
+        if let Some(text) = g.take_pending_clipboard_text() {
+            if let Err(e) = self.platform.set_clipboard_text(&text) {
+                error!("Could not copy text to the clipboard: {}", e);
+            }
+        }
+        if self.is_text_input_active != g.input.is_text_input_active() {
+            self.is_text_input_active = g.input.is_text_input_active();
+            if self.is_text_input_active {
+                self.platform.start_text_input();
+            } else {
+                self.platform.stop_text_input();
+            }
+        }
+        if self.text_input_rect != g.input.text_input_rect() {
+            self.text_input_rect = g.input.text_input_rect();
+            if let Some(rect) = self.text_input_rect {
+                self.platform.set_text_input_rect(rect);
+            }
+        }
 
         for sys in self.systems.iter_mut() {
             sys.draw(&mut g, &draw);
@@ -191,3 +293,213 @@ impl MainSystem for MainGame {
     }
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use std::cell::Cell;
+    use fate::math::{Extent2, Vec2};
+    use platform::{MockPlatform, MockPlatformCall};
+    use mouse_cursor::SystemCursor;
+    use system::{Key, KeyState, Keysym};
+
+    struct TestSystem {
+        nb_mouse_enters: Rc<Cell<u32>>,
+    }
+
+    impl System for TestSystem {
+        fn on_mouse_enter(&mut self, _g: &mut G) {
+            self.nb_mouse_enters.set(self.nb_mouse_enters.get() + 1);
+        }
+        fn tick(&mut self, g: &mut G, _t: &Tick) {
+            g.mouse_cursor = MouseCursor::System(SystemCursor::Hand);
+        }
+    }
+
+    #[test]
+    fn one_main_loop_iteration_against_mock_platform() {
+        let platform = MockPlatform::new(Extent2::new(320, 240));
+        let events = platform.events.clone();
+        let calls = platform.calls.clone();
+        let nb_mouse_enters = Rc::new(Cell::new(0));
+
+        let mut game = {
+            let nb_mouse_enters = nb_mouse_enters.clone();
+            MainGame::with_platform(Box::new(platform), Resources::empty().unwrap(), move |_| vec![Box::new(TestSystem { nb_mouse_enters }) as Box<System>])
+        };
+        assert_eq!(game.g.borrow().input.canvas_size(), Extent2::new(320, 240));
+        assert_eq!(*calls.borrow(), vec![MockPlatformCall::ShowWindow, MockPlatformCall::StopTextInput]);
+        calls.borrow_mut().clear();
+
+        events.borrow_mut().push_back(Event::MouseEnter);
+        game.begin_main_loop_iteration();
+        game.pump_events();
+        game.tick(&MainLoopTick { dt: Duration::from_millis(16) });
+        game.draw(&MainLoopDraw { tick_progress: 0. });
+        game.end_main_loop_iteration();
+
+        assert_eq!(nb_mouse_enters.get(), 1);
+        assert!(events.borrow().is_empty());
+        assert_eq!(*calls.borrow(), vec![
+            MockPlatformCall::SetMouseCursor(MouseCursor::System(SystemCursor::Hand)),
+            MockPlatformCall::GLSwapBuffers,
+        ]);
+    }
+
+    /// A game without systems, whose platform calls made so far were cleared.
+    fn mock_game() -> (MainGame, Rc<RefCell<Vec<MockPlatformCall>>>) {
+        let platform = MockPlatform::new(Extent2::new(320, 240));
+        let calls = platform.calls.clone();
+        let game = MainGame::with_platform(Box::new(platform), Resources::empty().unwrap(), |_| vec![]);
+        calls.borrow_mut().clear();
+        (game, calls)
+    }
+
+    #[test]
+    fn window_opacity_set_in_g_reaches_the_platform() {
+        let (mut game, calls) = mock_game();
+
+        game.g.borrow_mut().window_opacity = 0.5;
+        game.draw(&MainLoopDraw { tick_progress: 0. });
+        game.draw(&MainLoopDraw { tick_progress: 0. });
+        assert_eq!(*calls.borrow(), vec![
+            MockPlatformCall::SetWindowOpacity(0.5),
+            MockPlatformCall::GLSwapBuffers,
+            MockPlatformCall::GLSwapBuffers,
+        ]);
+    }
+    #[test]
+    fn refused_window_opacity_is_reset_in_g() {
+        let (mut game, calls) = mock_game();
+
+        game.g.borrow_mut().window_opacity = 1.5;
+        game.draw(&MainLoopDraw { tick_progress: 0. });
+        assert_eq!(game.g.borrow().window_opacity, 1.);
+        assert_eq!(*calls.borrow(), vec![MockPlatformCall::GLSwapBuffers]);
+    }
+    #[test]
+    fn window_decorations_set_in_g_reach_the_platform() {
+        let (mut game, calls) = mock_game();
+
+        game.g.borrow_mut().is_window_decorated = false;
+        game.draw(&MainLoopDraw { tick_progress: 0. });
+        assert!(!game.g.borrow().is_window_decorated);
+        assert_eq!(*calls.borrow(), vec![MockPlatformCall::SetWindowDecorated(false), MockPlatformCall::GLSwapBuffers]);
+    }
+    #[test]
+    fn window_position_set_in_g_reaches_the_platform() {
+        let (mut game, calls) = mock_game();
+
+        game.g.borrow_mut().set_window_position(Vec2::new(100, 50));
+        game.draw(&MainLoopDraw { tick_progress: 0. });
+        game.draw(&MainLoopDraw { tick_progress: 0. });
+        assert_eq!(*calls.borrow(), vec![
+            MockPlatformCall::SetWindowPosition(Vec2::new(100, 50)),
+            MockPlatformCall::GLSwapBuffers,
+            MockPlatformCall::GLSwapBuffers,
+        ]);
+    }
+    #[test]
+    fn clipboard_text_set_in_g_reaches_the_platform() {
+        let (mut game, _) = mock_game();
+
+        game.g.borrow_mut().set_clipboard_text("first".to_owned());
+        game.g.borrow_mut().set_clipboard_text("second".to_owned());
+        assert_eq!(game.platform.clipboard_text(), None);
+        game.draw(&MainLoopDraw { tick_progress: 0. });
+        assert_eq!(game.platform.clipboard_text().as_ref().map(String::as_str), Some("second"));
+    }
+    struct MouseMotionCounter {
+        nb_mouse_motions: Rc<Cell<u32>>,
+    }
+
+    impl System for MouseMotionCounter {
+        fn on_mouse_motion(&mut self, _g: &mut G, _pos: Vec2<f64>) {
+            self.nb_mouse_motions.set(self.nb_mouse_motions.get() + 1);
+        }
+    }
+
+    #[test]
+    fn mouse_motion_coalescing_can_be_turned_off() {
+        let platform = MockPlatform::new(Extent2::new(320, 240));
+        let events = platform.events.clone();
+        let nb_mouse_motions = Rc::new(Cell::new(0));
+        let mut game = {
+            let nb_mouse_motions = nb_mouse_motions.clone();
+            MainGame::with_platform(Box::new(platform), Resources::empty().unwrap(), move |_| vec![Box::new(MouseMotionCounter { nb_mouse_motions }) as Box<System>])
+        };
+
+        let push_motions = || for x in 0..3 {
+            events.borrow_mut().push_back(Event::MouseMotion(x as _, 0.));
+        };
+        push_motions();
+        game.pump_events();
+        assert_eq!(nb_mouse_motions.get(), 1);
+
+        game.g.borrow_mut().input.set_mouse_motion_coalesced(false);
+        push_motions();
+        game.pump_events();
+        assert_eq!(nb_mouse_motions.get(), 1 + 3);
+    }
+    struct KeyAndScrollCounter {
+        nb_keys: Rc<Cell<u32>>,
+        nb_scrolls: Rc<Cell<u32>>,
+    }
+
+    impl System for KeyAndScrollCounter {
+        fn on_key(&mut self, _g: &mut G, _key: Key, _state: KeyState) {
+            self.nb_keys.set(self.nb_keys.get() + 1);
+        }
+        fn on_mouse_scroll(&mut self, _g: &mut G, _delta: Vec2<f64>) {
+            self.nb_scrolls.set(self.nb_scrolls.get() + 1);
+        }
+    }
+
+    #[test]
+    fn unfocused_keys_and_scrolls_are_dropped_until_focus_is_gained() {
+        let platform = MockPlatform::new(Extent2::new(320, 240));
+        let events = platform.events.clone();
+        let nb_keys = Rc::new(Cell::new(0));
+        let nb_scrolls = Rc::new(Cell::new(0));
+        let mut game = {
+            let counter = KeyAndScrollCounter { nb_keys: nb_keys.clone(), nb_scrolls: nb_scrolls.clone() };
+            MainGame::with_platform(Box::new(platform), Resources::empty().unwrap(), move |_| vec![Box::new(InputUpdater::new()) as Box<System>, Box::new(counter)])
+        };
+
+        let push_key_and_scroll = || {
+            events.borrow_mut().push_back(Event::KeyboardKeyPressed(Key { code: Default::default(), sym: Some(Keysym::Z) }));
+            events.borrow_mut().push_back(Event::MouseScroll(0., 1.));
+        };
+        // The window is assumed to have focus until told otherwise
+        push_key_and_scroll();
+        game.pump_events();
+        assert_eq!((nb_keys.get(), nb_scrolls.get()), (1, 1));
+
+        events.borrow_mut().push_back(Event::KeyboardFocusLost);
+        push_key_and_scroll();
+        game.pump_events();
+        assert_eq!((nb_keys.get(), nb_scrolls.get()), (1, 1));
+
+        events.borrow_mut().push_back(Event::KeyboardFocusGained);
+        push_key_and_scroll();
+        game.pump_events();
+        assert_eq!((nb_keys.get(), nb_scrolls.get()), (2, 2));
+    }
+    #[test]
+    fn scale_factor_changes_reach_the_input() {
+        let platform = MockPlatform::new(Extent2::new(320, 240));
+        let events = platform.events.clone();
+        let mut game = MainGame::with_platform(Box::new(platform), Resources::empty().unwrap(), |_| vec![Box::new(InputUpdater::new()) as Box<System>]);
+        assert_eq!(game.g.borrow().input.canvas_physical_size(), Extent2::new(320, 240));
+
+        events.borrow_mut().push_back(Event::WindowScaleFactorChanged { new_scale: 2. });
+        game.pump_events();
+
+        let g = game.g.borrow();
+        assert_eq!(g.input.canvas_scale_factor(), 2.);
+        assert_eq!(g.input.canvas_size(), Extent2::new(320, 240));
+        assert_eq!(g.input.canvas_physical_size(), Extent2::new(640, 480));
+    }
+}
@@ -4,6 +4,25 @@ use texture2d::Texture2DSelector as Tex2D;
 #[derive(Debug, Default, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct MaterialID(pub u32);
 
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum BlendMode {
+    Opaque,
+    /// Drawn after opaque geometry, sorted back-to-front, without writing to the depth buffer.
+    AlphaBlend,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Opaque
+    }
+}
+
+impl BlendMode {
+    pub fn is_opaque(&self) -> bool {
+        *self == BlendMode::Opaque
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct Material {
@@ -15,5 +34,8 @@ pub struct Material {
     pub roughness_mul: f32,
     pub roughness_map: Tex2D,
     pub ao_map: Tex2D,
+    /// How much the environment map (e.g the skybox) is reflected, from 0 to 1.
+    pub reflectivity: f32,
+    pub blend_mode: BlendMode,
 }
 
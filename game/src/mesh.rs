@@ -1,4 +1,4 @@
-use fate::math::{Vec3, Rgba, Vec2, Mat4};
+use fate::math::{Vec3, Vec4, Rgba, Vec2, Mat4, Aabb, AabbSetOps};
 use texture2d::Texture2DSelector as Tex2D;
 use fate::gx;
 
@@ -50,6 +50,21 @@ pub struct MeshInfo {
     pub i_material_index: Vec<u16>,
 }
 
+impl MeshInfo {
+    /// The non-instanced attributes for which this mesh actually provides data.
+    pub fn vertex_attribs(&self) -> VertexAttribSet {
+        let mut set = VertexAttribSet::default();
+        if !self.v_position.is_empty() { set.insert(VertexAttribIndex::Position); }
+        if !self.v_normal.is_empty() { set.insert(VertexAttribIndex::Normal); }
+        if !self.v_uv.is_empty() { set.insert(VertexAttribIndex::UV); }
+        set
+    }
+    /// The object-space bounding box of the vertex positions, or `None` if there are none.
+    pub fn bounds(&self) -> Option<Aabb<f32>> {
+        Aabb::from_points(self.v_position.iter().cloned())
+    }
+}
+
 /// Vertex attrib indices
 // NOTE: OpenGL mandates a minimum of 16.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -71,3 +86,63 @@ pub enum VertexAttribIndex {
     ModelMatrix = 11,
     MaterialIndex = 15,
 }
+
+impl VertexAttribIndex {
+    /// The constant value to use when a mesh doesn't provide this attribute.
+    pub fn default_value(&self) -> Vec4<f32> {
+        match *self {
+            VertexAttribIndex::Normal => Vec4::new(0., 0., 1., 0.),
+            VertexAttribIndex::Tangent => Vec4::new(1., 0., 0., 0.),
+            VertexAttribIndex::Bitangent => Vec4::new(0., 1., 0., 0.),
+            VertexAttribIndex::Color => Rgba::white().into(),
+            VertexAttribIndex::Weights => Vec4::new(1., 0., 0., 0.),
+            _ => Vec4::new(0., 0., 0., 1.), // Same as GL's
+        }
+    }
+}
+
+/// Presence bitmask of vertex attributes, indexed by `VertexAttribIndex`.
+#[derive(Debug, Default, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct VertexAttribSet(u16);
+
+impl VertexAttribSet {
+    pub fn bits(&self) -> u16 { self.0 }
+    pub fn contains(&self, i: VertexAttribIndex) -> bool {
+        self.0 & (1 << i as u16) != 0
+    }
+    pub fn insert(&mut self, i: VertexAttribIndex) {
+        self.0 |= 1 << i as u16;
+    }
+    pub fn remove(&mut self, i: VertexAttribIndex) {
+        self.0 &= !(1 << i as u16);
+    }
+    pub fn intersection(&self, other: Self) -> Self {
+        VertexAttribSet(self.0 & other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mesh_without_normals_falls_back_to_default_normal() {
+        let mesh = MeshInfo {
+            nb_vertices: 3,
+            nb_indices: 3,
+            topology: gx::Topology::Triangles,
+            indices: vec![0, 1, 2],
+            v_position: vec![Vec3::zero(), Vec3::unit_x(), Vec3::unit_y()],
+            v_normal: vec![],
+            v_uv: vec![],
+            i_model_matrix: vec![],
+            i_material_index: vec![],
+        };
+        let attribs = mesh.vertex_attribs();
+        assert!(attribs.contains(VertexAttribIndex::Position));
+        assert!(!attribs.contains(VertexAttribIndex::Normal));
+        assert!(!attribs.contains(VertexAttribIndex::UV));
+        assert_eq!(Vec3::from(VertexAttribIndex::Normal.default_value()).magnitude(), 1.);
+        assert_eq!(mesh.bounds(), Some(Aabb { min: Vec3::zero(), max: Vec3::new(1., 1., 0.) }));
+    }
+}
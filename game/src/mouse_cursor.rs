@@ -1,9 +1,12 @@
 use dmc;
+use fate::math::{Extent2, Vec2};
 pub use dmc::SystemCursor;
 
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum MouseCursor {
     System(dmc::SystemCursor),
+    /// Returned by `G::add_custom_mouse_cursor()`.
+    Custom(CustomCursorId),
 }
 
 impl Default for MouseCursor {
@@ -12,3 +15,51 @@ impl Default for MouseCursor {
     }
 }
 
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct CustomCursorId(pub usize);
+
+/// Application-provided cursor art, as row-major RGBA8 pixels (the top row comes first).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CursorImage {
+    pixels: Vec<u8>,
+    size: Extent2<u32>,
+    hotspot: Vec2<u32>,
+}
+
+impl CursorImage {
+    /// `hotspot` is the clicking point, in pixels from the top-left corner.
+    pub fn from_rgba(pixels: Vec<u8>, size: Extent2<u32>, hotspot: Vec2<u32>) -> Result<Self, String> {
+        let expected_len = size.w as usize * size.h as usize * 4;
+        if pixels.len() != expected_len {
+            return Err(format!("A {}x{} RGBA8 cursor needs {} bytes, but {} were given", size.w, size.h, expected_len, pixels.len()));
+        }
+        if hotspot.x >= size.w || hotspot.y >= size.h {
+            return Err(format!("The cursor's hotspot ({}, {}) is outside of its {}x{} image", hotspot.x, hotspot.y, size.w, size.h));
+        }
+        Ok(Self { pixels, size, hotspot })
+    }
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+    pub fn size(&self) -> Extent2<u32> {
+        self.size
+    }
+    pub fn hotspot(&self) -> Vec2<u32> {
+        self.hotspot
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_images_are_validated() {
+        let size = Extent2::new(4, 2);
+        assert!(CursorImage::from_rgba(vec![0; 4 * 2 * 4], size, Vec2::new(3, 1)).is_ok());
+        assert!(CursorImage::from_rgba(vec![0; 4 * 2 * 3], size, Vec2::zero()).is_err());
+        assert!(CursorImage::from_rgba(vec![0; 4 * 2 * 4], size, Vec2::new(4, 0)).is_err());
+        assert!(CursorImage::from_rgba(vec![0; 4 * 2 * 4], size, Vec2::new(0, 2)).is_err());
+    }
+}
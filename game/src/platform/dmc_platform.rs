@@ -1,17 +1,18 @@
 use std::os::raw::c_void;
-use std::collections::VecDeque;
-use super::{Platform, Settings};
-use event::Event;
-use mouse_cursor::MouseCursor;
+use super::{Platform, Settings, FullscreenMode, check_window_opacity};
+use event::{Event, EventQueue, EventTypeMask};
+use mouse_cursor::{MouseCursor, CursorImage, CustomCursorId};
 use dmc;
-use fate::math::{Vec2, Extent2};
+use fate::gx;
+use fate::math::{Vec2, Extent2, Rect};
 
 pub struct DmcPlatform {
     dmc: dmc::Context,
     window: dmc::Window,
     #[allow(dead_code)]
     gl_context: dmc::gl::GLContext,
-    pending_events: VecDeque<Event>,
+    pending_events: EventQueue,
+    is_text_input_active: bool,
 }
 
 impl DmcPlatform {
@@ -21,6 +22,7 @@ impl DmcPlatform {
             canvas_size,
             ref gl_pixel_format_settings,
             ref gl_context_settings,
+            decorated,
         } = settings;
 
         let dmc = dmc::Context::new().unwrap();
@@ -32,21 +34,37 @@ impl DmcPlatform {
 
         window.set_size(canvas_size).unwrap();
         window.set_title(title).unwrap();
+        if !decorated {
+            warn!("The dmc platform can't create borderless windows yet");
+        }
 
         let gl_context = window.create_gl_context(gl_context_settings).unwrap();
         window.make_gl_context_current(Some(&gl_context)).unwrap();
 
-        if let Err(_) = window.gl_set_swap_interval(dmc::gl::GLSwapInterval::LateSwapTearing) {
-            let _ = window.gl_set_swap_interval(dmc::gl::GLSwapInterval::VSync);
+        // FIXME: dmc doesn't tell which mode it actually engaged, so we find out by trial and error.
+        if window.gl_set_swap_interval(dmc::gl::GLSwapInterval::LateSwapTearing).is_ok() {
+            info!("Swap interval: late swap tearing");
+        } else if window.gl_set_swap_interval(dmc::gl::GLSwapInterval::VSync).is_ok() {
+            info!("Swap interval: VSync (late swap tearing is unavailable)");
+        } else {
+            warn!("Could not set the swap interval; keeping the driver's default");
         }
 
         Self {
             dmc, window, gl_context,
-            pending_events: VecDeque::with_capacity(8),
+            pending_events: EventQueue::new(),
+            is_text_input_active: false,
         }
     }
 }
 
+impl Drop for DmcPlatform {
+    fn drop(&mut self) {
+        // The GL context is dropped right after this.
+        gx::mark_context_destroyed();
+    }
+}
+
 impl Platform for DmcPlatform {
     fn show_window(&mut self) {
         self.window.show().unwrap();
@@ -54,6 +72,14 @@ impl Platform for DmcPlatform {
     fn canvas_size(&self) -> Extent2<u32> {
         self.window.canvas_size().unwrap()
     }
+    // FIXME: dmc doesn't know about HiDPI yet (windows are created with `high_dpi: false`), nor about display sizes.
+    // The scale factor is thus always 1, and `Event::WindowScaleFactorChanged` is never emitted.
+    fn canvas_physical_size(&self) -> Extent2<u32> {
+        self.canvas_size()
+    }
+    fn display_dpi(&self) -> Option<Vec2<f32>> {
+        None
+    }
     fn gl_swap_buffers(&mut self) {
         self.window.gl_swap_buffers().unwrap();
     }
@@ -62,13 +88,26 @@ impl Platform for DmcPlatform {
     }
     fn poll_event(&mut self) -> Option<Event> {
         self.pump_events();
-        self.pending_events.pop_front()
+        self.pending_events.poll()
+    }
+    fn set_event_filter(&mut self, allowed: EventTypeMask) {
+        self.pending_events.set_filter(allowed);
+    }
+    fn drain_events(&mut self) -> Vec<Event> {
+        self.pump_events();
+        self.pending_events.drain_all()
     }
     fn set_mouse_cursor(&mut self, mouse_cursor: &MouseCursor) {
         match *mouse_cursor {
             MouseCursor::System(c) => self.window.set_cursor(self.dmc.create_system_cursor(c).as_ref().unwrap()).unwrap(),
+            // Never created; see below
+            MouseCursor::Custom(_) => (),
         }
     }
+    // FIXME: dmc can't create cursors from pixels yet (XcursorImage on X11, CreateIconIndirect on Windows).
+    fn create_custom_mouse_cursor(&mut self, _id: CustomCursorId, _image: &CursorImage) -> Result<(), String> {
+        Err("The dmc platform doesn't support custom cursors yet".to_owned())
+    }
     fn set_mouse_cursor_visible(&mut self, visible: bool) {
         if visible {
             self.window.show_cursor().unwrap();
@@ -76,6 +115,51 @@ impl Platform for DmcPlatform {
             self.window.hide_cursor().unwrap();
         }
     }
+    // FIXME: dmc has no way to start/stop the IME yet, so we can only drop text events ourselves.
+    fn start_text_input(&mut self) {
+        self.is_text_input_active = true;
+    }
+    fn stop_text_input(&mut self) {
+        self.is_text_input_active = false;
+    }
+    // FIXME: dmc has no IME support yet (XIM on X11, TSF on Windows): text only comes as committed `KeyboardTextChar`s.
+    fn set_text_input_rect(&mut self, _rect: Rect<i32, u32>) {}
+    // FIXME: dmc windows can't switch to fullscreen yet.
+    fn set_fullscreen(&mut self, mode: FullscreenMode) -> Result<(), String> {
+        match mode {
+            FullscreenMode::Windowed => Ok(()),
+            _ => Err(format!("The dmc platform doesn't support {:?} yet", mode)),
+        }
+    }
+    // FIXME: dmc can't change window opacity yet (_NET_WM_WINDOW_OPACITY on X11, layered windows on Windows).
+    fn set_window_opacity(&mut self, alpha: f32) -> Result<(), String> {
+        check_window_opacity(alpha)?;
+        if alpha == 1. {
+            return Ok(());
+        }
+        Err("The dmc platform doesn't support window opacity yet".to_owned())
+    }
+    // FIXME: dmc can't toggle decorations yet (_MOTIF_WM_HINTS on X11, WS_CAPTION | WS_THICKFRAME on Windows).
+    fn set_window_decorated(&mut self, decorated: bool) -> Result<(), String> {
+        if decorated {
+            return Ok(());
+        }
+        Err("The dmc platform doesn't support borderless windows yet".to_owned())
+    }
+    // FIXME: dmc windows can't be moved around yet (XMoveWindow on X11, SetWindowPos on Windows).
+    fn window_position(&self) -> Result<Vec2<i32>, String> {
+        Err("The dmc platform doesn't support querying the window position yet".to_owned())
+    }
+    fn set_window_position(&mut self, _position: Vec2<i32>) -> Result<(), String> {
+        Err("The dmc platform doesn't support moving windows yet".to_owned())
+    }
+    // FIXME: dmc has no clipboard yet (selections on X11, OpenClipboard on Windows).
+    fn clipboard_text(&self) -> Option<String> {
+        None
+    }
+    fn set_clipboard_text(&mut self, _text: &str) -> Result<(), String> {
+        Err("The dmc platform doesn't support the clipboard yet".to_owned())
+    }
 }
 
 impl DmcPlatform {
@@ -86,6 +170,7 @@ impl DmcPlatform {
         }
     }
     fn pump_dmc_event(&mut self, ev: dmc::Event) {
+        let is_text_input_active = self.is_text_input_active;
         let mut push = |e| self.pending_events.push_back(e);
         match ev {
             dmc::Event::Quit => push(Event::Quit),
@@ -107,12 +192,16 @@ impl DmcPlatform {
             dmc::Event::KeyboardKeyPressed  { key,  is_repeat, .. } if !is_repeat => push(Event::KeyboardKeyPressed(key)),
             dmc::Event::KeyboardKeyReleasedRaw { key, .. } => push(Event::KeyboardKeyReleasedRaw(key)),
             dmc::Event::KeyboardKeyPressedRaw  { key, .. } => push(Event::KeyboardKeyPressedRaw(key)),
-            dmc::Event::KeyboardTextChar    { char, .. } => push(Event::KeyboardTextChar(char)),
-            dmc::Event::KeyboardTextString  { ref text, .. } => {
+            dmc::Event::KeyboardTextChar    { char, .. } if is_text_input_active => push(Event::KeyboardTextChar(char)),
+            dmc::Event::KeyboardTextString  { ref text, .. } if is_text_input_active => {
                 for char in text.chars() {
                     push(Event::KeyboardTextChar(char));
                 }
             },
+            // NOTE: dmc doesn't report touch input yet (XI2 touch sequences on X11, WM_TOUCH on Windows),
+            // so the Event::Touch* variants only come from the SDL2 platform for now.
+            // The same goes for Event::FilesDropped (XDND on X11, IDropTarget on Windows),
+            // and Event::Window{Minimized,Maximized,Restored}.
             _ => (),
         }
     }
@@ -0,0 +1,126 @@
+use std::os::raw::c_void;
+use std::ptr;
+use std::rc::Rc;
+use std::cell::RefCell;
+use super::{Platform, FullscreenMode, check_window_opacity};
+use fate::math::{Extent2, Vec2, Rect};
+use event::{Event, EventQueue, EventTypeMask};
+use mouse_cursor::{MouseCursor, CursorImage, CustomCursorId};
+
+/// A call to the platform, as recorded by `MockPlatform`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MockPlatformCall {
+    ShowWindow,
+    GLSwapBuffers,
+    SetMouseCursor(MouseCursor),
+    CreateCustomMouseCursor(CustomCursorId),
+    SetMouseCursorVisible(bool),
+    StartTextInput,
+    StopTextInput,
+    SetTextInputRect(Rect<i32, u32>),
+    SetFullscreen(FullscreenMode),
+    SetWindowOpacity(f32),
+    SetWindowDecorated(bool),
+    SetWindowPosition(Vec2<i32>),
+    SetEventFilter(EventTypeMask),
+}
+
+/// A headless platform, for tests.
+///
+/// Events and calls are shared via `Rc`s, so that tests can still inject events
+/// and inspect calls once the platform is boxed and owned by the main loop.
+#[derive(Debug, Default)]
+pub struct MockPlatform {
+    pub canvas_size: Extent2<u32>,
+    pub events: Rc<RefCell<EventQueue>>,
+    pub calls: Rc<RefCell<Vec<MockPlatformCall>>>,
+    pub clipboard_text: Rc<RefCell<Option<String>>>,
+    pub window_position: Vec2<i32>,
+}
+
+impl MockPlatform {
+    pub fn new(canvas_size: Extent2<u32>) -> Self {
+        Self { canvas_size, .. Default::default() }
+    }
+    fn record(&self, call: MockPlatformCall) {
+        self.calls.borrow_mut().push(call);
+    }
+}
+
+impl Platform for MockPlatform {
+    fn show_window(&mut self) {
+        self.record(MockPlatformCall::ShowWindow);
+    }
+    fn canvas_size(&self) -> Extent2<u32> {
+        self.canvas_size
+    }
+    // Tests change the scale factor by injecting `Event::WindowScaleFactorChanged`.
+    fn canvas_physical_size(&self) -> Extent2<u32> {
+        self.canvas_size
+    }
+    fn display_dpi(&self) -> Option<Vec2<f32>> {
+        None
+    }
+    fn gl_swap_buffers(&mut self) {
+        self.record(MockPlatformCall::GLSwapBuffers);
+    }
+    fn gl_get_proc_address(&self, _proc_name: &str) -> *const c_void {
+        ptr::null()
+    }
+    fn set_mouse_cursor(&mut self, mouse_cursor: &MouseCursor) {
+        self.record(MockPlatformCall::SetMouseCursor(*mouse_cursor));
+    }
+    fn create_custom_mouse_cursor(&mut self, id: CustomCursorId, _image: &CursorImage) -> Result<(), String> {
+        self.record(MockPlatformCall::CreateCustomMouseCursor(id));
+        Ok(())
+    }
+    fn set_mouse_cursor_visible(&mut self, visible: bool) {
+        self.record(MockPlatformCall::SetMouseCursorVisible(visible));
+    }
+    fn start_text_input(&mut self) {
+        self.record(MockPlatformCall::StartTextInput);
+    }
+    fn stop_text_input(&mut self) {
+        self.record(MockPlatformCall::StopTextInput);
+    }
+    fn set_text_input_rect(&mut self, rect: Rect<i32, u32>) {
+        self.record(MockPlatformCall::SetTextInputRect(rect));
+    }
+    fn set_fullscreen(&mut self, mode: FullscreenMode) -> Result<(), String> {
+        self.record(MockPlatformCall::SetFullscreen(mode));
+        Ok(())
+    }
+    fn set_window_opacity(&mut self, alpha: f32) -> Result<(), String> {
+        check_window_opacity(alpha)?;
+        self.record(MockPlatformCall::SetWindowOpacity(alpha));
+        Ok(())
+    }
+    fn set_window_decorated(&mut self, decorated: bool) -> Result<(), String> {
+        self.record(MockPlatformCall::SetWindowDecorated(decorated));
+        Ok(())
+    }
+    fn window_position(&self) -> Result<Vec2<i32>, String> {
+        Ok(self.window_position)
+    }
+    fn set_window_position(&mut self, position: Vec2<i32>) -> Result<(), String> {
+        self.record(MockPlatformCall::SetWindowPosition(position));
+        Ok(())
+    }
+    fn clipboard_text(&self) -> Option<String> {
+        self.clipboard_text.borrow().clone()
+    }
+    fn set_clipboard_text(&mut self, text: &str) -> Result<(), String> {
+        *self.clipboard_text.borrow_mut() = Some(text.to_owned());
+        Ok(())
+    }
+    fn poll_event(&mut self) -> Option<Event> {
+        self.events.borrow_mut().poll()
+    }
+    fn set_event_filter(&mut self, allowed: EventTypeMask) {
+        self.record(MockPlatformCall::SetEventFilter(allowed));
+        self.events.borrow_mut().set_filter(allowed);
+    }
+    fn drain_events(&mut self) -> Vec<Event> {
+        self.events.borrow_mut().drain_all()
+    }
+}
@@ -1,22 +1,125 @@
 use std::os::raw::c_void;
-use fate::math::Extent2;
+use std::collections::VecDeque;
+use fate::math::{Extent2, Vec2, Rect};
 use dmc;
-use event::Event;
-use mouse_cursor::MouseCursor;
+use event::{Event, EventTypeMask};
+use mouse_cursor::{MouseCursor, CursorImage, CustomCursorId};
 
 pub mod sdl2_platform;
 pub use self::sdl2_platform::Sdl2Platform;
 pub mod dmc_platform;
 pub use self::dmc_platform::DmcPlatform;
+#[cfg(test)]
+pub mod mock_platform;
+#[cfg(test)]
+pub use self::mock_platform::{MockPlatform, MockPlatformCall};
 
 pub trait Platform {
     fn canvas_size(&self) -> Extent2<u32>;
+    /// The canvas size in physical pixels, which is larger than `canvas_size()` on HiDPI displays.
+    fn canvas_physical_size(&self) -> Extent2<u32>;
+    /// The ratio of physical to logical pixels, e.g 2 on Retina displays.
+    /// When it changes, the platform emits `Event::WindowScaleFactorChanged`.
+    fn canvas_scale_factor(&self) -> f32 {
+        let logical = self.canvas_size();
+        if logical.w == 0 {
+            return 1.;
+        }
+        self.canvas_physical_size().w as f32 / logical.w as f32
+    }
+    /// Horizontal and vertical dots per inch of the display the window is on, if known.
+    fn display_dpi(&self) -> Option<Vec2<f32>>;
     fn show_window(&mut self);
     fn gl_get_proc_address(&self, proc: &str) -> *const c_void;
     fn gl_swap_buffers(&mut self);
+    /// The oldest pending event whose type is allowed by the event filter.
     fn poll_event(&mut self) -> Option<Event>;
+    /// Events of other types stay queued: `poll_event` skips them, but `drain_events` still returns them.
+    fn set_event_filter(&mut self, allowed: EventTypeMask);
+    /// All pending events, in order, regardless of the event filter.
+    fn drain_events(&mut self) -> Vec<Event>;
     fn set_mouse_cursor(&mut self, mouse_cursor: &MouseCursor);
+    /// Called once per `id`, before any `set_mouse_cursor(&MouseCursor::Custom(id))`.
+    fn create_custom_mouse_cursor(&mut self, id: CustomCursorId, image: &CursorImage) -> Result<(), String>;
     fn set_mouse_cursor_visible(&mut self, visible: bool);
+    fn start_text_input(&mut self);
+    fn stop_text_input(&mut self);
+    /// Where the IME's candidate window should appear, in canvas pixels.
+    fn set_text_input_rect(&mut self, rect: Rect<i32, u32>);
+    fn set_fullscreen(&mut self, mode: FullscreenMode) -> Result<(), String>;
+    /// From 0 (invisible) to 1 (opaque); other values are refused, see `check_window_opacity()`.
+    /// Also fails if the window system can't do it (e.g X11 without a compositing WM).
+    fn set_window_opacity(&mut self, alpha: f32) -> Result<(), String>;
+    /// Adds or removes the title bar and borders, even while the window is visible.
+    fn set_window_decorated(&mut self, decorated: bool) -> Result<(), String>;
+    /// The window's top-left corner, in desktop coordinates (so it tells which display the window is on).
+    fn window_position(&self) -> Result<Vec2<i32>, String>;
+    /// `CENTERED_WINDOW_POSITION` centers the window on its current display.
+    fn set_window_position(&mut self, position: Vec2<i32>) -> Result<(), String>;
+    fn clipboard_text(&self) -> Option<String>;
+    fn set_clipboard_text(&mut self, text: &str) -> Result<(), String>;
+}
+
+/// Window opacities go from 0 (invisible) to 1 (opaque); anything else (including NaN) is refused.
+pub fn check_window_opacity(alpha: f32) -> Result<(), String> {
+    if alpha >= 0. && alpha <= 1. {
+        Ok(())
+    } else {
+        Err(format!("Window opacity must be between 0 and 1, not {}", alpha))
+    }
+}
+
+pub const CENTERED_WINDOW_POSITION: Vec2<i32> = Vec2 { x: ::std::i32::MIN, y: ::std::i32::MIN };
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum FullscreenMode {
+    Windowed,
+    /// Exclusive fullscreen, using the closest supported video mode.
+    Fullscreen,
+    /// Borderless window covering the whole screen, at the desktop's resolution.
+    DesktopFullscreen,
+}
+
+impl Default for FullscreenMode {
+    fn default() -> Self {
+        FullscreenMode::Windowed
+    }
+}
+
+/// Merges each run of consecutive mouse motion events into at most one `MouseMotion` (keeping the latest position)
+/// and one `MouseMotionRaw` (summing the displacements), so that systems get at most one motion call per run.
+pub fn coalesce_mouse_motion(events: &mut VecDeque<Event>) {
+    let mut coalesced = VecDeque::with_capacity(events.len());
+    let mut motion_index = None;
+    let mut motion_raw_index = None;
+
+    for ev in events.drain(..) {
+        match ev {
+            Event::MouseMotion(..) => match motion_index {
+                Some(i) => coalesced[i] = ev,
+                None => {
+                    motion_index = Some(coalesced.len());
+                    coalesced.push_back(ev);
+                },
+            },
+            Event::MouseMotionRaw(dx, dy) => match motion_raw_index {
+                Some(i) => if let Event::MouseMotionRaw(ref mut x, ref mut y) = coalesced[i] {
+                    *x += dx;
+                    *y += dy;
+                },
+                None => {
+                    motion_raw_index = Some(coalesced.len());
+                    coalesced.push_back(ev);
+                },
+            },
+            _ => {
+                motion_index = None;
+                motion_raw_index = None;
+                coalesced.push_back(ev);
+            },
+        }
+    }
+    *events = coalesced;
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +128,8 @@ pub struct Settings {
     pub canvas_size: Extent2<u32>,
     pub gl_pixel_format_settings: dmc::gl::GLPixelFormatSettings,
     pub gl_context_settings: dmc::gl::GLContextSettings,
+    /// Whether the window has a title bar and borders.
+    pub decorated: bool,
 }
 
 impl Settings {
@@ -56,6 +161,38 @@ impl Settings {
                 forward_compatible: true,
                 robust_access: None,
             },
+            decorated: true,
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consecutive_mouse_motions_coalesce() {
+        let mut events: VecDeque<_> = vec![
+            Event::MouseMotion(1., 2.),
+            Event::MouseMotionRaw(1., -1.),
+            Event::MouseMotion(3., 4.),
+            Event::MouseMotionRaw(2., -2.),
+            Event::MouseMotion(5., 6.),
+            Event::MouseMotionRaw(4., -4.),
+        ].into_iter().collect();
+        coalesce_mouse_motion(&mut events);
+        assert_eq!(events, vec![Event::MouseMotion(5., 6.), Event::MouseMotionRaw(7., -7.)].into_iter().collect::<VecDeque<_>>());
+    }
+    #[test]
+    fn other_events_split_motion_runs() {
+        let mut events: VecDeque<_> = vec![
+            Event::MouseMotionRaw(1., 0.),
+            Event::MouseMotionRaw(1., 0.),
+            Event::MouseEnter,
+            Event::MouseMotionRaw(1., 0.),
+        ].into_iter().collect();
+        coalesce_mouse_motion(&mut events);
+        assert_eq!(events, vec![Event::MouseMotionRaw(2., 0.), Event::MouseEnter, Event::MouseMotionRaw(1., 0.)].into_iter().collect::<VecDeque<_>>());
+    }
+}
@@ -1,13 +1,19 @@
 use std::os::raw::c_void;
-use super::{Platform, Settings};
-use fate::math::Extent2;
-use event::Event;
-use mouse_cursor::MouseCursor;
+use std::path::PathBuf;
+use std::collections::HashMap;
+use super::{Platform, Settings, FullscreenMode, CENTERED_WINDOW_POSITION, check_window_opacity};
+use fate::math::{Extent2, Vec2, Rect};
+use fate::gx;
+use event::{Event, EventQueue, EventTypeMask};
+use mouse_cursor::{MouseCursor, CursorImage, CustomCursorId};
 use dmc;
 use sdl2::{self, Sdl, EventPump};
 use sdl2::event::{Event as Sdl2Event, WindowEvent};
-use sdl2::video::{Window, GLContext};
+use sdl2::video::{Window, GLContext, FullscreenType, WindowPos};
 use sdl2::mouse::{Cursor as Sdl2Cursor, SystemCursor as Sdl2SystemCursor};
+use sdl2::rect::Rect as Sdl2Rect;
+use sdl2::surface::Surface;
+use sdl2::pixels::PixelFormatEnum;
 
 pub struct Sdl2Platform {
     sdl2: Sdl,
@@ -15,6 +21,12 @@ pub struct Sdl2Platform {
     #[allow(dead_code)]
     gl_context: GLContext,
     event_pump: EventPump,
+    // Polled while batching dropped files, but not handled yet
+    pending_event: Option<Sdl2Event>,
+    event_queue: EventQueue,
+    // To tell when it changes, since SDL2 has no event for it
+    scale_factor: f32,
+    custom_cursors: HashMap<CustomCursorId, Sdl2Cursor>,
 }
 
 impl Sdl2Platform {
@@ -46,6 +58,7 @@ impl Sdl2Platform {
                 forward_compatible,
                 robust_access: _,
             },
+            decorated,
         } = settings;
 
         let sdl2 = sdl2::init().unwrap();
@@ -89,18 +102,31 @@ impl Sdl2Platform {
         // This one could be interesting someday
         // gl_attr.set_framebuffer_srgb_compatible(value: bool);
 
-        let window = video_subsystem.window(title, w, h)
+        let mut window_builder = video_subsystem.window(title, w, h);
+        window_builder
             .opengl()
+            .allow_highdpi()
             .position_centered()
-            .resizable()
-            .build()
-            .expect("Could not create window");
+            .resizable();
+        if !decorated {
+            window_builder.borderless();
+        }
+        let window = window_builder.build().expect("Could not create window");
         let gl_context = window.gl_create_context().unwrap();
         let event_pump = sdl2.event_pump().unwrap();
 
-        Self {
-            sdl2, window, gl_context, event_pump
-        }
+        let mut platform = Self {
+            sdl2, window, gl_context, event_pump, pending_event: None, event_queue: EventQueue::new(), scale_factor: 1., custom_cursors: HashMap::new(),
+        };
+        platform.scale_factor = platform.canvas_scale_factor();
+        platform
+    }
+}
+
+impl Drop for Sdl2Platform {
+    fn drop(&mut self) {
+        // The GL context is dropped right after this.
+        gx::mark_context_destroyed();
     }
 }
 
@@ -111,6 +137,14 @@ impl Platform for Sdl2Platform {
     fn canvas_size(&self) -> Extent2<u32> {
         self.window.size().into()
     }
+    fn canvas_physical_size(&self) -> Extent2<u32> {
+        self.window.drawable_size().into()
+    }
+    fn display_dpi(&self) -> Option<Vec2<f32>> {
+        let index = self.window.display_index().ok()?;
+        let (_diagonal, h, v) = self.sdl2.video().ok()?.display_dpi(index).ok()?;
+        Some(Vec2::new(h, v))
+    }
     fn gl_swap_buffers(&mut self) {
         self.window.gl_swap_window();
     }
@@ -124,22 +158,160 @@ impl Platform for Sdl2Platform {
                 let c = Sdl2Cursor::from_system(s).expect("Failed to create SDL2 cursor");
                 c.set()
             },
+            MouseCursor::Custom(id) => if let Some(c) = self.custom_cursors.get(&id) {
+                c.set()
+            },
         }
     }
+    fn create_custom_mouse_cursor(&mut self, id: CustomCursorId, image: &CursorImage) -> Result<(), String> {
+        let Extent2 { w, h } = image.size();
+        let mut pixels = image.pixels().to_vec();
+        // ABGR8888 is packed, so on little-endian, it is R, G, B, A in memory.
+        let surface = Surface::from_data(&mut pixels, w, h, w * 4, PixelFormatEnum::ABGR8888)?;
+        let hotspot = image.hotspot();
+        let cursor = Sdl2Cursor::from_surface(surface, hotspot.x as _, hotspot.y as _)?;
+        self.custom_cursors.insert(id, cursor);
+        Ok(())
+    }
     fn set_mouse_cursor_visible(&mut self, visible: bool) {
         self.sdl2.mouse().show_cursor(visible)
     }
+    fn start_text_input(&mut self) {
+        self.sdl2.video().unwrap().text_input().start()
+    }
+    fn stop_text_input(&mut self) {
+        self.sdl2.video().unwrap().text_input().stop()
+    }
+    fn set_text_input_rect(&mut self, rect: Rect<i32, u32>) {
+        let Rect { x, y, w, h } = rect;
+        self.sdl2.video().unwrap().text_input().set_rect(&Sdl2Rect::new(x, y, w, h))
+    }
+    // SDL2 picks the closest display mode, and restores the window's size and position by itself.
+    fn set_fullscreen(&mut self, mode: FullscreenMode) -> Result<(), String> {
+        self.window.set_fullscreen(match mode {
+            FullscreenMode::Windowed => FullscreenType::Off,
+            FullscreenMode::Fullscreen => FullscreenType::True,
+            FullscreenMode::DesktopFullscreen => FullscreenType::Desktop,
+        })
+    }
+    fn set_window_opacity(&mut self, alpha: f32) -> Result<(), String> {
+        check_window_opacity(alpha)?;
+        self.window.set_opacity(alpha)
+    }
+    fn set_window_decorated(&mut self, decorated: bool) -> Result<(), String> {
+        self.window.set_bordered(decorated);
+        Ok(())
+    }
+    fn window_position(&self) -> Result<Vec2<i32>, String> {
+        let (x, y) = self.window.position();
+        Ok(Vec2::new(x, y))
+    }
+    fn set_window_position(&mut self, position: Vec2<i32>) -> Result<(), String> {
+        if position == CENTERED_WINDOW_POSITION {
+            self.window.set_position(WindowPos::Centered, WindowPos::Centered);
+        } else {
+            self.window.set_position(WindowPos::Positioned(position.x), WindowPos::Positioned(position.y));
+        }
+        Ok(())
+    }
+    fn clipboard_text(&self) -> Option<String> {
+        let clipboard = self.sdl2.video().ok()?.clipboard();
+        if !clipboard.has_clipboard_text() {
+            return None;
+        }
+        clipboard.clipboard_text().ok()
+    }
+    fn set_clipboard_text(&mut self, text: &str) -> Result<(), String> {
+        self.sdl2.video()?.clipboard().set_clipboard_text(text)
+    }
     fn poll_event(&mut self) -> Option<Event> {
-        match self.event_pump.poll_event()? {
-            Sdl2Event::Quit {..} => Some(Event::Quit),
-            Sdl2Event::MouseMotion { x, y, .. } => Some(Event::MouseMotion(x as _, y as _)),
-            Sdl2Event::Window { win_event, .. } => match win_event {
-                WindowEvent::Resized(w, h)
-                | WindowEvent::SizeChanged(w, h) => Some(Event::CanvasResized(w as _, h as _)),
-                _ => None,
+        self.pump_events();
+        self.event_queue.poll()
+    }
+    fn set_event_filter(&mut self, allowed: EventTypeMask) {
+        self.event_queue.set_filter(allowed);
+    }
+    fn drain_events(&mut self) -> Vec<Event> {
+        self.pump_events();
+        self.event_queue.drain_all()
+    }
+}
+
+impl Sdl2Platform {
+    fn pump_events(&mut self) {
+        loop {
+            let e = match self.pending_event.take() {
+                Some(e) => e,
+                None => match self.event_pump.poll_event() {
+                    Some(e) => e,
+                    None => break,
+                },
+            };
+            if let Some(ev) = self.translate_event(e) {
+                self.event_queue.push_back(ev);
+            }
+        }
+        // The scale can't be told while minimized, since the canvas is then zero-sized.
+        if self.canvas_size().w != 0 {
+            let new_scale = self.canvas_scale_factor();
+            if new_scale != self.scale_factor {
+                self.scale_factor = new_scale;
+                self.event_queue.push_back(Event::WindowScaleFactorChanged { new_scale });
+            }
+        }
+    }
+    fn translate_event(&mut self, e: Sdl2Event) -> Option<Event> {
+        match e {
+            // SDL2 normalizes finger positions to the window's size, and never cancels touches.
+            Sdl2Event::FingerDown { finger_id, x, y, pressure, .. } => Some(Event::TouchBegan { id: finger_id as _, position: self.finger_position(x, y), pressure }),
+            Sdl2Event::FingerMotion { finger_id, x, y, pressure, .. } => Some(Event::TouchMoved { id: finger_id as _, position: self.finger_position(x, y), pressure }),
+            Sdl2Event::FingerUp { finger_id, x, y, pressure, .. } => Some(Event::TouchEnded { id: finger_id as _, position: self.finger_position(x, y), pressure }),
+            Sdl2Event::DropFile { filename, .. } => Some(self.files_dropped(filename)),
+            e => translate_stateless_event(e),
+        }
+    }
+    fn finger_position(&self, x: f32, y: f32) -> Vec2<f32> {
+        let size = self.canvas_size().map(|x| x as f32);
+        Vec2::new(x * size.w, y * size.h)
+    }
+    // SDL2 sends one DropFile per file, back-to-back, and no position; the mouse is where they were dropped.
+    fn files_dropped(&mut self, first: String) -> Event {
+        let mut paths = vec![PathBuf::from(first)];
+        loop {
+            match self.event_pump.poll_event() {
+                Some(Sdl2Event::DropFile { filename, .. }) => paths.push(PathBuf::from(filename)),
+                e => {
+                    self.pending_event = e;
+                    break;
+                },
             }
+        }
+        let mouse = self.event_pump.mouse_state();
+        let position = Vec2::new(mouse.x().max(0) as u32, mouse.y().max(0) as u32);
+        Event::FilesDropped { paths, position }
+    }
+}
+
+// The events that can be translated without querying the window or the event pump.
+fn translate_stateless_event(e: Sdl2Event) -> Option<Event> {
+    match e {
+        Sdl2Event::Quit {..} => Some(Event::Quit),
+        Sdl2Event::MouseMotion { x, y, .. } => Some(Event::MouseMotion(x as _, y as _)),
+        Sdl2Event::Window { win_event, .. } => match win_event {
+            WindowEvent::Resized(w, h)
+            | WindowEvent::SizeChanged(w, h) => Some(Event::CanvasResized(w as _, h as _)),
+            WindowEvent::Minimized => Some(Event::WindowMinimized),
+            WindowEvent::Maximized => Some(Event::WindowMaximized),
+            WindowEvent::Restored => Some(Event::WindowRestored),
+            WindowEvent::Enter => Some(Event::MouseEnter),
+            WindowEvent::Leave => Some(Event::MouseLeave),
+            WindowEvent::FocusGained => Some(Event::KeyboardFocusGained),
+            WindowEvent::FocusLost => Some(Event::KeyboardFocusLost),
             _ => None,
         }
+        Sdl2Event::TextEditing { text, start, .. } => Some(Event::TextInputCompositionUpdated { text, cursor: start as _ }),
+        Sdl2Event::TextInput { text, .. } => Some(Event::TextInputCompositionCommitted { text }),
+        _ => None,
     }
 }
 
@@ -159,4 +331,37 @@ fn dmc_to_sdl2_system_cursor(s: dmc::SystemCursor) -> Option<Sdl2SystemCursor> {
         dmc::SystemCursor::Hand => Sdl2SystemCursor::Hand,
         _ => return None,
     })
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input::Input;
+
+    fn window_event(win_event: WindowEvent) -> Sdl2Event {
+        Sdl2Event::Window { timestamp: 0, window_id: 1, win_event }
+    }
+
+    #[test]
+    fn focus_and_hover_changes_are_translated() {
+        assert_eq!(translate_stateless_event(window_event(WindowEvent::FocusGained)), Some(Event::KeyboardFocusGained));
+        assert_eq!(translate_stateless_event(window_event(WindowEvent::FocusLost)), Some(Event::KeyboardFocusLost));
+        assert_eq!(translate_stateless_event(window_event(WindowEvent::Enter)), Some(Event::MouseEnter));
+        assert_eq!(translate_stateless_event(window_event(WindowEvent::Leave)), Some(Event::MouseLeave));
+    }
+    #[test]
+    fn ime_compositions_reach_a_fresh_input_during_text_input() {
+        let editing = Sdl2Event::TextEditing { timestamp: 0, window_id: 1, text: "にほ".to_owned(), start: 2, length: 0 };
+        let text_input = Sdl2Event::TextInput { timestamp: 0, window_id: 1, text: "日本".to_owned() };
+        let updated = translate_stateless_event(editing).unwrap();
+        let committed = translate_stateless_event(text_input).unwrap();
+        assert_eq!(updated, Event::TextInputCompositionUpdated { text: "にほ".to_owned(), cursor: 2 });
+        assert_eq!(committed, Event::TextInputCompositionCommitted { text: "日本".to_owned() });
+
+        // No focus event has been received yet
+        let mut input = Input::new(Extent2::new(800, 600), 1.);
+        input.start_text_input();
+        assert!(!input.is_suppressed(&updated));
+        assert!(!input.is_suppressed(&committed));
+    }
+}
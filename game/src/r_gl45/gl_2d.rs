@@ -0,0 +1,412 @@
+use std::mem;
+use std::ops::Range;
+use std::collections::HashMap;
+use fate::math::{Vec2, Extent2, Extent3, Rect, Rgba};
+use fate::font::{Atlas, AtlasGlyphInfo};
+use fate::img::AsSlice;
+use fate::gx::{self, Object, gl::{self, types::*}};
+
+use camera::pixel_ortho_matrix;
+use resources::FontID;
+
+// Immediate-mode 2D drawing, for GUIs and debug overlays.
+// All positions are in pixels, relative to the top-left corner of the current viewport.
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[repr(C)]
+struct GL2DVertex {
+    position: Vec2<f32>,
+    texcoords: Vec2<f32>,
+}
+
+assert_eq_size!(gl_2d_vertex_size; GL2DVertex, [f32; 4]);
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum GL2DMode {
+    Color = 0,
+    Texture = 1,
+    Glyph = 2,
+}
+
+/// Where a font's glyphs are, in the font atlas array.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasInfo {
+    pub layer: u32,
+    pub size: Extent2<u32>,
+    pub glyphs: HashMap<char, AtlasGlyphInfo>,
+    pub kerning_px: HashMap<(char, char), i16>,
+    pub height_px: u32,
+}
+
+/// Where a line of laid out text went.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineMetrics {
+    /// The line's bytes in the text, without the `'\n'`.
+    pub byte_range: Range<usize>,
+    pub width_px: f32,
+    /// Relative to the top of the text.
+    pub baseline_y_px: f32,
+}
+
+impl AtlasInfo {
+    pub fn pair_kerning_px(&self, left: char, right: char) -> i32 {
+        self.kerning_px.get(&(left, right)).cloned().unwrap_or(0) as _
+    }
+    /// The size of the box `text` fits in once laid out.
+    /// Tabs are as wide as 4 spaces; fonts without a space glyph get half their height as a space.
+    fn tab_advance_px(&self) -> i32 {
+        let space = self.glyphs.get(&' ').map_or(self.height_px as i32 / 2, |g| g.advance_px.x as i32);
+        space * 4
+    }
+    pub fn measure(&self, text: &str) -> Extent2<f32> {
+        let lines = self.line_metrics(text);
+        let w = lines.iter().fold(0_f32, |w, line| w.max(line.width_px));
+        Extent2::new(w, (lines.len() as u32 * self.height_px) as f32)
+    }
+    pub fn line_metrics(&self, text: &str) -> Vec<LineMetrics> {
+        self.layout(text, |_, _| {})
+    }
+    /// Lays out `text`, calling `f` with every glyph and the pen position it is drawn at (on the baseline,
+    /// relative to the top-left corner of the text).
+    pub fn layout<F>(&self, text: &str, mut f: F) -> Vec<LineMetrics> where F: FnMut(&AtlasGlyphInfo, Vec2<i32>) {
+        let mut lines = Vec::new();
+        let mut line_start = 0;
+        let mut pen = Vec2::new(0, self.height_px as i32);
+        let mut prev = None;
+
+        for (i, c) in text.char_indices() {
+            match c {
+                '\n' => {
+                    lines.push(LineMetrics { byte_range: line_start .. i, width_px: pen.x as _, baseline_y_px: pen.y as _ });
+                    line_start = i + 1;
+                    pen.x = 0;
+                    pen.y += self.height_px as i32;
+                    prev = None;
+                    continue;
+                },
+                '\t' => {
+                    pen.x += self.tab_advance_px();
+                    prev = None;
+                    continue;
+                },
+                c if c.is_ascii_control() => continue,
+                _ => (),
+            };
+            let c = if self.glyphs.contains_key(&c) { c } else { '?' };
+            let glyph = match self.glyphs.get(&c) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            if let Some(prev) = prev {
+                pen.x += self.pair_kerning_px(prev, c);
+            }
+            f(glyph, pen);
+            pen.x += glyph.advance_px.x as i32;
+            prev = Some(c);
+        }
+        lines.push(LineMetrics { byte_range: line_start .. text.len(), width_px: pen.x as _, baseline_y_px: pen.y as _ });
+        lines
+    }
+}
+
+/// All font atlases, one per layer of a single texture array.
+/// Layers have the size of the biggest atlas; smaller atlases occupy their top-left corner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GL2DFontAtlases {
+    pub layer_size: Extent2<u32>,
+    pub fonts: HashMap<FontID, AtlasInfo>,
+}
+
+impl GL2DFontAtlases {
+    pub fn new(fonts: &[(FontID, &Atlas, u32)]) -> Self {
+        assert!(!fonts.is_empty());
+        let mut layer_size = Extent2::zero();
+        let mut infos = HashMap::with_capacity(fonts.len());
+        for (i, &(font_id, atlas, height_px)) in fonts.iter().enumerate() {
+            let size = atlas.size();
+            layer_size = Extent2::new(layer_size.w.max(size.w), layer_size.h.max(size.h));
+            let info = AtlasInfo {
+                layer: i as _,
+                size,
+                glyphs: atlas.glyphs.clone(),
+                kerning_px: atlas.kerning_px.clone(),
+                height_px,
+            };
+            let prev = infos.insert(font_id, info);
+            assert!(prev.is_none(), "Font {:?} was registered twice", font_id);
+        }
+        Self { layer_size, fonts: infos }
+    }
+    pub fn nb_layers(&self) -> usize {
+        self.fonts.len()
+    }
+    /// Returns the atlas layer to sample from, and the quads for `text`.
+    /// `position` is the top-left corner of the first line of text.
+    fn text_vertices(&self, font: FontID, position: Vec2<i32>, text: &str) -> (u32, Vec<GL2DVertex>) {
+        let info = &self.fonts[&font];
+        let layer_size = self.layer_size.map(|x| x as f32);
+        let mut vertices = Vec::with_capacity(text.len() * 6);
+
+        info.layout(text, |glyph, pen| {
+            let pen = position + pen;
+            let bounds = glyph.bounds_px.into_rect();
+            if bounds.w == 0 || bounds.h == 0 {
+                return;
+            }
+            let rect = Rect {
+                x: pen.x + glyph.bearing_px.x as i32,
+                y: pen.y - glyph.bearing_px.y as i32,
+                w: bounds.w as u32,
+                h: bounds.h as u32,
+            };
+            let uv = Rect {
+                x: bounds.x as f32 / layer_size.w,
+                y: bounds.y as f32 / layer_size.h,
+                w: bounds.w as f32 / layer_size.w,
+                h: bounds.h as f32 / layer_size.h,
+            };
+            vertices.extend_from_slice(&quad_vertices(rect, uv));
+        });
+        (info.layer, vertices)
+    }
+}
+
+#[derive(Debug)]
+pub struct GL2D {
+    program: gx::ProgramEx,
+    vao: gx::VertexArray,
+    vbo: gx::Buffer,
+    font_atlas_array: gx::Texture,
+    font_atlases: GL2DFontAtlases,
+}
+
+impl GL2D {
+    pub const MAX_QUADS: usize = 1024;
+
+    /// Each font gets its own layer in the font atlas array, in order.
+    pub fn new(fonts: &[(FontID, &Atlas, u32)]) -> Self {
+        let font_atlases = GL2DFontAtlases::new(fonts);
+        let Extent2 { w, h } = font_atlases.layer_size;
+        unsafe {
+            let mut vbo = 0;
+            gl::CreateBuffers(1, &mut vbo);
+            gl::NamedBufferStorage(vbo, (Self::MAX_QUADS * 6 * mem::size_of::<GL2DVertex>()) as _, 0 as _, gl::DYNAMIC_STORAGE_BIT);
+
+            let vao = gx::VertexArray::new();
+            gl::BindVertexArray(vao.gl_id());
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::EnableVertexAttribArray(0);
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, mem::size_of::<GL2DVertex>() as _, 0 as _);
+            gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, mem::size_of::<GL2DVertex>() as _, (2*4) as _);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+
+            let font_atlas_array = gx::TextureBuilder::new(gl::TEXTURE_2D_ARRAY, gl::R8, Extent3::new(w, h, fonts.len() as _)).build().unwrap();
+            let tex = font_atlas_array.gl_id();
+            let zeroes = 0_u8;
+            gl::ClearTexImage(tex, 0, gl::RED, gl::UNSIGNED_BYTE, &zeroes as *const _ as _);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            for &(font_id, atlas, _) in fonts.iter() {
+                let layer = font_atlases.fonts[&font_id].layer;
+                let Extent2 { w, h } = atlas.size();
+                gl::TextureSubImage3D(tex, 0, 0, 0, layer as _, w as _, h as _, 1, gl::RED, gl::UNSIGNED_BYTE, atlas.img.as_ptr() as _);
+            }
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            gl::TextureParameteri(tex, gl::TEXTURE_MIN_FILTER, gl::NEAREST as _);
+            gl::TextureParameteri(tex, gl::TEXTURE_MAG_FILTER, gl::NEAREST as _);
+
+            Self {
+                program: super::new_program_ex_unwrap(GL2D_VS, GL2D_FS),
+                vao,
+                vbo: gx::Buffer::from_gl_id(vbo),
+                font_atlas_array,
+                font_atlases,
+            }
+        }
+    }
+
+    pub fn draw_rect(&self, viewport_size: Extent2<u32>, rect: Rect<i32, u32>, color: Rgba<f32>) {
+        let uv = Rect::new(0., 0., 1., 1.);
+        self.draw_quads(viewport_size, &quad_vertices(rect, uv), GL2DMode::Color, 0, 0., color);
+    }
+    pub fn draw_textured_rect(&self, viewport_size: Extent2<u32>, rect: Rect<i32, u32>, texture2d_array: GLuint, slot: usize, uv: Rect<f32, f32>, color: Rgba<f32>) {
+        self.draw_quads(viewport_size, &quad_vertices(rect, uv), GL2DMode::Texture, texture2d_array, slot as f32, color);
+    }
+    /// `position` is the top-left corner of the first line of text.
+    pub fn draw_text(&self, viewport_size: Extent2<u32>, font: FontID, position: Vec2<i32>, text: &str, color: Rgba<f32>) {
+        let (layer, vertices) = self.font_atlases.text_vertices(font, position, text);
+        self.draw_quads(viewport_size, &vertices, GL2DMode::Glyph, self.font_atlas_array.gl_id(), layer as f32, color);
+    }
+
+    fn draw_quads(&self, viewport_size: Extent2<u32>, vertices: &[GL2DVertex], mode: GL2DMode, tex: GLuint, slot: f32, color: Rgba<f32>) {
+        if vertices.is_empty() {
+            return;
+        }
+
+        unsafe {
+            gl::UseProgram(self.program.inner().gl_id());
+
+            // Unit 0 is for the font atlas array, unit 1 is for texture arrays.
+            let unit = if mode == GL2DMode::Glyph { 0 } else { 1 };
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, tex);
+
+            self.program.set_uniform_primitive("u_mvp", &[pixel_ortho_matrix(viewport_size)]);
+            self.program.set_uniform_primitive("u_color", &[color]);
+            self.program.set_uniform_primitive("u_mode", &[mode as u32]);
+            self.program.set_uniform_primitive("u_slot", &[slot]);
+            self.program.set_uniform("u_glyph_atlas_array", gx::GLSLType::Sampler2DArray, &[0_i32]);
+            self.program.set_uniform("u_texture2d_array", gx::GLSLType::Sampler2DArray, &[1_i32]);
+
+            // Restored afterwards, since we may be drawing in the middle of a 3D pass.
+            let was_depth_test_enabled = gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE;
+            let was_blend_enabled = gl::IsEnabled(gl::BLEND) == gl::TRUE;
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+            // The VBO holds MAX_QUADS quads; more are drawn in several batches.
+            gl::BindVertexArray(self.vao.gl_id());
+            for batch in vertices.chunks(Self::MAX_QUADS * 6) {
+                gl::NamedBufferSubData(self.vbo.gl_id(), 0, (batch.len() * mem::size_of::<GL2DVertex>()) as _, batch.as_ptr() as _);
+                gl::DrawArrays(gl::TRIANGLES, 0, batch.len() as _);
+            }
+            gl::BindVertexArray(0);
+
+            if !was_blend_enabled {
+                gl::Disable(gl::BLEND);
+            }
+            if was_depth_test_enabled {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+            gl::ActiveTexture(gl::TEXTURE0);
+
+            gl::UseProgram(0);
+        }
+    }
+}
+
+fn quad_vertices(rect: Rect<i32, u32>, uv: Rect<f32, f32>) -> [GL2DVertex; 6] {
+    let (x0, y0) = (rect.x as f32, rect.y as f32);
+    let (x1, y1) = (x0 + rect.w as f32, y0 + rect.h as f32);
+    let (u0, v0) = (uv.x, uv.y);
+    let (u1, v1) = (uv.x + uv.w, uv.y + uv.h);
+    let top_left     = GL2DVertex { position: Vec2::new(x0, y0), texcoords: Vec2::new(u0, v0) };
+    let top_right    = GL2DVertex { position: Vec2::new(x1, y0), texcoords: Vec2::new(u1, v0) };
+    let bottom_left  = GL2DVertex { position: Vec2::new(x0, y1), texcoords: Vec2::new(u0, v1) };
+    let bottom_right = GL2DVertex { position: Vec2::new(x1, y1), texcoords: Vec2::new(u1, v1) };
+    [top_left, bottom_left, top_right, top_right, bottom_left, bottom_right]
+}
+
+
+static GL2D_VS: &'static [u8] = b"
+#version 450 core
+
+uniform mat4 u_mvp;
+
+layout(location = 0) in vec2 a_position;
+layout(location = 1) in vec2 a_texcoords;
+
+out vec2 v_texcoords;
+
+void main() {
+    gl_Position = u_mvp * vec4(a_position, 0.0, 1.0);
+    v_texcoords = a_texcoords;
+}
+";
+
+static GL2D_FS: &'static [u8] = b"
+#version 450 core
+
+uniform uint u_mode;
+uniform vec4 u_color;
+uniform float u_slot;
+uniform sampler2DArray u_texture2d_array;
+uniform sampler2DArray u_glyph_atlas_array;
+
+in vec2 v_texcoords;
+
+out vec4 f_color;
+
+void main() {
+    switch(u_mode) {
+    case 0: f_color = u_color; break;
+    case 1: f_color = u_color * texture(u_texture2d_array, vec3(v_texcoords, u_slot)); break;
+    case 2: f_color = vec4(u_color.rgb, u_color.a * texture(u_glyph_atlas_array, vec3(v_texcoords, u_slot)).r); break;
+    }
+}
+";
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fate::math::Aabr;
+
+    fn atlas_with_glyph(tex_side: usize, c: char, bounds_px: Aabr<u16>) -> Atlas {
+        let mut atlas = Atlas::new(tex_side);
+        atlas.glyphs.insert(c, AtlasGlyphInfo { bounds_px, advance_px: Vec2::new(8, 0), .. Default::default() });
+        atlas
+    }
+
+    #[test]
+    fn two_fonts_make_two_layers() {
+        let body = atlas_with_glyph(64, 'a', Aabr { min: Vec2::new(0, 0), max: Vec2::new(8, 16) });
+        let heading = atlas_with_glyph(128, 'a', Aabr { min: Vec2::new(32, 64), max: Vec2::new(64, 128) });
+        let atlases = GL2DFontAtlases::new(&[(FontID(0), &body, 16), (FontID(1), &heading, 64)]);
+        assert_eq!(atlases.nb_layers(), 2);
+        assert_eq!(atlases.layer_size, Extent2::new(128, 128));
+
+        let (layer, vertices) = atlases.text_vertices(FontID(0), Vec2::zero(), "a");
+        assert_eq!(layer, 0);
+        assert_eq!(vertices.len(), 6);
+        assert!(vertices.iter().all(|v| v.texcoords.x <= 8. / 128. && v.texcoords.y <= 16. / 128.));
+
+        let (layer, vertices) = atlases.text_vertices(FontID(1), Vec2::zero(), "aa");
+        assert_eq!(layer, 1);
+        assert_eq!(vertices.len(), 12);
+        assert!(vertices.iter().all(|v| v.texcoords.x >= 0.25 && v.texcoords.y >= 0.5));
+        // The second glyph is one advance away from the first, on a line as tall as the font.
+        assert_eq!(vertices[6].position - vertices[0].position, Vec2::new(8., 0.));
+        assert_eq!(vertices[0].position.y, 64.);
+    }
+    #[test]
+    fn measured_width_is_advances_plus_kerning() {
+        let mut atlas = Atlas::new(64);
+        let glyph = |advance| AtlasGlyphInfo { bounds_px: Aabr { min: Vec2::new(0, 0), max: Vec2::new(6, 12) }, advance_px: Vec2::new(advance, 0), .. Default::default() };
+        atlas.glyphs.insert('A', glyph(9));
+        atlas.glyphs.insert('V', glyph(8));
+        atlas.glyphs.insert('o', glyph(7));
+        atlas.kerning_px.insert(('A', 'V'), -2);
+        atlas.kerning_px.insert(('V', 'o'), -1);
+        let atlases = GL2DFontAtlases::new(&[(FontID(0), &atlas, 16)]);
+        let info = &atlases.fonts[&FontID(0)];
+
+        assert_eq!(info.measure("AVo"), Extent2::new((9 + 8 + 7 - 2 - 1) as f32, 16.));
+        // No kerning for pairs that aren't in the table, nor across lines
+        assert_eq!(info.measure("oA"), Extent2::new(16., 16.));
+        assert_eq!(info.measure("A\nVVo\n"), Extent2::new((8 + 8 + 7 - 1) as f32, 48.));
+
+        let lines = info.line_metrics("A\nVVo\n");
+        assert_eq!(lines.iter().map(|l| l.byte_range.clone()).collect::<Vec<_>>(), vec![0..1, 2..5, 6..6]);
+        assert_eq!(lines[1].baseline_y_px, 32.);
+
+        // Glyph placement agrees with the measurement
+        let (_, vertices) = atlases.text_vertices(FontID(0), Vec2::new(100, 0), "AVo");
+        assert_eq!(vertices[12].position.x, 100. + (9 + 8 - 2 - 1) as f32);
+    }
+    #[test]
+    fn tabs_dont_need_a_space_glyph() {
+        let without_space = atlas_with_glyph(64, 'a', Aabr { min: Vec2::new(0, 0), max: Vec2::new(8, 16) });
+        let mut with_space = without_space.clone();
+        with_space.glyphs.insert(' ', AtlasGlyphInfo { advance_px: Vec2::new(5, 0), .. Default::default() });
+        let atlases = GL2DFontAtlases::new(&[(FontID(0), &without_space, 16), (FontID(1), &with_space, 16)]);
+
+        assert_eq!(atlases.fonts[&FontID(0)].measure("\ta").w, (8 * 4 + 8) as f32);
+        assert_eq!(atlases.fonts[&FontID(1)].measure("\ta").w, (5 * 4 + 8) as f32);
+    }
+}
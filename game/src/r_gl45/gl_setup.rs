@@ -34,13 +34,24 @@ fn gl_error_hook(e: Option<gx::Error>, s: &str) {
     }
 }
 
-pub fn gl_setup(platform: &Platform) {
+// Things we use unconditionally.
+static REQUIRED_GL_FEATURES: &'static [gx::GLFeature] = &[
+    gx::GLFeature { name: "Direct State Access (glCreate*, glNamedBufferStorage, ...)", core_since: (4, 5), extensions: &["GL_ARB_direct_state_access"] },
+    gx::GLFeature { name: "Immutable buffer storage", core_since: (4, 4), extensions: &["GL_ARB_buffer_storage"] },
+    gx::GLFeature { name: "Immutable texture storage", core_since: (4, 2), extensions: &["GL_ARB_texture_storage"] },
+    gx::GLFeature { name: "glClearTexImage", core_since: (4, 4), extensions: &["GL_ARB_clear_texture"] },
+    gx::GLFeature { name: "GL_TEXTURE_CUBE_MAP_ARRAY", core_since: (4, 0), extensions: &["GL_ARB_texture_cube_map_array"] },
+    gx::GLFeature { name: "glMultiDrawElementsIndirect", core_since: (4, 3), extensions: &["GL_ARB_multi_draw_indirect"] },
+];
+
+pub fn gl_setup(platform: &Platform) -> Result<(), String> {
     gl::load_with(|s| {
         let f = platform.gl_get_proc_address(s);
         trace!("GL: {}: {}", if f.is_null() { "Failed" } else { "Loaded" }, s);
         f
     });
     info!("OpenGL context summary:\n{}", gx::ContextSummary::new());
+    gx::check_current_features(REQUIRED_GL_FEATURES)?;
     gx::set_error_hook(gl_error_hook);
     unsafe { gl::POST_HOOK = gl_post_hook; }
     gx::boot_gl();
@@ -49,6 +60,7 @@ pub fn gl_setup(platform: &Platform) {
 
     let max_tex_units = gx::get::integer(gl::MAX_TEXTURE_IMAGE_UNITS); // NOTE: Min. 16
     info!("OpenGL max texture units: {}", max_tex_units);
+    super::gl_test_mdi_scene::check_max_texture_image_units(max_tex_units)
 }
 
 
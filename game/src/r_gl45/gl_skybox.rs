@@ -3,7 +3,9 @@ use fate::gx::{self, Object, gl::{self, types::*}};
 
 use camera::Camera;
 use camera::View;
+use super::GLDepthState;
 use cubemap::CubemapSelector;
+use gpu::GpuTextureFilters;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct GLSkybox {
@@ -11,17 +13,44 @@ pub struct GLSkybox {
     #[allow(dead_code)]
     vbo: gx::Buffer,
     vao: gx::VertexArray,
+    sampler: gx::Sampler,
+    filters: GpuTextureFilters,
 }
 
 impl GLSkybox {
     pub fn new() -> Self {
         let vbo = create_skybox_vbo();
+        let filters = GpuTextureFilters::default();
         Self {
             program: super::new_program_ex_unwrap(SKY_VS, SKY_FS),
             vao: create_skybox_vao(vbo.gl_id()),
             vbo,
+            sampler: create_skybox_sampler(&filters),
+            filters,
         }
     }
+    /// Only sends the parameters that actually changed.
+    pub fn set_filters(&mut self, filters: GpuTextureFilters) {
+        for (pname, param) in self.filters.gl_changed_params(&filters) {
+            self.sampler.set_parameter_i(pname, param as _);
+        }
+        self.filters = filters;
+    }
+    pub fn filters(&self) -> GpuTextureFilters {
+        self.filters
+    }
+}
+
+fn create_skybox_sampler(filters: &GpuTextureFilters) -> gx::Sampler {
+    let sampler = unsafe {
+        let mut sampler = 0;
+        gl::CreateSamplers(1, &mut sampler);
+        gx::Sampler::from_gl_id(sampler)
+    };
+    sampler.set_min_filter(filters.gl_min_filter());
+    sampler.set_mag_filter(filters.gl_mag_filter());
+    sampler.set_wrap(gl::CLAMP_TO_EDGE);
+    sampler
 }
 
 fn create_skybox_vbo() -> gx::Buffer {
@@ -78,13 +107,15 @@ static SKY_VS: &'static [u8] = b"
 #version 450 core
 
 uniform mat4 u_mvp;
+uniform float u_far_depth;
 
 layout(location = 0) in vec3 a_position;
 
 out vec3 v_uvw;
 
 void main() {
-    gl_Position = (u_mvp * vec4(a_position, 1.0)).xyww; // Z = 1 after perspective divide by w
+    vec4 p = u_mvp * vec4(a_position, 1.0);
+    gl_Position = vec4(p.xy, u_far_depth * p.w, p.w); // Z = u_far_depth after perspective divide by w
     v_uvw = a_position;
 }
 ";
@@ -122,17 +153,22 @@ impl GLSkybox {
 
             gl::ActiveTexture(gl::TEXTURE0);
             gl::BindTexture(gl::TEXTURE_CUBE_MAP_ARRAY, cubemap_array_tex);
+            self.sampler.bind(0);
 
             self.program.set_uniform_primitive("u_mvp", &[mvp]);
             self.program.set_uniform("u_cubemap_array", gx::GLSLType::SamplerCubeMapArray, &[0_i32]);
             self.program.set_uniform_primitive("u_cubemap_slot", &[cubemap.cubemap as f32]);
 
-            gl::DepthFunc(gl::LEQUAL);
+            let depth = GLDepthState::new(camera.camera.effective_depth_mode());
+            self.program.set_uniform_primitive("u_far_depth", &[depth.far_depth]);
+
+            gl::DepthFunc(depth.lequal);
             gl::BindVertexArray(self.vao.gl_id());
             gl::DrawArrays(gl::TRIANGLE_STRIP, 0, SKYBOX_NB_VERTICES as _);
             gl::BindVertexArray(0);
-            gl::DepthFunc(gl::LESS);
+            gl::DepthFunc(depth.less);
 
+            gx::Sampler::unbind(0);
             gl::BindTexture(gl::TEXTURE_CUBE_MAP_ARRAY, 0);
 
             gl::UseProgram(0);
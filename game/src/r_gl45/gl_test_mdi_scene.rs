@@ -1,10 +1,15 @@
 use std::ptr;
 use std::mem;
 use std::ops::Range;
-use fate::math::{Vec2, Vec3, Vec4, Mat4, Rgba, Rgb};
+use fate::math::{Vec2, Vec3, Vec4, Mat4, Rgba, Rgb, Aabb};
 use fate::gx::{self, Object, {gl::{self, types::*}}};
-use mesh::VertexAttribIndex;
-use camera::View;
+use mesh::{MeshInfo, VertexAttribIndex, VertexAttribSet};
+use material::{self, BlendMode};
+use texture2d::{Texture2DSelector, Texture2DArrayID};
+use camera::{View, Camera};
+use frustum;
+use super::GLDepthState;
+use cubemap::CubemapSelector;
 
 macro_rules! hashmap {
     ($($key:expr => $value:expr),*) => {
@@ -21,9 +26,36 @@ macro_rules! hashmap {
 const MAX_VERTICES : isize = 1024 << 4;
 const MAX_INSTANCES: isize = 4096;
 const MAX_INDICES  : isize = 1024 << 5;
-const MAX_CMDS     : isize = 1024;
+const MAX_CMDS     : usize = 1024;
+const ENV_MAP_UNIT : GLuint = Texture2DArrayID::MAX as GLuint; // Right after the texture2d arrays
 const MAX_MATERIALS: isize = 16384 / mem::size_of::<Material>() as isize; // min value in bytes of GL_MAX_UNIFORM_BLOCK_SIZE (limit does not apply to SSBOs)
 const MAX_POINT_LIGHTS: isize = 32;
+const FRUSTUM_CULL: bool = cfg!(feature = "frustum_cull"); // Opt-in for now
+
+/// The PBR fragment shader samples every texture2d array, plus the environment map, so it needs one more
+/// texture unit than the 16 GL guarantees.
+pub fn check_max_texture_image_units(max_texture_image_units: GLint) -> Result<(), String> {
+    let needed = ENV_MAP_UNIT as GLint + 1;
+    if max_texture_image_units < needed {
+        return Err(format!("The PBR shader needs {} texture image units, but the GPU only has {}", needed, max_texture_image_units));
+    }
+    Ok(())
+}
+
+/// Compiles and links the PBR program, and points its texture2d array samplers at their units once and for all.
+fn new_pbr_program() -> Result<gx::ProgramEx, String> {
+    let program = super::new_program_ex(PBR_VS, PBR_FS)?;
+    let units: Vec<_> = (0 .. Texture2DArrayID::MAX as GLint).collect();
+    unsafe {
+        gl::UseProgram(program.inner().gl_id());
+    }
+    let result = program.set_sampler_array("u_texture2d_arrays[0]", &units);
+    unsafe {
+        gl::UseProgram(0);
+    }
+    result?;
+    Ok(program)
+}
 
 #[derive(Debug)]
 pub struct GLTestMDIScene {
@@ -36,22 +68,24 @@ pub struct GLTestMDIScene {
     model_matrix_vbo: gx::Buffer,
     material_index_vbo: gx::Buffer,
     ibo: gx::Buffer,
-    cmd_buffer: gx::Buffer,
-    material_buffer: gx::Buffer,
-    point_light_buffer: gx::Buffer,
+    cmds: gx::IndirectCommandBuffer,
+    material_buffer: gx::ShaderStorageBuffer<Material>,
+    point_light_buffer: gx::ShaderStorageBuffer<PointLight>,
     program: gx::ProgramEx,
+    wireframe_program: gx::ProgramEx,
     heap_info: HeapInfo,
 }
 
 impl GLTestMDIScene {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, String> {
         unsafe {
             Self::new_unsafe()
         }
     }
-    unsafe fn new_unsafe() -> Self {
+    unsafe fn new_unsafe() -> Result<Self, String> {
+        let program = new_pbr_program()?;
         let vao = gx::VertexArray::new();
-        let mut buffers = [0; 11];
+        let mut buffers = [0; 8];
         gl::CreateBuffers(buffers.len() as _, buffers.as_mut_ptr());
         let position_vbo = buffers[0];
         let normal_vbo = buffers[1];
@@ -61,9 +95,6 @@ impl GLTestMDIScene {
         let model_matrix_vbo = buffers[5];
         let material_index_vbo = buffers[6];
         let ibo = buffers[7];
-        let cmd_buffer = buffers[8];
-        let material_buffer = buffers[9];
-        let point_light_buffer = buffers[10];
 
         let flags = gl::DYNAMIC_STORAGE_BIT;
         gl::NamedBufferStorage(position_vbo, MAX_VERTICES * 3 * 4, ptr::null(), flags);
@@ -74,9 +105,6 @@ impl GLTestMDIScene {
         gl::NamedBufferStorage(model_matrix_vbo, MAX_INSTANCES * 4 * 4 * 4, ptr::null(), flags);
         gl::NamedBufferStorage(material_index_vbo, MAX_INSTANCES * 2, ptr::null(), flags);
         gl::NamedBufferStorage(ibo, MAX_INDICES * 4, ptr::null(), flags);
-        gl::NamedBufferStorage(cmd_buffer, MAX_CMDS * mem::size_of::<GLDrawElementsIndirectCommand>() as isize, ptr::null(), flags);
-        gl::NamedBufferStorage(material_buffer, MAX_MATERIALS * mem::size_of::<Material>() as isize, ptr::null(), flags);
-        gl::NamedBufferStorage(point_light_buffer, MAX_POINT_LIGHTS * mem::size_of::<PointLight>() as isize, ptr::null(), flags);
 
         // Specifying vertex attrib layout
 
@@ -133,14 +161,17 @@ impl GLTestMDIScene {
             model_matrix_vbo: gx::Buffer::from_gl_id(model_matrix_vbo),
             material_index_vbo: gx::Buffer::from_gl_id(material_index_vbo),
             ibo: gx::Buffer::from_gl_id(ibo),
-            cmd_buffer: gx::Buffer::from_gl_id(cmd_buffer),
-            material_buffer: gx::Buffer::from_gl_id(material_buffer),
-            point_light_buffer: gx::Buffer::from_gl_id(point_light_buffer),
-            program: super::new_program_ex_unwrap(PBR_VS, PBR_FS),
+            cmds: gx::IndirectCommandBuffer::new(MAX_CMDS),
+            material_buffer: gx::ShaderStorageBuffer::new(MAX_MATERIALS as _, gx::BufferFlags::DYNAMIC_STORAGE),
+            point_light_buffer: gx::ShaderStorageBuffer::new(MAX_POINT_LIGHTS as _, gx::BufferFlags::DYNAMIC_STORAGE),
+            program,
+            wireframe_program: super::new_program_ex_with_geometry_unwrap(PBR_VS, WIREFRAME_GS, WIREFRAME_FS),
             heap_info: HeapInfo::default(),
         };
+        gx::set_object_label(s.material_buffer.buffer(), "Materials SSBO");
+        gx::set_object_label(s.point_light_buffer.buffer(), "PointLights SSBO");
         s.add_meshes();
-        s
+        Ok(s)
     }
     unsafe fn add_meshes(&mut self) {
         use ::std::collections::HashMap;
@@ -272,36 +303,117 @@ impl GLTestMDIScene {
         gl::NamedBufferSubData(self.material_index_vbo.gl_id(), 0, mem::size_of_val(&material_indices[..]) as _, material_indices.as_ptr() as _);
         gl::NamedBufferSubData(self.ibo.gl_id(), 0, mem::size_of_val(&indices[..]) as _, indices.as_ptr() as _);
 
+        let vertex_ranges = vec![0..3, 3..6, 6..9];
+        let index_ranges = vec![0..3, 3..6, 6..9];
+        let meshes: Vec<_> = vertex_ranges.iter().zip(index_ranges.iter()).map(|(v, i): (&Range<u32>, &Range<u32>)| {
+            let v = v.start as usize .. v.end as usize;
+            let i = i.start as usize .. i.end as usize;
+            MeshInfo {
+                nb_vertices: v.len() as _,
+                nb_indices: i.len() as _,
+                topology: gx::Topology::Triangles,
+                indices: indices[i].to_vec(),
+                v_position: positions[v.clone()].to_vec(),
+                v_normal: normals[v.clone()].to_vec(),
+                v_uv: uvs[v].to_vec(),
+                i_model_matrix: vec![],
+                i_material_index: vec![],
+            }
+        }).collect();
+        let instance_ranges = vec![0..3, 3..6, 0..2];
+        let instance_range_mesh_entry = vec![0, 1, 2];
+
+        // Computed once here, when meshes are added, rather than every frame
+        let mesh_bounds: Vec<_> = meshes.iter().map(|mesh| mesh.bounds().unwrap()).collect();
+        let mut instance_bounds = vec![Aabb { min: Vec3::zero(), max: Vec3::zero() }; model_matrices.len()];
+        for (range, mesh) in instance_ranges.iter().zip(instance_range_mesh_entry.iter()) {
+            for instance in range.clone() {
+                let instance = instance as usize;
+                instance_bounds[instance] = frustum::transformed_aabb(&mesh_bounds[*mesh as usize], model_matrices[instance]);
+            }
+        }
+
+        // Shows off the wireframe overlay on the first instance of the second row
+        let mut instance_wireframes = vec![false; model_matrices.len()];
+        instance_wireframes[3] = true;
+
         self.heap_info = HeapInfo {
-            vertex_ranges: vec![0..3, 3..6, 6..9],
-            index_ranges: vec![0..3, 3..6, 6..9],
-            instance_ranges: vec![0..3, 3..6, 0..2],
-            instance_range_mesh_entry: vec![0, 1, 2],
+            vertex_ranges,
+            index_ranges,
+            instance_ranges,
+            instance_range_mesh_entry,
+            instance_positions: model_matrices.iter().map(|m| m.cols.w.into()).collect(),
+            instance_bounds,
+            instance_material_indices: material_indices.to_vec(),
+            instance_visibilities: vec![true; model_matrices.len()],
+            instance_layer_masks: vec![Camera::ALL_LAYERS; model_matrices.len()],
+            instance_wireframes,
         };
+
+        // All meshes share the VAO, so an attribute is only read from its buffer if every mesh has it.
+        let mut attribs = meshes.iter().skip(1).fold(meshes[0].vertex_attribs(), |attribs, mesh| attribs.intersection(mesh.vertex_attribs()));
+        // Skinning data isn't part of MeshInfo yet
+        if !weights.is_empty() {
+            attribs.insert(VertexAttribIndex::Weights);
+        }
+        if !joints.is_empty() {
+            attribs.insert(VertexAttribIndex::Joints);
+        }
+        self.set_vertex_attribs(attribs);
     }
-    pub fn draw(&self, view: &View, texture2d_arrays: &[GLuint]) {
+    // Attributes that are missing are read as a constant instead of from their buffer.
+    // NOTE: This applies to the whole heap, since all meshes are drawn with the same VAO.
+    unsafe fn set_vertex_attribs(&self, attribs: VertexAttribSet) {
+        gl::BindVertexArray(self.vao.gl_id());
+        for &i in [VertexAttribIndex::Position, VertexAttribIndex::Normal, VertexAttribIndex::UV, VertexAttribIndex::Weights, VertexAttribIndex::Joints].iter() {
+            if attribs.contains(i) {
+                gl::EnableVertexAttribArray(i as _);
+            } else {
+                let v = i.default_value();
+                gl::DisableVertexAttribArray(i as _);
+                gl::VertexAttrib4f(i as _, v.x, v.y, v.z, v.w);
+            }
+        }
+        gl::BindVertexArray(0);
+    }
+    /// `env_map` is the cubemap that reflective materials reflect (usually the skybox's), along with its cubemap array texture.
+    pub fn draw(&mut self, view: &View, texture2d_arrays: &[GLuint], env_map: Option<(CubemapSelector, GLuint)>) {
         unsafe {
-            self.draw_unsafe(view, texture2d_arrays)
+            self.draw_unsafe(view, texture2d_arrays, env_map)
         }
     }
-    unsafe fn draw_unsafe(&self, view: &View, texture2d_arrays: &[GLuint]) {
+    unsafe fn draw_unsafe(&mut self, view: &View, texture2d_arrays: &[GLuint], env_map: Option<(CubemapSelector, GLuint)>) {
 
         let joint_matrices = [Mat4::<f32>::identity(); 32]; // FIXME: But this changes on a per-instance basis (driven by animation)
 
-        assert!(texture2d_arrays.len() <= 16, "Too many texture2d arrays for shader");
+        assert!(texture2d_arrays.len() <= Texture2DArrayID::MAX, "Too many texture2d arrays for shader");
 
         // FIXME: Hardcoded texture selectors
+        let tex2d = |array, slot| Texture2DSelector { array_id: Texture2DArrayID(array), slot };
+        let pbr = |albedo_mul, albedo_map| material::Material {
+            albedo_mul,
+            albedo_map,
+            normal_map: tex2d(0, 0),
+            metallic_mul: 1.,
+            metallic_map: tex2d(0, 1),
+            roughness_mul: 0.4,
+            roughness_map: tex2d(0, 1),
+            ao_map: tex2d(0, 1),
+            reflectivity: 0.,
+            blend_mode: BlendMode::Opaque,
+        };
         let materials = [
-            Material { albedo_mul: Rgba::red()   , albedo_map: (2 << 16) | 0, metallic_mul: 1., metallic_map: 1, roughness_mul: 0.4, roughness_map: 1, ao_map: 1, normal_map: 0, _pad: Default::default(), },
-            Material { albedo_mul: Rgba::yellow(), albedo_map: (2 << 16) | 0, metallic_mul: 1., metallic_map: 1, roughness_mul: 0.4, roughness_map: 1, ao_map: 1, normal_map: 0, _pad: Default::default(), },
-            Material { albedo_mul: Rgba::green() , albedo_map: (2 << 16) | 1, metallic_mul: 1., metallic_map: 1, roughness_mul: 0.4, roughness_map: 1, ao_map: 1, normal_map: 0, _pad: Default::default(), },
-            Material { albedo_mul: Rgba::white() , albedo_map: (2 << 16) | 1, metallic_mul: 1., metallic_map: 1, roughness_mul: 0.4, roughness_map: 1, ao_map: 1, normal_map: 0, _pad: Default::default(), },
-            Material { albedo_mul: Rgba::white() , albedo_map: (2 << 16) | 2, metallic_mul: 1., metallic_map: 1, roughness_mul: 0.4, roughness_map: 1, ao_map: 1, normal_map: 0, _pad: Default::default(), },
-            Material { albedo_mul: Rgba::cyan()  , albedo_map: (2 << 16) | 2, metallic_mul: 1., metallic_map: 1, roughness_mul: 0.4, roughness_map: 1, ao_map: 1, normal_map: 0, _pad: Default::default(), },
+            pbr(Rgba::new(255, 0, 0, 255), tex2d(2, 0)),
+            pbr(Rgba::new(255, 255, 0, 255), tex2d(2, 0)),
+            pbr(Rgba::new(0, 255, 0, 255), tex2d(2, 1)),
+            material::Material { reflectivity: 0.8, .. pbr(Rgba::new(255, 255, 255, 255), tex2d(2, 1)) },
+            material::Material { blend_mode: BlendMode::AlphaBlend, .. pbr(Rgba::new(255, 255, 255, 255), tex2d(2, 2)) },
+            material::Material { blend_mode: BlendMode::AlphaBlend, .. pbr(Rgba::new(0, 255, 255, 128), tex2d(2, 2)) },
         ];
+        let material_blend_modes: Vec<_> = materials.iter().map(|m| m.blend_mode).collect();
+        let materials: Vec<_> = materials.iter().map(Material::from).collect();
 
-        gl::NamedBufferSubData(self.material_buffer.gl_id(), 0, mem::size_of_val(&materials[..]) as _, materials.as_ptr() as _);
-        let nb_materials = materials.len();
+        self.material_buffer.set_data(&materials);
 
         let point_lights = [
             PointLight {
@@ -312,44 +424,61 @@ impl GLTestMDIScene {
                 pad: Default::default(),
             },
         ];
-        gl::NamedBufferSubData(self.point_light_buffer.gl_id(), 0, mem::size_of_val(&point_lights[..]) as _, point_lights.as_ptr() as _);
-        let nb_point_lights = point_lights.len();
-
-        let mut cmds = vec![];
-
-        let m = &self.heap_info;
-        for (i, mesh) in m.instance_ranges.iter().zip(m.instance_range_mesh_entry.iter()) {
-            let index_range = &m.index_ranges[*mesh as usize];
-            let vertex_range = &m.vertex_ranges[*mesh as usize];
-            cmds.push(GLDrawElementsIndirectCommand {
-                base_instance: i.start,
-                nb_instances: i.end - i.start,
-                first_index: index_range.start, // Offset into the index buffer
-                nb_indices: index_range.end - index_range.start,
-                base_vertex: vertex_range.start, // Value added to indices for vertex retrieval
-            });
-        }
+        self.point_light_buffer.set_data(&point_lights);
+
+        let (cmds, blended_cmds) = build_draw_cmds(&self.heap_info, &material_blend_modes, view);
+        let wireframe_cmds = build_wireframe_cmds(&self.heap_info, view);
         let nb_cmds = cmds.len();
-        gl::NamedBufferSubData(self.cmd_buffer.gl_id(), 0, mem::size_of_val(&cmds[..]) as _, cmds.as_ptr() as _); // PERF
+        let nb_shaded_cmds = nb_cmds + blended_cmds.len();
+        self.cmds.clear();
+        for cmd in cmds.into_iter().chain(blended_cmds.into_iter()).chain(wireframe_cmds.into_iter()) {
+            self.cmds.push(cmd);
+        }
+        self.cmds.upload();
 
         gl::BindTextures(0, texture2d_arrays.len() as _, texture2d_arrays.as_ptr());
-        let units = [0_i32, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, ];
+        if let Some((_, cubemap_array_tex)) = env_map {
+            gl::BindTextureUnit(ENV_MAP_UNIT, cubemap_array_tex);
+        }
 
         gl::UseProgram(self.program.inner().gl_id());
         self.program.set_uniform_primitive("u_joint_matrices[0]", &joint_matrices[..]);
-        self.program.set_uniform("u_texture2d_arrays[0]", gx::GLSLType::Sampler2DArray, &units[..texture2d_arrays.len()]);
+        self.program.set_uniform("u_env_cubemap_array", gx::GLSLType::SamplerCubeMapArray, &[ENV_MAP_UNIT as i32]);
+        self.program.set_uniform_primitive("u_env_cubemap_slot", &[env_map.map(|(cubemap, _)| cubemap.cubemap as f32).unwrap_or(0.)]);
+        self.program.set_uniform_primitive("u_is_env_map_enabled", &[env_map.is_some() as u32]);
         self.program.set_uniform_primitive("u_viewproj_matrix", &[view.proj_matrix() * view.view_matrix()]);
         self.program.set_uniform_primitive("u_eye_position_worldspace", &[view.xform.position]);
         self.program.set_uniform_primitive("u_directional_light.direction", &[Vec3::<f32>::new(1., 1., 1.).normalized()]);
         self.program.set_uniform_primitive("u_directional_light.color", &[Rgb::<f32>::black()]);
-        gl::BindBufferRange(gl::SHADER_STORAGE_BUFFER, 1, self.point_light_buffer.gl_id(), 0, (nb_point_lights * mem::size_of::<PointLight>()) as _);
-        gl::BindBufferRange(gl::SHADER_STORAGE_BUFFER, 2, self.material_buffer.gl_id(), 0, (nb_materials * mem::size_of::<Material>()) as _);
+        self.point_light_buffer.bind_base(1);
+        self.material_buffer.bind_base(2);
 
         gl::BindVertexArray(self.vao.gl_id());
         gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo.gl_id());
-        gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.cmd_buffer.gl_id()); // In core profile, we MUST use a buffer to store commands
-        gl::MultiDrawElementsIndirect(gl::TRIANGLES, gl::UNSIGNED_INT, 0 as _, nb_cmds as _, 0);
-        gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+        gl::Disable(gl::BLEND);
+        self.cmds.multi_draw_elements_range(gx::Topology::Triangles, gl::UNSIGNED_INT, 0 .. nb_cmds);
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        if nb_shaded_cmds > nb_cmds {
+            gl::DepthMask(gl::FALSE);
+            self.cmds.multi_draw_elements_range(gx::Topology::Triangles, gl::UNSIGNED_INT, nb_cmds .. nb_shaded_cmds);
+            gl::DepthMask(gl::TRUE);
+        }
+        if self.cmds.len() > nb_shaded_cmds {
+            // Overlay, on top of the shaded geometry
+            gl::UseProgram(self.wireframe_program.inner().gl_id());
+            self.wireframe_program.set_uniform_primitive("u_joint_matrices[0]", &joint_matrices[..]);
+            self.wireframe_program.set_uniform_primitive("u_viewproj_matrix", &[view.proj_matrix() * view.view_matrix()]);
+            self.wireframe_program.set_uniform_primitive("u_wireframe_color", &[Rgba::<f32>::white()]);
+            self.wireframe_program.set_uniform_primitive("u_wireframe_width_px", &[1.5_f32]);
+            let depth = GLDepthState::new(view.camera.effective_depth_mode());
+            gl::DepthFunc(depth.lequal);
+            gl::DepthMask(gl::FALSE);
+            self.cmds.multi_draw_elements_range(gx::Topology::Triangles, gl::UNSIGNED_INT, nb_shaded_cmds .. self.cmds.len());
+            gl::DepthMask(gl::TRUE);
+            gl::DepthFunc(depth.less);
+        }
+        gl::Disable(gl::BLEND);
         gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
         gl::BindVertexArray(0);
 
@@ -358,6 +487,9 @@ impl GLTestMDIScene {
         gl::UseProgram(0);
 
         gl::BindTextures(0, texture2d_arrays.len() as _, ptr::null());
+        if env_map.is_some() {
+            gl::BindTextureUnit(ENV_MAP_UNIT, 0);
+        }
     }
 }
 
@@ -370,6 +502,94 @@ pub struct HeapInfo {
     // Indexed by instance
     pub instance_ranges: Vec<Range<u32>>,
     pub instance_range_mesh_entry: Vec<u32>,
+
+    // Indexed by individual instance
+    pub instance_positions: Vec<Vec3<f32>>,
+    /// World-space.
+    pub instance_bounds: Vec<Aabb<f32>>,
+    pub instance_material_indices: Vec<u16>,
+    pub instance_visibilities: Vec<bool>,
+    pub instance_layer_masks: Vec<u32>,
+    pub instance_wireframes: Vec<bool>,
+}
+
+impl HeapInfo {
+    fn is_instance_seen(&self, instance: u32, view: &View, frustum_planes: &[Vec4<f32>; 6]) -> bool {
+        let i = instance as usize;
+        self.instance_visibilities[i] && view.camera.sees_layers(self.instance_layer_masks[i]) && !(FRUSTUM_CULL && self.is_instance_outside_frustum(i, frustum_planes))
+    }
+    fn is_instance_outside_frustum(&self, instance: usize, frustum_planes: &[Vec4<f32>; 6]) -> bool {
+        frustum::aabb_outside_frustum(&self.instance_bounds[instance], frustum_planes)
+    }
+    fn draw_cmd(&self, mesh: u32, instances: Range<u32>) -> gx::DrawElementsIndirectCommand {
+        let index_range = &self.index_ranges[mesh as usize];
+        let vertex_range = &self.vertex_ranges[mesh as usize];
+        gx::DrawElementsIndirectCommand {
+            base_instance: instances.start,
+            nb_instances: instances.end - instances.start,
+            first_index: index_range.start, // Offset into the index buffer
+            nb_indices: index_range.end - index_range.start,
+            base_vertex: vertex_range.start, // Value added to indices for vertex retrieval
+        }
+    }
+}
+
+// Returns opaque commands, then translucent commands sorted back-to-front.
+// Opaque instances are drawn in batches; translucent ones are drawn one by one.
+// Instances that are hidden or not on any of the camera's layers are skipped, and so are the ones outside
+// of the view's frustum with the `frustum_cull` feature.
+fn build_draw_cmds(m: &HeapInfo, material_blend_modes: &[BlendMode], view: &View) -> (Vec<gx::DrawElementsIndirectCommand>, Vec<gx::DrawElementsIndirectCommand>) {
+    let mut cmds = vec![];
+    let mut blended_cmds = vec![];
+    let frustum_planes = view.frustum_planes();
+
+    for (i, mesh) in m.instance_ranges.iter().zip(m.instance_range_mesh_entry.iter()) {
+        let cmd = |instances: Range<u32>| m.draw_cmd(*mesh, instances);
+        let mut opaque_start = i.start;
+        for instance in i.clone() {
+            let is_seen = m.is_instance_seen(instance, view, &frustum_planes);
+            let material = m.instance_material_indices[instance as usize] as usize;
+            if is_seen && material_blend_modes[material].is_opaque() {
+                continue;
+            }
+            if opaque_start < instance {
+                cmds.push(cmd(opaque_start .. instance));
+            }
+            opaque_start = instance + 1;
+            if is_seen {
+                blended_cmds.push((m.instance_positions[instance as usize], cmd(instance .. instance + 1)));
+            }
+        }
+        if opaque_start < i.end {
+            cmds.push(cmd(opaque_start .. i.end));
+        }
+    }
+    view.sort_back_to_front(&mut blended_cmds, |&(position, _)| position);
+    (cmds, blended_cmds.into_iter().map(|(_, cmd)| cmd).collect())
+}
+
+// Returns commands for the wireframe overlay, batching consecutive seen instances that have it enabled.
+fn build_wireframe_cmds(m: &HeapInfo, view: &View) -> Vec<gx::DrawElementsIndirectCommand> {
+    let mut cmds = vec![];
+    let frustum_planes = view.frustum_planes();
+    for (i, mesh) in m.instance_ranges.iter().zip(m.instance_range_mesh_entry.iter()) {
+        let mut start = None;
+        for instance in i.clone() {
+            let is_wireframe = m.instance_wireframes[instance as usize] && m.is_instance_seen(instance, view, &frustum_planes);
+            match (start, is_wireframe) {
+                (None, true) => start = Some(instance),
+                (Some(s), false) => {
+                    cmds.push(m.draw_cmd(*mesh, s .. instance));
+                    start = None;
+                },
+                _ => (),
+            }
+        }
+        if let Some(s) = start {
+            cmds.push(m.draw_cmd(*mesh, s .. i.end));
+        }
+    }
+    cmds
 }
 
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
@@ -383,11 +603,29 @@ pub struct Material {
     pub roughness_mul: f32,
     pub roughness_map: u32,
     pub ao_map       : u32,
-    pub _pad: u32,
+    /// How much the environment map is reflected, from 0 (not at all) to 1 (perfect mirror).
+    pub reflectivity : f32,
 }
 
 assert_eq_size!(material_struct_size; Material, [Vec4<f32>; 3]);
 
+impl<'a> From<&'a material::Material> for Material {
+    fn from(m: &'a material::Material) -> Self {
+        let tex2d = |t: Texture2DSelector| (t.array_id.0 as u32) << 16 | t.slot as u32;
+        Self {
+            albedo_mul: m.albedo_mul.map(|x| x as f32 / 255.),
+            albedo_map: tex2d(m.albedo_map),
+            normal_map: tex2d(m.normal_map),
+            metallic_mul: m.metallic_mul,
+            metallic_map: tex2d(m.metallic_map),
+            roughness_mul: m.roughness_mul,
+            roughness_map: tex2d(m.roughness_map),
+            ao_map: tex2d(m.ao_map),
+            reflectivity: m.reflectivity,
+        }
+    }
+}
+
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
 #[repr(C)]
 pub struct DirectionalLight {
@@ -408,16 +646,6 @@ pub struct PointLight {
 
 assert_eq_size!(point_light_struct_size; PointLight, [Vec4<f32>; 3]);
 
-#[derive(Debug, Default, Copy, Clone, Hash, PartialEq, Eq)]
-#[repr(C)]
-pub struct GLDrawElementsIndirectCommand {
-    pub nb_indices: GLuint,
-    pub nb_instances: GLuint,
-    pub first_index: GLuint,
-    pub base_vertex: GLuint,
-    pub base_instance: GLuint,
-}
-
 static PBR_VS : &'static [u8] = 
 b"#version 450 core
 
@@ -470,6 +698,7 @@ struct Material {
     float roughness_mul;
     uint  roughness_map;
     uint  ao_map;
+    float reflectivity;
 };
 
 struct PointLight {
@@ -489,6 +718,9 @@ struct DirectionalLight {
 uniform sampler2DArray u_texture2d_arrays[16];
 uniform vec3 u_eye_position_worldspace;
 uniform DirectionalLight u_directional_light;
+uniform samplerCubeArray u_env_cubemap_array;
+uniform float u_env_cubemap_slot;
+uniform uint u_is_env_map_enabled;
 layout(std430, binding = 1) buffer PointLights { PointLight u_point_lights[]; };
 layout(std430, binding = 2) buffer Materials { Material u_materials[]; };
 
@@ -596,6 +828,271 @@ void main() {
     color /= color + vec3(1.0);
     color = pow(color, vec3(1.0/2.2));
 
+    // Environment reflection. The skybox isn't gamma-corrected, so neither is its reflection.
+    if (u_is_env_map_enabled != 0u) {
+        vec3 R = reflect(-V, N);
+        vec3 env = texture(u_env_cubemap_array, vec4(R, u_env_cubemap_slot)).rgb;
+        color = mix(color, env, mat.reflectivity);
+    }
+
     f_color = vec4(color, albedo.a);
 }
 ";
+
+// Passes barycentric coordinates to the fragment shader, which draws edges where one of them nears zero.
+// Unlike glPolygonMode(), lines have a constant width in pixels and are anti-aliased.
+static WIREFRAME_GS: &'static [u8] = 
+b"#version 450 core
+
+layout(triangles) in;
+layout(triangle_strip, max_vertices = 3) out;
+
+out vec3 g_barycentric;
+
+void main() {
+    const vec3 barycentrics[3] = vec3[3](vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0));
+    for (int i = 0; i < 3; ++i) {
+        gl_Position = gl_in[i].gl_Position;
+        g_barycentric = barycentrics[i];
+        EmitVertex();
+    }
+    EndPrimitive();
+}
+";
+
+static WIREFRAME_FS: &'static [u8] = 
+b"#version 450 core
+
+uniform vec4 u_wireframe_color;
+uniform float u_wireframe_width_px;
+
+in vec3 g_barycentric;
+
+out vec4 f_color;
+
+void main() {
+    vec3 d = fwidth(g_barycentric);
+    vec3 a = smoothstep(vec3(0.0), d * u_wireframe_width_px, g_barycentric);
+    float edge = 1.0 - min(min(a.x, a.y), a.z);
+    if (edge <= 0.0) {
+        discard;
+    }
+    f_color = vec4(u_wireframe_color.rgb, u_wireframe_color.a * edge);
+}
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fate::math::Rect;
+    use camera::{CameraProjectionMode, CameraDepthMode};
+    use xform::Xform;
+
+    fn unit_box_at(p: Vec3<f32>) -> Aabb<f32> {
+        Aabb { min: p - 0.5, max: p + 0.5 }
+    }
+
+    // A single triangle mesh, with one opaque, visible, non-wireframe instance at each position.
+    fn single_mesh_heap_info(instance_positions: Vec<Vec3<f32>>) -> HeapInfo {
+        let n = instance_positions.len();
+        HeapInfo {
+            vertex_ranges: vec![0..3],
+            index_ranges: vec![0..3],
+            instance_ranges: vec![0..n as u32],
+            instance_range_mesh_entry: vec![0],
+            instance_bounds: instance_positions.iter().map(|p| unit_box_at(*p)).collect(),
+            instance_positions,
+            instance_material_indices: vec![0; n],
+            instance_visibilities: vec![true; n],
+            instance_layer_masks: vec![Camera::ALL_LAYERS; n],
+            instance_wireframes: vec![false; n],
+        }
+    }
+
+    fn view_with_layers(layer_mask: u32) -> View {
+        View {
+            xform: Xform::default(),
+            camera: Camera { projection_mode: CameraProjectionMode::Perspective, fov_y_radians: 1., near: 0.1, far: 100., depth_mode: CameraDepthMode::Standard, layer_mask, },
+            viewport: Rect::new(0, 0, 800, 600),
+        }
+    }
+
+    fn instances(cmds: &[gx::DrawElementsIndirectCommand]) -> Vec<(u32, u32)> {
+        cmds.iter().map(|cmd| (cmd.base_instance, cmd.nb_instances)).collect()
+    }
+
+    #[test]
+    fn instances_outside_camera_layers_are_not_drawn() {
+        let mut heap_info = single_mesh_heap_info(vec![Vec3::new(0., 0., 5.); 3]);
+        heap_info.instance_visibilities = vec![true, true, false];
+        heap_info.instance_layer_masks = vec![0b01, 0b10, 0b01];
+        heap_info.instance_wireframes = vec![true; 3];
+        let view = view_with_layers(0b01);
+        let (cmds, blended_cmds) = build_draw_cmds(&heap_info, &[BlendMode::Opaque], &view);
+        assert!(blended_cmds.is_empty());
+        assert_eq!(instances(&cmds), vec![(0, 1)]);
+
+        let wireframe_cmds = build_wireframe_cmds(&heap_info, &view);
+        assert_eq!(wireframe_cmds, cmds);
+    }
+
+    #[test]
+    fn translucent_instances_are_drawn_far_to_near_after_opaque_ones() {
+        let mut heap_info = single_mesh_heap_info(vec![Vec3::new(0., 0., 5.), Vec3::new(0., 0., 1.), Vec3::new(0., 0., 10.), Vec3::new(0., 2., 4.)]);
+        heap_info.instance_material_indices = vec![0, 1, 1, 1];
+        let (cmds, blended_cmds) = build_draw_cmds(&heap_info, &[BlendMode::Opaque, BlendMode::AlphaBlend], &view_with_layers(Camera::ALL_LAYERS));
+        assert_eq!(instances(&cmds), vec![(0, 1)]);
+        assert_eq!(instances(&blended_cmds), vec![(2, 1), (3, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn wireframe_is_selected_per_instance() {
+        let mut heap_info = single_mesh_heap_info(vec![Vec3::new(0., 0., 5.); 5]);
+        heap_info.instance_wireframes = vec![true, true, false, false, true];
+        let cmds = build_wireframe_cmds(&heap_info, &view_with_layers(Camera::ALL_LAYERS));
+        assert_eq!(instances(&cmds), vec![(0, 2), (4, 1)]);
+        assert!(cmds.iter().all(|cmd| cmd.nb_indices == 3));
+    }
+
+    #[test]
+    fn instances_outside_the_frustum_are_not_drawn() {
+        let heap_info = single_mesh_heap_info(vec![Vec3::new(0., 0., 5.), Vec3::new(0., 0., -5.), Vec3::new(0., 0., 10.)]);
+        let view = view_with_layers(Camera::ALL_LAYERS);
+        let frustum_planes = view.frustum_planes();
+        let outside: Vec<_> = (0 .. 3).map(|i| heap_info.is_instance_outside_frustum(i, &frustum_planes)).collect();
+        assert_eq!(outside, vec![false, true, false]);
+
+        let (cmds, _) = build_draw_cmds(&heap_info, &[BlendMode::Opaque], &view);
+        if FRUSTUM_CULL {
+            assert_eq!(instances(&cmds), vec![(0, 1), (2, 1)]);
+        } else {
+            assert_eq!(instances(&cmds), vec![(0, 3)]);
+        }
+    }
+
+    // The offsets of the members of the GLSL struct `name` in `src`, in declaration order.
+    // Only scalars and `vec4`s are supported, for which std430 offsets are the same as std140's.
+    fn glsl_struct_offsets(src: &[u8], name: &str) -> (Vec<usize>, usize) {
+        let src = ::std::str::from_utf8(src).unwrap();
+        let start = src.find(&format!("struct {} {{", name)).unwrap();
+        let body = &src[start ..];
+        let body = &body[body.find('{').unwrap() + 1 .. body.find('}').unwrap()];
+        let mut layout = gx::Std140Layout::new();
+        let offsets = body.split(';').map(str::trim).filter(|m| !m.is_empty()).map(|member| {
+            match member.split_whitespace().next().unwrap() {
+                "float" => layout.push::<f32>(),
+                "uint" => layout.push::<u32>(),
+                "vec4" => layout.push::<Vec4<f32>>(),
+                ty => panic!("Unsupported member type: {}", ty),
+            }
+        }).collect();
+        (offsets, layout.size())
+    }
+
+    #[test]
+    fn material_matches_the_glsl_struct() {
+        let (glsl_offsets, glsl_size) = glsl_struct_offsets(PBR_FS, "Material");
+        let m = Material::default();
+        let offset = |member: usize| member - &m as *const _ as usize;
+        let offsets = vec![
+            offset(&m.albedo_mul as *const _ as usize),
+            offset(&m.albedo_map as *const _ as usize),
+            offset(&m.normal_map as *const _ as usize),
+            offset(&m.metallic_mul as *const _ as usize),
+            offset(&m.metallic_map as *const _ as usize),
+            offset(&m.roughness_mul as *const _ as usize),
+            offset(&m.roughness_map as *const _ as usize),
+            offset(&m.ao_map as *const _ as usize),
+            offset(&m.reflectivity as *const _ as usize),
+        ];
+        assert_eq!(offsets, glsl_offsets);
+        assert_eq!(mem::size_of::<Material>(), glsl_size);
+    }
+
+    #[test]
+    fn reflectivity_reaches_the_gpu_material() {
+        assert_eq!(Material::from(&material::Material { reflectivity: 1., .. Default::default() }).reflectivity, 1.);
+        assert_eq!(Material::from(&material::Material::default()).reflectivity, 0.);
+    }
+
+    // Needs a display and an OpenGL 4.5 driver: run with `cargo test -- --ignored`.
+    // Draws a single pixel with the PBR program, facing a cubemap whose faces are all `env_color`.
+    #[test]
+    #[ignore]
+    fn fully_reflective_material_outputs_the_env_map_color() {
+        use platform::{self, Sdl2Platform};
+        use super::super::gl_setup::gl_setup;
+
+        let platform = Sdl2Platform::new(&platform::Settings::new());
+        gl_setup(&platform).unwrap();
+        let program = new_pbr_program().unwrap();
+
+        let env_color = [51_u8, 102, 153, 255];
+        let mut pixel = [0_u8; 4];
+        unsafe {
+            let mut textures = [0; 2];
+            gl::CreateTextures(gl::TEXTURE_CUBE_MAP_ARRAY, 1, &mut textures[0]);
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut textures[1]);
+            let (env_map, target) = (gx::Texture::from_gl_id(textures[0]), gx::Texture::from_gl_id(textures[1]));
+            gl::TextureStorage3D(env_map.gl_id(), 1, gl::RGBA8, 1, 1, 6);
+            gl::ClearTexImage(env_map.gl_id(), 0, gl::RGBA, gl::UNSIGNED_BYTE, env_color.as_ptr() as _);
+            gl::TextureStorage2D(target.gl_id(), 1, gl::RGBA8, 1, 1);
+            let fb = gx::Framebuffer::new_dsa();
+            fb.attach_color(0, &target, 0);
+            fb.check_status().unwrap();
+
+            let mut material_buffer = gx::ShaderStorageBuffer::new(1, gx::BufferFlags::DYNAMIC_STORAGE);
+            material_buffer.set_data(&[Material::from(&material::Material { reflectivity: 1., .. Default::default() })]);
+            // Away from the pixel, otherwise its attenuation divides by zero
+            let mut point_light_buffer = gx::ShaderStorageBuffer::new(1, gx::BufferFlags::DYNAMIC_STORAGE);
+            point_light_buffer.set_data(&[PointLight { position: Vec4::new(0., 0., 10., 1.), color: Rgba::white(), .. Default::default() }]);
+
+            // No vertex arrays: every vertex gets the same attribs, i.e a point at the origin, facing +Z,
+            // with no skinning, an identity model matrix, and material 0.
+            let vao = gx::VertexArray::new();
+            gl::VertexAttrib3f(VertexAttribIndex::Position as _, 0., 0., 0.);
+            gl::VertexAttrib3f(VertexAttribIndex::Normal as _, 0., 0., 1.);
+            gl::VertexAttrib2f(VertexAttribIndex::UV as _, 0., 0.);
+            gl::VertexAttrib4f(VertexAttribIndex::Weights as _, 1., 0., 0., 0.);
+            gl::VertexAttrib4f(VertexAttribIndex::Joints as _, 0., 0., 0., 0.);
+            for i in 0 .. 4 {
+                let col = Mat4::<f32>::identity().cols[i];
+                gl::VertexAttrib4f(VertexAttribIndex::ModelMatrix as GLuint + i as GLuint, col.x, col.y, col.z, col.w);
+            }
+            gl::VertexAttribI1ui(VertexAttribIndex::MaterialIndex as _, 0);
+
+            gl::UseProgram(program.inner().gl_id());
+            program.set_uniform_primitive("u_joint_matrices[0]", &[Mat4::<f32>::identity(); 32][..]);
+            program.set_uniform_primitive("u_viewproj_matrix", &[Mat4::<f32>::identity()]);
+            program.set_uniform_primitive("u_eye_position_worldspace", &[Vec3::<f32>::new(0., 0., 1.)]);
+            program.set_uniform("u_env_cubemap_array", gx::GLSLType::SamplerCubeMapArray, &[ENV_MAP_UNIT as i32]);
+            program.set_uniform_primitive("u_env_cubemap_slot", &[0_f32]);
+            program.set_uniform_primitive("u_is_env_map_enabled", &[1_u32]);
+            gl::BindTextureUnit(ENV_MAP_UNIT, env_map.gl_id());
+            point_light_buffer.bind_base(1);
+            material_buffer.bind_base(2);
+
+            fb.bind();
+            gl::Viewport(0, 0, 1, 1);
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::BLEND);
+            gl::PointSize(1.);
+            gl::BindVertexArray(vao.gl_id());
+            gl::DrawArrays(gl::POINTS, 0, 1);
+            gl::ReadPixels(0, 0, 1, 1, gl::RGBA, gl::UNSIGNED_BYTE, pixel.as_mut_ptr() as _);
+            gl::BindVertexArray(0);
+            gl::UseProgram(0);
+            gx::Framebuffer::unbind();
+        }
+        for i in 0 .. 3 {
+            assert!((pixel[i] as i32 - env_color[i] as i32).abs() <= 1, "{:?} != {:?}", &pixel[.. 3], &env_color[.. 3]);
+        }
+    }
+
+    #[test]
+    fn env_map_needs_one_more_texture_unit_than_guaranteed() {
+        assert!(check_max_texture_image_units(16).is_err());
+        assert!(check_max_texture_image_units(17).is_ok());
+        assert!(check_max_texture_image_units(32).is_ok());
+    }
+}
@@ -1,16 +1,19 @@
 use fate::math::{Extent2, Rgba, Rect, Vec3, Vec4};
 use fate::gx::{self, Object, gl::{self, types::*}};
-use camera::{Camera, View};
+use camera::{Camera, View, CameraDepthMode};
 use cubemap::CubemapSelector;
 
 use super::gl_skybox::GLSkybox;
 use super::gl_test_mdi_scene::GLTestMDIScene;
+use super::gl_2d::GL2D;
+use super::GLDepthState;
 
-use gpu::GpuCmd;
+use gpu::{GpuCmd, format_mem};
 use viewport::{ViewportVisitor, AcceptLeafViewport};
 use cubemap::{CubemapArrayID};
 use texture2d::Texture2DArrayID;
 use mesh::VertexAttribIndex;
+use resources;
 use system::*;
 
 pub struct GLSystem {
@@ -21,27 +24,68 @@ pub struct GLSystem {
     // Skybox
     skybox: GLSkybox,
     test_mdi_scene: GLTestMDIScene,
+
+    // GUI, debug overlays
+    gl_2d: GL2D,
+    // None if timer queries are not supported
+    scene_gpu_timer: Option<gx::TimerQueryRing>,
+
+    window: WindowState,
+}
+
+/// Tracks whether there's anything to draw to.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+struct WindowState {
+    is_minimized: bool,
+}
+
+impl WindowState {
+    fn on_minimized(&mut self) {
+        self.is_minimized = true;
+    }
+    // Some platforms go straight from minimized to maximized, without a "restored" event in-between.
+    fn on_maximized(&mut self) {
+        self.is_minimized = false;
+    }
+    fn on_restored(&mut self) {
+        self.is_minimized = false;
+    }
+    // Some platforms report a zero-sized canvas instead of (or on top of) minimizing.
+    fn is_canvas_visible(&self, canvas_size: Extent2<u32>) -> bool {
+        !self.is_minimized && canvas_size.w != 0 && canvas_size.h != 0
+    }
 }
 
 impl GLSystem {
-    pub fn new() -> Self {
+    pub fn new(g: &G) -> Self {
         let mut cubemap_arrays = [0; CubemapArrayID::MAX];
         let mut texture2d_arrays = [0; Texture2DArrayID::MAX];
         unsafe {
             gl::CreateTextures(gl::TEXTURE_CUBE_MAP_ARRAY, cubemap_arrays.len() as _, cubemap_arrays.as_mut_ptr());
             gl::CreateTextures(gl::TEXTURE_2D_ARRAY, texture2d_arrays.len() as _, texture2d_arrays.as_mut_ptr());
         }
+        let test_mdi_scene = match GLTestMDIScene::new() {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not set up the test MDI scene:\n{}", e);
+                panic!("Could not set up the test MDI scene:\n{}", e)
+            },
+        };
 
         Self {
             cubemap_arrays,
             texture2d_arrays,
             skybox: GLSkybox::new(),
-            test_mdi_scene: GLTestMDIScene::new(),
+            test_mdi_scene,
+            gl_2d: GL2D::new(&[(resources::BASIS33, g.res.basis33_atlas(), g.res.basis33().height_px())]),
+            scene_gpu_timer: if gx::QueryTarget::TimeElapsed.is_supported() { Some(gx::TimerQueryRing::new(3)) } else { None },
+            window: WindowState::default(),
         }
     }
     pub fn cubemap_array(&self, id: CubemapArrayID) -> GLuint { self.cubemap_arrays[id.0 as usize] }
     pub fn texture2d_array(&self, id: Texture2DArrayID) -> GLuint { self.texture2d_arrays[id.0 as usize] }
     pub fn cubemap_array_mut(&mut self, id: CubemapArrayID) -> &mut GLuint { &mut self.cubemap_arrays[id.0 as usize] }
+    pub fn gl_2d(&self) -> &GL2D { &self.gl_2d }
     pub fn texture2d_array_mut(&mut self, id: Texture2DArrayID) -> &mut GLuint { &mut self.texture2d_arrays[id.0 as usize] }
 }
 
@@ -60,18 +104,54 @@ impl Drop for GLSystem {
 }
 
 impl System for GLSystem {
+    fn on_window_minimized(&mut self, _g: &mut G) {
+        self.window.on_minimized();
+    }
+    // The canvas size isn't cached: it is re-read from `g.input` on the next draw.
+    fn on_window_maximized(&mut self, _g: &mut G) {
+        self.window.on_maximized();
+    }
+    fn on_window_restored(&mut self, _g: &mut G) {
+        self.window.on_restored();
+    }
     fn draw(&mut self, g: &mut G, _d: &Draw) {
+        // Commands must still be processed, otherwise GPU resources would go out of sync with G.
         self.process_gpu_cmd_queue(g);
 
-        let Extent2 { w, h } = g.input.canvas_size();
+        let canvas_size = g.input.canvas_size();
+        if !self.window.is_canvas_visible(canvas_size) {
+            return;
+        }
+        // Viewports are in logical pixels, like the mouse, but rendering is done in physical pixels.
+        let physical_size = g.input.canvas_physical_size();
+        let canvas_rect = Rect::new(0, 0, physical_size.w, physical_size.h);
+        gx::set_viewport(canvas_rect);
         unsafe {
-            gl::Viewport(0, 0, w as _, h as _);
             let Rgba { r, g, b, a } = g.viewport_db().border_color();
             gl::ClearColor(r, g, b, a);
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
 
+        if let Some(timer) = self.scene_gpu_timer.as_mut() {
+            timer.begin();
+        }
         g.visit_viewports(&mut GLViewportVisitor { g, sys: self, });
+        if let Some(timer) = self.scene_gpu_timer.as_mut() {
+            timer.end();
+        }
+
+        // Debug overlay
+        if let Some(fps_stats) = g.last_fps_stats() {
+            let _group = gx::debug_group("Debug overlay");
+            gx::set_viewport(canvas_rect);
+            let fps = match self.scene_gpu_timer.as_mut().and_then(|timer| timer.try_get_latest_nanos()) {
+                Some(ns) => format!("{:.1} FPS (GPU scene: {:.2} ms)", fps_stats.fps(), ns as f64 / 1_000_000.),
+                None => format!("{:.1} FPS", fps_stats.fps()),
+            };
+            self.gl_2d.draw_text(canvas_size, resources::BASIS33, Vec2::new(4, 4), &fps, Rgba::white());
+            let mem = g.gpu_memory_stats();
+            self.gl_2d.draw_text(canvas_size, resources::BASIS33, Vec2::new(4, 24), &format!("GPU: {} / {}", format_mem(mem.used()), format_mem(mem.budget)), Rgba::white());
+        }
     }
 }
 
@@ -88,6 +168,7 @@ impl GLSystem {
                     let Rgba { r, g, b, a } = g.clear_color();
                     gl::ClearColor(r, g, b, a);
                 },
+                GpuCmd::SkyboxFiltersEdit => self.skybox.set_filters(g.skybox_filters()),
                 GpuCmd::Texture2DArrayCreate(id) => {
                     let info = g.texture2d_array_info(id).unwrap();
                     gl::TextureStorage3D(self.texture2d_array(id), info.nb_levels as _, info.internal_format as _, info.size.w as _, info.size.h as _, info.nb_slots as _);
@@ -133,6 +214,21 @@ impl GLSystem {
                 GpuCmd::CubemapArraySetMagFilter(id, filter)   => gl::TextureParameteri(self.cubemap_array(id), gl::TEXTURE_MAG_FILTER, filter as _),
                 GpuCmd::Texture2DArraySetMinFilter(id, filter) => gl::TextureParameteri(self.texture2d_array(id), gl::TEXTURE_MIN_FILTER, filter as _),
                 GpuCmd::Texture2DArraySetMagFilter(id, filter) => gl::TextureParameteri(self.texture2d_array(id), gl::TEXTURE_MAG_FILTER, filter as _),
+                GpuCmd::CubemapArraySetFilters(id, filters) => {
+                    gl::TextureParameteri(self.cubemap_array(id), gl::TEXTURE_MIN_FILTER, filters.gl_min_filter() as _);
+                    gl::TextureParameteri(self.cubemap_array(id), gl::TEXTURE_MAG_FILTER, filters.gl_mag_filter() as _);
+                },
+                GpuCmd::Texture2DArraySetFilters(id, filters) => {
+                    gl::TextureParameteri(self.texture2d_array(id), gl::TEXTURE_MIN_FILTER, filters.gl_min_filter() as _);
+                    gl::TextureParameteri(self.texture2d_array(id), gl::TEXTURE_MAG_FILTER, filters.gl_mag_filter() as _);
+                },
+                GpuCmd::CubemapArrayGenerateMipmaps(id) => gl::GenerateTextureMipmap(self.cubemap_array(id)),
+                GpuCmd::Texture2DArrayGenerateMipmaps(id) => gl::GenerateTextureMipmap(self.texture2d_array(id)),
+                GpuCmd::Texture2DArraySetAnisotropy(id, level) => {
+                    if gx::sampler::set_texture_anisotropy(self.texture2d_array(id), level).is_none() {
+                        warn!("Anisotropic filtering is not supported; ignoring anisotropy of {} for {:?}", level, id);
+                    }
+                },
             }
         }
     }
@@ -140,42 +236,151 @@ impl GLSystem {
 
 struct GLViewportVisitor<'a> {
     pub g: &'a G,
-    pub sys: &'a GLSystem,
+    pub sys: &'a mut GLSystem,
 }
 
 impl<'a> ViewportVisitor for GLViewportVisitor<'a> {
     fn accept_leaf_viewport(&mut self, args: AcceptLeafViewport) {
+        let _group = gx::debug_group("Leaf viewport");
+        let scale = self.g.input.canvas_scale_factor();
+        let rect = to_physical_rect(args.rect, scale);
+        let border_px = (args.border_px as f32 * scale).round() as u32;
         unsafe {
-            let Rect { x, y, w, h } = args.rect;
-            gl::Viewport(x as _, y as _, w as _, h as _);
+            gx::set_viewport(rect.map(|p| p as i32, |e| e));
 
             // Temporary
             gl::Enable(gl::SCISSOR_TEST);
 
-            let (bx, by) = (args.border_px, args.border_px);
-            if w < bx+bx || h < by+by {
-                return;
-            }
-            let (x, y, w, h) = (x+bx, y+by, w-bx-bx, h-by-by);
-            let Rgba { r, g, b, a } = args.info.clear_color;
-            gl::Scissor(x as _, y as _, w as _, h as _);
-            gl::ClearColor(r, g, b, a);
-            gl::Clear(gl::COLOR_BUFFER_BIT/* | gl::DEPTH_BUFFER_BIT*/);
-
+            let (Rect { x, y, w, h }, Rgba { r, g, b, a }) = match leaf_viewport_clear(rect, border_px, args.info.clear_color, self.g.clear_color()) {
+                None => {
+                    gl::Disable(gl::SCISSOR_TEST);
+                    return;
+                },
+                Some(x) => x,
+            };
             let eid = args.info.camera;
             let view = View {
                 xform: *self.g.eid_xform(eid).unwrap(),
                 camera: *self.g.eid_camera(eid).unwrap(),
                 viewport: Rect { x, y, w, h },
             };
+            GLDepthState::new(view.camera.effective_depth_mode()).apply();
 
-            self.sys.test_mdi_scene.draw(&view, &self.sys.texture2d_arrays);
+            gx::set_scissor(Rect { x: x as _, y: y as _, w, h });
+            gl::ClearColor(r, g, b, a);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+            let env_map = args.info.skybox_cubemap_selector.map(|selector| (selector, self.sys.cubemap_array(selector.array_id)));
+            {
+                let _group = gx::debug_group("Test MDI scene");
+                self.sys.test_mdi_scene.draw(&view, &self.sys.texture2d_arrays, env_map);
+            }
 
             if let Some(skybox_cubemap_selector) = args.info.skybox_cubemap_selector {
+                let _group = gx::debug_group("Skybox");
                 self.sys.skybox.draw(skybox_cubemap_selector, self.sys.cubemap_array(skybox_cubemap_selector.array_id), &view);
             }
 
+            GLDepthState::new(CameraDepthMode::Standard).apply();
             gl::Disable(gl::SCISSOR_TEST);
         }
     }
 }
+
+/// Scales a rect in logical pixels to physical pixels; edges are rounded, so that adjacent rects stay adjacent.
+fn to_physical_rect(rect: Rect<u32, u32>, scale: f32) -> Rect<u32, u32> {
+    let scale = |x: u32| (x as f32 * scale).round() as u32;
+    let (x0, y0, x1, y1) = (scale(rect.x), scale(rect.y), scale(rect.x + rect.w), scale(rect.y + rect.h));
+    Rect { x: x0, y: y0, w: x1 - x0, h: y1 - y0 }
+}
+
+/// Returns the scissor rect (inset by the border) and color a leaf viewport should be cleared with,
+/// or `None` if the viewport is too small to be visible.
+fn leaf_viewport_clear(rect: Rect<u32, u32>, border_px: u32, clear_color: Option<Rgba<f32>>, default_clear_color: Rgba<f32>) -> Option<(Rect<u32, u32>, Rgba<f32>)> {
+    let Rect { x, y, w, h } = rect;
+    let (bx, by) = (border_px, border_px);
+    if w < bx+bx || h < by+by {
+        return None;
+    }
+    let rect = Rect { x: x+bx, y: y+by, w: w-bx-bx, h: h-by-by };
+    Some((rect, clear_color.unwrap_or(default_clear_color)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use viewport::{ViewportDB, LeafViewport, SplitDirection};
+    use eid::EID;
+
+    struct ClearRecorder {
+        default_clear_color: Rgba<f32>,
+        clears: Vec<(Rect<u32, u32>, Rgba<f32>)>,
+    }
+
+    impl ViewportVisitor for ClearRecorder {
+        fn accept_leaf_viewport(&mut self, args: AcceptLeafViewport) {
+            if let Some(clear) = leaf_viewport_clear(args.rect, args.border_px, args.info.clear_color, self.default_clear_color) {
+                self.clears.push(clear);
+            }
+        }
+    }
+
+    #[test]
+    fn each_leaf_viewport_is_cleared_to_its_own_color() {
+        let mut db = ViewportDB::new(LeafViewport {
+            clear_color: Some(Rgba::red()),
+            skybox_cubemap_selector: None,
+            camera: EID(0),
+        });
+        let root = db.root();
+        let c1 = db.split(root, SplitDirection::Vertical);
+        let c0 = db.focused();
+        db.node(c1).unwrap().value.unwrap_leaf().borrow_mut().clear_color = Some(Rgba::green());
+        db.node(c0).unwrap().value.unwrap_leaf().borrow_mut().clear_color = None;
+
+        let mut recorder = ClearRecorder { default_clear_color: Rgba::blue(), clears: vec![] };
+        db.visit(Rect::new(0, 0, 200, 100), &mut recorder);
+
+        assert_eq!(recorder.clears.len(), 2);
+        let (r0, color0) = recorder.clears[0];
+        let (r1, color1) = recorder.clears[1];
+        assert_ne!(r0, r1);
+        for r in &[r0, r1] {
+            assert!(r.x + r.w <= 200 && r.y + r.h <= 100);
+        }
+        let mut colors = vec![color0, color1];
+        colors.sort_by(|a, b| a.g.partial_cmp(&b.g).unwrap());
+        assert_eq!(colors, vec![Rgba::blue(), Rgba::green()]);
+    }
+
+    #[test]
+    fn too_small_leaf_viewport_is_not_cleared() {
+        assert_eq!(leaf_viewport_clear(Rect::new(0, 0, 1, 10), 1, None, Rgba::blue()), None);
+        assert_eq!(leaf_viewport_clear(Rect::new(2, 4, 10, 10), 1, None, Rgba::blue()), Some((Rect::new(3, 5, 8, 8), Rgba::blue())));
+    }
+
+    #[test]
+    fn viewports_are_scaled_to_physical_pixels() {
+        assert_eq!(to_physical_rect(Rect::new(10, 20, 100, 50), 1.), Rect::new(10, 20, 100, 50));
+        assert_eq!(to_physical_rect(Rect::new(10, 20, 100, 50), 2.), Rect::new(20, 40, 200, 100));
+        // Two halves of a 101px wide canvas still cover it exactly at 1.5x
+        let (left, right) = (to_physical_rect(Rect::new(0, 0, 50, 10), 1.5), to_physical_rect(Rect::new(50, 0, 51, 10), 1.5));
+        assert_eq!(left.x + left.w, right.x);
+        assert_eq!(right.x + right.w, (101_f32 * 1.5).round() as u32);
+    }
+    #[test]
+    fn maximizing_a_minimized_window_resumes_drawing() {
+        let size = Extent2::new(800, 600);
+        let mut window = WindowState::default();
+        assert!(window.is_canvas_visible(size));
+        window.on_minimized();
+        assert!(!window.is_canvas_visible(size));
+        window.on_maximized();
+        assert!(window.is_canvas_visible(size));
+        window.on_minimized();
+        window.on_restored();
+        assert!(window.is_canvas_visible(size));
+        assert!(!window.is_canvas_visible(Extent2::new(800, 0)));
+    }
+}
@@ -1,13 +1,47 @@
-use fate::gx;
+use fate::gx::{self, gl::{self, types::*}};
+use camera::CameraDepthMode;
 
 pub mod glsystem;
 pub mod gl_setup;
 pub mod gl_skybox;
 pub mod gl_test_mdi_scene;
+pub mod gl_2d;
 
 pub use self::glsystem::GLSystem;
 
 
+/// The GL depth state a camera's depth mode calls for.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GLDepthState {
+    pub clip_depth_mode: GLenum,
+    pub clear_depth: GLdouble,
+    /// Replaces `GL_LESS`
+    pub less: GLenum,
+    /// Replaces `GL_LEQUAL`
+    pub lequal: GLenum,
+    /// Depth of the far plane, e.g for the skybox
+    pub far_depth: f32,
+}
+
+impl GLDepthState {
+    pub fn new(depth_mode: CameraDepthMode) -> Self {
+        match depth_mode {
+            CameraDepthMode::Standard => Self {
+                clip_depth_mode: gl::NEGATIVE_ONE_TO_ONE, clear_depth: 1., less: gl::LESS, lequal: gl::LEQUAL, far_depth: 1.,
+            },
+            CameraDepthMode::ReversedZ => Self {
+                clip_depth_mode: gl::ZERO_TO_ONE, clear_depth: 0., less: gl::GREATER, lequal: gl::GEQUAL, far_depth: 0.,
+            },
+        }
+    }
+    pub unsafe fn apply(&self) {
+        gl::ClipControl(gl::LOWER_LEFT, self.clip_depth_mode);
+        gl::ClearDepth(self.clear_depth);
+        gl::DepthFunc(self.less);
+    }
+}
+
+
 fn unwrap_or_display_error(r: Result<gx::ProgramEx, String>) -> gx::ProgramEx {
     match r {
         Ok(p) => p,
@@ -25,4 +59,14 @@ fn new_program_ex(vs: &[u8], fs: &[u8]) -> Result<gx::ProgramEx, String> {
 }
 fn new_program_ex_unwrap(vs: &[u8], fs: &[u8]) -> gx::ProgramEx {
     unwrap_or_display_error(new_program_ex(vs, fs))
+}
+fn new_program_ex_with_geometry(vs: &[u8], gs: &[u8], fs: &[u8]) -> Result<gx::ProgramEx, String> {
+    let vs = gx::VertexShader::try_from_source(vs)?;
+    let gs = gx::GeometryShader::try_from_source(gs)?;
+    let fs = gx::FragmentShader::try_from_source(fs)?;
+    let prog = gx::Program::try_from_stages(Some(&vs), None, None, Some(&gs), Some(&fs))?;
+    Ok(gx::ProgramEx::new(prog))
+}
+fn new_program_ex_with_geometry_unwrap(vs: &[u8], gs: &[u8], fs: &[u8]) -> gx::ProgramEx {
+    unwrap_or_display_error(new_program_ex_with_geometry(vs, gs, fs))
 }
\ No newline at end of file
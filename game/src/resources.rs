@@ -23,59 +23,173 @@ use fate::img;
 // - Comment identifier les références de ressources ?
 //   - De base les fichiers peuvent être complètement bougés et changés "en notre absence". Il faut pas que ce soit chiant.
 
+#[derive(Debug, Default, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FontID(pub u8);
+
+pub const BASIS33: FontID = FontID(0);
+
+/// Name of the environment variable that overrides the data path.
+/// It can also be set from the command line with `--data-path <dir>`.
+pub const DATA_PATH_ENV_VAR: &'static str = "FATE_DATA_PATH";
+
 #[derive(Debug)]
 pub struct Resources {
     font_loader: FontLoader,
-    data_path: PathBuf,
+    data_roots: Vec<PathBuf>,
+    fonts: Option<Fonts>,
+}
+
+#[derive(Debug)]
+struct Fonts {
     basis33: Font,
     basis33_atlas: Atlas,
 }
 
 impl Resources {
     pub fn new() -> Result<Self, String> {
-        let data_path = {
-            let mut dir = env::current_exe().map_err(|io| format!("{}", io))?;
-            loop {
-                if !dir.pop() {
-                    break Err(format!("Could not find `data` directory!"));
-                }
-                trace!("Searching for data path in `{}`", dir.display());
-                dir.push("data");
-                if dir.is_dir() {
-                    break Ok(dir);
-                }
-                dir.pop();
-            }
-        }?;
-        trace!("Found data path at `{}`", data_path.display());
+        let data_roots = default_data_roots();
+        if data_roots.is_empty() {
+            return Err(format!("Could not find `data` directory! (You may set `{}` to override it)", DATA_PATH_ENV_VAR));
+        }
+        Self::with_data_roots(data_roots)
+    }
+    /// Uses `data_path` as the only data directory, ignoring `FATE_DATA_PATH` and the default locations.
+    #[allow(dead_code)]
+    pub fn with_data_path<P: Into<PathBuf>>(data_path: P) -> Result<Self, String> {
+        Self::with_data_roots(vec![data_path.into()])
+    }
+    fn with_data_roots(data_roots: Vec<PathBuf>) -> Result<Self, String> {
+        trace!("Using data paths {:?}", data_roots);
         let font_loader = FontLoader::new().map_err(|e| format!("Could not create FontLoader: {}", e))?;
-        let mut basis33 = font_loader.load_font(data_path.join(PathBuf::from("fonts/basis33/basis33.ttf"))).map_err(|e| format!("Could not load basis33 font: {}", e))?;
+        let basis33_path = match resolve_in(&data_roots, Path::new("fonts/basis33/basis33.ttf")) {
+            Some(path) => path,
+            None => return Err(format!("Could not find basis33 font in any of the data directories: {:?}", data_roots)),
+        };
+        let mut basis33 = font_loader.load_font(&basis33_path).map_err(|e| format!("Could not load basis33 font: {}", e))?;
         basis33.set_height_px(16).unwrap();
         let basis33_atlas = basis33.build_exhaustive_atlas(256);
 
         if env::var("export_font_atlases").is_ok() {
-            let path = data_path.join(PathBuf::from("fonts/basis33/atlas.png"));
+            let path = basis33_path.with_file_name("atlas.png");
             img::save_gray_u8(&path, img::ImageFormat::PNG, basis33_atlas.img.as_ref()).unwrap();
             info!("Saved `{}`", path.display());
         }
 
         Ok(Self {
-            data_path,
+            data_roots,
             font_loader,
-            basis33,
-            basis33_atlas,
+            fonts: Some(Fonts { basis33, basis33_atlas }),
         })
     }
-    pub fn data_path(&self) -> &Path {
-        &self.data_path
+    /// No data directory and no fonts, for tests that don't need any asset.
+    #[cfg(test)]
+    pub fn empty() -> Result<Self, String> {
+        Ok(Self {
+            data_roots: vec![],
+            font_loader: FontLoader::new().map_err(|e| format!("Could not create FontLoader: {}", e))?,
+            fonts: None,
+        })
+    }
+    /// Finds an asset given its path relative to the data directories.
+    /// Returns `None` (and logs an error) if none of them has it.
+    pub fn resolve<P: AsRef<Path>>(&self, relative: P) -> Option<PathBuf> {
+        let relative = relative.as_ref();
+        let path = resolve_in(&self.data_roots, relative);
+        if path.is_none() {
+            error!("Could not find asset `{}` in any of the data directories: {:?}", relative.display(), self.data_roots);
+        }
+        path
     }
     pub fn font_loader(&self) -> &FontLoader {
         &self.font_loader
     }
     pub fn basis33(&self) -> &Font {
-        &self.basis33
+        &self.fonts().basis33
     }
     pub fn basis33_atlas(&self) -> &Atlas {
-        &self.basis33_atlas
+        &self.fonts().basis33_atlas
+    }
+    fn fonts(&self) -> &Fonts {
+        self.fonts.as_ref().expect("These resources were created without fonts")
+    }
+}
+
+/// The existing data directories, by decreasing priority: the `FATE_DATA_PATH` override,
+/// then the first `data` directory found in the ancestors of the executable, then `data` in the current directory.
+pub fn default_data_roots() -> Vec<PathBuf> {
+    let data_path_override = env::var_os(DATA_PATH_ENV_VAR).map(PathBuf::from);
+    let exe = env::current_exe().ok();
+    let cwd = env::current_dir().ok();
+    data_root_candidates(data_path_override, exe, cwd).into_iter().filter(|dir| dir.is_dir()).collect()
+}
+
+fn data_root_candidates(data_path_override: Option<PathBuf>, exe: Option<PathBuf>, cwd: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut candidates = vec![];
+    candidates.extend(data_path_override);
+    if let Some(mut dir) = exe {
+        while dir.pop() {
+            trace!("Searching for data path in `{}`", dir.display());
+            if dir.join("data").is_dir() {
+                candidates.push(dir.join("data"));
+                break;
+            }
+        }
+    }
+    if let Some(cwd) = cwd {
+        let dir = cwd.join("data");
+        if !candidates.contains(&dir) {
+            candidates.push(dir);
+        }
+    }
+    candidates
+}
+
+fn resolve_in(roots: &[PathBuf], relative: &Path) -> Option<PathBuf> {
+    roots.iter().map(|root| root.join(relative)).find(|path| path.exists())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_data_root(name: &str, assets: &[&str]) -> PathBuf {
+        let root = env::temp_dir().join(format!("fate-resources-test-{}-{}", name, ::std::process::id()));
+        for asset in assets {
+            let path = root.join(asset);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, b"").unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn override_takes_precedence() {
+        let data_path_override = temp_data_root("override", &["art/a.png"]);
+        let exe_data = temp_data_root("exe", &["data/art/a.png", "data/art/b.png"]);
+        let exe = exe_data.join("bin").join("game");
+        let roots = data_root_candidates(Some(data_path_override.clone()), Some(exe), None);
+        assert_eq!(roots, vec![data_path_override.clone(), exe_data.join("data")]);
+
+        assert_eq!(resolve_in(&roots, Path::new("art/a.png")), Some(data_path_override.join("art/a.png")));
+        assert_eq!(resolve_in(&roots, Path::new("art/b.png")), Some(exe_data.join("data/art/b.png")));
+
+        fs::remove_dir_all(data_path_override).unwrap();
+        fs::remove_dir_all(exe_data).unwrap();
+    }
+    #[test]
+    fn missing_asset_resolves_to_none() {
+        let root = temp_data_root("missing", &["art/a.png"]);
+        assert_eq!(resolve_in(&[root.clone()], Path::new("art/missing.png")), None);
+        assert_eq!(resolve_in(&[], Path::new("art/a.png")), None);
+        fs::remove_dir_all(root).unwrap();
+    }
+    #[test]
+    fn data_path_without_fonts_is_an_error() {
+        let root = temp_data_root("nofonts", &["art/a.png"]);
+        let err = Resources::with_data_path(root.clone()).unwrap_err();
+        assert!(err.contains("basis33"), "{}", err);
+        fs::remove_dir_all(root).unwrap();
     }
 }
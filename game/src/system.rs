@@ -4,6 +4,7 @@
 // Solved: It depends. Finding by key is annoying; Why not directly typing g.my_sys ? We know our game.
 
 pub use std::time::Duration;
+pub use std::path::PathBuf;
 pub use fate::math::{Vec2, Extent2};
 pub use fate::lab::duration_ext::DurationExt;
 pub use dmc::device::{Key, KeyState, MouseButton, ButtonState, Keysym};
@@ -26,6 +27,8 @@ pub struct Draw {
     pub smooth_dt_as_duration: Duration,
     pub smooth_dt: f32,
     pub tick_progress: f64,
+    /// Whether `dt` was clamped because the frame took too long (e.g a hitch).
+    pub is_dt_clamped: bool,
 }
 
 // All items take &mut self since we know we're single-threaded.
@@ -42,6 +45,10 @@ pub trait System {
     // events
     fn on_quit(&mut self, _g: &mut G) {}
     fn on_canvas_resized(&mut self, _g: &mut G, _size: Extent2<u32>) {}
+    fn on_window_minimized(&mut self, _g: &mut G) {}
+    fn on_window_maximized(&mut self, _g: &mut G) {}
+    fn on_window_restored(&mut self, _g: &mut G) {}
+    fn on_window_scale_factor_changed(&mut self, _g: &mut G, _new_scale: f32) {}
     fn on_mouse_enter(&mut self, _g: &mut G) {}
     fn on_mouse_leave(&mut self, _g: &mut G) {}
     fn on_keyboard_focus_gained(&mut self, _g: &mut G) {}
@@ -55,5 +62,12 @@ pub trait System {
     fn on_key(&mut self, _g: &mut G, _key: Key, _state: KeyState) {}
     fn on_key_raw(&mut self, _g: &mut G, _key: Key, _state: KeyState) {}
     fn on_text_char(&mut self, _g: &mut G, _char: char) {}
+    fn on_touch_began(&mut self, _g: &mut G, _id: u64, _pos: Vec2<f32>, _pressure: f32) {}
+    fn on_touch_moved(&mut self, _g: &mut G, _id: u64, _pos: Vec2<f32>, _pressure: f32) {}
+    fn on_touch_ended(&mut self, _g: &mut G, _id: u64, _pos: Vec2<f32>, _pressure: f32) {}
+    fn on_touch_cancelled(&mut self, _g: &mut G, _id: u64) {}
+    fn on_files_dropped(&mut self, _g: &mut G, _paths: &[PathBuf], _pos: Vec2<u32>) {}
+    fn on_text_composition_updated(&mut self, _g: &mut G, _text: &str, _cursor: usize) {}
+    fn on_text_composition_committed(&mut self, _g: &mut G, _text: &str) {}
 }
 
@@ -5,7 +5,7 @@ use eid::EID;
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct LeafViewport {
     // TODO: Describes what a viewport displays    
-    pub clear_color: Rgba<f32>,
+    pub clear_color: Option<Rgba<f32>>, // If None, the global clear color is used
     pub skybox_cubemap_selector: Option<CubemapSelector>, // If None, skybox is disabled
     pub camera: EID, // TODO: Multiple (stacked) cameras (but draw skybox once with one of them)
 }
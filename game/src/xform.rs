@@ -1,4 +1,4 @@
-use fate::math::{Vec3, Quaternion, Mat4};
+use fate::math::{Vec3, Quaternion, Mat4, Lerp, QuaternionSlerp, LookAt};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Xform {
@@ -32,7 +32,123 @@ impl Xform {
     }
     pub fn view_matrix_with_up(&self, up: Vec3<f32>) -> Mat4<f32> {
         let zoom = Mat4::<f32>::scaling_3d(self.scale.recip());
-        let look = Mat4::look_at(self.position, self.position + self.forward(), up);
+        let look = Mat4::look_at_view_lh(self.position, self.position + self.forward(), up);
         zoom * look
     }
 }
+
+impl Xform {
+    pub fn model_matrix(&self) -> Mat4<f32> {
+        Mat4::translation_3d(self.position) * Mat4::from(self.orientation) * Mat4::scaling_3d(self.scale)
+    }
+}
+
+/// An `Xform` with a double-precision position, for objects that may be far from the world origin.
+///
+/// It should never be used directly for rendering; instead, convert it with `relative_to()`, using
+/// the camera's position as the origin, so that the GPU only ever sees small values.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Xform64 {
+    pub position: Vec3<f64>,
+    pub orientation: Quaternion<f32>,
+    pub scale: Vec3<f32>,
+}
+
+impl Default for Xform64 {
+    fn default() -> Self {
+        Self {
+            position: Vec3::zero(),
+            orientation: Quaternion::identity(),
+            scale: Vec3::one(),
+        }
+    }
+}
+
+impl Xform64 {
+    pub fn relative_to(&self, origin: Vec3<f64>) -> Xform {
+        Xform {
+            position: (self.position - origin).map(|x| x as f32),
+            orientation: self.orientation,
+            scale: self.scale,
+        }
+    }
+}
+
+/// Lerps position and scale, and slerps orientation along the shortest arc.
+impl Lerp<f32> for Xform {
+    type Output = Self;
+    fn lerp_unclamped(from: Self, to: Self, factor: f32) -> Self {
+        Self {
+            position: Vec3::lerp_unclamped(from.position, to.position, factor),
+            orientation: from.orientation.shortest_slerp_unclamped(to.orientation, factor),
+            scale: Vec3::lerp_unclamped(from.scale, to.scale, factor),
+        }
+    }
+}
+
+/// Same as `Xform`'s, but the position is lerped in double precision.
+impl Lerp<f32> for Xform64 {
+    type Output = Self;
+    fn lerp_unclamped(from: Self, to: Self, factor: f32) -> Self {
+        Self {
+            position: Vec3::lerp_unclamped(from.position, to.position, factor as f64),
+            orientation: from.orientation.shortest_slerp_unclamped(to.orientation, factor),
+            scale: Vec3::lerp_unclamped(from.scale, to.scale, factor),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lerp_at_half_is_midpoint_and_slerped() {
+        let from = Xform::default();
+        let to = Xform {
+            position: Vec3::new(2., 4., -6.),
+            orientation: Quaternion::rotation_y(::std::f32::consts::PI / 2.),
+            scale: Vec3::broadcast(3.),
+        };
+        let mid = Xform::lerp(from, to, 0.5);
+        assert_relative_eq!(mid.position, Vec3::new(1., 2., -3.));
+        assert_relative_eq!(mid.scale, Vec3::broadcast(2.));
+        let expected = Quaternion::rotation_y(::std::f32::consts::PI / 4.);
+        assert_relative_eq!(mid.orientation.x, expected.x, epsilon = 0.0001);
+        assert_relative_eq!(mid.orientation.y, expected.y, epsilon = 0.0001);
+        assert_relative_eq!(mid.orientation.z, expected.z, epsilon = 0.0001);
+        assert_relative_eq!(mid.orientation.w, expected.w, epsilon = 0.0001);
+
+        // Clamped vs. unclamped
+        assert_eq!(Xform::lerp(from, to, 2.), to);
+        assert_relative_eq!(Xform::lerp_unclamped(from, to, 2.).position, Vec3::new(4., 8., -12.));
+    }
+    #[test]
+    fn lerp_endpoints_and_shortest_arc() {
+        let from = Xform { position: Vec3::new(1., 0., 0.), orientation: Quaternion::rotation_x(0.5), scale: Vec3::one() };
+        let to = Xform { position: Vec3::new(0., 1., 0.), orientation: Quaternion::rotation_z(1.5), scale: Vec3::broadcast(2.) };
+        assert_eq!(Xform::lerp(from, to, 0.), from);
+        let end = Xform::lerp(from, to, 1.);
+        assert_relative_eq!(end.position, to.position);
+        assert_relative_eq!(end.scale, to.scale);
+        assert_relative_eq!(end.orientation.dot(to.orientation).abs(), 1., epsilon = 0.0001);
+
+        // -q is the same rotation as q; blending towards it must not go the long way around
+        let negated = Xform { orientation: -from.orientation, .. from };
+        let mid = Xform::lerp(from, negated, 0.5);
+        assert_relative_eq!(mid.orientation.dot(from.orientation).abs(), 1., epsilon = 0.0001);
+    }
+    #[test]
+    fn xform64_lerp_keeps_precision() {
+        let far = Vec3::new(1e9_f64, -3e8, 7e9);
+        let from = Xform64 { position: far, .. Xform64::default() };
+        let to = Xform64 { position: far + Vec3::new(0.5, 0., 0.), .. Xform64::default() };
+        assert_eq!(Xform64::lerp(from, to, 0.5).relative_to(far).position, Vec3::new(0.25, 0., 0.));
+    }
+    #[test]
+    fn relative_xform_is_precise_far_from_origin() {
+        let far = Vec3::new(1e9_f64, -3e8, 7e9);
+        let object = Xform64 { position: far + Vec3::new(0.25, 0.5, 0.125), .. Xform64::default() };
+        assert_eq!(object.relative_to(far).position, Vec3::new(0.25, 0.5, 0.125));
+    }
+}
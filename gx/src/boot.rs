@@ -8,6 +8,8 @@ pub fn boot_gl() {
         nvx_gpu_memory_info,
         arb_pipeline_statistics_query,
         arb_timer_query,
+        khr_parallel_shader_compile,
+        texture_filter_anisotropic,
     } = ext.as_ref().unwrap();
 
     if khr_debug {
@@ -25,5 +27,13 @@ pub fn boot_gl() {
     if arb_timer_query {
         ::query::init_arb_timer_query();
     }
+    if khr_parallel_shader_compile {
+        unsafe {
+            ::gl::MaxShaderCompilerThreadsKHR(0xffffffff); // Let the implementation decide
+        }
+    }
+    if texture_filter_anisotropic {
+        ::get::init_max_texture_max_anisotropy();
+    }
     ::init_reasonable_default_gl_state();
 }
@@ -5,7 +5,7 @@ use std::ptr;
 use std::marker::PhantomData;
 
 use gl::{self, types::*};
-use {Buffer, Object};
+use {Buffer, Object, Fence};
 
 /// STREAM: The data store contents will be modified once and used at most a few times.
 /// STATIC: The data store contents will be modified once and used many times.
@@ -285,22 +285,11 @@ impl<T> BufferEx<T> {
 #[derive(Debug)]
 pub struct FenceSwapChain {
     cpu_updates: bool,
-    fences: [GLsync; 4], // Up to quadruple buffering
+    fences: [Option<Fence>; 4], // Up to quadruple buffering
     capacity: u8,
     i: u8,
 }
 
-impl Drop for FenceSwapChain {
-    fn drop(&mut self) {
-        let &mut Self { cpu_updates: _, fences, capacity: _, i: _ } = self;
-        for fence in fences.into_iter() {
-            unsafe {
-                gl::DeleteSync(*fence); // Tolerates zero
-            }
-        }
-    }
-}
-
 fn toast() {
     let mut chain = FenceSwapChain::new_for_cpu_updates(3);
     loop {
@@ -316,58 +305,29 @@ impl FenceSwapChain {
         assert!(capacity <= 4);
         Self {
             cpu_updates: true,
-            fences: [ptr::null_mut(); 4],
+            fences: [None, None, None, None],
             capacity: capacity as u8,
             i: 0,
         }
     }
     // Returns a chunk index (index in N-buffered buffers)
     pub fn start_frame(&mut self) -> usize {
-        let fence = &mut self.fences[self.i as usize];
-        if !fence.is_null() {
+        if let Some(fence) = self.fences[self.i as usize].take() {
             if self.cpu_updates {
-                Self::wait_cpu(*fence);
+                fence.wait();
             } else {
-                Self::wait_gpu(*fence);
-            }
-            unsafe {
-                gl::DeleteSync(*fence);
+                fence.wait_gpu();
             }
-            *fence = ptr::null_mut();
         }
         self.i as _
     }
     pub fn end_frame(&mut self) {
         {
             let fence = &mut self.fences[self.i as usize];
-            assert!(fence.is_null());
-            *fence = unsafe {
-                gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)
-            };
-            assert!(!fence.is_null());
+            assert!(fence.is_none());
+            *fence = Some(Fence::insert());
         }
         self.i += 1;
         self.i %= self.capacity;
     }
-
-    fn wait_gpu(sync: GLsync) {
-        assert!(!sync.is_null());
-        unsafe {
-            gl::WaitSync(sync, 0, gl::TIMEOUT_IGNORED);
-        }
-    }
-    fn wait_cpu(sync: GLsync) {
-        assert!(!sync.is_null());
-        let mut flags = 0;
-        let mut timeout_nanos = 0;
-        loop {
-            match unsafe { gl::ClientWaitSync(sync, flags, timeout_nanos) } {
-                gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => break,
-                gl::WAIT_FAILED => panic!(),
-                _ => (),
-            }
-            flags = gl::SYNC_FLUSH_COMMANDS_BIT;
-            timeout_nanos = 1_000_000_000; // 1 second. The timeout is not how long we'll actually wait, but a deadline before we give up.
-        }
-    }
 }
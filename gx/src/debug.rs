@@ -8,6 +8,8 @@ use gl::types::*;
 pub fn init_debug_output_khr() {
     unsafe {
         SET_LABEL = set_label_real as _;
+        PUSH_DEBUG_GROUP = push_debug_group_real as _;
+        POP_DEBUG_GROUP = pop_debug_group_real as _;
 
         gl::Enable(gl::DEBUG_OUTPUT);
         gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
@@ -16,6 +18,10 @@ pub fn init_debug_output_khr() {
             gl::DONT_CARE, gl::DONT_CARE, gl::DONT_CARE,
             0, ptr::null_mut(), gl::TRUE
         );
+        // Every debug group push and pop would be logged too, which is noise: groups are for tools like RenderDoc.
+        for &type_ in &[gl::DEBUG_TYPE_PUSH_GROUP, gl::DEBUG_TYPE_POP_GROUP] {
+            gl::DebugMessageControl(gl::DONT_CARE, type_, gl::DONT_CARE, 0, ptr::null(), gl::FALSE);
+        }
         log_debug_message("OpenGL debugging is set up.");
     }
 }
@@ -43,6 +49,48 @@ pub fn set_label<T: ::Object>(o: &T, label: &[u8]) {
     let f = unsafe { SET_LABEL };
     (f)(T::NAMESPACE, o.gl_id(), label)
 }
+/// Names the object in debug messages and in tools such as RenderDoc. Does nothing without `KHR_debug`.
+pub fn set_object_label<T: ::Object>(o: &T, name: &str) {
+    set_label(o, name.as_bytes())
+}
+
+fn push_debug_group_stub(_name: &[u8]) {}
+fn push_debug_group_real(name: &[u8]) {
+    unsafe {
+        gl::PushDebugGroup(gl::DEBUG_SOURCE_APPLICATION, 0, name.len() as _, name.as_ptr() as _);
+    }
+}
+fn pop_debug_group_stub() {}
+fn pop_debug_group_real() {
+    unsafe {
+        gl::PopDebugGroup();
+    }
+}
+static mut PUSH_DEBUG_GROUP: fn(&[u8]) = push_debug_group_stub;
+static mut POP_DEBUG_GROUP: fn() = pop_debug_group_stub;
+
+/// Pops the debug group it was returned for when dropped. See `debug_group()`.
+#[must_use]
+#[derive(Debug)]
+pub struct DebugGroup {
+    _private: (),
+}
+
+impl Drop for DebugGroup {
+    fn drop(&mut self) {
+        let f = unsafe { POP_DEBUG_GROUP };
+        (f)()
+    }
+}
+
+/// Tags the GL commands issued until the returned guard is dropped, e.g a render pass, so that they show up as a
+/// group in tools such as RenderDoc, and debug messages are bracketed by push/pop notifications.
+/// Groups may be nested. Does nothing without `KHR_debug`.
+pub fn debug_group(name: &str) -> DebugGroup {
+    let f = unsafe { PUSH_DEBUG_GROUP };
+    (f)(name.as_bytes());
+    DebugGroup { _private: () }
+}
 
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -161,3 +209,18 @@ extern "system" fn gx_debug_message_callback(
     })
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_groups_are_no_ops_without_khr_debug() {
+        // GL functions are not loaded in tests, so calling any of them would panic.
+        let outer = debug_group("Outer pass");
+        {
+            let _inner = debug_group("Inner pass");
+        }
+        drop(outer);
+    }
+}
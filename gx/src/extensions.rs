@@ -11,6 +11,8 @@ pub struct UsefulExtensions {
     pub nvx_gpu_memory_info: bool,
     pub arb_pipeline_statistics_query: bool,
     pub arb_timer_query: bool,
+    pub khr_parallel_shader_compile: bool,
+    pub texture_filter_anisotropic: bool,
 }
 
 pub static mut CACHE: Option<UsefulExtensions> = None;
@@ -32,6 +34,8 @@ impl ExtensionsStore {
             nvx_gpu_memory_info: self.has("GL_NVX_gpu_memory_info"),
             arb_pipeline_statistics_query: self.has("ARB_pipeline_statistics_query"),
             arb_timer_query: v.gl(3, 3) || self.has("ARB_timer_query"),
+            khr_parallel_shader_compile: self.has("GL_KHR_parallel_shader_compile") || self.has("GL_ARB_parallel_shader_compile"),
+            texture_filter_anisotropic: v.gl(4, 6) || self.has("GL_ARB_texture_filter_anisotropic") || self.has("GL_EXT_texture_filter_anisotropic"),
         }
     }
 }
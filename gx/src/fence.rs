@@ -0,0 +1,102 @@
+use gl::{self, types::*};
+use super::is_context_destroyed;
+
+/// What waiting for a `Fence` resulted in.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum FenceStatus {
+    /// The fence was already signaled when the wait began.
+    Signaled           = gl::ALREADY_SIGNALED,
+    /// The fence got signaled while waiting.
+    ConditionSatisfied = gl::CONDITION_SATISFIED,
+    TimeoutExpired     = gl::TIMEOUT_EXPIRED,
+}
+
+impl FenceStatus {
+    /// `GL_WAIT_FAILED` gives `None`.
+    pub fn try_from_glenum(e: GLenum) -> Option<Self> {
+        match e {
+            gl::ALREADY_SIGNALED    => Some(FenceStatus::Signaled          ),
+            gl::CONDITION_SATISFIED => Some(FenceStatus::ConditionSatisfied),
+            gl::TIMEOUT_EXPIRED     => Some(FenceStatus::TimeoutExpired    ),
+            _ => None,
+        }
+    }
+    /// Are the commands issued before the fence done executing?
+    pub fn is_signaled(&self) -> bool {
+        *self != FenceStatus::TimeoutExpired
+    }
+}
+
+/// A sync object that gets signaled once the GPU has executed all the commands issued before it, e.g to know when
+/// a buffer that was used for drawing can be written to again.
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub struct Fence(GLsync);
+
+impl Drop for Fence {
+    fn drop(&mut self) {
+        if !is_context_destroyed() {
+            unsafe {
+                gl::DeleteSync(self.0);
+            }
+        }
+    }
+}
+
+impl Fence {
+    /// Inserts the fence after the commands issued so far.
+    pub fn insert() -> Self {
+        let sync = unsafe {
+            gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)
+        };
+        assert!(!sync.is_null());
+        Fence(sync)
+    }
+    pub fn gl_sync(&self) -> GLsync {
+        self.0
+    }
+    /// Waits at most `timeout_ns` nanoseconds; 0 only polls.
+    /// Also flushes the command stream, otherwise the fence might never be reached, and never get signaled.
+    pub fn try_wait(&self, timeout_ns: u64) -> FenceStatus {
+        let status = unsafe {
+            gl::ClientWaitSync(self.0, gl::SYNC_FLUSH_COMMANDS_BIT, timeout_ns)
+        };
+        FenceStatus::try_from_glenum(status).expect("glClientWaitSync failed")
+    }
+    /// Blocks until the fence is signaled.
+    pub fn wait(&self) {
+        // The timeout is not how long we'll actually wait, but a deadline before we try again.
+        while !self.try_wait(1_000_000_000).is_signaled() {}
+    }
+    /// Makes the GPU (not the CPU) wait for the fence before executing further commands, e.g to order work across
+    /// shared contexts.
+    pub fn wait_gpu(&self) {
+        unsafe {
+            gl::WaitSync(self.0, 0, gl::TIMEOUT_IGNORED);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{mark_context_destroyed, mark_context_created};
+
+    #[test]
+    fn wait_statuses() {
+        assert_eq!(FenceStatus::try_from_glenum(gl::ALREADY_SIGNALED), Some(FenceStatus::Signaled));
+        assert_eq!(FenceStatus::try_from_glenum(gl::CONDITION_SATISFIED), Some(FenceStatus::ConditionSatisfied));
+        assert_eq!(FenceStatus::try_from_glenum(gl::TIMEOUT_EXPIRED), Some(FenceStatus::TimeoutExpired));
+        assert_eq!(FenceStatus::try_from_glenum(gl::WAIT_FAILED), None);
+        assert!(FenceStatus::Signaled.is_signaled());
+        assert!(FenceStatus::ConditionSatisfied.is_signaled());
+        assert!(!FenceStatus::TimeoutExpired.is_signaled());
+    }
+    #[test]
+    fn drop_after_context_destruction_does_not_call_gl() {
+        mark_context_destroyed();
+        drop(Fence(1 as GLsync));
+        mark_context_created();
+    }
+}
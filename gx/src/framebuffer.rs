@@ -0,0 +1,363 @@
+use std::fmt;
+use std::marker::PhantomData;
+use gl::{self, types::*};
+use super::{Object, Framebuffer, Renderbuffer, Texture};
+
+/// Why `glCheckNamedFramebufferStatus` didn't return `GL_FRAMEBUFFER_COMPLETE`.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum FramebufferError {
+    /// The status query itself failed (e.g the framebuffer name is invalid).
+    UnknownError                = 0,
+    Undefined                   = gl::FRAMEBUFFER_UNDEFINED,
+    IncompleteAttachment        = gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT,
+    IncompleteMissingAttachment = gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT,
+    IncompleteDrawBuffer        = gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER,
+    IncompleteReadBuffer        = gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER,
+    Unsupported                 = gl::FRAMEBUFFER_UNSUPPORTED,
+    IncompleteMultisample       = gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE,
+    IncompleteLayerTargets      = gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS,
+}
+
+impl FramebufferError {
+    /// Translates a framebuffer status. Values that aren't known statuses are reported as `UnknownError`.
+    pub fn from_status(status: GLenum) -> Result<(), Self> {
+        match status {
+            gl::FRAMEBUFFER_COMPLETE                      => Ok(()),
+            gl::FRAMEBUFFER_UNDEFINED                     => Err(FramebufferError::Undefined                  ),
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT         => Err(FramebufferError::IncompleteAttachment       ),
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => Err(FramebufferError::IncompleteMissingAttachment),
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER        => Err(FramebufferError::IncompleteDrawBuffer       ),
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER        => Err(FramebufferError::IncompleteReadBuffer       ),
+            gl::FRAMEBUFFER_UNSUPPORTED                   => Err(FramebufferError::Unsupported                ),
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE        => Err(FramebufferError::IncompleteMultisample      ),
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS      => Err(FramebufferError::IncompleteLayerTargets     ),
+            _ => Err(FramebufferError::UnknownError),
+        }
+    }
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            FramebufferError::UnknownError                => "the framebuffer status could not be queried",
+            FramebufferError::Undefined                   => "the default framebuffer is bound, but doesn't exist",
+            FramebufferError::IncompleteAttachment        => "an attachment is incomplete, or its format isn't renderable",
+            FramebufferError::IncompleteMissingAttachment => "there are no attachments",
+            FramebufferError::IncompleteDrawBuffer        => "a draw buffer refers to a color attachment that has no image",
+            FramebufferError::IncompleteReadBuffer        => "the read buffer refers to a color attachment that has no image",
+            FramebufferError::Unsupported                 => "this combination of attachment formats is not supported by the implementation",
+            FramebufferError::IncompleteMultisample       => "attachments have different numbers of samples, or mix fixed and non-fixed sample locations",
+            FramebufferError::IncompleteLayerTargets      => "some attachments are layered and others aren't, or layered textures have different targets",
+        };
+        write!(f, "Incomplete framebuffer: {}", s)
+    }
+}
+
+/// Objects with an image that can be attached to a framebuffer.
+pub trait FramebufferAttachment: Object {
+    /// `level` is the mipmap level, and is ignored by renderbuffers.
+    fn attach_to(&self, fb: &Framebuffer, attachment: GLenum, level: u32);
+}
+
+impl FramebufferAttachment for Texture {
+    fn attach_to(&self, fb: &Framebuffer, attachment: GLenum, level: u32) {
+        unsafe {
+            gl::NamedFramebufferTexture(fb.gl_id(), attachment, self.gl_id(), level as _);
+        }
+    }
+}
+
+impl FramebufferAttachment for Renderbuffer {
+    fn attach_to(&self, fb: &Framebuffer, attachment: GLenum, _level: u32) {
+        unsafe {
+            gl::NamedFramebufferRenderbuffer(fb.gl_id(), attachment, gl::RENDERBUFFER, self.gl_id());
+        }
+    }
+}
+
+impl Renderbuffer {
+    /// Allocates storage right away, e.g with `GL_DEPTH24_STENCIL8` for a depth/stencil buffer that is never sampled.
+    pub fn with_storage(internal_format: GLenum, w: u32, h: u32) -> Self {
+        unsafe {
+            let mut rb = 0;
+            gl::CreateRenderbuffers(1, &mut rb);
+            gl::NamedRenderbufferStorage(rb, internal_format, w as _, h as _);
+            Self::from_gl_id(rb)
+        }
+    }
+}
+
+impl Framebuffer {
+    /// Unlike `new()`, which only reserves a name, this creates the framebuffer object, as DSA functions require.
+    pub fn new_dsa() -> Self {
+        unsafe {
+            let mut fb = 0;
+            gl::CreateFramebuffers(1, &mut fb);
+            Self::from_gl_id(fb)
+        }
+    }
+    /// Attaches the texture's `level` to `GL_COLOR_ATTACHMENT0 + index`.
+    /// Only attachment 0 is drawn to until `set_draw_buffers()` says otherwise.
+    pub fn attach_color(&self, index: u32, texture: &Texture, level: u32) {
+        texture.attach_to(self, gl::COLOR_ATTACHMENT0 + index, level);
+    }
+    /// Like `attach_color()`, but only attaches a single layer of an array, cube map or 3D texture.
+    pub fn attach_color_layer(&self, index: u32, texture: &Texture, level: u32, layer: u32) {
+        unsafe {
+            gl::NamedFramebufferTextureLayer(self.gl_id(), gl::COLOR_ATTACHMENT0 + index, texture.gl_id(), level as _, layer as _);
+        }
+    }
+    pub fn attach_depth<A: FramebufferAttachment>(&self, a: &A) {
+        a.attach_to(self, gl::DEPTH_ATTACHMENT, 0);
+    }
+    pub fn attach_stencil<A: FramebufferAttachment>(&self, a: &A) {
+        a.attach_to(self, gl::STENCIL_ATTACHMENT, 0);
+    }
+    /// For combined formats such as `GL_DEPTH24_STENCIL8`.
+    pub fn attach_depth_stencil<A: FramebufferAttachment>(&self, a: &A) {
+        a.attach_to(self, gl::DEPTH_STENCIL_ATTACHMENT, 0);
+    }
+    /// Fragment shader output `i` goes to `GL_COLOR_ATTACHMENT0 + color_indices[i]`.
+    pub fn set_draw_buffers(&self, color_indices: &[u32]) {
+        let bufs: Vec<_> = color_indices.iter().map(|i| gl::COLOR_ATTACHMENT0 + i).collect();
+        unsafe {
+            gl::NamedFramebufferDrawBuffers(self.gl_id(), bufs.len() as _, bufs.as_ptr());
+        }
+    }
+    /// Should be called once all attachments are set up; drawing to an incomplete framebuffer is an error.
+    pub fn check_status(&self) -> Result<(), FramebufferError> {
+        FramebufferError::from_status(unsafe { gl::CheckNamedFramebufferStatus(self.gl_id(), gl::FRAMEBUFFER) })
+    }
+    /// Binds for both drawing and reading.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.gl_id());
+        }
+    }
+    /// Like `bind()`, but binds the default framebuffer back when the returned guard is dropped.
+    pub fn bind_scoped(&self) -> BoundFramebuffer {
+        self.bind();
+        BoundFramebuffer { _framebuffer: PhantomData }
+    }
+    /// Binds the default framebuffer (the window) back.
+    pub fn unbind() {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+}
+
+/// See `Framebuffer::bind_scoped()`.
+#[must_use]
+#[derive(Debug)]
+pub struct BoundFramebuffer<'a> {
+    _framebuffer: PhantomData<&'a Framebuffer>,
+}
+
+impl<'a> Drop for BoundFramebuffer<'a> {
+    fn drop(&mut self) {
+        Framebuffer::unbind();
+    }
+}
+
+/// Creates a framebuffer from textures and renderbuffers, and checks that it's complete.
+///
+/// Fragment shader outputs go to the color attachment of the same number, i.e `layout(location = 1) out` is
+/// drawn to the texture attached to slot 1.
+#[derive(Debug)]
+pub struct FramebufferBuilder {
+    fb: Framebuffer,
+    color_slots: Vec<u32>,
+}
+
+/// Draw buffers that send each output to the color attachment of the same number; unused slots are `GL_NONE`.
+fn draw_buffers_for_slots<I: IntoIterator<Item=u32>>(slots: I) -> Vec<GLenum> {
+    let mut bufs = Vec::new();
+    for slot in slots {
+        let slot = slot as usize;
+        if slot >= bufs.len() {
+            bufs.resize(slot + 1, gl::NONE);
+        }
+        bufs[slot] = gl::COLOR_ATTACHMENT0 + slot as GLenum;
+    }
+    bufs
+}
+
+impl FramebufferBuilder {
+    pub fn new() -> Self {
+        Self {
+            fb: Framebuffer::new_dsa(),
+            color_slots: Vec::new(),
+        }
+    }
+    /// `layer` selects a single layer of an array, cube map or 3D texture; otherwise, all layers are attached
+    /// (for layered rendering with a geometry shader).
+    /// Attaching to the same slot twice replaces the previous texture.
+    pub fn attach_color(mut self, slot: u32, texture: &Texture, level: u32, layer: Option<u32>) -> Self {
+        match layer {
+            None => self.fb.attach_color(slot, texture, level),
+            Some(layer) => self.fb.attach_color_layer(slot, texture, level, layer),
+        }
+        if !self.color_slots.contains(&slot) {
+            self.color_slots.push(slot);
+        }
+        self
+    }
+    pub fn attach_depth<A: FramebufferAttachment>(self, a: &A) -> Self {
+        self.fb.attach_depth(a);
+        self
+    }
+    pub fn attach_stencil<A: FramebufferAttachment>(self, a: &A) -> Self {
+        self.fb.attach_stencil(a);
+        self
+    }
+    /// For combined formats such as `GL_DEPTH24_STENCIL8`.
+    pub fn attach_depth_stencil<A: FramebufferAttachment>(self, a: &A) -> Self {
+        self.fb.attach_depth_stencil(a);
+        self
+    }
+    /// The framebuffer is deleted if it isn't complete.
+    pub fn build(self) -> Result<Framebuffer, FramebufferError> {
+        let bufs = draw_buffers_for_slots(self.color_slots.iter().cloned());
+        unsafe {
+            gl::NamedFramebufferDrawBuffers(self.fb.gl_id(), bufs.len() as _, bufs.as_ptr());
+        }
+        self.fb.check_status()?;
+        Ok(self.fb)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_translation() {
+        assert_eq!(FramebufferError::from_status(gl::FRAMEBUFFER_COMPLETE), Ok(()));
+        assert_eq!(FramebufferError::from_status(gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT), Err(FramebufferError::IncompleteMissingAttachment));
+        assert_eq!(FramebufferError::from_status(0), Err(FramebufferError::UnknownError));
+        assert_eq!(FramebufferError::from_status(gl::TEXTURE_2D), Err(FramebufferError::UnknownError));
+        let statuses = [
+            gl::FRAMEBUFFER_UNDEFINED,
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT,
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT,
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER,
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER,
+            gl::FRAMEBUFFER_UNSUPPORTED,
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE,
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS,
+        ];
+        for status in statuses.iter() {
+            let e = FramebufferError::from_status(*status).unwrap_err();
+            assert_eq!(e as GLenum, *status);
+            assert!(e.to_string().starts_with("Incomplete framebuffer: "));
+        }
+    }
+    #[test]
+    fn outputs_go_to_the_slot_of_the_same_number() {
+        assert_eq!(draw_buffers_for_slots(vec![]), vec![]);
+        assert_eq!(draw_buffers_for_slots(vec![0]), vec![gl::COLOR_ATTACHMENT0]);
+        assert_eq!(draw_buffers_for_slots(vec![2, 0]), vec![gl::COLOR_ATTACHMENT0, gl::NONE, gl::COLOR_ATTACHMENT2]);
+    }
+    // Stands in for the driver, with a single framebuffer that is complete as soon as it has an attachment and
+    // its draw buffers all have an image.
+    // The function pointers are global, but the state is per-thread, so that tests may run in parallel.
+    mod fake_gl {
+        use std::cell::RefCell;
+        use std::slice;
+        use gl::{self, types::*};
+
+        #[derive(Debug, Default)]
+        pub struct FakeFramebuffer {
+            /// (attachment, object type, name)
+            pub attachments: Vec<(GLenum, GLenum, GLuint)>,
+            pub draw_buffers: Vec<GLenum>,
+            pub nb_deleted: usize,
+        }
+
+        thread_local! {
+            pub static FRAMEBUFFER: RefCell<FakeFramebuffer> = RefCell::new(FakeFramebuffer::default());
+        }
+
+        fn attach(attachment: GLenum, object_type: GLenum, name: GLuint) {
+            FRAMEBUFFER.with(|fb| {
+                let mut fb = fb.borrow_mut();
+                fb.attachments.retain(|a| a.0 != attachment);
+                fb.attachments.push((attachment, object_type, name));
+            });
+        }
+
+        extern "system" fn create_framebuffers(n: GLsizei, framebuffers: *mut GLuint) {
+            assert_eq!(n, 1);
+            unsafe { *framebuffers = 1; }
+        }
+        extern "system" fn delete_framebuffers(_n: GLsizei, _framebuffers: *const GLuint) {
+            FRAMEBUFFER.with(|fb| fb.borrow_mut().nb_deleted += 1);
+        }
+        extern "system" fn create_renderbuffers(n: GLsizei, renderbuffers: *mut GLuint) {
+            assert_eq!(n, 1);
+            unsafe { *renderbuffers = 2; }
+        }
+        extern "system" fn named_renderbuffer_storage(_renderbuffer: GLuint, _internal_format: GLenum, _w: GLsizei, _h: GLsizei) {}
+        extern "system" fn delete_renderbuffers(_n: GLsizei, _renderbuffers: *const GLuint) {}
+        extern "system" fn delete_textures(_n: GLsizei, _textures: *const GLuint) {}
+        extern "system" fn named_framebuffer_texture(_framebuffer: GLuint, attachment: GLenum, texture: GLuint, _level: GLint) {
+            attach(attachment, gl::TEXTURE, texture);
+        }
+        extern "system" fn named_framebuffer_renderbuffer(_framebuffer: GLuint, attachment: GLenum, _target: GLenum, renderbuffer: GLuint) {
+            attach(attachment, gl::RENDERBUFFER, renderbuffer);
+        }
+        extern "system" fn named_framebuffer_draw_buffers(_framebuffer: GLuint, n: GLsizei, bufs: *const GLenum) {
+            let bufs = unsafe { slice::from_raw_parts(bufs, n as _) }.to_vec();
+            FRAMEBUFFER.with(|fb| fb.borrow_mut().draw_buffers = bufs);
+        }
+        extern "system" fn check_named_framebuffer_status(_framebuffer: GLuint, _target: GLenum) -> GLenum {
+            FRAMEBUFFER.with(|fb| {
+                let fb = fb.borrow();
+                if fb.attachments.is_empty() {
+                    gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT
+                } else if fb.draw_buffers.iter().any(|&buf| buf != gl::NONE && fb.attachments.iter().all(|a| a.0 != buf)) {
+                    gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER
+                } else {
+                    gl::FRAMEBUFFER_COMPLETE
+                }
+            })
+        }
+
+        pub fn load() {
+            FRAMEBUFFER.with(|fb| *fb.borrow_mut() = FakeFramebuffer::default());
+            gl::CreateFramebuffers::load_with(|_| create_framebuffers as *const _);
+            gl::DeleteFramebuffers::load_with(|_| delete_framebuffers as *const _);
+            gl::CreateRenderbuffers::load_with(|_| create_renderbuffers as *const _);
+            gl::NamedRenderbufferStorage::load_with(|_| named_renderbuffer_storage as *const _);
+            gl::DeleteRenderbuffers::load_with(|_| delete_renderbuffers as *const _);
+            gl::DeleteTextures::load_with(|_| delete_textures as *const _);
+            gl::NamedFramebufferTexture::load_with(|_| named_framebuffer_texture as *const _);
+            gl::NamedFramebufferRenderbuffer::load_with(|_| named_framebuffer_renderbuffer as *const _);
+            gl::NamedFramebufferDrawBuffers::load_with(|_| named_framebuffer_draw_buffers as *const _);
+            gl::CheckNamedFramebufferStatus::load_with(|_| check_named_framebuffer_status as *const _);
+        }
+    }
+
+    #[test]
+    fn color_texture_and_depth_renderbuffer_are_complete() {
+        fake_gl::load();
+        let color = unsafe { Texture::from_gl_id(3) };
+        let depth = Renderbuffer::with_storage(gl::DEPTH_COMPONENT24, 64, 64);
+        let fb = FramebufferBuilder::new().attach_color(0, &color, 0, None).attach_depth(&depth).build().unwrap();
+        assert_eq!(fb.gl_id(), 1);
+        fake_gl::FRAMEBUFFER.with(|fb| {
+            let fb = fb.borrow();
+            assert_eq!(fb.attachments, vec![(gl::COLOR_ATTACHMENT0, gl::TEXTURE, 3), (gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, 2)]);
+            assert_eq!(fb.draw_buffers, vec![gl::COLOR_ATTACHMENT0]);
+        });
+    }
+    #[test]
+    fn incomplete_framebuffer_is_deleted() {
+        fake_gl::load();
+        assert_eq!(FramebufferBuilder::new().build().unwrap_err(), FramebufferError::IncompleteMissingAttachment);
+        assert_eq!(fake_gl::FRAMEBUFFER.with(|fb| fb.borrow().nb_deleted), 1);
+    }
+}
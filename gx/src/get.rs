@@ -45,6 +45,28 @@ pub fn boolean(x: GLenum) -> bool {
     }
     i == gl::TRUE as GLboolean
 }
+pub fn float(x: GLenum) -> GLfloat {
+    let mut f = 0.;
+    unsafe {
+        gl::GetFloatv(x, &mut f);
+    }
+    f
+}
+
+static mut MAX_TEXTURE_MAX_ANISOTROPY: Option<GLfloat> = None;
+
+pub fn init_max_texture_max_anisotropy() {
+    unsafe {
+        MAX_TEXTURE_MAX_ANISOTROPY = Some(float(gl::MAX_TEXTURE_MAX_ANISOTROPY_EXT));
+    }
+}
+/// `GL_MAX_TEXTURE_MAX_ANISOTROPY`, as queried at boot, or `None` if anisotropic filtering is unsupported
+/// (it's core since GL 4.6, and otherwise needs `GL_ARB_texture_filter_anisotropic` or `GL_EXT_texture_filter_anisotropic`).
+pub fn max_texture_max_anisotropy() -> Option<GLfloat> {
+    unsafe {
+        MAX_TEXTURE_MAX_ANISOTROPY
+    }
+}
 
 
 pub fn context_flags() -> GLuint { integer(gl::CONTEXT_FLAGS) as _ }
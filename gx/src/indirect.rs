@@ -0,0 +1,207 @@
+use std::mem;
+use std::ops::Range;
+use gl::{self, types::*};
+use {Buffer, Object, Topology, PersistentMappedBuffer};
+
+/// The layout expected by `glMultiDrawElementsIndirect`.
+#[derive(Debug, Default, Copy, Clone, Hash, PartialEq, Eq)]
+#[repr(C)]
+pub struct DrawElementsIndirectCommand {
+    pub nb_indices: GLuint,
+    pub nb_instances: GLuint,
+    pub first_index: GLuint,
+    pub base_vertex: GLuint,
+    pub base_instance: GLuint,
+}
+
+/// The number of frames in flight an `IndirectCommandBuffer` is split into.
+pub const INDIRECT_COMMAND_BUFFER_NB_FRAMES: usize = 3;
+
+/// Accumulates draw commands on the CPU, then writes them to a fixed-capacity, persistently mapped GPU buffer in one go.
+#[derive(Debug)]
+pub struct IndirectCommandBuffer {
+    buffer: PersistentMappedBuffer<DrawElementsIndirectCommand>,
+    cmds: Vec<DrawElementsIndirectCommand>,
+    nb_uploaded_cmds: usize,
+}
+
+impl IndirectCommandBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: PersistentMappedBuffer::new(capacity, INDIRECT_COMMAND_BUFFER_NB_FRAMES),
+            cmds: Vec::with_capacity(capacity),
+            nb_uploaded_cmds: 0,
+        }
+    }
+    pub fn buffer(&self) -> &Buffer { self.buffer.buffer() }
+    pub fn capacity(&self) -> usize { self.buffer.frame_capacity() }
+    pub fn cmds(&self) -> &[DrawElementsIndirectCommand] { &self.cmds }
+    pub fn len(&self) -> usize { self.cmds.len() }
+    pub fn is_empty(&self) -> bool { self.cmds.is_empty() }
+    pub fn clear(&mut self) {
+        self.cmds.clear();
+    }
+    pub fn push(&mut self, cmd: DrawElementsIndirectCommand) {
+        assert!(self.cmds.len() < self.capacity(), "IndirectCommandBuffer can only hold {} commands", self.capacity());
+        self.cmds.push(cmd);
+    }
+    /// Sends all accumulated commands to the GPU buffer.
+    /// Each upload goes to the next frame's region, so the commands of the previous upload may still be in use
+    /// by the GPU.
+    pub fn upload(&mut self) {
+        self.buffer.advance_frame();
+        self.buffer.as_mut_slice()[.. self.cmds.len()].copy_from_slice(&self.cmds);
+        self.nb_uploaded_cmds = self.cmds.len();
+    }
+    /// Issues all uploaded commands. The VAO and element array buffer must be bound.
+    pub fn multi_draw_elements(&self, topology: Topology, index_type: GLenum) {
+        self.multi_draw_elements_range(topology, index_type, 0 .. self.nb_uploaded_cmds);
+    }
+    /// Issues the uploaded commands in `range`. The VAO and element array buffer must be bound.
+    pub fn multi_draw_elements_range(&self, topology: Topology, index_type: GLenum, range: Range<usize>) {
+        assert!(range.end <= self.nb_uploaded_cmds, "Only {} commands were uploaded", self.nb_uploaded_cmds);
+        let (offset, count) = multi_draw_args(range);
+        if count == 0 {
+            return;
+        }
+        unsafe {
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.buffer().gl_id()); // In core profile, we MUST use a buffer to store commands
+            gl::MultiDrawElementsIndirect(topology as _, index_type, (self.buffer.offset() + offset) as _, count, 0);
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+        }
+    }
+}
+
+// Returns the byte offset into the buffer and the number of commands.
+fn multi_draw_args(range: Range<usize>) -> (usize, GLsizei) {
+    assert!(range.start <= range.end);
+    (range.start * mem::size_of::<DrawElementsIndirectCommand>(), (range.end - range.start) as _)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn multi_draw_args_are_offsets_in_bytes() {
+        assert_eq!(mem::size_of::<DrawElementsIndirectCommand>(), 5 * 4);
+        assert_eq!(multi_draw_args(0 .. 7), (0, 7));
+        assert_eq!(multi_draw_args(7 .. 10), (7 * 20, 3));
+        assert_eq!(multi_draw_args(3 .. 3), (60, 0));
+    }
+    // Stands in for the driver: the buffer's storage is plain memory, fences are always signaled, and draw calls
+    // are recorded.
+    // The function pointers are global, but the state is per-thread, so that tests may run in parallel.
+    mod fake_gl {
+        use std::cell::RefCell;
+        use std::os::raw::c_void;
+        use gl::{self, types::*};
+
+        /// The arguments of a `glMultiDrawElementsIndirect` call, plus the buffer bound to `GL_DRAW_INDIRECT_BUFFER`.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        pub struct MultiDraw {
+            pub mode: GLenum,
+            pub index_type: GLenum,
+            pub offset: usize,
+            pub draw_count: GLsizei,
+            pub stride: GLsizei,
+            pub indirect_buffer: GLuint,
+        }
+
+        #[derive(Debug, Default)]
+        pub struct FakeGL {
+            // u32s, so that it's suitably aligned
+            pub storage: Vec<u32>,
+            pub indirect_buffer: GLuint,
+            pub draws: Vec<MultiDraw>,
+        }
+
+        thread_local! {
+            pub static GL: RefCell<FakeGL> = RefCell::new(FakeGL::default());
+        }
+
+        extern "system" fn create_buffers(n: GLsizei, buffers: *mut GLuint) {
+            assert_eq!(n, 1);
+            unsafe { *buffers = 1; }
+        }
+        extern "system" fn delete_buffers(_n: GLsizei, _buffers: *const GLuint) {}
+        extern "system" fn named_buffer_storage(_buffer: GLuint, size: GLsizeiptr, _data: *const c_void, _flags: GLbitfield) {
+            GL.with(|gl| gl.borrow_mut().storage = vec![0; (size as usize + 3) / 4]);
+        }
+        extern "system" fn map_named_buffer_range(_buffer: GLuint, offset: GLintptr, _length: GLsizeiptr, _access: GLbitfield) -> *mut c_void {
+            GL.with(|gl| unsafe {
+                (gl.borrow_mut().storage.as_mut_ptr() as *mut u8).offset(offset) as _
+            })
+        }
+        extern "system" fn fence_sync(_condition: GLenum, _flags: GLbitfield) -> GLsync {
+            1 as GLsync
+        }
+        extern "system" fn client_wait_sync(_sync: GLsync, _flags: GLbitfield, _timeout: GLuint64) -> GLenum {
+            gl::ALREADY_SIGNALED
+        }
+        extern "system" fn delete_sync(_sync: GLsync) {}
+        extern "system" fn bind_buffer(target: GLenum, buffer: GLuint) {
+            assert_eq!(target, gl::DRAW_INDIRECT_BUFFER);
+            GL.with(|gl| gl.borrow_mut().indirect_buffer = buffer);
+        }
+        extern "system" fn multi_draw_elements_indirect(mode: GLenum, index_type: GLenum, indirect: *const c_void, draw_count: GLsizei, stride: GLsizei) {
+            GL.with(|gl| {
+                let mut gl = gl.borrow_mut();
+                let indirect_buffer = gl.indirect_buffer;
+                gl.draws.push(MultiDraw { mode, index_type, offset: indirect as usize, draw_count, stride, indirect_buffer });
+            });
+        }
+
+        pub fn load() {
+            GL.with(|gl| *gl.borrow_mut() = FakeGL::default());
+            gl::CreateBuffers::load_with(|_| create_buffers as *const _);
+            gl::DeleteBuffers::load_with(|_| delete_buffers as *const _);
+            gl::NamedBufferStorage::load_with(|_| named_buffer_storage as *const _);
+            gl::MapNamedBufferRange::load_with(|_| map_named_buffer_range as *const _);
+            gl::FenceSync::load_with(|_| fence_sync as *const _);
+            gl::ClientWaitSync::load_with(|_| client_wait_sync as *const _);
+            gl::DeleteSync::load_with(|_| delete_sync as *const _);
+            gl::BindBuffer::load_with(|_| bind_buffer as *const _);
+            gl::MultiDrawElementsIndirect::load_with(|_| multi_draw_elements_indirect as *const _);
+        }
+    }
+
+    fn cmd(i: u32) -> DrawElementsIndirectCommand {
+        DrawElementsIndirectCommand { nb_indices: 3 * i, nb_instances: 1, first_index: 0, base_vertex: 0, base_instance: i }
+    }
+
+    #[test]
+    fn multi_draw_is_called_with_the_uploaded_range() {
+        fake_gl::load();
+        let mut cmds = IndirectCommandBuffer::new(8);
+        for i in 0 .. 5 {
+            cmds.push(cmd(i));
+        }
+        cmds.upload();
+        cmds.multi_draw_elements_range(Topology::Triangles, gl::UNSIGNED_INT, 2 .. 5);
+        cmds.multi_draw_elements_range(Topology::Triangles, gl::UNSIGNED_INT, 5 .. 5);
+
+        // Each upload goes to the next region, so this one went to the 2nd region, 8 commands in.
+        let region_offset = 8 * mem::size_of::<DrawElementsIndirectCommand>();
+        let draws = fake_gl::GL.with(|gl| gl.borrow().draws.clone());
+        assert_eq!(draws, vec![fake_gl::MultiDraw {
+            mode: gl::TRIANGLES,
+            index_type: gl::UNSIGNED_INT,
+            offset: region_offset + 2 * 20,
+            draw_count: 3,
+            stride: 0, // Tightly packed
+            indirect_buffer: cmds.buffer().gl_id(),
+        }]);
+        assert_eq!(fake_gl::GL.with(|gl| gl.borrow().indirect_buffer), 0);
+
+        // What the draw reads is what was pushed
+        let uploaded = fake_gl::GL.with(|gl| {
+            let storage = gl.borrow().storage.clone();
+            let start = region_offset / 4;
+            (0 .. 5).map(|i| {
+                let c = &storage[start + i * 5 ..];
+                DrawElementsIndirectCommand { nb_indices: c[0], nb_instances: c[1], first_index: c[2], base_vertex: c[3], base_instance: c[4] }
+            }).collect::<Vec<_>>()
+        });
+        assert_eq!(&uploaded[..], cmds.cmds());
+    }
+}
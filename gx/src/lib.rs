@@ -16,9 +16,24 @@ pub mod object;
 pub use self::object::*;
 pub mod buffer;
 pub use self::buffer::*;
+pub mod fence;
+pub use self::fence::*;
 pub mod shader;
 pub mod program;
 pub use self::program::*;
+pub mod sampler;
+pub mod texture_builder;
+pub use self::texture_builder::*;
+pub mod uniform_buffer;
+pub use self::uniform_buffer::*;
+pub mod shader_storage_buffer;
+pub use self::shader_storage_buffer::*;
+pub mod persistent_mapped_buffer;
+pub use self::persistent_mapped_buffer::*;
+pub mod framebuffer;
+pub use self::framebuffer::*;
+pub mod pending_program;
+pub use self::pending_program::*;
 pub mod texture_unit;
 pub use self::texture_unit::*;
 pub mod missing_bits;
@@ -32,6 +47,8 @@ pub mod version;
 pub use self::version::*;
 pub mod extensions;
 pub use extensions::*;
+pub mod requirements;
+pub use requirements::*;
 pub mod meminfo;
 pub use meminfo::*;
 pub mod query;
@@ -40,4 +57,6 @@ pub mod boot;
 pub use boot::*;
 pub mod topology;
 pub use topology::*;
+pub mod indirect;
+pub use indirect::*;
 
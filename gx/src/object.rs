@@ -1,6 +1,24 @@
+use std::cell::Cell;
 use gl;
 use gl::types::*;
 
+thread_local! {
+    static IS_CONTEXT_DESTROYED: Cell<bool> = Cell::new(false);
+}
+
+/// To be called by the platform right before the GL context of the current thread is destroyed.
+/// From then on, dropping objects doesn't call into GL (their names die with the context anyway).
+pub fn mark_context_destroyed() {
+    IS_CONTEXT_DESTROYED.with(|x| x.set(true));
+}
+/// To be called when a new GL context is made current on this thread.
+pub fn mark_context_created() {
+    IS_CONTEXT_DESTROYED.with(|x| x.set(false));
+}
+pub fn is_context_destroyed() -> bool {
+    IS_CONTEXT_DESTROYED.with(|x| x.get())
+}
+
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u32)]
 pub enum Namespace {
@@ -55,7 +73,9 @@ macro_rules! object {
         }
         impl Drop for $Object {
             fn drop(&mut self) {
-                delete::$single(self.0);
+                if !is_context_destroyed() {
+                    delete::$single(self.0);
+                }
             }
         }
         impl Object for $Object {
@@ -173,4 +193,24 @@ object!{ Sampler              Sampler            sampler                 sampler
 object!{ Texture              Texture            texture                 textures               }
 object!{ Renderbuffer         Renderbuffer       renderbuffer            renderbuffers          }
 object!{ Framebuffer          Framebuffer        framebuffer             framebuffers           }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drop_after_context_destruction_does_not_call_gl() {
+        // GL functions are not loaded in tests, so calling any of them would panic.
+        mark_context_destroyed();
+        let buffer = unsafe { Buffer::from_gl_id(42) };
+        let program = unsafe { Program::from_gl_id(43) };
+        let sampler = unsafe { Sampler::from_gl_id(44) };
+        drop(buffer);
+        drop(program);
+        drop(sampler);
+        assert!(is_context_destroyed());
+        mark_context_created();
+        assert!(!is_context_destroyed());
+    }
+}
  
@@ -0,0 +1,173 @@
+use std::mem;
+use gl::{self, types::*};
+use super::{Object, Program, is_context_destroyed};
+use super::shader::checked_source_len;
+
+/// A program whose shaders are being compiled and linked, possibly on driver threads.
+///
+/// With `GL_KHR_parallel_shader_compile`, `is_ready()` can be polled without blocking, e.g for
+/// showing progress in a loading screen. Otherwise, it reports ready right away and `finish()` blocks like
+/// a regular synchronous compile.
+#[derive(Debug)]
+pub struct PendingProgram {
+    program: Program,
+    shaders: Vec<GLuint>,
+    is_parallel: bool,
+}
+
+impl Program {
+    /// Kicks off compilation of the given `(shader type, source)` stages and links them,
+    /// without querying any status (which would wait for the result).
+    ///
+    /// Fails right away, without calling GL, if there are no stages or if a source is empty.
+    pub fn compile_async(stages: &[(GLenum, &[u8])]) -> Result<PendingProgram, String> {
+        if stages.is_empty() {
+            return Err("No shaders were given to compile".to_owned());
+        }
+        let lens = stages.iter().map(|&(_, src)| checked_source_len(src)).collect::<Result<Vec<_>, _>>()?;
+        let is_parallel = is_parallel_shader_compile_supported();
+        unsafe {
+            let shaders: Vec<_> = stages.iter().zip(lens.iter()).map(|(&(type_, src), len)| {
+                let shader = gl::CreateShader(type_);
+                assert_ne!(shader, 0);
+                let glchars = src.as_ptr() as *const GLchar;
+                gl::ShaderSource(shader, 1, &glchars, len);
+                gl::CompileShader(shader);
+                shader
+            }).collect();
+
+            let program = gl::CreateProgram();
+            assert_ne!(program, 0);
+            for shader in shaders.iter() {
+                gl::AttachShader(program, *shader);
+            }
+            gl::LinkProgram(program);
+
+            Ok(PendingProgram { program: Program::from_gl_id(program), shaders, is_parallel })
+        }
+    }
+}
+
+impl PendingProgram {
+    /// Returns true when `finish()` won't block.
+    pub fn is_ready(&self) -> bool {
+        self.is_ready_with(&|program| unsafe {
+            let mut status = 0;
+            gl::GetProgramiv(program, gl::COMPLETION_STATUS_KHR, &mut status);
+            status != 0
+        })
+    }
+    /// Waits for compilation to end, and returns the program, or the compile or link errors.
+    pub fn finish(self) -> Result<Program, String> {
+        self.finish_with(&|program, shaders| {
+            let mut errors = String::new();
+            for shader in shaders.iter() {
+                if let Err(e) = shader_compile_status(*shader) {
+                    errors += &e;
+                }
+            }
+            if !program.link_status() {
+                errors += &program.info_log();
+            }
+            if errors.is_empty() { Ok(()) } else { Err(errors) }
+        })
+    }
+
+    fn is_ready_with(&self, completion_status: &Fn(GLuint) -> bool) -> bool {
+        !self.is_parallel || completion_status(self.program.gl_id())
+    }
+    fn finish_with(mut self, check_status: &Fn(&Program, &[GLuint]) -> Result<(), String>) -> Result<Program, String> {
+        let status = check_status(&self.program, &self.shaders);
+        self.delete_shaders();
+        let program = mem::replace(&mut self.program, unsafe { Program::from_gl_id(0) });
+        status.map(|()| program)
+    }
+    fn delete_shaders(&mut self) {
+        if is_context_destroyed() {
+            self.shaders.clear();
+            return;
+        }
+        for shader in self.shaders.drain(..) {
+            unsafe {
+                gl::DetachShader(self.program.gl_id(), shader);
+                gl::DeleteShader(shader);
+            }
+        }
+    }
+}
+
+impl Drop for PendingProgram {
+    fn drop(&mut self) {
+        self.delete_shaders();
+    }
+}
+
+fn is_parallel_shader_compile_supported() -> bool {
+    unsafe {
+        ::extensions::CACHE.as_ref().map(|ext| ext.khr_parallel_shader_compile).unwrap_or(false)
+    }
+}
+
+fn shader_compile_status(shader: GLuint) -> Result<(), String> {
+    unsafe {
+        let mut status = gl::FALSE as GLint;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+        if status == gl::TRUE as _ {
+            return Ok(());
+        }
+        let mut len = 0;
+        gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
+        let mut buf = vec![0_u8; len.max(1) as usize];
+        gl::GetShaderInfoLog(shader, len, ::std::ptr::null_mut(), buf.as_mut_ptr() as *mut GLchar);
+        buf.pop(); // Trailing null
+        Err(String::from_utf8(buf).unwrap_or("<UTF-8 error>".to_owned()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use object::mark_context_destroyed;
+
+    // No GL context here; make sure dropping objects doesn't call into GL.
+    fn mock_pending_program(is_parallel: bool) -> PendingProgram {
+        mark_context_destroyed();
+        PendingProgram { program: unsafe { Program::from_gl_id(42) }, shaders: vec![7, 8], is_parallel }
+    }
+
+    #[test]
+    fn parallel_compile_is_not_ready_then_ready_then_links() {
+        let p = mock_pending_program(true);
+        let nb_polls = Cell::new(0);
+        let completion_status = |program: GLuint| {
+            assert_eq!(program, 42);
+            nb_polls.set(nb_polls.get() + 1);
+            nb_polls.get() >= 3
+        };
+        assert!(!p.is_ready_with(&completion_status));
+        assert!(!p.is_ready_with(&completion_status));
+        assert!(p.is_ready_with(&completion_status));
+
+        let program = p.finish_with(&|program, shaders| {
+            assert_eq!(program.gl_id(), 42);
+            assert_eq!(shaders, &[7, 8]);
+            Ok(())
+        }).unwrap();
+        assert_eq!(program.gl_id(), 42);
+    }
+    #[test]
+    fn empty_sources_fail_without_calling_gl() {
+        assert!(Program::compile_async(&[]).is_err());
+        let e = Program::compile_async(&[(gl::VERTEX_SHADER, &b"void main() {}"[..]), (gl::FRAGMENT_SHADER, &b""[..])]).unwrap_err();
+        assert!(e.contains("empty"), "{}", e);
+    }
+    #[test]
+    fn sync_fallback_is_ready_right_away() {
+        let p = mock_pending_program(false);
+        assert!(p.is_ready_with(&|_| panic!("Should not query COMPLETION_STATUS without the extension")));
+        let e = p.finish_with(&|_, _| Err("link error".to_owned())).unwrap_err();
+        assert_eq!(e, "link error");
+    }
+}
@@ -0,0 +1,219 @@
+use std::mem;
+use std::slice;
+use std::ops::Range;
+use std::marker::PhantomData;
+use gl::{self, types::*};
+use super::{Buffer, Object, BufferFlags, FenceSwapChain};
+
+/// A buffer that stays mapped for its whole lifetime, so that data can be streamed to the GPU every frame by
+/// writing to it directly, instead of having the driver copy it over as `glNamedBufferSubData` does.
+///
+/// It's split into one region per frame in flight (usually 3). Each frame writes to its own region, and
+/// `advance_frame()` fences it and moves on to the next one, waiting for the GPU to be done with it if needed;
+/// otherwise, the CPU would overwrite data that the GPU is still reading.
+///
+/// The mapping is coherent (`GL_MAP_COHERENT_BIT`), so writes become visible to the GPU without flushing. A
+/// non-coherent mapping would also need `glFlushMappedNamedBufferRange` plus a
+/// `glMemoryBarrier(GL_CLIENT_MAPPED_BUFFER_BARRIER_BIT)` after writing, but the fences would still be needed:
+/// coherency is about visibility, not about knowing when the GPU is done reading.
+#[derive(Debug)]
+pub struct PersistentMappedBuffer<T> {
+    buffer: Buffer,
+    ptr: *mut T,
+    frame_capacity: usize,
+    region: usize,
+    fences: FenceSwapChain,
+    _phantom_data: PhantomData<T>,
+}
+
+/// The range of elements of region `region`.
+fn region_range(frame_capacity: usize, region: usize) -> Range<usize> {
+    region * frame_capacity .. (region + 1) * frame_capacity
+}
+
+/// The offset of region `region` in the buffer, in bytes.
+fn region_offset<T>(frame_capacity: usize, region: usize) -> usize {
+    region_range(frame_capacity, region).start * mem::size_of::<T>()
+}
+
+/// The size of the whole buffer, in bytes.
+fn storage_size<T>(frame_capacity: usize, nb_frames: usize) -> usize {
+    frame_capacity * nb_frames * mem::size_of::<T>()
+}
+
+/// `ptr` must point to at least `(region + 1) * frame_capacity` `T`s.
+unsafe fn region_slice<'a, T>(ptr: *mut T, frame_capacity: usize, region: usize) -> &'a mut [T] {
+    let range = region_range(frame_capacity, region);
+    slice::from_raw_parts_mut(ptr.offset(range.start as isize), range.end - range.start)
+}
+
+impl<T: Copy> PersistentMappedBuffer<T> {
+    /// `nb_frames` is the number of regions, at most 4; each region holds `frame_capacity` elements.
+    pub fn new(frame_capacity: usize, nb_frames: usize) -> Self {
+        assert_ne!(mem::size_of::<T>(), 0);
+        assert!(frame_capacity > 0 && nb_frames > 0);
+        let flags = BufferFlags::MAP_WRITE | BufferFlags::MAP_PERSISTENT | BufferFlags::MAP_COHERENT;
+        let size = storage_size::<T>(frame_capacity, nb_frames) as _;
+        let mut fences = FenceSwapChain::new_for_cpu_updates(nb_frames);
+        unsafe {
+            let mut buffer = 0;
+            gl::CreateBuffers(1, &mut buffer);
+            gl::NamedBufferStorage(buffer, size, 0 as _, flags.bits());
+            let ptr = gl::MapNamedBufferRange(buffer, 0, size, flags.bits());
+            assert!(!ptr.is_null(), "Could not map buffer {}", buffer);
+            Self {
+                buffer: Buffer::from_gl_id(buffer),
+                ptr: ptr as *mut T,
+                frame_capacity,
+                region: fences.start_frame(),
+                fences,
+                _phantom_data: PhantomData,
+            }
+        }
+    }
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+    pub fn frame_capacity(&self) -> usize {
+        self.frame_capacity
+    }
+    /// The current frame's region, to be written to before issuing the GL commands that use it.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe {
+            region_slice(self.ptr, self.frame_capacity, self.region)
+        }
+    }
+    /// The offset of the current frame's region in the buffer, in bytes, e.g for `glBindBufferRange`, or as the
+    /// `indirect` argument of `glMultiDrawElementsIndirect`.
+    pub fn offset(&self) -> usize {
+        region_offset::<T>(self.frame_capacity, self.region)
+    }
+    /// To be called once the GL commands that use the current region have been issued.
+    pub fn advance_frame(&mut self) {
+        self.fences.end_frame();
+        self.region = self.fences.start_frame();
+    }
+}
+
+// Deleting the buffer unmaps it, so there's no need to implement Drop.
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_dont_overlap() {
+        assert_eq!(region_range(16, 0), 0 .. 16);
+        assert_eq!(region_range(16, 1), 16 .. 32);
+        assert_eq!(region_range(16, 2), 32 .. 48);
+    }
+    #[test]
+    fn offsets_are_in_bytes() {
+        // e.g a `DrawElementsIndirectCommand`, which is 5 u32s
+        type Cmd = [u32; 5];
+        assert_eq!(region_offset::<Cmd>(16, 0), 0);
+        assert_eq!(region_offset::<Cmd>(16, 1), 320);
+        assert_eq!(region_offset::<Cmd>(16, 2), 640);
+    }
+    #[test]
+    fn last_region_ends_at_the_end_of_the_storage() {
+        for nb_frames in 1 .. 5 {
+            let last = region_offset::<f32>(7, nb_frames - 1) + 7 * mem::size_of::<f32>();
+            assert_eq!(last, storage_size::<f32>(7, nb_frames));
+        }
+    }
+    // Stands in for the driver: the buffer's storage is plain memory, and fences are always signaled.
+    // The function pointers are global, but the state is per-thread, so that tests may run in parallel.
+    mod fake_gl {
+        use std::cell::RefCell;
+        use std::os::raw::c_void;
+        use std::ptr;
+        use gl::{self, types::*};
+
+        #[derive(Debug, Default)]
+        pub struct FakeGL {
+            // u32s, so that it's suitably aligned
+            pub storage: Vec<u32>,
+            pub nb_fences: usize,
+            pub nb_waits: usize,
+        }
+
+        thread_local! {
+            pub static GL: RefCell<FakeGL> = RefCell::new(FakeGL::default());
+        }
+
+        extern "system" fn create_buffers(n: GLsizei, buffers: *mut GLuint) {
+            assert_eq!(n, 1);
+            unsafe { *buffers = 1; }
+        }
+        extern "system" fn delete_buffers(_n: GLsizei, _buffers: *const GLuint) {}
+        extern "system" fn named_buffer_storage(_buffer: GLuint, size: GLsizeiptr, _data: *const c_void, _flags: GLbitfield) {
+            GL.with(|gl| gl.borrow_mut().storage = vec![0; (size as usize + 3) / 4]);
+        }
+        extern "system" fn map_named_buffer_range(_buffer: GLuint, offset: GLintptr, _length: GLsizeiptr, _access: GLbitfield) -> *mut c_void {
+            GL.with(|gl| unsafe {
+                (gl.borrow_mut().storage.as_mut_ptr() as *mut u8).offset(offset) as _
+            })
+        }
+        extern "system" fn get_named_buffer_sub_data(_buffer: GLuint, offset: GLintptr, size: GLsizeiptr, data: *mut c_void) {
+            GL.with(|gl| unsafe {
+                let src = gl.borrow().storage.as_ptr() as *const u8;
+                ptr::copy_nonoverlapping(src.offset(offset), data as *mut u8, size as usize);
+            });
+        }
+        extern "system" fn fence_sync(_condition: GLenum, _flags: GLbitfield) -> GLsync {
+            GL.with(|gl| {
+                let mut gl = gl.borrow_mut();
+                gl.nb_fences += 1;
+                gl.nb_fences as GLsync
+            })
+        }
+        extern "system" fn client_wait_sync(_sync: GLsync, _flags: GLbitfield, _timeout: GLuint64) -> GLenum {
+            GL.with(|gl| gl.borrow_mut().nb_waits += 1);
+            gl::ALREADY_SIGNALED
+        }
+        extern "system" fn delete_sync(_sync: GLsync) {}
+
+        pub fn load() {
+            gl::CreateBuffers::load_with(|_| create_buffers as *const _);
+            gl::DeleteBuffers::load_with(|_| delete_buffers as *const _);
+            gl::NamedBufferStorage::load_with(|_| named_buffer_storage as *const _);
+            gl::MapNamedBufferRange::load_with(|_| map_named_buffer_range as *const _);
+            gl::GetNamedBufferSubData::load_with(|_| get_named_buffer_sub_data as *const _);
+            gl::FenceSync::load_with(|_| fence_sync as *const _);
+            gl::ClientWaitSync::load_with(|_| client_wait_sync as *const _);
+            gl::DeleteSync::load_with(|_| delete_sync as *const _);
+        }
+    }
+
+    fn read_back(buf: &PersistentMappedBuffer<[u32; 5]>, offset: usize) -> Vec<[u32; 5]> {
+        let mut data = vec![[0; 5]; buf.frame_capacity()];
+        unsafe {
+            gl::GetNamedBufferSubData(buf.buffer().gl_id(), offset as _, mem::size_of_val(&data[..]) as _, data.as_mut_ptr() as _);
+        }
+        data
+    }
+
+    #[test]
+    fn writes_are_read_back_after_advancing_the_frame() {
+        fake_gl::load();
+        let mut buf = PersistentMappedBuffer::<[u32; 5]>::new(4, 3);
+        let mut written = vec![];
+        for frame in 0 .. 4 {
+            let data: Vec<_> = (0 .. 4).map(|i| [frame, i, 0, 0, frame * 10 + i]).collect();
+            buf.as_mut_slice().copy_from_slice(&data);
+            let offset = buf.offset();
+            buf.advance_frame();
+            assert_eq!(read_back(&buf, offset), data);
+            written.push((offset, data));
+        }
+        // The 4th frame reused the 1st frame's region; moving on to a region that was written to before waits
+        // for its fence, which happened for the 4th and 5th frames.
+        assert_eq!(written[3].0, written[0].0);
+        assert_eq!(fake_gl::GL.with(|gl| (gl.borrow().nb_fences, gl.borrow().nb_waits)), (4, 2));
+        // The other regions were left alone.
+        assert_eq!(read_back(&buf, written[1].0), written[1].1);
+        assert_eq!(read_back(&buf, written[2].0), written[2].1);
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::cell::RefCell;
+use std::ffi::CString;
 use super::{
     Object,
     Program, 
@@ -11,13 +12,17 @@ use super::{
     ComputeShader,
 };
 use gl::{self, types::*};
-use math::{Mat4, Vec3, Vec4, Rgba, Rgb};
+use math::{Mat3, Mat4, Vec2, Vec3, Vec4, Rgba, Rgb};
 
 impl Program {
     pub fn link_status(&self) -> bool {
         self.program_iv(gl::LINK_STATUS) != 0
     }
     pub fn try_from_shaders(shaders: &[GLuint]) -> Result<Self, String> {
+        Self::try_from_shaders_with(shaders, &|_| ())
+    }
+    // `before_link` is given the program, with the shaders attached.
+    fn try_from_shaders_with(shaders: &[GLuint], before_link: &Fn(GLuint)) -> Result<Self, String> {
         unsafe {
             let program = gl::CreateProgram();
             assert_ne!(program, 0);
@@ -29,6 +34,7 @@ impl Program {
             }
             assert_ne!(nb_attached, 0);
 
+            before_link(program);
             gl::LinkProgram(program);
 
             for shader in shaders.iter().filter(|&s| *s != 0)  {
@@ -97,15 +103,23 @@ impl Program {
             i @ _ => Some(i),
         }
     }
-    /*
-    // WISH: Refactor this into a program Builer (do before linking)
-    pub fn bind_attrib_location(&self, loc: GLuint, name: &[u8]) {
-        assert_eq!(name[name.len()-1], 0);
+    pub fn uniform_block_index(&self, name: &[u8]) -> Option<GLuint> {
+        assert_eq!(0, *name.last().unwrap());
+        let i = unsafe {
+            gl::GetUniformBlockIndex(self.gl_id(), name.as_ptr() as *const GLchar)
+        };
+        match i {
+            gl::INVALID_INDEX => None,
+            i @ _ => Some(i),
+        }
+    }
+    /// Makes the uniform block at `block_index` read from the buffer bound to `binding` (see `UniformBuffer::bind_base()`).
+    /// This is what `layout(binding = ...)` does in the shader.
+    pub fn set_uniform_block_binding(&self, block_index: GLuint, binding: GLuint) {
         unsafe {
-            gl::BindAttribLocation(self.gl_id(), loc, name.as_ptr() as *const GLchar);
+            gl::UniformBlockBinding(self.gl_id(), block_index, binding);
         }
     }
-    */
     pub fn program_iv(&self, param: GLenum) -> GLint {
         let mut i = 0;
         unsafe {
@@ -171,6 +185,57 @@ impl Program {
     }
 }
 
+/// Links a program, first binding attribute and fragment output locations by name.
+///
+/// This is for GLSL versions that don't have `layout(location = ...)`, or shaders that don't use it.
+/// Explicit `layout` qualifiers in the shaders take precedence over these.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProgramBuilder<'a> {
+    shaders: Vec<GLuint>,
+    attrib_locations: Vec<(GLuint, &'a str)>,
+    frag_data_locations: Vec<(GLuint, &'a str)>,
+}
+
+impl<'a> ProgramBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn shader<S: Object>(mut self, shader: &S) -> Self {
+        self.shaders.push(shader.gl_id());
+        self
+    }
+    pub fn attrib_location(mut self, location: GLuint, name: &'a str) -> Self {
+        self.attrib_locations.push((location, name));
+        self
+    }
+    /// `color_number` is the index of the draw buffer the output goes to.
+    pub fn frag_data_location(mut self, color_number: GLuint, name: &'a str) -> Self {
+        self.frag_data_locations.push((color_number, name));
+        self
+    }
+    pub fn build(&self) -> Result<Program, String> {
+        if self.shaders.is_empty() {
+            return Err("No shaders were given to the program builder".to_owned());
+        }
+        let attrib_locations = c_names(&self.attrib_locations)?;
+        let frag_data_locations = c_names(&self.frag_data_locations)?;
+        Program::try_from_shaders_with(&self.shaders, &|program| unsafe {
+            for &(loc, ref name) in attrib_locations.iter() {
+                gl::BindAttribLocation(program, loc, name.as_ptr());
+            }
+            for &(loc, ref name) in frag_data_locations.iter() {
+                gl::BindFragDataLocation(program, loc, name.as_ptr());
+            }
+        })
+    }
+}
+
+fn c_names(names: &[(GLuint, &str)]) -> Result<Vec<(GLuint, CString)>, String> {
+    names.iter().map(|&(loc, name)| {
+        CString::new(name).map(|s| (loc, s)).map_err(|_| format!("{:?} contains a nul byte", name))
+    }).collect()
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct GLSLActiveVar {
     pub name: String,
@@ -343,6 +408,120 @@ gl_type_enum!{
     UnsignedIntSamplerCubeMapArray       = UNSIGNED_INT_SAMPLER_CUBE_MAP_ARRAY      ,
 }
 
+impl GLSLType {
+    pub fn is_sampler(&self) -> bool {
+        match *self {
+            GLSLType::Sampler1D
+            | GLSLType::Sampler2D
+            | GLSLType::Sampler3D
+            | GLSLType::SamplerCube
+            | GLSLType::Sampler1DShadow
+            | GLSLType::Sampler2DShadow
+            | GLSLType::Sampler1DArray
+            | GLSLType::Sampler2DArray
+            | GLSLType::Sampler1DArrayShadow
+            | GLSLType::Sampler2DArrayShadow
+            | GLSLType::Sampler2DMultisample
+            | GLSLType::Sampler2DMultisampleArray
+            | GLSLType::SamplerCubeShadow
+            | GLSLType::SamplerBuffer
+            | GLSLType::Sampler2DRect
+            | GLSLType::Sampler2DRectShadow
+            | GLSLType::IntSampler1D
+            | GLSLType::IntSampler2D
+            | GLSLType::IntSampler3D
+            | GLSLType::IntSamplerCube
+            | GLSLType::IntSampler1DArray
+            | GLSLType::IntSampler2DArray
+            | GLSLType::IntSampler2DMultisample
+            | GLSLType::IntSampler2DMultisampleArray
+            | GLSLType::IntSamplerBuffer
+            | GLSLType::IntSampler2DRect
+            | GLSLType::UnsignedIntSampler1D
+            | GLSLType::UnsignedIntSampler2D
+            | GLSLType::UnsignedIntSampler3D
+            | GLSLType::UnsignedIntSamplerCube
+            | GLSLType::UnsignedIntSampler1DArray
+            | GLSLType::UnsignedIntSampler2DArray
+            | GLSLType::UnsignedIntSampler2DMultisample
+            | GLSLType::UnsignedIntSampler2DMultisampleArray
+            | GLSLType::UnsignedIntSamplerBuffer
+            | GLSLType::UnsignedIntSampler2DRect
+            | GLSLType::SamplerCubeMapArray
+            | GLSLType::SamplerCubeMapArrayShadow
+            | GLSLType::IntSamplerCubeMapArray
+            | GLSLType::UnsignedIntSamplerCubeMapArray => true,
+            _ => false,
+        }
+    }
+    /// The type of the components, or `None` for opaque types (samplers, images and atomic counters).
+    pub fn base_type(&self) -> Option<GLSLBaseType> {
+        match *self {
+            GLSLType::Float | GLSLType::FloatVec2 | GLSLType::FloatVec3 | GLSLType::FloatVec4
+            | GLSLType::FloatMat2 | GLSLType::FloatMat3 | GLSLType::FloatMat4
+            | GLSLType::FloatMat2x3 | GLSLType::FloatMat2x4 | GLSLType::FloatMat3x2
+            | GLSLType::FloatMat3x4 | GLSLType::FloatMat4x2 | GLSLType::FloatMat4x3 => Some(GLSLBaseType::Float),
+            GLSLType::Int | GLSLType::IntVec2 | GLSLType::IntVec3 | GLSLType::IntVec4 => Some(GLSLBaseType::Int),
+            GLSLType::UnsignedInt | GLSLType::UnsignedIntVec2 | GLSLType::UnsignedIntVec3 | GLSLType::UnsignedIntVec4 => Some(GLSLBaseType::UnsignedInt),
+            GLSLType::Double | GLSLType::DoubleVec2 | GLSLType::DoubleVec3 | GLSLType::DoubleVec4
+            | GLSLType::DoubleMat2 | GLSLType::DoubleMat3 | GLSLType::DoubleMat4
+            | GLSLType::DoubleMat2x3 | GLSLType::DoubleMat2x4 | GLSLType::DoubleMat3x2
+            | GLSLType::DoubleMat3x4 | GLSLType::DoubleMat4x2 | GLSLType::DoubleMat4x3 => Some(GLSLBaseType::Double),
+            GLSLType::Bool | GLSLType::BoolVec2 | GLSLType::BoolVec3 | GLSLType::BoolVec4 => Some(GLSLBaseType::Bool),
+            _ => None,
+        }
+    }
+    /// `(columns, rows)`: `(1, 1)` for scalars, `(1, n)` for vectors, and `(c, r)` for `matCxR` (e.g `mat2x3` has
+    /// 2 columns of 3 rows). `None` for opaque types.
+    pub fn dimensions(&self) -> Option<(usize, usize)> {
+        let dims = match *self {
+            GLSLType::Float | GLSLType::Int | GLSLType::UnsignedInt | GLSLType::Double | GLSLType::Bool => (1, 1),
+            GLSLType::FloatVec2 | GLSLType::IntVec2 | GLSLType::UnsignedIntVec2 | GLSLType::DoubleVec2 | GLSLType::BoolVec2 => (1, 2),
+            GLSLType::FloatVec3 | GLSLType::IntVec3 | GLSLType::UnsignedIntVec3 | GLSLType::DoubleVec3 | GLSLType::BoolVec3 => (1, 3),
+            GLSLType::FloatVec4 | GLSLType::IntVec4 | GLSLType::UnsignedIntVec4 | GLSLType::DoubleVec4 | GLSLType::BoolVec4 => (1, 4),
+            GLSLType::FloatMat2   | GLSLType::DoubleMat2   => (2, 2),
+            GLSLType::FloatMat3   | GLSLType::DoubleMat3   => (3, 3),
+            GLSLType::FloatMat4   | GLSLType::DoubleMat4   => (4, 4),
+            GLSLType::FloatMat2x3 | GLSLType::DoubleMat2x3 => (2, 3),
+            GLSLType::FloatMat2x4 | GLSLType::DoubleMat2x4 => (2, 4),
+            GLSLType::FloatMat3x2 | GLSLType::DoubleMat3x2 => (3, 2),
+            GLSLType::FloatMat3x4 | GLSLType::DoubleMat3x4 => (3, 4),
+            GLSLType::FloatMat4x2 | GLSLType::DoubleMat4x2 => (4, 2),
+            GLSLType::FloatMat4x3 | GLSLType::DoubleMat4x3 => (4, 3),
+            _ => return None,
+        };
+        Some(dims)
+    }
+    /// Rows times columns for matrices. `None` for opaque types.
+    pub fn component_count(&self) -> Option<usize> {
+        self.dimensions().map(|(c, r)| c * r)
+    }
+    /// The size of the components, tightly packed. Layouts such as `std140` may add padding, e.g between the
+    /// columns of a `mat3`. `None` for opaque types.
+    pub fn size_bytes(&self) -> Option<usize> {
+        Some(self.component_count()? * self.base_type()?.size_bytes())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum GLSLBaseType {
+    Float,
+    Int,
+    UnsignedInt,
+    Double,
+    Bool,
+}
+
+impl GLSLBaseType {
+    /// A `bool` is 4 bytes in buffer-backed blocks, like an `int`.
+    pub fn size_bytes(&self) -> usize {
+        match *self {
+            GLSLBaseType::Double => 8,
+            _ => 4,
+        }
+    }
+}
+
 
 
 pub trait UniformElement: Sized {
@@ -372,10 +551,34 @@ impl UniformElement for Mat4<f32> {
         }
     }
 }
+impl UniformElement for Mat3<f32> {
+    const GLSL_TYPE: GLSLType = GLSLType::FloatMat3;
+    fn gl_uniform(loc: GLint, m: &[Self]) {
+        unsafe {
+            gl::UniformMatrix3fv(loc, m.len() as _, m[0].gl_should_transpose() as _, &m[0][(0, 0)]);
+        }
+    }
+}
+
+// GLSL bools are set from ints, but Rust bools are bytes, so they have to be converted first.
+impl UniformElement for bool {
+    const GLSL_TYPE: GLSLType = GLSLType::Bool;
+    fn gl_uniform(loc: GLint, m: &[Self]) {
+        let m: Vec<i32> = m.iter().map(|&b| b as i32).collect();
+        i32::gl_uniform(loc, &m);
+    }
+}
 
 impl_gl_uniform_element!{
     Vec4<f32>: FloatVec4 => Uniform4fv,
     Vec3<f32>: FloatVec3 => Uniform3fv,
+    Vec2<f32>: FloatVec2 => Uniform2fv,
+    Vec4<i32>: IntVec4 => Uniform4iv,
+    Vec3<i32>: IntVec3 => Uniform3iv,
+    Vec2<i32>: IntVec2 => Uniform2iv,
+    Vec4<u32>: UnsignedIntVec4 => Uniform4uiv,
+    Vec3<u32>: UnsignedIntVec3 => Uniform3uiv,
+    Vec2<u32>: UnsignedIntVec2 => Uniform2uiv,
     Rgba<f32>: FloatVec4 => Uniform4fv,
     Rgb <f32>: FloatVec3 => Uniform3fv,
     u32: UnsignedInt => Uniform1uiv,
@@ -392,6 +595,7 @@ pub struct ProgramEx {
     uniforms: HashMap<String, GLSLActiveVar>,
     // For more complex stuff such as "u_foobar[2].field[0]"
     extra_uniform_locations: RefCell<HashMap<String, GLint>>,
+    uniform_block_indices: RefCell<HashMap<String, GLuint>>,
 }
 
 impl ProgramEx {
@@ -401,11 +605,33 @@ impl ProgramEx {
             program,
             uniforms,
             extra_uniform_locations: Default::default(),
+            uniform_block_indices: Default::default(),
         }
     }
+    pub fn try_from_compute(cs: &ComputeShader) -> Result<Self, String> {
+        Program::try_from_compute(cs).map(Self::new)
+    }
     pub fn inner(&self) -> &Program {
         &self.program
     }
+    /// The `local_size_x`, `local_size_y` and `local_size_z` of a compute program. See `nb_work_groups()`.
+    pub fn query_work_group_size(&self) -> [u32; 3] {
+        let mut size = [0_i32; 3];
+        unsafe {
+            gl::GetProgramiv(self.program.gl_id(), gl::COMPUTE_WORK_GROUP_SIZE, size.as_mut_ptr());
+        }
+        [size[0] as _, size[1] as _, size[2] as _]
+    }
+    /// Runs a compute program, which must be in use (`glUseProgram`), then makes its writes to shader storage
+    /// buffers visible to the commands that follow (e.g draws that read the results).
+    ///
+    /// Other kinds of writes, e.g to images, need their own `glMemoryBarrier` bits.
+    pub fn dispatch_compute(&self, groups_x: u32, groups_y: u32, groups_z: u32) {
+        unsafe {
+            gl::DispatchCompute(groups_x, groups_y, groups_z);
+            gl::MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+        }
+    }
     pub fn into_inner(self) -> Program {
         self.program
     }
@@ -440,7 +666,122 @@ impl ProgramEx {
     pub fn set_uniform_unchecked<T: UniformElement>(&self, location: GLint, value: &[T]) {
         T::gl_uniform(location, value);
     }
+    pub fn uniform_block_index(&self, name: &str) -> Option<GLuint> {
+        let mut uniform_block_indices = self.uniform_block_indices.borrow_mut();
+        if let Some(i) = uniform_block_indices.get(name) {
+            return Some(*i);
+        }
+        let cstring = CString::new(name).unwrap();
+        let i = self.program.uniform_block_index(cstring.as_bytes_with_nul())?;
+        uniform_block_indices.insert(name.to_owned(), i);
+        Some(i)
+    }
+    pub fn set_uniform_block_binding(&self, block_name: &str, binding: GLuint) -> Result<(), String> {
+        let i = self.uniform_block_index(block_name).ok_or_else(|| format!("No such uniform block: `{}`", block_name))?;
+        self.program.set_uniform_block_binding(i, binding);
+        Ok(())
+    }
+    /// Assigns texture units to a sampler (or array of samplers), ensuring the uniform's type and length are correct.
+    pub fn set_sampler_array(&self, name: &str, units: &[i32]) -> Result<(), String> {
+        let uniform = self.uniform(name).ok_or_else(|| format!("No such uniform: `{}`", name))?;
+        check_sampler_array(uniform, units.len())?;
+        self.set_uniform_unchecked(uniform.location, units);
+        Ok(())
+    }
+}
+
+/// How many work groups of `work_group_size` it takes to cover `nb_invocations` along each axis, rounding up.
+/// The shader must then ignore the extra invocations, e.g with `if (gl_GlobalInvocationID.x >= count) return;`.
+pub fn nb_work_groups(nb_invocations: [u32; 3], work_group_size: [u32; 3]) -> [u32; 3] {
+    let f = |i: usize| {
+        assert_ne!(work_group_size[i], 0);
+        (nb_invocations[i] + work_group_size[i] - 1) / work_group_size[i]
+    };
+    [f(0), f(1), f(2)]
+}
+
+fn check_sampler_array(uniform: &GLSLActiveVar, nb_units: usize) -> Result<(), String> {
+    match uniform.type_ {
+        Some(ty) if ty.is_sampler() => (),
+        ty => return Err(format!("Uniform `{}` is not a sampler (type: {:?})", uniform.name, ty)),
+    };
+    if uniform.array_len != nb_units as GLsizei {
+        return Err(format!("Uniform `{}` is an array of {} samplers, but {} texture units were given", uniform.name, uniform.array_len, nb_units));
+    }
+    Ok(())
 }
 
 impl From<Program> for ProgramEx { fn from(p: Program) -> Self { Self::new(p) } }
 impl From<ProgramEx> for Program { fn from(p: ProgramEx) -> Self { p.into_inner() } }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn var(name: &str, type_: GLSLType, array_len: GLsizei) -> GLSLActiveVar {
+        GLSLActiveVar {
+            name: name.to_owned(),
+            array_len,
+            type_: Some(type_),
+            type_glenum: type_ as _,
+            location: 0,
+        }
+    }
+    #[test]
+    fn sampler_array_checks() {
+        let tabs = var("u_cube_map_tabs[0]", GLSLType::SamplerCubeMapArray, 4);
+        assert!(check_sampler_array(&tabs, 4).is_ok());
+        assert!(check_sampler_array(&tabs, 3).is_err());
+        assert!(check_sampler_array(&tabs, 5).is_err());
+
+        let not_a_sampler = var("u_mvp", GLSLType::FloatMat4, 1);
+        assert!(check_sampler_array(&not_a_sampler, 1).is_err());
+        let an_int = var("u_index", GLSLType::Int, 1);
+        assert!(check_sampler_array(&an_int, 1).is_err());
+    }
+    #[test]
+    fn builder_names_are_checked_before_linking() {
+        let names = c_names(&[(0, "a_position"), (3, "a_normal")]).unwrap();
+        assert_eq!(names[1].0, 3);
+        assert_eq!(names[1].1.as_bytes_with_nul(), b"a_normal\0");
+        assert!(c_names(&[(0, "a_pos\0ition")]).is_err());
+        // There's no GL context in tests, but failing early doesn't need one.
+        assert!(ProgramBuilder::new().attrib_location(0, "a_position").build().is_err());
+    }
+    #[test]
+    fn work_groups_cover_all_invocations() {
+        assert_eq!(nb_work_groups([4096, 1, 1], [64, 1, 1]), [64, 1, 1]);
+        assert_eq!(nb_work_groups([4097, 1, 1], [64, 1, 1]), [65, 1, 1]);
+        assert_eq!(nb_work_groups([1920, 1080, 1], [8, 8, 1]), [240, 135, 1]);
+        assert_eq!(nb_work_groups([0, 1, 1], [32, 1, 1]), [0, 1, 1]);
+    }
+    #[test]
+    fn type_sizes() {
+        assert_eq!(GLSLType::FloatMat4.size_bytes(), Some(64));
+        assert_eq!(GLSLType::FloatVec3.component_count(), Some(3));
+        assert_eq!(GLSLType::FloatMat2x3.dimensions(), Some((2, 3)));
+        assert_eq!(GLSLType::DoubleMat4x3.size_bytes(), Some(96));
+        assert_eq!(GLSLType::BoolVec2.size_bytes(), Some(8));
+        assert_eq!(GLSLType::UnsignedIntVec4.base_type(), Some(GLSLBaseType::UnsignedInt));
+        assert_eq!(GLSLType::Sampler2DArray.base_type(), None);
+        assert_eq!(GLSLType::Sampler2DArray.size_bytes(), None);
+    }
+    #[test]
+    fn matrix_uniform_element_types() {
+        assert_eq!(<Mat4<f32> as UniformElement>::GLSL_TYPE, GLSLType::FloatMat4);
+        assert_eq!(<Mat3<f32> as UniformElement>::GLSL_TYPE, GLSLType::FloatMat3);
+        // A mat3 array is tightly packed, which is what glUniformMatrix3fv expects
+        assert_eq!(::std::mem::size_of::<[Mat3<f32>; 2]>(), 2 * 9 * 4);
+    }
+    #[test]
+    fn vector_uniform_element_types() {
+        assert_eq!(<Vec2<f32> as UniformElement>::GLSL_TYPE, GLSLType::FloatVec2);
+        assert_eq!(<Vec2<i32> as UniformElement>::GLSL_TYPE, GLSLType::IntVec2);
+        assert_eq!(<Vec3<i32> as UniformElement>::GLSL_TYPE, GLSLType::IntVec3);
+        assert_eq!(<Vec4<i32> as UniformElement>::GLSL_TYPE, GLSLType::IntVec4);
+        assert_eq!(<Vec2<u32> as UniformElement>::GLSL_TYPE, GLSLType::UnsignedIntVec2);
+        assert_eq!(<Vec3<u32> as UniformElement>::GLSL_TYPE, GLSLType::UnsignedIntVec3);
+        assert_eq!(<Vec4<u32> as UniformElement>::GLSL_TYPE, GLSLType::UnsignedIntVec4);
+        assert_eq!(<bool as UniformElement>::GLSL_TYPE, GLSLType::Bool);
+    }
+}
@@ -111,3 +111,139 @@ impl Query {
         result
     }
 }
+
+/// Where a `TimerQuery` is in its lifetime.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum TimerQueryState {
+    /// Never began.
+    Unused,
+    /// Between `begin()` and `end()`.
+    Active,
+    /// Ended, but the GPU hasn't finished executing the commands yet.
+    Pending,
+    /// The elapsed time, in nanoseconds.
+    Done(u64),
+}
+
+impl TimerQueryState {
+    fn begin(self) -> Self {
+        assert_ne!(self, TimerQueryState::Active, "Timer query already began");
+        TimerQueryState::Active
+    }
+    fn end(self) -> Self {
+        assert_eq!(self, TimerQueryState::Active, "Timer query ended without having begun");
+        TimerQueryState::Pending
+    }
+    /// `result` is only called if `is_available` returns true, so that polling never blocks.
+    fn poll<A: FnOnce() -> bool, R: FnOnce() -> u64>(self, is_available: A, result: R) -> Self {
+        match self {
+            TimerQueryState::Pending if is_available() => TimerQueryState::Done(result()),
+            state => state,
+        }
+    }
+}
+
+/// Measures the time the GPU takes to execute the commands between `begin()` and `end()` (`GL_TIME_ELAPSED`).
+///
+/// GL doesn't allow nesting timer queries: only one of them may be active at a time, so timing a pass inside
+/// another pass that is being timed is an error. Time consecutive passes with separate queries instead.
+///
+/// The result is only available a frame or two later; see `TimerQueryRing` to keep measuring every frame meanwhile.
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub struct TimerQuery {
+    query: Query,
+    state: TimerQueryState,
+}
+
+impl TimerQuery {
+    pub fn new() -> Self {
+        Self { query: Query::new(), state: TimerQueryState::Unused }
+    }
+    pub fn state(&self) -> TimerQueryState {
+        self.state
+    }
+    /// Discards the previous result, if any.
+    pub fn begin(&mut self) {
+        self.state = self.state.begin();
+        QueryTarget::TimeElapsed.begin(&self.query);
+    }
+    pub fn end(&mut self) {
+        self.state = self.state.end();
+        QueryTarget::TimeElapsed.end();
+    }
+    /// The elapsed time in nanoseconds. Doesn't wait for the GPU: returns `None` until the result is available,
+    /// and then keeps returning it until the next `begin()`.
+    pub fn try_get_nanos(&mut self) -> Option<u64> {
+        let query = &self.query;
+        self.state = self.state.poll(|| query.is_result_available(), || query.wait_result());
+        match self.state {
+            TimerQueryState::Done(ns) => Some(ns),
+            _ => None,
+        }
+    }
+}
+
+/// Timer queries that are reused in turn, so that there's one for each frame whose result is still in flight.
+///
+/// Use `begin()`/`end()` around the pass every frame, and `try_get_latest_nanos()` to get the most recent result
+/// that is available. With enough queries (3 is usually plenty), this never stalls.
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub struct TimerQueryRing {
+    queries: [TimerQuery; 4], // Up to quadruple buffering
+    len: u8,
+    i: u8,
+}
+
+impl TimerQueryRing {
+    /// `len` is the number of queries in use, at most 4.
+    pub fn new(len: usize) -> Self {
+        assert!(len > 0 && len <= 4);
+        Self {
+            queries: [TimerQuery::new(), TimerQuery::new(), TimerQuery::new(), TimerQuery::new()],
+            len: len as u8,
+            i: 0,
+        }
+    }
+    pub fn begin(&mut self) {
+        self.queries[self.i as usize].begin();
+    }
+    pub fn end(&mut self) {
+        self.queries[self.i as usize].end();
+        self.i = (self.i + 1) % self.len;
+    }
+    /// The result of the most recently ended query that is available.
+    pub fn try_get_latest_nanos(&mut self) -> Option<u64> {
+        let len = self.len as usize;
+        let newest = self.i as usize + len - 1;
+        (0 .. len).filter_map(|age| self.queries[(newest - age) % len].try_get_nanos()).next()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timer_query_state_machine() {
+        let state = TimerQueryState::Unused;
+        assert_eq!(state.poll(|| true, || 42), TimerQueryState::Unused);
+        let state = state.begin();
+        assert_eq!(state, TimerQueryState::Active);
+        assert_eq!(state.poll(|| true, || 42), TimerQueryState::Active);
+        let state = state.end();
+        assert_eq!(state, TimerQueryState::Pending);
+        let state = state.poll(|| false, || panic!("Fetching the result would block"));
+        assert_eq!(state, TimerQueryState::Pending);
+        let state = state.poll(|| true, || 1_000_000);
+        assert_eq!(state, TimerQueryState::Done(1_000_000));
+        // The result sticks around, without querying GL again
+        assert_eq!(state.poll(|| panic!(), || panic!()), TimerQueryState::Done(1_000_000));
+        assert_eq!(state.begin(), TimerQueryState::Active);
+    }
+    #[test]
+    #[should_panic]
+    fn nested_begin() {
+        TimerQueryState::Unused.begin().begin();
+    }
+}
@@ -0,0 +1,73 @@
+use gl::types::*;
+
+/// A feature that is either part of core GL since some version, or provided by an extension.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct GLFeature {
+    pub name: &'static str,
+    pub core_since: (GLuint, GLuint),
+    pub extensions: &'static [&'static str],
+}
+
+impl GLFeature {
+    pub fn is_supported_by(&self, version: ::GLVersion, has_extension: &Fn(&str) -> bool) -> bool {
+        let (major, minor) = self.core_since;
+        version.gl(major, minor) || self.extensions.iter().any(|ext| has_extension(ext))
+    }
+}
+
+/// Returns the features that are missing, given a GL version and a way to query extensions.
+pub fn missing_features(version: ::GLVersion, has_extension: &Fn(&str) -> bool, features: &[GLFeature]) -> Vec<GLFeature> {
+    features.iter().filter(|f| !f.is_supported_by(version, has_extension)).cloned().collect()
+}
+
+/// Checks that all features are supported; otherwise, returns a message listing each missing feature.
+pub fn check_features(version: ::GLVersion, has_extension: &Fn(&str) -> bool, features: &[GLFeature]) -> Result<(), String> {
+    let missing = missing_features(version, has_extension, features);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let mut msg = format!("The OpenGL context (version {}) lacks the following required features:", version);
+    for f in missing.iter() {
+        let (major, minor) = f.core_since;
+        msg += &format!("\n- {} (core since OpenGL {}.{}", f.name, major, minor);
+        if !f.extensions.is_empty() {
+            msg += &format!(", or {}", f.extensions.join(" or "));
+        }
+        msg += ")";
+    }
+    Err(msg)
+}
+
+/// Same as `check_features()`, against the current context.
+pub fn check_current_features(features: &[GLFeature]) -> Result<(), String> {
+    let ext = ::ExtensionsStore::new();
+    check_features(::GLVersion::current(), &|s| ext.has(s), features)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {GLVersion, GLVariant};
+
+    static FEATURES: &'static [GLFeature] = &[
+        GLFeature { name: "Direct State Access", core_since: (4, 5), extensions: &["GL_ARB_direct_state_access"] },
+        GLFeature { name: "Cube map arrays", core_since: (4, 0), extensions: &["GL_ARB_texture_cube_map_array"] },
+    ];
+
+    #[test]
+    fn too_old_version_fails() {
+        let version = GLVersion::new(GLVariant::Desktop, 3, 9);
+        let e = check_features(version, &|_| false, FEATURES).unwrap_err();
+        assert_eq!(e, "The OpenGL context (version 3.9 Desktop) lacks the following required features:\n\
+            - Direct State Access (core since OpenGL 4.5, or GL_ARB_direct_state_access)\n\
+            - Cube map arrays (core since OpenGL 4.0, or GL_ARB_texture_cube_map_array)");
+    }
+    #[test]
+    fn extensions_make_up_for_version() {
+        let version = GLVersion::new(GLVariant::Desktop, 4, 3);
+        assert!(check_features(version, &|_| false, FEATURES).is_err());
+        assert!(check_features(version, &|s| s == "GL_ARB_direct_state_access", FEATURES).is_ok());
+        assert!(check_features(GLVersion::new(GLVariant::Desktop, 4, 5), &|_| false, FEATURES).is_ok());
+    }
+}
@@ -0,0 +1,84 @@
+use gl::{self, types::*};
+use super::{get, Object, Sampler};
+
+/// Brings an anisotropy level within what the implementation supports, i.e `[1, max]`. NaN gives 1 (no anisotropy).
+pub fn clamp_anisotropy(level: f32, max: f32) -> f32 {
+    if level >= 1. { level.min(max) } else { 1. }
+}
+
+/// For texture objects, which also have sampling state of their own.
+/// See `Sampler::set_anisotropy()`.
+pub fn set_texture_anisotropy(texture: GLuint, level: f32) -> Option<f32> {
+    let level = clamp_anisotropy(level, get::max_texture_max_anisotropy()?);
+    unsafe {
+        gl::TextureParameterf(texture, gl::TEXTURE_MAX_ANISOTROPY_EXT, level);
+    }
+    Some(level)
+}
+
+/// Sampling state lives in sampler objects rather than in each texture, so that one texture can be sampled
+/// in different ways, and so that filters can change without touching textures.
+/// A sampler bound to a texture unit overrides the sampling parameters of whatever texture is bound there.
+impl Sampler {
+    pub fn set_parameter_i(&self, pname: GLenum, param: GLint) {
+        unsafe {
+            gl::SamplerParameteri(self.gl_id(), pname, param);
+        }
+    }
+    pub fn set_parameter_f(&self, pname: GLenum, param: GLfloat) {
+        unsafe {
+            gl::SamplerParameterf(self.gl_id(), pname, param);
+        }
+    }
+    pub fn set_min_filter(&self, filter: GLenum) {
+        self.set_parameter_i(gl::TEXTURE_MIN_FILTER, filter as _);
+    }
+    pub fn set_mag_filter(&self, filter: GLenum) {
+        self.set_parameter_i(gl::TEXTURE_MAG_FILTER, filter as _);
+    }
+    /// Sets the same wrap mode for the S, T and R coordinates.
+    pub fn set_wrap(&self, wrap: GLenum) {
+        self.set_parameter_i(gl::TEXTURE_WRAP_S, wrap as _);
+        self.set_parameter_i(gl::TEXTURE_WRAP_T, wrap as _);
+        self.set_parameter_i(gl::TEXTURE_WRAP_R, wrap as _);
+    }
+    /// Requires `GL_EXT_texture_filter_anisotropic`. 1 disables anisotropic filtering.
+    /// The value isn't checked against the implementation's maximum; see `set_anisotropy()`.
+    pub fn set_max_anisotropy(&self, max_anisotropy: f32) {
+        assert!(max_anisotropy >= 1.);
+        self.set_parameter_f(gl::TEXTURE_MAX_ANISOTROPY_EXT, max_anisotropy);
+    }
+    /// Clamps `level` to what the implementation supports, and returns the level that was actually set,
+    /// or `None` (doing nothing) if anisotropic filtering isn't supported at all.
+    pub fn set_anisotropy(&self, level: f32) -> Option<f32> {
+        let level = clamp_anisotropy(level, get::max_texture_max_anisotropy()?);
+        self.set_max_anisotropy(level);
+        Some(level)
+    }
+    pub fn bind(&self, unit: GLuint) {
+        unsafe {
+            gl::BindSampler(unit, self.gl_id());
+        }
+    }
+    /// Lets the texture bound to `unit` use its own sampling parameters again.
+    pub fn unbind(unit: GLuint) {
+        unsafe {
+            gl::BindSampler(unit, 0);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anisotropy_is_clamped() {
+        assert_eq!(clamp_anisotropy(1000., 16.), 16.);
+        assert_eq!(clamp_anisotropy(8., 16.), 8.);
+        assert_eq!(clamp_anisotropy(0., 16.), 1.);
+        assert_eq!(clamp_anisotropy(-4., 16.), 1.);
+        assert_eq!(clamp_anisotropy(::std::f32::NAN, 16.), 1.);
+    }
+}
@@ -3,12 +3,23 @@ use gl;
 use gl::types::*;
 use super::object::*;
 
+/// The length of `src` to pass to `glShaderSource`, i.e without the trailing null byte if any.
+/// Fails if there's nothing left, since GL would only report a cryptic compile error.
+pub fn checked_source_len(src: &[u8]) -> Result<GLint, String> {
+    let src = match src.last() {
+        Some(&0) => &src[..src.len() - 1],
+        _ => src,
+    };
+    if src.is_empty() {
+        return Err("The shader source is empty".to_owned());
+    }
+    Ok(src.len() as _)
+}
+
+// An empty source isn't rejected here, but then fails to compile.
 fn set_source(shader: GLuint, src: &[u8]) {
+    let len = checked_source_len(src).unwrap_or(0);
     unsafe {
-        let mut len = src.len() as GLint;
-        if src[len as usize - 1] == 0 {
-            len -= 1;
-        }
         let glchars = src.as_ptr() as *const GLchar;
         gl::ShaderSource(shader, 1, &glchars, &len);
     }
@@ -24,6 +35,9 @@ fn info_log_length(shader: GLuint) -> GLint {
 fn info_log(shader: GLuint) -> String {
     unsafe {
         let len = info_log_length(shader);
+        if len <= 1 {
+            return String::new();
+        }
         let mut buf = Vec::<u8>::with_capacity((len-1) as _); // -1 to skip trailing null
         buf.set_len((len-1) as _);
         gl::GetShaderInfoLog(shader, len, ptr::null_mut(), buf.as_mut_ptr() as *mut GLchar);
@@ -35,6 +49,7 @@ macro_rules! shader {
     ($Self:ident $ty:ident) => {
         impl $Self {
             pub fn try_from_source(src: &[u8]) -> Result<Self, String> {
+                checked_source_len(src)?;
                 let s = Self::new();
                 s.set_source(src);
                 s.compile();
@@ -82,3 +97,16 @@ shader!{ TessEvaluationShader TESS_EVALUATION_SHADER }
 shader!{ GeometryShader       GEOMETRY_SHADER        }
 shader!{ FragmentShader       FRAGMENT_SHADER        }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_sources_are_refused() {
+        assert!(checked_source_len(b"").is_err());
+        assert!(checked_source_len(b"\0").is_err());
+        assert_eq!(checked_source_len(b"void main() {}"), Ok(14));
+        assert_eq!(checked_source_len(b"void main() {}\0"), Ok(14));
+    }
+}
@@ -0,0 +1,311 @@
+use std::mem;
+use std::ptr;
+use std::slice;
+use std::marker::PhantomData;
+use gl::{self, types::*};
+use super::{Buffer, Object, BufferUsage, BufferFlags, Fence};
+
+/// A growable array of `T` for a shader's `buffer` block, e.g `layout(std430, binding = 1) buffer Lights { Light lights[]; };`.
+///
+/// With `with_capacity()`, storage is mutable (`glNamedBufferData`), so that it can be reallocated when `set_data()`
+/// is given more elements than it can hold. Reallocating orphans the old storage, so it isn't a stall, but it isn't
+/// free either.
+/// With `new()`, storage is immutable (`glNamedBufferStorage`) and can't grow, but it can be mapped persistently
+/// (see `persistent_slice_mut()`).
+///
+/// `T` must match the GLSL struct's `std430` layout, which is `std140` without the 16-byte rounding of arrays
+/// and structs (so e.g a `float[]` is tightly packed).
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub struct ShaderStorageBuffer<T> {
+    buffer: Buffer,
+    len: usize,
+    capacity: usize,
+    storage: Storage,
+    // Null unless created with `BufferFlags::MAP_PERSISTENT`, in which case the whole storage stays mapped.
+    mapped: *mut T,
+    // Inserted after the GPU commands that read the mapped storage, see `fence_gpu_reads()`.
+    fence: Option<Fence>,
+    _phantom_data: PhantomData<T>,
+}
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+enum Storage {
+    Mutable(BufferUsage),
+    Immutable(BufferFlags),
+}
+
+/// Doubles the capacity until `len` fits, to amortize reallocations when the array grows a bit every frame.
+fn grown_capacity(capacity: usize, len: usize) -> usize {
+    if len <= capacity {
+        return capacity;
+    }
+    len.max(capacity * 2)
+}
+
+/// `ptr` must point to at least `len` `T`s, e.g a buffer mapped with `GL_MAP_READ_BIT`.
+unsafe fn copy_from_mapped<T: Copy>(ptr: *const T, len: usize) -> Vec<T> {
+    slice::from_raw_parts(ptr, len).to_vec()
+}
+
+impl<T: Copy> ShaderStorageBuffer<T> {
+    pub fn with_capacity(capacity: usize, usage: BufferUsage) -> Self {
+        assert_ne!(mem::size_of::<T>(), 0);
+        unsafe {
+            let mut buffer = 0;
+            gl::CreateBuffers(1, &mut buffer);
+            gl::NamedBufferData(buffer, (capacity * mem::size_of::<T>()) as _, ptr::null(), usage as _);
+            Self { buffer: Buffer::from_gl_id(buffer), len: 0, capacity, storage: Storage::Mutable(usage), mapped: ptr::null_mut(), fence: None, _phantom_data: PhantomData }
+        }
+    }
+    /// Immutable storage for `capacity` elements. `set_data()` and `update_range()` need `BufferFlags::DYNAMIC_STORAGE`.
+    /// With `BufferFlags::MAP_PERSISTENT`, the whole storage is mapped here, once and for all.
+    pub fn new(capacity: usize, flags: BufferFlags) -> Self {
+        assert_ne!(mem::size_of::<T>(), 0);
+        assert!(flags.are_valid());
+        unsafe {
+            let mut buffer = 0;
+            gl::CreateBuffers(1, &mut buffer);
+            let size = (capacity * mem::size_of::<T>()) as _;
+            gl::NamedBufferStorage(buffer, size, ptr::null(), flags.bits());
+            let mut mapped = ptr::null_mut();
+            if flags.contains(BufferFlags::MAP_PERSISTENT) {
+                assert!(capacity > 0);
+                let access = flags & (BufferFlags::MAP_READ | BufferFlags::MAP_WRITE | BufferFlags::MAP_PERSISTENT | BufferFlags::MAP_COHERENT);
+                mapped = gl::MapNamedBufferRange(buffer, 0, size, access.bits()) as *mut T;
+                assert!(!mapped.is_null(), "Could not map shader storage buffer {}", buffer);
+            }
+            Self { buffer: Buffer::from_gl_id(buffer), len: 0, capacity, storage: Storage::Immutable(flags), mapped, fence: None, _phantom_data: PhantomData }
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+    /// Replaces the whole contents, growing mutable storage if needed (the previous contents are then lost).
+    /// Immutable storage can't grow, so `data` must fit in it.
+    pub fn set_data(&mut self, data: &[T]) {
+        if data.len() > self.capacity {
+            let usage = match self.storage {
+                Storage::Mutable(usage) => usage,
+                Storage::Immutable(_) => panic!("{} elements don't fit in an immutable shader storage buffer of {}", data.len(), self.capacity),
+            };
+            self.capacity = grown_capacity(self.capacity, data.len());
+            unsafe {
+                gl::NamedBufferData(self.buffer.gl_id(), (self.capacity * mem::size_of::<T>()) as _, ptr::null(), usage as _);
+            }
+        }
+        self.len = 0;
+        self.update_range(0, data);
+    }
+    /// Overwrites elements starting at element `offset`, which may extend `len()`, but not past the capacity.
+    pub fn update_range(&mut self, offset: usize, data: &[T]) {
+        assert!(offset + data.len() <= self.capacity, "Range {}..{} is out of the buffer's capacity of {}", offset, offset + data.len(), self.capacity);
+        if let Storage::Immutable(flags) = self.storage {
+            assert!(flags.contains(BufferFlags::DYNAMIC_STORAGE));
+        }
+        if !data.is_empty() {
+            unsafe {
+                gl::NamedBufferSubData(self.buffer.gl_id(), (offset * mem::size_of::<T>()) as _, mem::size_of_val(data) as _, data.as_ptr() as _);
+            }
+        }
+        self.len = self.len.max(offset + data.len());
+    }
+    /// The first `count` elements of the persistently mapped storage, for writing; also sets `len()` to `count`.
+    /// Since the mapping is persistent, the buffer may be used for drawing while it is mapped, so that elements can be
+    /// streamed in without any copy by the driver.
+    ///
+    /// If `fence_gpu_reads()` was called, this first waits for the GPU to be done with the elements it was reading,
+    /// so they can't be overwritten under its feet. To avoid waiting, stream to one region per frame in flight
+    /// with a `PersistentMappedBuffer` instead.
+    ///
+    /// The buffer must have been created with `new()` and
+    /// `BufferFlags::MAP_WRITE | BufferFlags::MAP_PERSISTENT | BufferFlags::MAP_COHERENT`, so that writes become
+    /// visible to the GPU without explicit flushes.
+    pub fn persistent_slice_mut(&mut self, count: usize) -> &mut [T] {
+        let required = BufferFlags::MAP_WRITE | BufferFlags::MAP_PERSISTENT | BufferFlags::MAP_COHERENT;
+        match self.storage {
+            Storage::Immutable(flags) if flags.contains(required) => (),
+            storage => panic!("Can't write to a shader storage buffer with {:?} through a persistent mapping", storage),
+        };
+        assert!(count <= self.capacity);
+        if let Some(fence) = self.fence.take() {
+            fence.wait();
+        }
+        self.len = count;
+        unsafe {
+            slice::from_raw_parts_mut(self.mapped, count)
+        }
+    }
+    /// To be called after issuing the GL commands that read the elements written through `persistent_slice_mut()`.
+    pub fn fence_gpu_reads(&mut self) {
+        self.fence = Some(Fence::insert());
+    }
+    /// Only the first `len()` elements are bound, so that `.length()` of the runtime-sized array in the shader
+    /// is `len()` instead of the capacity.
+    ///
+    /// Panics if the buffer is empty, since GL can't bind an empty range; binding the whole storage instead
+    /// would have the shader read `capacity()` stale elements.
+    pub fn bind_base(&self, binding: GLuint) {
+        assert!(self.len > 0, "Can't bind an empty shader storage buffer");
+        unsafe {
+            gl::BindBufferRange(gl::SHADER_STORAGE_BUFFER, binding, self.buffer.gl_id(), 0, (self.len * mem::size_of::<T>()) as _);
+        }
+    }
+    pub fn unbind_base(binding: GLuint) {
+        unsafe {
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, 0);
+        }
+    }
+    /// Maps the buffer to copy its contents back, e.g to check what a compute shader wrote.
+    /// This waits for the GPU, so it's meant for debugging only.
+    pub fn read_back(&self) -> Vec<T> {
+        if self.len == 0 {
+            return Vec::new();
+        }
+        // A buffer can't be mapped twice, but a persistent mapping may be read directly once the GPU is done.
+        if !self.mapped.is_null() {
+            if let Storage::Immutable(flags) = self.storage {
+                assert!(flags.contains(BufferFlags::MAP_READ | BufferFlags::MAP_COHERENT), "Can't read back a shader storage buffer mapped with {:?}", flags);
+            }
+            unsafe {
+                gl::Finish();
+                return copy_from_mapped(self.mapped, self.len);
+            }
+        }
+        unsafe {
+            let size = (self.len * mem::size_of::<T>()) as _;
+            let p = gl::MapNamedBufferRange(self.buffer.gl_id(), 0, size, gl::MAP_READ_BIT);
+            assert!(!p.is_null(), "Could not map shader storage buffer {}", self.buffer.gl_id());
+            let data = copy_from_mapped(p as *const T, self.len);
+            gl::UnmapNamedBuffer(self.buffer.gl_id());
+            data
+        }
+    }
+}
+
+// Deleting the buffer unmaps it, so there's no need to implement Drop.
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    struct PointLight {
+        position: [f32; 3],
+        radius: f32,
+        color: [f32; 4],
+    }
+
+    #[test]
+    fn capacity_grows_geometrically() {
+        assert_eq!(grown_capacity(16, 10), 16);
+        assert_eq!(grown_capacity(16, 16), 16);
+        assert_eq!(grown_capacity(16, 17), 32);
+        assert_eq!(grown_capacity(16, 100), 100);
+        assert_eq!(grown_capacity(0, 3), 3);
+    }
+    // Stands in for the driver, with a single buffer whose storage is plain memory.
+    // The function pointers are global, but the storage is per-thread, so that tests may run in parallel.
+    mod fake_gl {
+        use std::cell::RefCell;
+        use std::os::raw::c_void;
+        use std::ptr;
+        use gl::{self, types::*};
+
+        #[derive(Debug, Default)]
+        pub struct FakeBuffer {
+            // u32s, so that it's suitably aligned
+            pub storage: Vec<u32>,
+            pub bound_range: Option<(GLuint, GLintptr, GLsizeiptr)>,
+        }
+
+        thread_local! {
+            pub static BUFFER: RefCell<FakeBuffer> = RefCell::new(FakeBuffer::default());
+        }
+
+        extern "system" fn create_buffers(n: GLsizei, buffers: *mut GLuint) {
+            assert_eq!(n, 1);
+            unsafe { *buffers = 1; }
+        }
+        extern "system" fn delete_buffers(_n: GLsizei, _buffers: *const GLuint) {}
+        extern "system" fn named_buffer_storage(_buffer: GLuint, size: GLsizeiptr, _data: *const c_void, _flags: GLbitfield) {
+            BUFFER.with(|b| b.borrow_mut().storage = vec![0; (size as usize + 3) / 4]);
+        }
+        extern "system" fn named_buffer_sub_data(_buffer: GLuint, offset: GLintptr, size: GLsizeiptr, data: *const c_void) {
+            BUFFER.with(|b| unsafe {
+                let dst = b.borrow_mut().storage.as_mut_ptr() as *mut u8;
+                ptr::copy_nonoverlapping(data as *const u8, dst.offset(offset), size as usize);
+            });
+        }
+        extern "system" fn map_named_buffer_range(_buffer: GLuint, offset: GLintptr, _length: GLsizeiptr, _access: GLbitfield) -> *mut c_void {
+            BUFFER.with(|b| unsafe {
+                (b.borrow_mut().storage.as_mut_ptr() as *mut u8).offset(offset) as _
+            })
+        }
+        extern "system" fn unmap_named_buffer(_buffer: GLuint) -> GLboolean {
+            gl::TRUE
+        }
+        extern "system" fn bind_buffer_range(_target: GLenum, index: GLuint, _buffer: GLuint, offset: GLintptr, size: GLsizeiptr) {
+            BUFFER.with(|b| b.borrow_mut().bound_range = Some((index, offset, size)));
+        }
+
+        pub fn load() {
+            gl::CreateBuffers::load_with(|_| create_buffers as *const _);
+            gl::DeleteBuffers::load_with(|_| delete_buffers as *const _);
+            gl::NamedBufferStorage::load_with(|_| named_buffer_storage as *const _);
+            gl::NamedBufferSubData::load_with(|_| named_buffer_sub_data as *const _);
+            gl::MapNamedBufferRange::load_with(|_| map_named_buffer_range as *const _);
+            gl::UnmapNamedBuffer::load_with(|_| unmap_named_buffer as *const _);
+            gl::BindBufferRange::load_with(|_| bind_buffer_range as *const _);
+        }
+    }
+
+    fn lights(count: usize) -> Vec<PointLight> {
+        (0..count).map(|i| {
+            let i = i as f32;
+            PointLight { position: [i, -i, i * 0.5], radius: 1. / (i + 1.), color: [i, 0.25, 0.5, 1.] }
+        }).collect()
+    }
+
+    #[test]
+    fn read_back_is_bytewise_identical() {
+        fake_gl::load();
+        let lights = lights(13);
+        let mut ssbo = ShaderStorageBuffer::new(16, BufferFlags::DYNAMIC_STORAGE | BufferFlags::MAP_READ);
+        ssbo.set_data(&lights);
+        assert_eq!(ssbo.len(), 13);
+        let read = ssbo.read_back();
+        assert_eq!(read, lights);
+        let size = lights.len() * mem::size_of::<PointLight>();
+        let (read_bytes, stored_bytes) = fake_gl::BUFFER.with(|b| unsafe {
+            (slice::from_raw_parts(read.as_ptr() as *const u8, size).to_vec(), slice::from_raw_parts(b.borrow().storage.as_ptr() as *const u8, size).to_vec())
+        });
+        assert_eq!(read_bytes, stored_bytes);
+    }
+    #[test]
+    fn only_the_elements_in_use_are_bound() {
+        fake_gl::load();
+        let mut ssbo = ShaderStorageBuffer::new(16, BufferFlags::DYNAMIC_STORAGE);
+        ssbo.set_data(&lights(3));
+        ssbo.bind_base(1);
+        let bound = fake_gl::BUFFER.with(|b| b.borrow().bound_range);
+        assert_eq!(bound, Some((1, 0, 3 * mem::size_of::<PointLight>() as GLsizeiptr)));
+    }
+    #[test]
+    #[should_panic]
+    fn empty_buffer_cant_be_bound() {
+        fake_gl::load();
+        let ssbo = ShaderStorageBuffer::<PointLight>::new(16, BufferFlags::DYNAMIC_STORAGE);
+        ssbo.bind_base(1);
+    }
+}
@@ -0,0 +1,266 @@
+use gl::{self, types::*};
+use math::{Extent3, Rect};
+use super::{get, Object, Texture};
+
+/// Creates textures with immutable storage (`glTextureStorage*D`), checking their size first.
+///
+/// `size.d` is the depth of 3D textures and the number of layers for array textures (times 6 for cube map
+/// arrays), and is ignored for 1D and 2D textures (including cube maps).
+/// For 1D array textures, `size.h` is ignored instead, since the layers are given by `size.d`.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct TextureBuilder {
+    pub target: GLenum,
+    pub internal_format: GLenum,
+    pub size: Extent3<u32>,
+    pub nb_levels: u32,
+    /// Reject sizes that aren't powers of two, e.g for atlases that are addressed with bit shifts.
+    pub require_power_of_two: bool,
+}
+
+/// The implementation's size limits that `TextureBuilder::validate()` checks against.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct TextureLimits {
+    /// `GL_MAX_TEXTURE_SIZE`, for the width and height of all but 3D textures.
+    pub max_size: u32,
+    /// `GL_MAX_3D_TEXTURE_SIZE`, for all three dimensions of 3D textures.
+    pub max_3d_size: u32,
+    /// `GL_MAX_ARRAY_TEXTURE_LAYERS`, for the number of layers of array textures.
+    pub max_array_layers: u32,
+}
+
+impl TextureLimits {
+    pub fn query() -> Self {
+        Self {
+            max_size: get::integer(gl::MAX_TEXTURE_SIZE) as _,
+            max_3d_size: get::integer(gl::MAX_3D_TEXTURE_SIZE) as _,
+            max_array_layers: get::integer(gl::MAX_ARRAY_TEXTURE_LAYERS) as _,
+        }
+    }
+}
+
+/// The number of levels of a full mipmap chain for a texture of this size.
+pub fn max_mipmap_levels(w: u32, h: u32) -> u32 {
+    32 - w.max(h).max(1).leading_zeros()
+}
+
+impl TextureBuilder {
+    pub fn new(target: GLenum, internal_format: GLenum, size: Extent3<u32>) -> Self {
+        Self { target, internal_format, size, nb_levels: 1, require_power_of_two: false }
+    }
+    pub fn nb_levels(self, nb_levels: u32) -> Self {
+        Self { nb_levels, .. self }
+    }
+    /// Allocates the full mipmap chain, i.e `nb_levels(max_mipmap_levels(w, h))`, to be filled with
+    /// `Texture::generate_mipmaps()` after uploading level 0.
+    pub fn with_auto_mipmaps(self) -> Self {
+        self.nb_levels(max_mipmap_levels(self.size.w, self.size.h))
+    }
+    pub fn require_power_of_two(self, require_power_of_two: bool) -> Self {
+        Self { require_power_of_two, .. self }
+    }
+    /// The dimensions that are actually used by `target`, as (width, height, depth or layers);
+    /// the ones that are ignored are 1.
+    fn used_size(&self) -> Extent3<u32> {
+        let Extent3 { w, h, d } = self.size;
+        match self.target {
+            gl::TEXTURE_1D => Extent3::new(w, 1, 1),
+            gl::TEXTURE_1D_ARRAY => Extent3::new(w, 1, d),
+            gl::TEXTURE_2D | gl::TEXTURE_RECTANGLE | gl::TEXTURE_CUBE_MAP => Extent3::new(w, h, 1),
+            _ => self.size,
+        }
+    }
+    pub fn validate(&self, limits: &TextureLimits) -> Result<(), String> {
+        let Extent3 { w, h, d } = self.used_size();
+        if w == 0 || h == 0 || d == 0 {
+            return Err(format!("Texture size {}x{}x{} has a zero dimension", w, h, d));
+        }
+        match self.target {
+            gl::TEXTURE_3D => if w > limits.max_3d_size || h > limits.max_3d_size || d > limits.max_3d_size {
+                return Err(format!("3D texture size {}x{}x{} exceeds the maximum of {}", w, h, d, limits.max_3d_size));
+            },
+            _ => if w > limits.max_size || h > limits.max_size {
+                return Err(format!("Texture size {}x{} exceeds the maximum of {}", w, h, limits.max_size));
+            },
+        }
+        match self.target {
+            gl::TEXTURE_1D_ARRAY | gl::TEXTURE_2D_ARRAY | gl::TEXTURE_CUBE_MAP_ARRAY => if d > limits.max_array_layers {
+                return Err(format!("{} layers exceed the maximum of {}", d, limits.max_array_layers));
+            },
+            _ => (),
+        }
+        if self.require_power_of_two && !(w.is_power_of_two() && h.is_power_of_two()) {
+            return Err(format!("Texture size {}x{} is not a power of two", w, h));
+        }
+        if self.nb_levels == 0 || self.nb_levels > max_mipmap_levels(w, h) {
+            return Err(format!("Texture size {}x{} can't have {} mipmap levels (max is {})", w, h, self.nb_levels, max_mipmap_levels(w, h)));
+        }
+        if (self.target == gl::TEXTURE_CUBE_MAP || self.target == gl::TEXTURE_CUBE_MAP_ARRAY) && w != h {
+            return Err(format!("Cube map faces must be square, not {}x{}", w, h));
+        }
+        if self.target == gl::TEXTURE_CUBE_MAP_ARRAY && d % 6 != 0 {
+            return Err(format!("Cube map arrays need a multiple of 6 layers, not {}", d));
+        }
+        Ok(())
+    }
+    pub fn build(&self) -> Result<Texture, String> {
+        self.validate(&TextureLimits::query())?;
+        let Extent3 { w, h, d } = self.used_size();
+        let (levels, internal_format) = (self.nb_levels as GLsizei, self.internal_format);
+        unsafe {
+            let mut tex = 0;
+            gl::CreateTextures(self.target, 1, &mut tex);
+            // Deleted if we return an error below
+            let texture = Texture::from_gl_id(tex);
+            match self.target {
+                gl::TEXTURE_1D => gl::TextureStorage1D(tex, levels, internal_format, w as _),
+                gl::TEXTURE_1D_ARRAY => gl::TextureStorage2D(tex, levels, internal_format, w as _, d as _),
+                gl::TEXTURE_2D | gl::TEXTURE_RECTANGLE | gl::TEXTURE_CUBE_MAP => gl::TextureStorage2D(tex, levels, internal_format, w as _, h as _),
+                gl::TEXTURE_3D | gl::TEXTURE_2D_ARRAY | gl::TEXTURE_CUBE_MAP_ARRAY => gl::TextureStorage3D(tex, levels, internal_format, w as _, h as _, d as _),
+                target => return Err(format!("Unsupported texture target 0x{:x}", target)),
+            }
+            Ok(texture)
+        }
+    }
+}
+
+impl Texture {
+    /// Fills levels 1 and above from level 0, for all the levels that were allocated.
+    ///
+    /// For cube maps and cube map arrays, all six faces of every cube map must have been uploaded first, since
+    /// the whole texture is filtered at once (and GL requires cube maps to be "cube complete").
+    pub fn generate_mipmaps(&self) {
+        unsafe {
+            gl::GenerateTextureMipmap(self.gl_id());
+        }
+    }
+    /// Wraps `glTextureSubImage2D`; `data` holds `rect.w * rect.h` pixels of the given format and type, each row
+    /// being padded to `GL_UNPACK_ALIGNMENT` bytes.
+    /// For array textures, use `glTextureSubImage3D` instead.
+    ///
+    /// Panics if `data` is too small, since GL would read past its end; or if the format and type
+    /// aren't supported by `pixel_size()`.
+    pub fn upload_2d(&self, level: u32, rect: Rect<u32, u32>, format: GLenum, type_: GLenum, data: &[u8]) {
+        let Rect { x, y, w, h } = rect;
+        let pixel_size = pixel_size(format, type_).unwrap_or_else(|| panic!("Unsupported pixel format 0x{:x} with type 0x{:x}", format, type_));
+        let needed = image_size_2d(w as _, h as _, pixel_size, get::integer(gl::UNPACK_ALIGNMENT) as _);
+        assert!(data.len() >= needed, "Uploading {}x{} pixels needs {} bytes, but only {} were given", w, h, needed, data.len());
+        unsafe {
+            gl::TextureSubImage2D(self.gl_id(), level as _, x as _, y as _, w as _, h as _, format, type_, data.as_ptr() as _);
+        }
+    }
+}
+
+/// The size in bytes of one pixel of client data with the given format and type (as in `glTexSubImage*`),
+/// or `None` if the combination isn't supported.
+pub fn pixel_size(format: GLenum, type_: GLenum) -> Option<usize> {
+    let nb_components = match format {
+        gl::RED | gl::GREEN | gl::BLUE | gl::RED_INTEGER | gl::DEPTH_COMPONENT | gl::STENCIL_INDEX => 1,
+        gl::RG | gl::RG_INTEGER | gl::DEPTH_STENCIL => 2,
+        gl::RGB | gl::BGR | gl::RGB_INTEGER | gl::BGR_INTEGER => 3,
+        gl::RGBA | gl::BGRA | gl::RGBA_INTEGER | gl::BGRA_INTEGER => 4,
+        _ => return None,
+    };
+    let size = match type_ {
+        gl::UNSIGNED_BYTE | gl::BYTE => nb_components,
+        gl::UNSIGNED_SHORT | gl::SHORT | gl::HALF_FLOAT => 2 * nb_components,
+        gl::UNSIGNED_INT | gl::INT | gl::FLOAT => 4 * nb_components,
+        // Packed types hold a whole pixel
+        gl::UNSIGNED_BYTE_3_3_2 | gl::UNSIGNED_BYTE_2_3_3_REV => 1,
+        gl::UNSIGNED_SHORT_5_6_5 | gl::UNSIGNED_SHORT_5_6_5_REV
+        | gl::UNSIGNED_SHORT_4_4_4_4 | gl::UNSIGNED_SHORT_4_4_4_4_REV
+        | gl::UNSIGNED_SHORT_5_5_5_1 | gl::UNSIGNED_SHORT_1_5_5_5_REV => 2,
+        gl::UNSIGNED_INT_8_8_8_8 | gl::UNSIGNED_INT_8_8_8_8_REV
+        | gl::UNSIGNED_INT_10_10_10_2 | gl::UNSIGNED_INT_2_10_10_10_REV
+        | gl::UNSIGNED_INT_24_8 | gl::UNSIGNED_INT_10F_11F_11F_REV | gl::UNSIGNED_INT_5_9_9_9_REV => 4,
+        gl::FLOAT_32_UNSIGNED_INT_24_8_REV => 8,
+        _ => return None,
+    };
+    Some(size)
+}
+
+/// The number of bytes GL reads for a `w`x`h` image, when rows start at multiples of `alignment` bytes
+/// (`GL_UNPACK_ALIGNMENT`). The last row isn't padded.
+fn image_size_2d(w: usize, h: usize, pixel_size: usize, alignment: usize) -> usize {
+    if w == 0 || h == 0 {
+        return 0;
+    }
+    let row = w * pixel_size;
+    let stride = (row + alignment - 1) / alignment * alignment;
+    stride * (h - 1) + row
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mipmap_levels() {
+        assert_eq!(max_mipmap_levels(1, 1), 1);
+        assert_eq!(max_mipmap_levels(256, 256), 9);
+        assert_eq!(max_mipmap_levels(300, 17), 9);
+        assert_eq!(max_mipmap_levels(1, 1024), 11);
+        let b = TextureBuilder::new(gl::TEXTURE_2D, gl::RGBA8, Extent3::new(256, 256, 1)).with_auto_mipmaps();
+        assert_eq!(b.nb_levels, 9);
+    }
+    const LIMITS: TextureLimits = TextureLimits { max_size: 4096, max_3d_size: 256, max_array_layers: 64 };
+
+    #[test]
+    fn validation() {
+        let b = TextureBuilder::new(gl::TEXTURE_2D, gl::RGBA8, Extent3::new(512, 256, 1));
+        assert!(b.validate(&LIMITS).is_ok());
+        assert!(b.with_auto_mipmaps().validate(&LIMITS).is_ok());
+        assert!(b.nb_levels(11).validate(&LIMITS).is_err());
+        assert!(b.validate(&TextureLimits { max_size: 256, .. LIMITS }).is_err());
+        assert!(TextureBuilder { size: Extent3::new(0, 256, 1), .. b }.validate(&LIMITS).is_err());
+
+        let npot = TextureBuilder { size: Extent3::new(300, 256, 1), .. b };
+        assert!(npot.validate(&LIMITS).is_ok());
+        assert!(npot.require_power_of_two(true).validate(&LIMITS).is_err());
+        assert!(b.require_power_of_two(true).validate(&LIMITS).is_ok());
+
+        let cubes = TextureBuilder::new(gl::TEXTURE_CUBE_MAP_ARRAY, gl::RGB8, Extent3::new(64, 64, 12));
+        assert!(cubes.validate(&LIMITS).is_ok());
+        assert!(TextureBuilder { size: Extent3::new(64, 64, 8), .. cubes }.validate(&LIMITS).is_err());
+        assert!(TextureBuilder { size: Extent3::new(64, 32, 12), .. cubes }.validate(&LIMITS).is_err());
+    }
+    #[test]
+    fn depth_is_ignored_for_2d_targets() {
+        for &target in &[gl::TEXTURE_2D, gl::TEXTURE_CUBE_MAP] {
+            let b = TextureBuilder::new(target, gl::RGBA8, Extent3::new(64, 64, 0));
+            assert!(b.validate(&LIMITS).is_ok());
+            assert!(TextureBuilder { size: Extent3::new(64, 64, 100_000), .. b }.validate(&LIMITS).is_ok());
+        }
+        let b = TextureBuilder::new(gl::TEXTURE_1D_ARRAY, gl::RGBA8, Extent3::new(64, 0, 8));
+        assert!(b.validate(&LIMITS).is_ok());
+        assert!(TextureBuilder { size: Extent3::new(64, 0, 0), .. b }.validate(&LIMITS).is_err());
+    }
+    #[test]
+    fn depth_and_layers_have_their_own_limits() {
+        let volume = TextureBuilder::new(gl::TEXTURE_3D, gl::R8, Extent3::new(256, 256, 256));
+        assert!(volume.validate(&LIMITS).is_ok());
+        assert!(TextureBuilder { size: Extent3::new(512, 256, 256), .. volume }.validate(&LIMITS).is_err());
+        assert!(TextureBuilder { size: Extent3::new(256, 256, 257), .. volume }.validate(&LIMITS).is_err());
+
+        let layers = TextureBuilder::new(gl::TEXTURE_2D_ARRAY, gl::R8, Extent3::new(1024, 1024, 64));
+        assert!(layers.validate(&LIMITS).is_ok());
+        assert!(TextureBuilder { size: Extent3::new(1024, 1024, 65), .. layers }.validate(&LIMITS).is_err());
+        let cubes = TextureBuilder::new(gl::TEXTURE_CUBE_MAP_ARRAY, gl::RGB8, Extent3::new(64, 64, 66));
+        assert!(cubes.validate(&LIMITS).is_err());
+    }
+    #[test]
+    fn upload_sizes() {
+        assert_eq!(pixel_size(gl::RED, gl::UNSIGNED_BYTE), Some(1));
+        assert_eq!(pixel_size(gl::RGB, gl::UNSIGNED_BYTE), Some(3));
+        assert_eq!(pixel_size(gl::RGBA, gl::FLOAT), Some(16));
+        assert_eq!(pixel_size(gl::RGB, gl::UNSIGNED_SHORT_5_6_5), Some(2));
+        assert_eq!(pixel_size(gl::BGRA, gl::UNSIGNED_INT_8_8_8_8_REV), Some(4));
+        assert_eq!(pixel_size(gl::RGBA, gl::RGBA8), None);
+
+        // Rows of 3 RGB8 pixels are 9 bytes, padded to 12 except for the last one
+        assert_eq!(image_size_2d(3, 2, 3, 4), 12 + 9);
+        assert_eq!(image_size_2d(3, 2, 3, 1), 9 + 9);
+        assert_eq!(image_size_2d(4, 4, 4, 4), 64);
+        assert_eq!(image_size_2d(0, 4, 4, 4), 0);
+    }
+}
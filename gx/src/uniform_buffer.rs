@@ -0,0 +1,209 @@
+use std::mem;
+use std::marker::PhantomData;
+use gl::{self, types::*};
+use math::{Vec2, Vec3, Vec4, Mat4, Rgba};
+use super::{Buffer, Object, BufferFlags, ProgramEx};
+
+/// Types whose memory representation matches the GLSL `std140` layout, so that they can be copied as-is
+/// into uniform blocks.
+///
+/// The rules that matter most (see section 7.6.2.2 of the GL 4.5 spec):
+///
+/// - Scalars are aligned to 4 bytes, `vec2` to 8 bytes, and both `vec3` and `vec4` to 16 bytes.
+///   A `vec3` is only 12 bytes though, so a scalar may follow it without padding.
+/// - Array elements and struct members that are structs are aligned to 16 bytes, and array strides are
+///   rounded up to 16 bytes, so a `float[4]` takes 64 bytes, not 16.
+/// - Matrices are arrays of column vectors, so a `mat3` is three 16-byte columns (48 bytes). `Mat3<f32>` doesn't
+///   match it and doesn't implement this trait.
+/// - A struct's size is rounded up to its alignment, which is itself rounded up to 16 bytes.
+///
+/// Rust doesn't know about these rules: implementors are `#[repr(C)]` structs with explicit padding fields where
+/// needed, and it's their responsibility to check their layout against `Std140Layout` (typically in a test).
+/// Getting it wrong doesn't produce any error, only garbage in the shader.
+pub unsafe trait Std140: Copy {
+    const STD140_ALIGNMENT: usize;
+    const STD140_SIZE: usize;
+}
+
+macro_rules! impl_std140 {
+    ($($T:ty: $alignment:expr, $size:expr;)+) => {
+        $(
+            unsafe impl Std140 for $T {
+                const STD140_ALIGNMENT: usize = $alignment;
+                const STD140_SIZE: usize = $size;
+            }
+        )+
+    };
+}
+
+impl_std140!{
+    f32: 4, 4;
+    i32: 4, 4;
+    u32: 4, 4;
+    Vec2<f32>: 8, 8;
+    Vec2<i32>: 8, 8;
+    Vec2<u32>: 8, 8;
+    Vec3<f32>: 16, 12;
+    Vec4<f32>: 16, 16;
+    Vec4<i32>: 16, 16;
+    Vec4<u32>: 16, 16;
+    Rgba<f32>: 16, 16;
+    Mat4<f32>: 16, 64;
+}
+
+fn round_up(x: usize, alignment: usize) -> usize {
+    (x + alignment - 1) / alignment * alignment
+}
+
+/// Computes `std140` member offsets, in declaration order, the way GLSL does.
+#[derive(Debug, Default, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct Std140Layout {
+    size: usize,
+    max_alignment: usize,
+}
+
+impl Std140Layout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns the offset of the new member.
+    pub fn push<T: Std140>(&mut self) -> usize {
+        self.push_raw(T::STD140_ALIGNMENT, T::STD140_SIZE)
+    }
+    /// A `T[len]` member. Returns its offset.
+    pub fn push_array<T: Std140>(&mut self, len: usize) -> usize {
+        let stride = Self::array_stride::<T>();
+        self.push_raw(round_up(T::STD140_ALIGNMENT, 16), stride * len)
+    }
+    /// A member whose type is a struct with the given layout. Returns its offset.
+    pub fn push_struct(&mut self, layout: &Self) -> usize {
+        self.push_raw(layout.alignment(), layout.size())
+    }
+    pub fn array_stride<T: Std140>() -> usize {
+        round_up(T::STD140_SIZE, 16)
+    }
+    pub fn alignment(&self) -> usize {
+        round_up(self.max_alignment, 16)
+    }
+    /// The size of the whole struct, including trailing padding.
+    pub fn size(&self) -> usize {
+        round_up(self.size, self.alignment())
+    }
+    fn push_raw(&mut self, alignment: usize, size: usize) -> usize {
+        let offset = round_up(self.size, alignment);
+        self.size = offset + size;
+        self.max_alignment = self.max_alignment.max(alignment);
+        offset
+    }
+}
+
+/// A buffer holding a single `T`, for a uniform block.
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub struct UniformBuffer<T> {
+    buffer: Buffer,
+    _phantom_data: PhantomData<T>,
+}
+
+impl<T: Std140> UniformBuffer<T> {
+    pub fn new(value: &T) -> Self {
+        debug_assert_eq!(mem::size_of::<T>(), T::STD140_SIZE);
+        unsafe {
+            let mut buffer = 0;
+            gl::CreateBuffers(1, &mut buffer);
+            gl::NamedBufferStorage(buffer, mem::size_of::<T>() as _, value as *const T as _, BufferFlags::DYNAMIC_STORAGE.bits());
+            Self { buffer: Buffer::from_gl_id(buffer), _phantom_data: PhantomData }
+        }
+    }
+    pub fn update(&self, value: &T) {
+        unsafe {
+            gl::NamedBufferSubData(self.buffer.gl_id(), 0, mem::size_of::<T>() as _, value as *const T as _);
+        }
+    }
+    /// Makes the buffer back the uniform block declared with `layout(std140, binding = <binding>)`.
+    pub fn bind_base(&self, binding: GLuint) {
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, self.buffer.gl_id());
+        }
+    }
+    /// For shaders that can't use `layout(binding = ...)`: points the program's block to `binding`, and binds
+    /// the buffer there.
+    pub fn bind_to_program(&self, program: &ProgramEx, block_name: &str, binding: GLuint) -> Result<(), String> {
+        program.set_uniform_block_binding(block_name, binding)?;
+        self.bind_base(binding);
+        Ok(())
+    }
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Debug, Copy, Clone)]
+    struct Light {
+        position: Vec3<f32>,
+        intensity: f32,
+        color: Rgba<f32>,
+        view: Mat4<f32>,
+    }
+
+    unsafe impl Std140 for Light {
+        const STD140_ALIGNMENT: usize = 16;
+        const STD140_SIZE: usize = 96;
+    }
+
+    fn light_layout() -> (Std140Layout, [usize; 4]) {
+        let mut layout = Std140Layout::new();
+        let offsets = [
+            layout.push::<Vec3<f32>>(),
+            layout.push::<f32>(),
+            layout.push::<Rgba<f32>>(),
+            layout.push::<Mat4<f32>>(),
+        ];
+        (layout, offsets)
+    }
+
+    #[test]
+    fn a_scalar_fits_right_after_a_vec3() {
+        let (layout, offsets) = light_layout();
+        assert_eq!(offsets, [0, 12, 16, 32]);
+        assert_eq!(layout.size(), Light::STD140_SIZE);
+        assert_eq!(layout.alignment(), Light::STD140_ALIGNMENT);
+
+        // The Rust struct agrees
+        let light = Light { position: Vec3::zero(), intensity: 0., color: Rgba::zero(), view: Mat4::identity() };
+        let base = &light as *const _ as usize;
+        let rust_offsets = [
+            &light.position as *const _ as usize - base,
+            &light.intensity as *const _ as usize - base,
+            &light.color as *const _ as usize - base,
+            &light.view as *const _ as usize - base,
+        ];
+        assert_eq!(rust_offsets, offsets);
+        assert_eq!(mem::size_of::<Light>(), layout.size());
+    }
+    #[test]
+    fn padding_that_rust_doesnt_insert() {
+        // A vec3 after a scalar starts at 16, where #[repr(C)] would put it at 4
+        let mut layout = Std140Layout::new();
+        assert_eq!(layout.push::<f32>(), 0);
+        assert_eq!(layout.push::<Vec3<f32>>(), 16);
+        assert_eq!(layout.size(), 32);
+        // Arrays of scalars have a 16-byte stride
+        let mut layout = Std140Layout::new();
+        assert_eq!(layout.push_array::<f32>(4), 0);
+        assert_eq!(layout.push::<f32>(), 64);
+        assert_eq!(Std140Layout::array_stride::<Vec3<f32>>(), 16);
+        // Structs are aligned to 16 bytes, even if their members aren't
+        let mut inner = Std140Layout::new();
+        inner.push::<f32>();
+        let mut outer = Std140Layout::new();
+        outer.push::<f32>();
+        assert_eq!(outer.push_struct(&inner), 16);
+        assert_eq!(outer.size(), 32);
+    }
+}
@@ -1,5 +1,6 @@
 // Keep this mod lightweight.
 use gl::{self, types::*};
+use math::Rect;
 
 pub fn gl_at_least(major: GLuint, minor: GLuint) -> bool {
     ::GLVersion::current().gl(major, minor)
@@ -8,6 +9,26 @@ pub fn gles_at_least(major: GLuint, minor: GLuint) -> bool {
     ::GLVersion::current().gles(major, minor)
 }
 
+fn rect_to_gl_args(rect: Rect<i32, u32>) -> (GLint, GLint, GLsizei, GLsizei) {
+    let Rect { x, y, w, h } = rect;
+    (x as _, y as _, w as _, h as _)
+}
+
+/// Sets the viewport; `x` and `y` are the lower-left corner, as GL expects.
+pub fn set_viewport(rect: Rect<i32, u32>) {
+    let (x, y, w, h) = rect_to_gl_args(rect);
+    unsafe {
+        gl::Viewport(x, y, w, h);
+    }
+}
+/// Sets the scissor box; `x` and `y` are the lower-left corner, as GL expects.
+pub fn set_scissor(rect: Rect<i32, u32>) {
+    let (x, y, w, h) = rect_to_gl_args(rect);
+    unsafe {
+        gl::Scissor(x, y, w, h);
+    }
+}
+
 pub fn init_reasonable_default_gl_state() {
     unsafe {
         gl::Enable(gl::DEPTH_TEST);
@@ -35,3 +56,47 @@ pub fn init_reasonable_default_gl_state() {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn rect_is_passed_through() {
+        assert_eq!(rect_to_gl_args(Rect::new(0, 0, 800, 600)), (0, 0, 800, 600));
+        assert_eq!(rect_to_gl_args(Rect::new(12, 34, 56, 78)), (12, 34, 56, 78));
+        assert_eq!(rect_to_gl_args(Rect::new(-4, -2, 1, 1)), (-4, -2, 1, 1));
+    }
+    // Stands in for the driver, recording the viewport and scissor box.
+    // The function pointers are global, but the state is per-thread, so that tests may run in parallel.
+    mod fake_gl {
+        use std::cell::Cell;
+        use gl::{self, types::*};
+
+        thread_local! {
+            pub static VIEWPORT: Cell<Option<(GLint, GLint, GLsizei, GLsizei)>> = Cell::new(None);
+            pub static SCISSOR: Cell<Option<(GLint, GLint, GLsizei, GLsizei)>> = Cell::new(None);
+        }
+
+        extern "system" fn viewport(x: GLint, y: GLint, w: GLsizei, h: GLsizei) {
+            VIEWPORT.with(|v| v.set(Some((x, y, w, h))));
+        }
+        extern "system" fn scissor(x: GLint, y: GLint, w: GLsizei, h: GLsizei) {
+            SCISSOR.with(|s| s.set(Some((x, y, w, h))));
+        }
+
+        pub fn load() {
+            gl::Viewport::load_with(|_| viewport as *const _);
+            gl::Scissor::load_with(|_| scissor as *const _);
+        }
+    }
+
+    #[test]
+    fn viewport_and_scissor_reach_gl() {
+        fake_gl::load();
+        set_viewport(Rect::new(12, 34, 800, 600));
+        assert_eq!(fake_gl::VIEWPORT.with(|v| v.get()), Some((12, 34, 800, 600)));
+        assert_eq!(fake_gl::SCISSOR.with(|s| s.get()), None);
+        set_scissor(Rect::new(-4, -2, 56, 78));
+        assert_eq!(fake_gl::SCISSOR.with(|s| s.get()), Some((-4, -2, 56, 78)));
+        assert_eq!(fake_gl::VIEWPORT.with(|v| v.get()), Some((12, 34, 800, 600)));
+    }
+}
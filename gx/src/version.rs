@@ -52,7 +52,7 @@ impl GLVersion {
         Self::new(variant, major, minor)
     }
     fn at_least(&self, major: GLuint, minor: GLuint) -> bool {
-        self.major > major || (self.major == self.major && self.minor >= minor)
+        self.major > major || (self.major == major && self.minor >= minor)
     }
     pub fn gl(&self, major: GLuint, minor: GLuint) -> bool {
         self.variant == GLVariant::Desktop && self.at_least(major, minor)
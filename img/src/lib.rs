@@ -9,6 +9,8 @@ pub use image::{
     ImageError as Error,
     // traits
     Pixel,
+    // pixel types, as held by AnyImage
+    Luma, LumaA, Rgb, Rgba,
 };
 
 use std::fs;
@@ -0,0 +1,103 @@
+use super::{Aabb, Vec3};
+
+/// Building and combining axis-aligned bounding boxes, e.g for culling meshes.
+///
+/// Unlike `RectSetOps`, boxes are closed: they contain all of their faces, so boxes that touch do overlap
+/// (as a flat box), and a box made from a single point is valid.
+/// See `vek`'s own methods for `center()` and `contains_point()`.
+pub trait AabbSetOps<T>: Sized {
+    /// The smallest box that contains all the points, or `None` if there are none.
+    fn from_points<I: IntoIterator<Item=Vec3<T>>>(points: I) -> Option<Self>;
+    /// Half of the box's size along each axis, i.e the distance from its center to its faces.
+    fn half_extents(&self) -> Vec3<T>;
+    /// The volume shared by both boxes, or `None` if they are disjoint.
+    fn overlap(self, other: Self) -> Option<Self>;
+    /// The smallest box that contains both boxes.
+    fn bounding_union(self, other: Self) -> Self;
+    /// Does `other` fit entirely inside this box? Faces may coincide.
+    fn contains_whole_aabb(&self, other: &Self) -> bool;
+}
+
+macro_rules! impl_aabb_set_ops {
+    ($($T:ident)+) => {
+        $(
+            impl AabbSetOps<$T> for Aabb<$T> {
+                fn from_points<I: IntoIterator<Item=Vec3<$T>>>(points: I) -> Option<Self> {
+                    let mut points = points.into_iter();
+                    let first = points.next()?;
+                    Some(points.fold(Aabb { min: first, max: first }, |aabb, p| Aabb {
+                        min: Vec3::partial_min(aabb.min, p),
+                        max: Vec3::partial_max(aabb.max, p),
+                    }))
+                }
+                fn half_extents(&self) -> Vec3<$T> {
+                    (self.max - self.min) / 2.
+                }
+                fn overlap(self, other: Self) -> Option<Self> {
+                    let min = Vec3::partial_max(self.min, other.min);
+                    let max = Vec3::partial_min(self.max, other.max);
+                    if max.x < min.x || max.y < min.y || max.z < min.z {
+                        return None;
+                    }
+                    Some(Aabb { min, max })
+                }
+                fn bounding_union(self, other: Self) -> Self {
+                    Aabb {
+                        min: Vec3::partial_min(self.min, other.min),
+                        max: Vec3::partial_max(self.max, other.max),
+                    }
+                }
+                fn contains_whole_aabb(&self, other: &Self) -> bool {
+                    other.min.x >= self.min.x && other.min.y >= self.min.y && other.min.z >= self.min.z
+                        && other.max.x <= self.max.x && other.max.y <= self.max.y && other.max.z <= self.max.z
+                }
+            }
+        )+
+    };
+}
+
+impl_aabb_set_ops!{f32 f64}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aabb(min: (f32, f32, f32), max: (f32, f32, f32)) -> Aabb<f32> {
+        Aabb { min: Vec3::new(min.0, min.1, min.2), max: Vec3::new(max.0, max.1, max.2) }
+    }
+
+    #[test]
+    fn from_points() {
+        assert_eq!(Aabb::<f32>::from_points(vec![]), None);
+        let p = Vec3::new(1., 2., 3.);
+        assert_eq!(Aabb::from_points(vec![p]), Some(Aabb { min: p, max: p }));
+        let points = vec![Vec3::new(1., -2., 0.), Vec3::new(-1., 5., 2.), Vec3::new(0., 0., -4.)];
+        let bounds = Aabb::from_points(points).unwrap();
+        assert_eq!(bounds, aabb((-1., -2., -4.), (1., 5., 2.)));
+        assert_eq!(bounds.half_extents(), Vec3::new(1., 3.5, 3.));
+    }
+    #[test]
+    fn overlap_and_union() {
+        let a = aabb((0., 0., 0.), (4., 3., 2.));
+        let b = aabb((2., 1., 1.), (6., 5., 5.));
+        assert_eq!(a.overlap(b), Some(aabb((2., 1., 1.), (4., 3., 2.))));
+        assert_eq!(b.overlap(a), a.overlap(b));
+        assert_eq!(a.bounding_union(b), aabb((0., 0., 0.), (6., 5., 5.)));
+        // Touching boxes overlap as a flat box
+        let c = aabb((4., 0., 0.), (5., 1., 1.));
+        assert_eq!(a.overlap(c), Some(aabb((4., 0., 0.), (4., 1., 1.))));
+        let d = aabb((4.5, 0., 0.), (5., 1., 1.));
+        assert_eq!(a.overlap(d), None);
+    }
+    #[test]
+    fn nested_boxes() {
+        let outer = aabb((-10., -10., -10.), (10., 10., 10.));
+        let inner = aabb((-1., 0., 2.), (1., 3., 10.));
+        assert!(outer.contains_whole_aabb(&inner));
+        assert!(outer.contains_whole_aabb(&outer));
+        assert!(!inner.contains_whole_aabb(&outer));
+        assert_eq!(outer.overlap(inner), Some(inner));
+        assert_eq!(outer.bounding_union(inner), outer);
+    }
+}
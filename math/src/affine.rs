@@ -0,0 +1,98 @@
+use super::{Mat3, Mat4, Vec3};
+
+/// Inverses of affine matrices (i.e whose bottom row is `[0, 0, 0, 1]`), such as TRS transforms.
+///
+/// Only the 3x3 linear part is inverted, then the translation is transformed by it, which is cheaper
+/// and more numerically stable than a general 4x4 inverse.
+pub trait AffineInverse<T> {
+    /// Debug builds assert that the bottom row is `[0, 0, 0, 1]`.
+    fn inverted_affine(self) -> Self;
+    /// The inverse transpose of the 3x3 linear part, for transforming normals
+    /// (it keeps them perpendicular to surfaces under non-uniform scale).
+    fn normal_matrix(self) -> Mat3<T>;
+}
+
+trait AffineInverseRows: Sized {
+    fn affine_inverse_rows(m: Mat4<Self>) -> (Vec3<Self>, Vec3<Self>, Vec3<Self>);
+}
+
+macro_rules! impl_affine_inverse {
+    ($($T:ident)+) => {
+        $(
+            // Rows of the inverse of the 3x3 linear part, from the cofactors of its columns.
+            impl AffineInverseRows for $T {
+                fn affine_inverse_rows(m: Mat4<$T>) -> (Vec3<$T>, Vec3<$T>, Vec3<$T>) {
+                    let c = m.cols;
+                    let (a, b, c) = (Vec3::new(c.x.x, c.x.y, c.x.z), Vec3::new(c.y.x, c.y.y, c.y.z), Vec3::new(c.z.x, c.z.y, c.z.z));
+                    let (bc, ca, ab) = (b.cross(c), c.cross(a), a.cross(b));
+                    let det = a.dot(bc);
+                    (bc / det, ca / det, ab / det)
+                }
+            }
+            impl AffineInverse<$T> for Mat4<$T> {
+                fn inverted_affine(self) -> Self {
+                    debug_assert!({
+                        let c = self.cols;
+                        (c.x.w, c.y.w, c.z.w, c.w.w) == (0., 0., 0., 1.)
+                    }, "Not an affine matrix: {:?}", self);
+                    let (r0, r1, r2) = $T::affine_inverse_rows(self);
+                    let t = Vec3::new(self.cols.w.x, self.cols.w.y, self.cols.w.z);
+                    Mat4::new(
+                        r0.x, r0.y, r0.z, -r0.dot(t),
+                        r1.x, r1.y, r1.z, -r1.dot(t),
+                        r2.x, r2.y, r2.z, -r2.dot(t),
+                        0., 0., 0., 1.
+                    )
+                }
+                fn normal_matrix(self) -> Mat3<$T> {
+                    let (r0, r1, r2) = $T::affine_inverse_rows(self);
+                    Mat3::new(
+                        r0.x, r1.x, r2.x,
+                        r0.y, r1.y, r2.y,
+                        r0.z, r1.z, r2.z
+                    )
+                }
+            }
+        )+
+    };
+}
+
+impl_affine_inverse!{f32 f64}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Quaternion, Transform};
+
+    fn assert_approx_eq(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert!((x - y).abs() <= 1e-4, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn matches_the_general_inverse() {
+        let transforms = [
+            Transform { position: Vec3::new(1., -2., 30.), orientation: Quaternion::rotation_y(1.2), scale: Vec3::new(2., 0.5, 3.) },
+            Transform { position: Vec3::new(-5., 7., 0.25), orientation: Quaternion::rotation_3d(2.5, Vec3::new(1., 1., -1.).normalized()), scale: Vec3::broadcast(0.1) },
+            Transform { position: Vec3::zero(), orientation: Quaternion::identity(), scale: Vec3::one() },
+        ];
+        for &xform in transforms.iter() {
+            let m = Mat4::from(xform);
+            assert_approx_eq(&m.inverted_affine().into_col_array(), &m.inverted().into_col_array());
+            assert_approx_eq(&(m * m.inverted_affine()).into_col_array(), &Mat4::identity().into_col_array());
+            assert_approx_eq(&m.normal_matrix().into_col_array(), &Mat3::from(m.inverted().transposed()).into_col_array());
+        }
+    }
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let m = Mat4::<f32>::scaling_3d(Vec3::new(4., 1., 1.)) * Mat4::rotation_z(0.5);
+        let tangent = Vec3::new(1., 1., 0.);
+        let normal = Vec3::new(1., -1., 0.);
+        let t = Mat3::from(m) * tangent;
+        let n = m.normal_matrix() * normal;
+        assert!(t.dot(n).abs() <= 1e-5);
+    }
+}
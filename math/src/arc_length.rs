@@ -0,0 +1,131 @@
+use super::{QuadraticBezier2, QuadraticBezier3, CubicBezier2, CubicBezier3};
+
+/// Maps distances along a curve to the curve's parameter, so that points can be placed at regular intervals
+/// (moving along a Bezier curve at a constant `t` speed doesn't give a constant spatial speed).
+///
+/// The curve is approximated by straight segments, which slightly underestimates its length.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArcLengthLut<T> {
+    /// `(cumulative_length, t)` pairs, sorted by both, starting at `(0, 0)` and ending at `(length, t_end)`
+    /// (`t_end` is 1 for Bezier curves).
+    pub samples: Vec<(T, T)>,
+}
+
+/// Building `ArcLengthLut`s for curves, such as Bezier curves and Catmull-Rom splines.
+pub trait BuildArcLengthLut<T> {
+    /// `steps` is the number of segments the curve is split into; the table holds up to `steps + 1` samples,
+    /// so more steps trade memory for accuracy.
+    fn build_arc_length_lut(&self, steps: usize) -> ArcLengthLut<T>;
+}
+
+macro_rules! impl_arc_length_lut {
+    ($($T:ident)+) => {
+        $(
+            impl ArcLengthLut<$T> {
+                /// Splits `[0, t_end]` into `steps` equal intervals; `segment_length(t0, t1)` is the distance
+                /// between the curve's points at `t0` and `t1`.
+                pub fn from_segment_lengths<F: FnMut($T, $T) -> $T>(steps: usize, t_end: $T, mut segment_length: F) -> Self {
+                    assert!(steps > 0);
+                    let mut samples = Vec::with_capacity(steps + 1);
+                    samples.push((0., 0.));
+                    let (mut length, mut prev_t) = (0., 0.);
+                    for i in 1 ..= steps {
+                        let t = t_end * i as $T / steps as $T;
+                        length += segment_length(prev_t, t);
+                        prev_t = t;
+                        // Zero-length segments would make t_at_distance() divide by zero
+                        if length > samples.last().unwrap().0 {
+                            samples.push((length, t));
+                        }
+                    }
+                    if samples.len() == 1 {
+                        samples.push((0., t_end)); // The curve is a single point
+                    } else {
+                        samples.last_mut().unwrap().1 = t_end; // Trailing zero-length segments
+                    }
+                    Self { samples }
+                }
+                pub fn length(&self) -> $T {
+                    self.samples.last().unwrap().0
+                }
+                /// `distance` is clamped to `[0, length()]`.
+                pub fn t_at_distance(&self, distance: $T) -> $T {
+                    if self.length() == 0. {
+                        return 0.;
+                    }
+                    let distance = distance.max(0.).min(self.length());
+                    let i = match self.samples.binary_search_by(|s| s.0.partial_cmp(&distance).unwrap()) {
+                        Ok(i) => return self.samples[i].1,
+                        Err(i) => i,
+                    };
+                    // 0 < i < len, because distance is clamped and the first sample has a length of 0
+                    let ((l0, t0), (l1, t1)) = (self.samples[i - 1], self.samples[i]);
+                    t0 + (t1 - t0) * (distance - l0) / (l1 - l0)
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! impl_build_arc_length_lut {
+    ($($T:ident)+) => {
+        $(
+            impl_build_arc_length_lut!{@curves $T QuadraticBezier2 QuadraticBezier3 CubicBezier2 CubicBezier3}
+        )+
+    };
+    (@curves $T:ident $($Curve:ident)+) => {
+        $(
+            impl BuildArcLengthLut<$T> for $Curve<$T> {
+                fn build_arc_length_lut(&self, steps: usize) -> ArcLengthLut<$T> {
+                    ArcLengthLut::<$T>::from_segment_lengths(steps, 1., |t0, t1| self.evaluate(t0).distance(self.evaluate(t1)))
+                }
+            }
+        )+
+    };
+}
+
+impl_arc_length_lut!{f32 f64}
+impl_build_arc_length_lut!{f32 f64}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Vec2;
+
+    fn assert_approx_eq(a: f32, b: f32, epsilon: f32) {
+        assert!((a - b).abs() <= epsilon, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn equal_distances_along_a_straight_but_uneven_curve() {
+        // x(t) = 3t^3, which is very slow at first
+        let curve = CubicBezier2 { start: Vec2::new(0_f32, 0.), ctrl0: Vec2::zero(), ctrl1: Vec2::zero(), end: Vec2::new(3., 0.) };
+        let lut = curve.build_arc_length_lut(1000);
+        assert_approx_eq(lut.length(), 3., 1e-4);
+        assert_eq!(lut.t_at_distance(0.), 0.);
+        assert_eq!(lut.t_at_distance(3.), 1.);
+        assert_eq!(lut.t_at_distance(10.), 1.);
+        for d in [0.5, 1., 2., 2.9].iter() {
+            assert_approx_eq(curve.evaluate(lut.t_at_distance(*d)).x, *d, 1e-3);
+        }
+    }
+    #[test]
+    fn quadratic_lut_is_monotonic() {
+        let curve = QuadraticBezier2 { start: Vec2::new(0_f32, 0.), ctrl: Vec2::new(2., 4.), end: Vec2::new(4., 0.) };
+        let coarse = curve.build_arc_length_lut(4);
+        let fine = curve.build_arc_length_lut(256);
+        assert_eq!(coarse.samples.len(), 5);
+        assert!(coarse.length() < fine.length());
+        assert!(fine.samples.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+        // Symmetric curve
+        assert_approx_eq(fine.t_at_distance(fine.length() / 2.), 0.5, 1e-4);
+    }
+    #[test]
+    fn degenerate_curve() {
+        let p = Vec2::new(1_f32, 2.);
+        let lut = QuadraticBezier2 { start: p, ctrl: p, end: p }.build_arc_length_lut(8);
+        assert_eq!(lut.length(), 0.);
+        assert_eq!(lut.t_at_distance(1.), 0.);
+    }
+}
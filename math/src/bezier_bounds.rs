@@ -0,0 +1,160 @@
+use super::{Vec2, Vec3, Aabr, Aabb, QuadraticBezier2, QuadraticBezier3, CubicBezier2, CubicBezier3};
+
+/// Tight bounds of Bezier curves.
+///
+/// The extrema on each axis are found by solving for the roots of the curve's derivative in [0, 1],
+/// rather than by sampling, and then the endpoints are folded in.
+pub trait BezierBounds {
+    type Bounds;
+    fn bounds(&self) -> Self::Bounds;
+}
+
+// Per-axis extrema, as (min, max).
+trait BezierExtrema: Sized {
+    fn quadratic_extrema(p0: Self, p1: Self, p2: Self) -> (Self, Self);
+    fn cubic_extrema(p0: Self, p1: Self, p2: Self, p3: Self) -> (Self, Self);
+}
+
+macro_rules! impl_bezier_bounds {
+    ($($T:ident)+) => {
+        $(
+            impl BezierExtrema for $T {
+                fn quadratic_extrema(p0: $T, p1: $T, p2: $T) -> ($T, $T) {
+                    let eval = |t: $T| {
+                        let u = 1. - t;
+                        u*u*p0 + 2.*u*t*p1 + t*t*p2
+                    };
+                    let (mut min, mut max) = (p0.min(p2), p0.max(p2));
+                    // B'(t)/2 = (p1 - p0) + t*(p0 - 2*p1 + p2)
+                    let a = p0 - 2.*p1 + p2;
+                    if a != 0. {
+                        let t = (p0 - p1) / a;
+                        if t > 0. && t < 1. {
+                            let p = eval(t);
+                            min = min.min(p);
+                            max = max.max(p);
+                        }
+                    }
+                    (min, max)
+                }
+                fn cubic_extrema(p0: $T, p1: $T, p2: $T, p3: $T) -> ($T, $T) {
+                    let eval = |t: $T| {
+                        let u = 1. - t;
+                        u*u*u*p0 + 3.*u*u*t*p1 + 3.*u*t*t*p2 + t*t*t*p3
+                    };
+                    let (mut min, mut max) = (p0.min(p3), p0.max(p3));
+                    {
+                        let mut fold = |t: $T| if t > 0. && t < 1. {
+                            let p = eval(t);
+                            min = min.min(p);
+                            max = max.max(p);
+                        };
+                        // B'(t)/3 = a*t^2 + b*t + c
+                        let a = -p0 + 3.*p1 - 3.*p2 + p3;
+                        let b = 2. * (p0 - 2.*p1 + p2);
+                        let c = p1 - p0;
+                        if a.abs() <= ::std::$T::EPSILON {
+                            // The derivative is linear (or constant)
+                            if b != 0. {
+                                fold(-c / b);
+                            }
+                        } else {
+                            let discriminant = b*b - 4.*a*c;
+                            if discriminant >= 0. {
+                                let sqrt_d = discriminant.sqrt();
+                                fold((-b + sqrt_d) / (2. * a));
+                                fold((-b - sqrt_d) / (2. * a));
+                            }
+                        }
+                    }
+                    (min, max)
+                }
+            }
+
+            impl BezierBounds for QuadraticBezier2<$T> {
+                type Bounds = Aabr<$T>;
+                fn bounds(&self) -> Aabr<$T> {
+                    let x = $T::quadratic_extrema(self.start.x, self.ctrl.x, self.end.x);
+                    let y = $T::quadratic_extrema(self.start.y, self.ctrl.y, self.end.y);
+                    Aabr { min: Vec2::new(x.0, y.0), max: Vec2::new(x.1, y.1) }
+                }
+            }
+            impl BezierBounds for QuadraticBezier3<$T> {
+                type Bounds = Aabb<$T>;
+                fn bounds(&self) -> Aabb<$T> {
+                    let x = $T::quadratic_extrema(self.start.x, self.ctrl.x, self.end.x);
+                    let y = $T::quadratic_extrema(self.start.y, self.ctrl.y, self.end.y);
+                    let z = $T::quadratic_extrema(self.start.z, self.ctrl.z, self.end.z);
+                    Aabb { min: Vec3::new(x.0, y.0, z.0), max: Vec3::new(x.1, y.1, z.1) }
+                }
+            }
+            impl BezierBounds for CubicBezier2<$T> {
+                type Bounds = Aabr<$T>;
+                fn bounds(&self) -> Aabr<$T> {
+                    let x = $T::cubic_extrema(self.start.x, self.ctrl0.x, self.ctrl1.x, self.end.x);
+                    let y = $T::cubic_extrema(self.start.y, self.ctrl0.y, self.ctrl1.y, self.end.y);
+                    Aabr { min: Vec2::new(x.0, y.0), max: Vec2::new(x.1, y.1) }
+                }
+            }
+            impl BezierBounds for CubicBezier3<$T> {
+                type Bounds = Aabb<$T>;
+                fn bounds(&self) -> Aabb<$T> {
+                    let x = $T::cubic_extrema(self.start.x, self.ctrl0.x, self.ctrl1.x, self.end.x);
+                    let y = $T::cubic_extrema(self.start.y, self.ctrl0.y, self.ctrl1.y, self.end.y);
+                    let z = $T::cubic_extrema(self.start.z, self.ctrl0.z, self.ctrl1.z, self.end.z);
+                    Aabb { min: Vec3::new(x.0, y.0, z.0), max: Vec3::new(x.1, y.1, z.1) }
+                }
+            }
+        )+
+    };
+}
+
+impl_bezier_bounds!{f32 f64}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: f32, b: f32) {
+        assert!((a - b).abs() <= 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn quadratic_bounds_include_the_apex() {
+        // y(t) = 4t(1-t), which peaks at 1 for t = 0.5
+        let curve = QuadraticBezier2 { start: Vec2::new(0_f32, 0.), ctrl: Vec2::new(1., 2.), end: Vec2::new(2., 0.) };
+        let Aabr { min, max } = curve.bounds();
+        assert_eq!(min, Vec2::new(0., 0.));
+        assert_approx_eq(max.x, 2.);
+        assert_approx_eq(max.y, 1.);
+    }
+    #[test]
+    fn cubic_bounds() {
+        // x(t) = 3t^2 - 2t^3 is monotonic, and its derivative's roots are the endpoints.
+        // y(t) = 3t(1-t) peaks at 0.75 for t = 0.5; its derivative is linear (a == 0).
+        let curve = CubicBezier2 { start: Vec2::new(0_f32, 0.), ctrl0: Vec2::new(0., 1.), ctrl1: Vec2::new(1., 1.), end: Vec2::new(1., 0.) };
+        let Aabr { min, max } = curve.bounds();
+        assert_eq!(min, Vec2::new(0., 0.));
+        assert_approx_eq(max.x, 1.);
+        assert_approx_eq(max.y, 0.75);
+
+        // An S-curve overshoots on both sides: z(t) = 6t(1-t)(1-2t) reaches +-1/sqrt(3) at t = (3 -+ sqrt(3)) / 6
+        let curve = CubicBezier3 {
+            start: Vec3::new(0_f32, 0., 0.), ctrl0: Vec3::new(1., 0., 2.), ctrl1: Vec3::new(2., 0., -2.), end: Vec3::new(3., 0., 0.),
+        };
+        let Aabb { min, max } = curve.bounds();
+        assert_approx_eq(min.x, 0.);
+        assert_approx_eq(max.x, 3.);
+        assert_eq!((min.y, max.y), (0., 0.));
+        assert_approx_eq(max.z, 1. / 3_f32.sqrt());
+        assert_approx_eq(min.z, -1. / 3_f32.sqrt());
+    }
+    #[test]
+    fn straight_curves_are_bounded_by_their_endpoints() {
+        let curve = CubicBezier2 { start: Vec2::new(3_f64, -1.), ctrl0: Vec2::new(2., 0.), ctrl1: Vec2::new(1., 1.), end: Vec2::new(0., 2.) };
+        assert_eq!(curve.bounds(), Aabr { min: Vec2::new(0., -1.), max: Vec2::new(3., 2.) });
+        let curve = QuadraticBezier3 { start: Vec3::new(1_f64, 1., 1.), ctrl: Vec3::new(1., 1., 1.), end: Vec3::new(1., 1., 1.) };
+        assert_eq!(curve.bounds(), Aabb { min: Vec3::one(), max: Vec3::one() });
+    }
+}
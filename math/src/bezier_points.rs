@@ -0,0 +1,120 @@
+use std::iter::{Chain, Once, once};
+use vek::bezier::{repr_c as c, repr_simd as simd};
+
+// `IntoIterator` can't be implemented here: both the trait and the curves are foreign types.
+// `into_points()` is the next best thing.
+
+/// Iterates over the 3 points of a quadratic curve: `start`, `ctrl` and `end`.
+pub type QuadraticBezierPointsIter<P> = Chain<Chain<Once<P>, Once<P>>, Once<P>>;
+/// Iterates over the 4 points of a cubic curve: `start`, `ctrl0`, `ctrl1` and `end`.
+pub type CubicBezierPointsIter<P> = Chain<QuadraticBezierPointsIter<P>, Once<P>>;
+
+/// Access to all points of a quadratic Bezier curve at once, in order.
+///
+/// This is useful for concisely applying the same transform to all of them.
+pub trait QuadraticBezierPoints<P>: Sized {
+    fn into_points(self) -> QuadraticBezierPointsIter<P>;
+    fn iter(&self) -> QuadraticBezierPointsIter<&P>;
+    fn iter_mut(&mut self) -> QuadraticBezierPointsIter<&mut P>;
+    fn map_points<F: FnMut(P) -> P>(self, f: F) -> Self;
+}
+
+/// Access to all points of a cubic Bezier curve at once, in order.
+///
+/// This is useful for concisely applying the same transform to all of them.
+pub trait CubicBezierPoints<P>: Sized {
+    fn into_points(self) -> CubicBezierPointsIter<P>;
+    fn iter(&self) -> CubicBezierPointsIter<&P>;
+    fn iter_mut(&mut self) -> CubicBezierPointsIter<&mut P>;
+    fn map_points<F: FnMut(P) -> P>(self, f: F) -> Self;
+}
+
+macro_rules! impl_bezier_points {
+    ($($repr:ident::{$Quadratic:ident, $Cubic:ident}($Vec:ident))+) => {
+        $(
+            impl<T> QuadraticBezierPoints<$repr::$Vec<T>> for $repr::$Quadratic<T> {
+                fn into_points(self) -> QuadraticBezierPointsIter<$repr::$Vec<T>> {
+                    let $repr::$Quadratic { start, ctrl, end } = self;
+                    once(start).chain(once(ctrl)).chain(once(end))
+                }
+                fn iter(&self) -> QuadraticBezierPointsIter<&$repr::$Vec<T>> {
+                    let $repr::$Quadratic { ref start, ref ctrl, ref end } = *self;
+                    once(start).chain(once(ctrl)).chain(once(end))
+                }
+                fn iter_mut(&mut self) -> QuadraticBezierPointsIter<&mut $repr::$Vec<T>> {
+                    let $repr::$Quadratic { ref mut start, ref mut ctrl, ref mut end } = *self;
+                    once(start).chain(once(ctrl)).chain(once(end))
+                }
+                fn map_points<F: FnMut($repr::$Vec<T>) -> $repr::$Vec<T>>(self, mut f: F) -> Self {
+                    let $repr::$Quadratic { start, ctrl, end } = self;
+                    $repr::$Quadratic { start: f(start), ctrl: f(ctrl), end: f(end) }
+                }
+            }
+            impl<T> CubicBezierPoints<$repr::$Vec<T>> for $repr::$Cubic<T> {
+                fn into_points(self) -> CubicBezierPointsIter<$repr::$Vec<T>> {
+                    let $repr::$Cubic { start, ctrl0, ctrl1, end } = self;
+                    once(start).chain(once(ctrl0)).chain(once(ctrl1)).chain(once(end))
+                }
+                fn iter(&self) -> CubicBezierPointsIter<&$repr::$Vec<T>> {
+                    let $repr::$Cubic { ref start, ref ctrl0, ref ctrl1, ref end } = *self;
+                    once(start).chain(once(ctrl0)).chain(once(ctrl1)).chain(once(end))
+                }
+                fn iter_mut(&mut self) -> CubicBezierPointsIter<&mut $repr::$Vec<T>> {
+                    let $repr::$Cubic { ref mut start, ref mut ctrl0, ref mut ctrl1, ref mut end } = *self;
+                    once(start).chain(once(ctrl0)).chain(once(ctrl1)).chain(once(end))
+                }
+                fn map_points<F: FnMut($repr::$Vec<T>) -> $repr::$Vec<T>>(self, mut f: F) -> Self {
+                    let $repr::$Cubic { start, ctrl0, ctrl1, end } = self;
+                    $repr::$Cubic { start: f(start), ctrl0: f(ctrl0), ctrl1: f(ctrl1), end: f(end) }
+                }
+            }
+        )+
+    };
+}
+
+impl_bezier_points!{
+    c::{QuadraticBezier2, CubicBezier2}(Vec2)
+    c::{QuadraticBezier3, CubicBezier3}(Vec3)
+    simd::{QuadraticBezier2, CubicBezier2}(Vec2)
+    simd::{QuadraticBezier3, CubicBezier3}(Vec3)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Vec3, Vec4, Mat4};
+
+    fn assert_approx_eq(a: Vec3<f32>, b: Vec3<f32>) {
+        assert!((a - b).magnitude() <= 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn translating_a_curve_translates_its_points() {
+        let curve = c::CubicBezier3 {
+            start: Vec3::new(0_f32, 0., 0.), ctrl0: Vec3::new(1., 2., 0.), ctrl1: Vec3::new(2., -2., 1.), end: Vec3::new(3., 0., 0.),
+        };
+        let offset = Vec3::new(10., -5., 2.);
+        let m = Mat4::translation_3d(offset);
+        let moved = curve.map_points(|p| Vec3::from(m * Vec4::from_point(p)));
+        for &t in &[0_f32, 0.25, 0.5, 0.8, 1.] {
+            assert_approx_eq(moved.evaluate(t), curve.evaluate(t) + offset);
+        }
+
+        let mut moved = curve;
+        for p in moved.iter_mut() {
+            *p += offset;
+        }
+        assert_eq!(moved.iter().count(), 4);
+        for (a, b) in moved.into_points().zip(curve.into_points()) {
+            assert_eq!(a, b + offset);
+        }
+    }
+    #[test]
+    fn quadratic_points_are_in_order() {
+        let curve = simd::QuadraticBezier2 { start: simd::Vec2::new(1, 2), ctrl: simd::Vec2::new(3, 4), end: simd::Vec2::new(5, 6) };
+        let xs: Vec<_> = curve.iter().map(|p| p.x).collect();
+        assert_eq!(xs, vec![1, 3, 5]);
+        assert_eq!(curve.map_points(|p| p * 2).end, simd::Vec2::new(10, 12));
+    }
+}
@@ -0,0 +1,221 @@
+//! Catmull-Rom spline segments, which pass through their control points, unlike Bezier curves,
+//! and splines made of consecutive segments.
+//!
+//! Like `vek`'s curves, they come in `repr_c` and `repr_simd` flavors; `repr_c` is re-exported at the crate root.
+
+macro_rules! catmull_rom_types {
+    ($($CatmullRom:ident $CatmullRomSpline:ident $CubicBezier:ident $Vec:ident)+) => {
+        $(
+            /// A segment going from `p1` to `p2`; `p0` and `p3` only shape the tangents at its ends.
+            ///
+            /// `alpha` selects the parameterization: 0 is uniform, 0.5 is centripetal and 1 is chordal.
+            /// Centripetal segments never form cusps or self-intersections, even on sharp turns.
+            #[derive(Debug, Copy, Clone, PartialEq)]
+            pub struct $CatmullRom<T> {
+                pub p0: $Vec<T>,
+                pub p1: $Vec<T>,
+                pub p2: $Vec<T>,
+                pub p3: $Vec<T>,
+                pub alpha: T,
+            }
+            /// A chain of segments that passes through all of its `points`, e.g for camera paths.
+            ///
+            /// `t` goes from 0 at the first point to `nb_segments()` at the last one, each segment spanning 1.
+            /// At both ends, tangents are shaped as if the first and last points were repeated.
+            #[derive(Debug, Clone, PartialEq)]
+            pub struct $CatmullRomSpline<T> {
+                pub points: Vec<$Vec<T>>,
+                /// Same as for segments.
+                pub alpha: T,
+            }
+            impl_catmull_rom!{$CatmullRom $CatmullRomSpline $CubicBezier $Vec: f32 f64}
+        )+
+    };
+}
+
+macro_rules! impl_catmull_rom {
+    ($CatmullRom:ident $CatmullRomSpline:ident $CubicBezier:ident $Vec:ident: $($T:ident)+) => {
+        $(
+            impl $CatmullRom<$T> {
+                pub fn uniform(p0: $Vec<$T>, p1: $Vec<$T>, p2: $Vec<$T>, p3: $Vec<$T>) -> Self {
+                    Self { p0, p1, p2, p3, alpha: 0. }
+                }
+                pub fn centripetal(p0: $Vec<$T>, p1: $Vec<$T>, p2: $Vec<$T>, p3: $Vec<$T>) -> Self {
+                    Self { p0, p1, p2, p3, alpha: 0.5 }
+                }
+                /// The cubic Bezier curve that traces the same segment, for the same `t`.
+                pub fn to_cubic_bezier(self) -> $CubicBezier<$T> {
+                    // See "Parameterization and Applications of Catmull-Rom Curves", Yuksel et al.
+                    let Self { p0, p1, p2, p3, alpha } = self;
+                    let d1 = (p1 - p0).magnitude().powf(alpha);
+                    let d2 = (p2 - p1).magnitude().powf(alpha);
+                    let d3 = (p3 - p2).magnitude().powf(alpha);
+                    // A control point that coincides with its neighbour gives no direction to follow.
+                    let epsilon = ::std::$T::EPSILON;
+                    let ctrl0 = if d1 <= epsilon { p1 } else {
+                        (p2 * (d1*d1) - p0 * (d2*d2) + p1 * (2.*d1*d1 + 3.*d1*d2 + d2*d2)) / (3. * d1 * (d1 + d2))
+                    };
+                    let ctrl1 = if d3 <= epsilon { p2 } else {
+                        (p1 * (d3*d3) - p3 * (d2*d2) + p2 * (2.*d3*d3 + 3.*d3*d2 + d2*d2)) / (3. * d3 * (d3 + d2))
+                    };
+                    $CubicBezier { start: p1, ctrl0, ctrl1, end: p2 }
+                }
+                /// `t` goes from 0 (at `p1`) to 1 (at `p2`).
+                pub fn evaluate(self, t: $T) -> $Vec<$T> {
+                    self.to_cubic_bezier().evaluate(t)
+                }
+                pub fn evaluate_derivative(self, t: $T) -> $Vec<$T> {
+                    let $CubicBezier { start, ctrl0, ctrl1, end } = self.to_cubic_bezier();
+                    let u = 1. - t;
+                    ((ctrl0 - start) * (u*u) + (ctrl1 - ctrl0) * (2.*u*t) + (end - ctrl1) * (t*t)) * 3.
+                }
+            }
+            impl $CatmullRomSpline<$T> {
+                pub fn new(points: Vec<$Vec<$T>>, alpha: $T) -> Self {
+                    assert!(points.len() >= 2, "A spline needs at least two points");
+                    Self { points, alpha }
+                }
+                pub fn nb_segments(&self) -> usize {
+                    self.points.len() - 1
+                }
+                /// The segment going from `points[i]` to `points[i + 1]`.
+                pub fn segment(&self, i: usize) -> $CatmullRom<$T> {
+                    assert!(i < self.nb_segments());
+                    let last = self.points.len() - 1;
+                    let p = |j: usize| self.points[j.min(last)];
+                    $CatmullRom { p0: p(i.saturating_sub(1)), p1: p(i), p2: p(i + 1), p3: p(i + 2), alpha: self.alpha }
+                }
+                // Returns the segment and the `t` within it; `t` is clamped to the spline.
+                fn locate(&self, t: $T) -> (usize, $T) {
+                    let n = self.nb_segments();
+                    let t = t.max(0.).min(n as $T);
+                    let i = (t.floor() as usize).min(n - 1);
+                    (i, t - i as $T)
+                }
+                pub fn evaluate(&self, t: $T) -> $Vec<$T> {
+                    let (i, t) = self.locate(t);
+                    self.segment(i).evaluate(t)
+                }
+                /// Since each segment spans 1, this is also the derivative with respect to the spline's `t`.
+                pub fn evaluate_derivative(&self, t: $T) -> $Vec<$T> {
+                    let (i, t) = self.locate(t);
+                    self.segment(i).evaluate_derivative(t)
+                }
+                /// Approximates each segment with `steps_per_segment` straight segments.
+                pub fn approx_length(&self, steps_per_segment: usize) -> $T {
+                    assert!(steps_per_segment > 0);
+                    let mut length = 0.;
+                    for i in 0 .. self.nb_segments() {
+                        let curve = self.segment(i).to_cubic_bezier();
+                        let mut prev = curve.start;
+                        for step in 1 ..= steps_per_segment {
+                            let p = curve.evaluate(step as $T / steps_per_segment as $T);
+                            length += prev.distance(p);
+                            prev = p;
+                        }
+                    }
+                    length
+                }
+            }
+            /// `t` in the table is the spline's `t`, from 0 to `nb_segments()`.
+            impl BuildArcLengthLut<$T> for $CatmullRomSpline<$T> {
+                fn build_arc_length_lut(&self, steps: usize) -> ArcLengthLut<$T> {
+                    ArcLengthLut::<$T>::from_segment_lengths(steps, self.nb_segments() as $T, |t0, t1| self.evaluate(t0).distance(self.evaluate(t1)))
+                }
+            }
+        )+
+    };
+}
+
+pub mod repr_c {
+    use vek::vec::repr_c::{Vec2, Vec3};
+    use vek::bezier::repr_c::{CubicBezier2, CubicBezier3};
+    use arc_length::{ArcLengthLut, BuildArcLengthLut};
+
+    catmull_rom_types!{
+        CatmullRom2 CatmullRomSpline2 CubicBezier2 Vec2
+        CatmullRom3 CatmullRomSpline3 CubicBezier3 Vec3
+    }
+}
+
+pub mod repr_simd {
+    use vek::vec::repr_simd::{Vec2, Vec3};
+    use vek::bezier::repr_simd::{CubicBezier2, CubicBezier3};
+    use arc_length::{ArcLengthLut, BuildArcLengthLut};
+
+    catmull_rom_types!{
+        CatmullRom2 CatmullRomSpline2 CubicBezier2 Vec2
+        CatmullRom3 CatmullRomSpline3 CubicBezier3 Vec3
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::repr_c::*;
+    use vek::vec::repr_c::{Vec2, Vec3};
+    use arc_length::BuildArcLengthLut;
+
+    fn assert_approx_eq(a: Vec3<f32>, b: Vec3<f32>) {
+        assert!((a - b).magnitude() <= 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn segments_interpolate_their_inner_points() {
+        let (p0, p1, p2, p3) = (Vec3::new(0., 0., 0.), Vec3::new(1., 2., 0.), Vec3::new(3., 2., 1.), Vec3::new(4., -1., 1.));
+        for curve in [CatmullRom3::<f32>::uniform(p0, p1, p2, p3), CatmullRom3::<f32>::centripetal(p0, p1, p2, p3)].iter() {
+            assert_eq!(curve.evaluate(0.), p1);
+            assert_eq!(curve.evaluate(1.), p2);
+        }
+        // Uniform tangents are half the vector between the neighbours
+        let curve = CatmullRom3::<f32>::uniform(p0, p1, p2, p3);
+        assert_approx_eq(curve.evaluate_derivative(0.), (p2 - p0) / 2.);
+        assert_approx_eq(curve.evaluate_derivative(1.), (p3 - p1) / 2.);
+    }
+    #[test]
+    fn collinear_points_stay_collinear() {
+        let dir = Vec3::new(1_f32, 2., 3.).normalized();
+        let curve = CatmullRom3::<f32>::centripetal(dir * -1., dir * 0.5, dir * 4., dir * 4.2);
+        for i in 0..11 {
+            let p = curve.evaluate(i as f32 / 10.);
+            assert!(p.cross(dir).magnitude() <= 1e-5, "{:?} is off the line", p);
+        }
+    }
+    #[test]
+    fn coincident_points_dont_produce_nans() {
+        let p = Vec2::new(1_f32, 1.);
+        let curve = CatmullRom2::<f32>::centripetal(p, p, Vec2::new(2., 3.), Vec2::new(2., 3.));
+        for i in 0..11 {
+            let t = i as f32 / 10.;
+            for v in [curve.evaluate(t), curve.evaluate_derivative(t)].iter() {
+                assert!(v.x.is_finite() && v.y.is_finite(), "{:?}", v);
+            }
+        }
+    }
+    #[test]
+    fn splines_pass_through_all_points() {
+        let points = vec![Vec2::new(0_f32, 0.), Vec2::new(1., 1.), Vec2::new(3., 1.), Vec2::new(4., 0.)];
+        let spline = CatmullRomSpline2::<f32>::new(points.clone(), 0.5);
+        assert_eq!(spline.nb_segments(), 3);
+        for (i, p) in points.iter().enumerate() {
+            assert_eq!(spline.evaluate(i as f32), *p);
+        }
+        // Clamped
+        assert_eq!(spline.evaluate(-1.), points[0]);
+        assert_eq!(spline.evaluate(10.), points[3]);
+        // Segments join without kinks (the speed isn't continuous though, unless alpha is 0)
+        let (a, b) = (spline.segment(0).evaluate_derivative(1.).normalized(), spline.segment(1).evaluate_derivative(0.).normalized());
+        assert!((a - b).magnitude() <= 1e-4, "{:?} != {:?}", a, b);
+    }
+    #[test]
+    fn spline_length() {
+        // Evenly spaced collinear points make a straight line
+        let spline = CatmullRomSpline3::<f32>::new((0..5).map(|i| Vec3::new(i as f32, 0., 0.)).collect(), 0.);
+        assert!((spline.approx_length(16) - 4.).abs() <= 1e-4);
+        let lut = spline.build_arc_length_lut(256);
+        assert!((lut.length() - 4.).abs() <= 1e-4);
+        assert_eq!(lut.samples.last().unwrap().1, 4.);
+        let t = lut.t_at_distance(2.5);
+        assert_approx_eq(spline.evaluate(t), Vec3::new(2.5, 0., 0.));
+    }
+}
@@ -0,0 +1,72 @@
+use super::{Vec2, Vec3, Vec4, Rgb, Rgba};
+
+/// Component-wise clamping, each lane against the same lane of `min` and `max`.
+///
+/// When `min > max` for a lane, that lane is `min`; this doesn't panic.
+pub trait ClampLanes {
+    fn clamp_lanes(self, min: Self, max: Self) -> Self;
+}
+
+/// Clamps every lane of a float vector or color to [0, 1].
+pub trait Saturate {
+    fn saturate(self) -> Self;
+}
+
+fn clamp_lane<T: PartialOrd>(x: T, min: T, max: T) -> T {
+    let x = if x > max { max } else { x };
+    if x < min { min } else { x }
+}
+
+macro_rules! impl_clamp_lanes {
+    ($($Vec:ident { $($field:ident),+ })+) => {
+        $(
+            impl<T: PartialOrd> ClampLanes for $Vec<T> {
+                fn clamp_lanes(self, min: Self, max: Self) -> Self {
+                    $Vec { $($field: clamp_lane(self.$field, min.$field, max.$field)),+ }
+                }
+            }
+            impl_saturate!{$Vec f32 f64}
+        )+
+    };
+}
+macro_rules! impl_saturate {
+    ($Vec:ident $($T:ident)+) => {
+        $(
+            impl Saturate for $Vec<$T> {
+                fn saturate(self) -> Self {
+                    self.clamp_lanes($Vec::broadcast(0.), $Vec::broadcast(1.))
+                }
+            }
+        )+
+    };
+}
+
+impl_clamp_lanes!{
+    Vec2 { x, y }
+    Vec3 { x, y, z }
+    Vec4 { x, y, z, w }
+    Rgb { r, g, b }
+    Rgba { r, g, b, a }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lanes_are_clamped_independently() {
+        let v = Vec3::new(-1_f32, 0.5, 7.);
+        assert_eq!(v.clamp_lanes(Vec3::zero(), Vec3::new(1., 0.25, 10.)), Vec3::new(0., 0.25, 7.));
+        assert_eq!(Vec3::new(4, -3, 2).clamp_lanes(Vec3::broadcast(-2), Vec3::broadcast(3)), Vec3::new(3, -2, 2));
+        // min > max yields min
+        assert_eq!(v.clamp_lanes(Vec3::broadcast(2.), Vec3::broadcast(1.)), Vec3::broadcast(2.));
+    }
+    #[test]
+    fn colors_saturate() {
+        let c = Rgba::new(1.5_f32, -0.2, 0.3, 1.);
+        assert_eq!(c.saturate(), Rgba::new(1., 0., 0.3, 1.));
+        assert_eq!(Rgb::new(0.5_f64, 2., -1.).saturate(), Rgb::new(0.5, 1., 0.));
+        assert_eq!(Vec3::new(-1_f32, 0.5, 7.).saturate(), Vec3::new(0., 0.5, 1.));
+    }
+}
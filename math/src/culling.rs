@@ -0,0 +1,145 @@
+use super::{Mat4, Vec3, Vec4, Aabb};
+
+/// Where a volume is relative to a `CullingFrustum`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Containment {
+    Outside,
+    Intersecting,
+    Inside,
+}
+
+/// The six planes of a view frustum, extracted from a view-projection matrix (Gribb-Hartmann method).
+///
+/// Each plane is `(normal, d)`, normalized and facing inwards, so that a point `p` is on the inner side
+/// when `dot(normal, p) + d >= 0`.
+/// Planes at infinity (e.g the far plane of an infinite projection) let everything through.
+///
+/// The tests are conservative: a volume near a corner of the frustum may be reported as intersecting it
+/// while being actually outside.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CullingFrustum<T> {
+    /// Left, right, bottom, top, near, far.
+    pub planes: [Vec4<T>; 6],
+}
+
+macro_rules! impl_culling_frustum {
+    ($($T:ident)+) => {
+        $(
+            impl CullingFrustum<$T> {
+                /// For projections whose clip space depth is in [-1, 1] (GL's default), i.e the `_no` ones.
+                pub fn from_mat4_no(viewproj: Mat4<$T>) -> Self {
+                    let (r0, r1, r2, r3) = Self::rows(viewproj);
+                    Self::from_planes([r3 + r0, r3 - r0, r3 + r1, r3 - r1, r3 + r2, r3 - r2])
+                }
+                /// For projections whose clip space depth is in [0, 1], i.e the `_zo` and reversed-Z ones.
+                pub fn from_mat4_zo(viewproj: Mat4<$T>) -> Self {
+                    let (r0, r1, r2, r3) = Self::rows(viewproj);
+                    Self::from_planes([r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2])
+                }
+                fn rows(m: Mat4<$T>) -> (Vec4<$T>, Vec4<$T>, Vec4<$T>, Vec4<$T>) {
+                    let c = m.cols;
+                    (
+                        Vec4::new(c.x.x, c.y.x, c.z.x, c.w.x),
+                        Vec4::new(c.x.y, c.y.y, c.z.y, c.w.y),
+                        Vec4::new(c.x.z, c.y.z, c.z.z, c.w.z),
+                        Vec4::new(c.x.w, c.y.w, c.z.w, c.w.w),
+                    )
+                }
+                fn from_planes(mut planes: [Vec4<$T>; 6]) -> Self {
+                    for plane in planes.iter_mut() {
+                        let len = Vec3::from(*plane).magnitude();
+                        *plane = if len <= ::std::$T::EPSILON {
+                            Vec4::new(0., 0., 0., 1.)
+                        } else {
+                            *plane / len
+                        };
+                    }
+                    Self { planes }
+                }
+                fn distance(plane: Vec4<$T>, p: Vec3<$T>) -> $T {
+                    Vec3::from(plane).dot(p) + plane.w
+                }
+                pub fn contains_point(&self, p: Vec3<$T>) -> bool {
+                    self.planes.iter().all(|plane| Self::distance(*plane, p) >= 0.)
+                }
+                pub fn intersects_aabb(&self, aabb: &Aabb<$T>) -> Containment {
+                    let mut containment = Containment::Inside;
+                    for plane in self.planes.iter() {
+                        // The corners furthest along and against the plane's normal
+                        let select = |a: $T, b: $T, n: $T| if n >= 0. { a } else { b };
+                        let p = Vec3::new(select(aabb.max.x, aabb.min.x, plane.x), select(aabb.max.y, aabb.min.y, plane.y), select(aabb.max.z, aabb.min.z, plane.z));
+                        let n = Vec3::new(select(aabb.min.x, aabb.max.x, plane.x), select(aabb.min.y, aabb.max.y, plane.y), select(aabb.min.z, aabb.max.z, plane.z));
+                        if Self::distance(*plane, p) < 0. {
+                            return Containment::Outside;
+                        }
+                        if Self::distance(*plane, n) < 0. {
+                            containment = Containment::Intersecting;
+                        }
+                    }
+                    containment
+                }
+                pub fn intersects_sphere(&self, center: Vec3<$T>, radius: $T) -> Containment {
+                    let mut containment = Containment::Inside;
+                    for plane in self.planes.iter() {
+                        let d = Self::distance(*plane, center);
+                        if d < -radius {
+                            return Containment::Outside;
+                        }
+                        if d < radius {
+                            containment = Containment::Intersecting;
+                        }
+                    }
+                    containment
+                }
+            }
+        )+
+    };
+}
+
+impl_culling_frustum!{f32 f64}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{FrustumPlanes, ReversedZProjection};
+
+    // Looks down -Z, from z = -1 to z = -10, and x, y in [-2, 2]
+    fn ortho() -> CullingFrustum<f32> {
+        CullingFrustum::<f32>::from_mat4_no(Mat4::orthographic_rh_no(FrustumPlanes {
+            left: -2., right: 2., bottom: -2., top: 2., near: 1., far: 10.,
+        }))
+    }
+
+    #[test]
+    fn points_just_outside_a_plane_are_rejected() {
+        let f = ortho();
+        assert!(f.contains_point(Vec3::new(0., 0., -5.)));
+        assert!(f.contains_point(Vec3::new(1.999, -1.999, -9.999)));
+        assert!(!f.contains_point(Vec3::new(2.001, 0., -5.)));
+        assert!(!f.contains_point(Vec3::new(0., -2.001, -5.)));
+        assert!(!f.contains_point(Vec3::new(0., 0., -0.999)));
+        assert!(!f.contains_point(Vec3::new(0., 0., -10.001)));
+        // The planes are normalized, so distances are in world units
+        assert!((CullingFrustum::<f32>::distance(f.planes[0], Vec3::new(0., 0., -5.)) - 2.).abs() <= 1e-5);
+    }
+    #[test]
+    fn aabbs_and_spheres() {
+        let f = ortho();
+        let aabb = |min: Vec3<f32>, max: Vec3<f32>| Aabb { min, max };
+        assert_eq!(f.intersects_aabb(&aabb(Vec3::new(-1., -1., -6.), Vec3::new(1., 1., -4.))), Containment::Inside);
+        assert_eq!(f.intersects_aabb(&aabb(Vec3::new(1., -1., -6.), Vec3::new(3., 1., -4.))), Containment::Intersecting);
+        assert_eq!(f.intersects_aabb(&aabb(Vec3::new(2.5, -1., -6.), Vec3::new(3., 1., -4.))), Containment::Outside);
+        assert_eq!(f.intersects_sphere(Vec3::new(0., 0., -5.), 1.), Containment::Inside);
+        assert_eq!(f.intersects_sphere(Vec3::new(0., 2.5, -5.), 1.), Containment::Intersecting);
+        assert_eq!(f.intersects_sphere(Vec3::new(0., 3.5, -5.), 1.), Containment::Outside);
+    }
+    #[test]
+    fn infinite_reversed_z_far_plane_lets_everything_through() {
+        let f = CullingFrustum::<f32>::from_mat4_zo(Mat4::perspective_rh_reversed_z_infinite(1., 1., 0.1));
+        assert!(f.planes.iter().all(|p| p.x.is_finite() && p.y.is_finite() && p.z.is_finite() && p.w.is_finite()));
+        assert!(f.contains_point(Vec3::new(0., 0., -1e6)));
+        assert!(!f.contains_point(Vec3::new(0., 0., -0.05)));
+        assert!(!f.contains_point(Vec3::new(0., 0., 1.)));
+    }
+}
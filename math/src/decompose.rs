@@ -0,0 +1,126 @@
+use std::fmt::{self, Display, Formatter};
+use std::error::Error;
+use super::{Mat3, Mat4, Vec3, Quaternion, Transform, QuaternionFromMatrix};
+
+/// Recovers translation, rotation and scale from an affine matrix.
+///
+/// Translation is the last column, scale is the magnitudes of the first three columns, and orientation is
+/// what remains once they are normalized.
+/// When the determinant is negative (the matrix mirrors), the X scale is made negative.
+///
+/// Shear isn't recoverable: a sheared matrix yields the closest-looking TRS, which doesn't round-trip.
+/// Projection (the last row) is ignored.
+///
+/// Fails if an axis is scaled to zero (its orientation is lost), or if an element isn't finite.
+pub trait Mat4IntoTransform<T> {
+    fn into_transform(self) -> Result<Transform<T, T, T>, DecomposeError>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DecomposeError {
+    /// The index of the first column (0 for X, 1 for Y, 2 for Z) whose magnitude is zero.
+    ZeroScale(usize),
+    NotFinite,
+}
+
+impl Display for DecomposeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            DecomposeError::ZeroScale(axis) => write!(f, "The {} axis has zero scale", ["X", "Y", "Z"][axis]),
+            DecomposeError::NotFinite => write!(f, "The matrix has infinite or NaN elements"),
+        }
+    }
+}
+
+impl Error for DecomposeError {
+    fn description(&self) -> &str {
+        match *self {
+            DecomposeError::ZeroScale(_) => "zero scale",
+            DecomposeError::NotFinite => "non-finite matrix",
+        }
+    }
+}
+
+macro_rules! impl_mat4_into_transform {
+    ($($T:ident)+) => {
+        $(
+            impl Mat4IntoTransform<$T> for Mat4<$T> {
+                fn into_transform(self) -> Result<Transform<$T, $T, $T>, DecomposeError> {
+                    if !self.into_row_array().iter().all(|x| x.is_finite()) {
+                        return Err(DecomposeError::NotFinite);
+                    }
+                    let c = self.cols;
+                    let position = Vec3::new(c.w.x, c.w.y, c.w.z);
+                    let (x, y, z) = (Vec3::new(c.x.x, c.x.y, c.x.z), Vec3::new(c.y.x, c.y.y, c.y.z), Vec3::new(c.z.x, c.z.y, c.z.z));
+                    let mut scale = Vec3::new(x.magnitude(), y.magnitude(), z.magnitude());
+                    if let Some(axis) = scale.iter().position(|&s| s == 0.) {
+                        return Err(DecomposeError::ZeroScale(axis));
+                    }
+                    if x.cross(y).dot(z) < 0. {
+                        scale.x = -scale.x;
+                    }
+                    let (x, y, z) = (x / scale.x, y / scale.y, z / scale.z);
+                    let rotation = Mat3::new(
+                        x.x, y.x, z.x,
+                        x.y, y.y, z.y,
+                        x.z, y.z, z.z
+                    );
+                    Ok(Transform { position, orientation: Quaternion::from_mat3(rotation).normalized(), scale })
+                }
+            }
+        )+
+    };
+}
+
+impl_mat4_into_transform!{f32 f64}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Vec4;
+
+    fn assert_approx_eq(a: Vec3<f32>, b: Vec3<f32>) {
+        assert!((a - b).magnitude() <= 1e-4, "{:?} != {:?}", a, b);
+    }
+    fn assert_same_transform(a: Transform<f32, f32, f32>, b: Transform<f32, f32, f32>) {
+        assert_approx_eq(a.position, b.position);
+        assert_approx_eq(a.scale, b.scale);
+        for v in [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()].iter() {
+            assert_approx_eq(a.orientation * *v, b.orientation * *v);
+        }
+    }
+
+    #[test]
+    fn trs_round_trips() {
+        let transforms = [
+            Transform { position: Vec3::zero(), orientation: Quaternion::identity(), scale: Vec3::one() },
+            Transform { position: Vec3::new(1., -2., 30.), orientation: Quaternion::rotation_y(1.2), scale: Vec3::new(2., 0.5, 3.) },
+            Transform { position: Vec3::new(-5., 0., 0.), orientation: Quaternion::rotation_3d(2.5, Vec3::new(1., 1., -1.).normalized()), scale: Vec3::broadcast(0.1) },
+        ];
+        for &xform in transforms.iter() {
+            assert_same_transform(Mat4::from(xform).into_transform().unwrap(), xform);
+        }
+    }
+    #[test]
+    fn mirrored_matrices_get_a_negative_scale() {
+        let xform = Transform { position: Vec3::new(1., 2., 3.), orientation: Quaternion::rotation_x(0.7), scale: Vec3::new(1., -2., 1.) };
+        let m = Mat4::from(xform);
+        let back = m.into_transform().unwrap();
+        assert!(back.scale.x < 0.);
+        // The sign may move to another axis, but the matrix is the same
+        let m2 = Mat4::from(back);
+        for p in [Vec4::unit_x(), Vec4::unit_y(), Vec4::unit_z(), Vec4::new(1., 2., 3., 1.)].iter() {
+            assert!((m * *p - m2 * *p).magnitude() <= 1e-4);
+        }
+    }
+    #[test]
+    fn zero_scale_is_refused() {
+        let xform = Transform { position: Vec3::new(1., 2., 3.), orientation: Quaternion::rotation_x(0.7), scale: Vec3::new(1., 0., 1.) };
+        assert_eq!(Mat4::from(xform).into_transform().err(), Some(DecomposeError::ZeroScale(1)));
+        assert_eq!(Mat4::<f32>::zero().into_transform().err(), Some(DecomposeError::ZeroScale(0)));
+        let mut m = Mat4::<f32>::identity();
+        m.cols.w.x = ::std::f32::NAN;
+        assert_eq!(m.into_transform().err(), Some(DecomposeError::NotFinite));
+    }
+}
@@ -0,0 +1,133 @@
+use std::ops::{Mul, Sub};
+use super::{Vec2, Vec3, Vec4, Quaternion};
+
+// GLSL-like free functions, so that code ported from shaders reads the same.
+// They delegate to the methods, which remain the idiomatic way to do it in Rust.
+
+/// Vectors and quaternions, which have a dot product.
+pub trait GlslDot: Copy {
+    type Scalar;
+    fn glsl_dot(self, other: Self) -> Self::Scalar;
+    fn glsl_normalize(self) -> Self;
+}
+
+/// Vectors, which can be reflected and refracted.
+pub trait GlslVector: GlslDot {
+    fn glsl_reflect(self, n: Self) -> Self;
+    fn glsl_refract(self, n: Self, eta: Self::Scalar) -> Self;
+    fn glsl_face_forward(self, i: Self, nref: Self) -> Self;
+}
+
+macro_rules! impl_glsl {
+    ($($T:ident)+) => {
+        $(
+            impl GlslDot for Quaternion<$T> {
+                type Scalar = $T;
+                fn glsl_dot(self, other: Self) -> $T { self.dot(other) }
+                fn glsl_normalize(self) -> Self { self.normalized() }
+            }
+            impl_glsl_vector!{$T: Vec2 Vec3 Vec4}
+        )+
+    };
+}
+macro_rules! impl_glsl_vector {
+    ($T:ident: $($Vec:ident)+) => {
+        $(
+            impl GlslDot for $Vec<$T> {
+                type Scalar = $T;
+                fn glsl_dot(self, other: Self) -> $T { self.dot(other) }
+                fn glsl_normalize(self) -> Self { self.normalized() }
+            }
+            impl GlslVector for $Vec<$T> {
+                fn glsl_reflect(self, n: Self) -> Self {
+                    self - n * (2. * self.dot(n))
+                }
+                fn glsl_refract(self, n: Self, eta: $T) -> Self {
+                    let d = n.dot(self);
+                    let k = 1. - eta * eta * (1. - d * d);
+                    if k < 0. {
+                        return Self::zero();
+                    }
+                    self * eta - n * (eta * d + k.sqrt())
+                }
+                fn glsl_face_forward(self, i: Self, nref: Self) -> Self {
+                    if nref.dot(i) < 0. { self } else { -self }
+                }
+            }
+        )+
+    };
+}
+
+impl_glsl!{f32 f64}
+
+pub fn dot<V: GlslDot>(u: V, v: V) -> V::Scalar {
+    u.glsl_dot(v)
+}
+pub fn normalize<V: GlslDot>(v: V) -> V {
+    v.glsl_normalize()
+}
+pub fn cross<T: Copy + Mul<Output=T> + Sub<Output=T>>(u: Vec3<T>, v: Vec3<T>) -> Vec3<T> {
+    Vec3::new(u.y * v.z - u.z * v.y, u.z * v.x - u.x * v.z, u.x * v.y - u.y * v.x)
+}
+/// Reflects the incident vector `i` off the surface of normal `n`, i.e `i - 2*dot(i,n)*n`.
+/// `n` should be normalized.
+///
+/// ```
+/// # extern crate fate_math;
+/// # use fate_math::{Vec2, reflect};
+/// # fn main() {
+/// // Hitting the ground at 45°
+/// assert_eq!(reflect(Vec2::new(1_f32, -1.), Vec2::unit_y()), Vec2::new(1., 1.));
+/// # }
+/// ```
+pub fn reflect<V: GlslVector>(i: V, n: V) -> V {
+    i.glsl_reflect(n)
+}
+/// Refracts the incident vector `i` through the surface of normal `n`, given `eta`, the ratio of indices of refraction.
+/// `i` and `n` should be normalized. Returns zero on total internal reflection.
+pub fn refract<V: GlslVector>(i: V, n: V, eta: V::Scalar) -> V {
+    i.glsl_refract(n, eta)
+}
+/// Returns `n` if `dot(nref, i) < 0`, `-n` otherwise, i.e orients `n` so that it faces against `i`.
+pub fn face_forward<V: GlslVector>(n: V, i: V, nref: V) -> V {
+    n.glsl_face_forward(i, nref)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: Vec3<f32>, b: Vec3<f32>) {
+        assert!((a - b).magnitude() <= 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn free_functions_match_methods() {
+        let (u, v) = (Vec3::new(1_f32, 2., 3.), Vec3::new(-2., 0.5, 4.));
+        assert_eq!(dot(u, v), u.dot(v));
+        assert_eq!(cross(u, v), u.cross(v));
+        assert_eq!(normalize(u), u.normalized());
+        let q = Quaternion::<f32>::rotation_y(1.);
+        assert_eq!(dot(q, q), q.dot(q));
+    }
+    #[test]
+    fn refraction() {
+        let i = Vec3::new(1_f32, -1., 0.).normalized();
+        let n = Vec3::unit_y();
+        // Going straight through when indices are the same
+        assert_approx_eq(refract(i, n, 1.), i);
+        // Total internal reflection at 45° from glass to air
+        assert_eq!(refract(i, n, 1.5), Vec3::zero());
+        // Bent towards the normal from air to water
+        let r = refract(i, n, 1. / 1.33);
+        assert!(r.y < 0. && r.x > 0. && r.x < i.x);
+        assert!((r.magnitude() - 1.).abs() <= 1e-5);
+    }
+    #[test]
+    fn face_forward_faces_against_the_incident_vector() {
+        let n = Vec3::unit_y();
+        assert_eq!(face_forward(n, -Vec3::unit_y(), n), n);
+        assert_eq!(face_forward(n, Vec3::unit_y(), n), -n);
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::{self, Display, Formatter};
+use std::error::Error;
+use super::{Rgb, Rgba};
+
+/// Colors written as CSS-like hex strings, e.g `"#ffaf2d"`.
+///
+/// The leading `#` is optional, and digits may be upper or lower case.
+/// `Rgb` accepts `rgb` and `rrggbb`; `Rgba` also accepts `rgba` and `rrggbbaa`, and defaults alpha to `0xff`.
+/// A single digit `x` stands for `xx`, so `"#f00"` is `"#ff0000"`.
+pub trait HexColor: Sized {
+    fn from_hex_str(s: &str) -> Result<Self, ParseColorError>;
+    /// Lower case, with the leading `#`, and always 2 digits per channel.
+    fn to_hex_str(&self) -> String;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ParseColorError {
+    /// The number of digits, not counting the leading `#`.
+    WrongLength(usize),
+    InvalidDigit(char),
+}
+
+impl Display for ParseColorError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ParseColorError::WrongLength(len) => write!(f, "Expected 3, 4, 6 or 8 hex digits, got {}", len),
+            ParseColorError::InvalidDigit(c) => write!(f, "{:?} is not a hex digit", c),
+        }
+    }
+}
+
+impl Error for ParseColorError {
+    fn description(&self) -> &str {
+        match *self {
+            ParseColorError::WrongLength(_) => "wrong number of hex digits",
+            ParseColorError::InvalidDigit(_) => "invalid hex digit",
+        }
+    }
+}
+
+// Returns the channels in order; alpha is None if it wasn't given.
+fn parse_hex_channels(s: &str) -> Result<([u8; 3], Option<u8>), ParseColorError> {
+    let s = if s.starts_with('#') { &s[1..] } else { s };
+    let mut digits = [0_u8; 8];
+    let mut len = 0;
+    for c in s.chars() {
+        let d = c.to_digit(16).ok_or(ParseColorError::InvalidDigit(c))?;
+        if len < digits.len() {
+            digits[len] = d as u8;
+        }
+        len += 1;
+    }
+    let channel = |i: usize| match len {
+        3 | 4 => digits[i] * 0x11,
+        _ => digits[2*i] * 0x10 + digits[2*i + 1],
+    };
+    match len {
+        3 | 6 => Ok(([channel(0), channel(1), channel(2)], None)),
+        4 | 8 => Ok(([channel(0), channel(1), channel(2)], Some(channel(3)))),
+        _ => Err(ParseColorError::WrongLength(len)),
+    }
+}
+
+impl HexColor for Rgb<u8> {
+    fn from_hex_str(s: &str) -> Result<Self, ParseColorError> {
+        match parse_hex_channels(s)? {
+            ([r, g, b], None) => Ok(Rgb::new(r, g, b)),
+            (_, Some(_)) => Err(ParseColorError::WrongLength(if s.starts_with('#') { s.len() - 1 } else { s.len() })),
+        }
+    }
+    fn to_hex_str(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl HexColor for Rgba<u8> {
+    fn from_hex_str(s: &str) -> Result<Self, ParseColorError> {
+        let ([r, g, b], a) = parse_hex_channels(s)?;
+        Ok(Rgba::new(r, g, b, a.unwrap_or(0xff)))
+    }
+    fn to_hex_str(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_and_long_forms() {
+        assert_eq!(Rgb::from_hex_str("#f00"), Ok(Rgb::new(255, 0, 0)));
+        assert_eq!(Rgb::from_hex_str("ffaf2d"), Ok(Rgb::new(255, 175, 45)));
+        assert_eq!(Rgba::from_hex_str("#FFAF2D"), Ok(Rgba::new(255, 175, 45, 255)));
+        assert_eq!(Rgba::from_hex_str("#1234"), Ok(Rgba::new(0x11, 0x22, 0x33, 0x44)));
+        assert_eq!(Rgba::from_hex_str("#ffaf2d80"), Ok(Rgba::new(255, 175, 45, 128)));
+    }
+    #[test]
+    fn round_trips() {
+        assert_eq!(Rgb::new(255, 0, 0).to_hex_str(), "#ff0000");
+        for s in ["#ffaf2d", "#000000", "#0a0b0c"].iter() {
+            assert_eq!(Rgb::from_hex_str(s).unwrap().to_hex_str(), *s);
+        }
+        let c = Rgba::new(1, 2, 254, 255);
+        assert_eq!(Rgba::from_hex_str(&c.to_hex_str()), Ok(c));
+    }
+    #[test]
+    fn errors() {
+        assert_eq!(Rgb::from_hex_str("#ff00"), Err(ParseColorError::WrongLength(4)));
+        assert_eq!(Rgba::from_hex_str("#ff00f"), Err(ParseColorError::WrongLength(5)));
+        assert_eq!(Rgba::from_hex_str(""), Err(ParseColorError::WrongLength(0)));
+        assert_eq!(Rgba::from_hex_str("#ff00gg"), Err(ParseColorError::InvalidDigit('g')));
+        assert_eq!(Rgba::from_hex_str("##fff"), Err(ParseColorError::InvalidDigit('#')));
+    }
+}
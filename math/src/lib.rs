@@ -19,3 +19,85 @@ pub use vek::{
     geom::*,
 };
 
+mod rotation;
+pub use rotation::*;
+mod repr;
+pub use repr::*;
+mod projection;
+pub use projection::*;
+mod look_at;
+pub use look_at::*;
+mod swizzle;
+pub use swizzle::*;
+mod bezier_bounds;
+pub use bezier_bounds::*;
+mod bezier_points;
+pub use bezier_points::*;
+mod rect;
+pub use rect::*;
+mod glsl;
+pub use glsl::*;
+mod decompose;
+pub use decompose::*;
+mod clamp_lanes;
+pub use clamp_lanes::*;
+mod hex_color;
+pub use hex_color::*;
+mod srgb;
+pub use srgb::*;
+mod affine;
+pub use affine::*;
+pub mod catmull_rom;
+pub use catmull_rom::repr_c::*;
+mod culling;
+pub use culling::*;
+mod ray;
+pub use ray::*;
+mod aabb;
+pub use aabb::*;
+mod arc_length;
+pub use arc_length::*;
+
+
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn mat4_round_trip() {
+        let m = Mat4::<f32>::translation_3d(Vec3::new(1., 2., 3.)) * Mat4::scaling_3d(2.);
+        let json = serde_json::to_string(&m).unwrap();
+        assert_eq!(serde_json::from_str::<Mat4<f32>>(&json).unwrap(), m);
+    }
+    #[test]
+    fn quaternion_round_trip() {
+        let q = Quaternion::<f32>::rotation_y(1.5);
+        let json = serde_json::to_string(&q).unwrap();
+        assert_eq!(serde_json::from_str::<Quaternion<f32>>(&json).unwrap(), q);
+    }
+    #[test]
+    fn simd_form_is_the_same_as_repr_c() {
+        let v = Vec4::new(1_f32, 2., 3., 4.);
+        let json = serde_json::to_string(&v).unwrap();
+        let s = Simd4::new(1_f32, 2., 3., 4.);
+        assert_eq!(serde_json::to_string(&s).unwrap(), json);
+        assert_eq!(serde_json::from_str::<Simd4<f32>>(&json).unwrap(), s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_lerp_component_wise() {
+        let c = Rgba::lerp(Rgba::new(0_f32, 0.25, 1., 1.), Rgba::new(1., 0.75, 0., 0.), 0.5);
+        assert_eq!(c, Rgba::new(0.5, 0.5, 0.5, 0.5));
+        assert_eq!(Rgb::lerp(Rgb::<f32>::black(), Rgb::white(), 2.), Rgb::white());
+        assert_eq!(Rgb::lerp_unclamped(Rgb::<f32>::black(), Rgb::white(), 2.), Rgb::broadcast(2.));
+    }
+}
@@ -0,0 +1,171 @@
+use super::{Mat4, Vec3};
+
+/// "Look-at" matrices, which don't produce NaNs when `target - eye` is parallel to `up`.
+///
+/// In that case, another up axis is picked (Z, or X if the direction is close to Z), so the roll is arbitrary
+/// but the matrix stays valid.
+/// When `eye` and `target` are the same point, the view looks down the canonical forward axis instead
+/// (so the view matrix is a mere translation if `up` is `Vec3::up()`).
+///
+/// `lh` matches `Vec3::forward_lh()` (looking down +Z), and `rh` matches `Vec3::forward_rh()` (looking down -Z).
+/// In both cases, `Vec3::right()` and `Vec3::up()` are the right and up directions of the view.
+pub trait LookAt<T> {
+    /// World-to-view matrix.
+    fn look_at_view_lh(eye: Vec3<T>, target: Vec3<T>, up: Vec3<T>) -> Self;
+    fn look_at_view_rh(eye: Vec3<T>, target: Vec3<T>, up: Vec3<T>) -> Self;
+    /// Object-to-world matrix, i.e the inverse of the view matrix: it puts an object at `eye`, facing `target`.
+    fn look_at_model_lh(eye: Vec3<T>, target: Vec3<T>, up: Vec3<T>) -> Self;
+    fn look_at_model_rh(eye: Vec3<T>, target: Vec3<T>, up: Vec3<T>) -> Self;
+}
+
+trait LookAtBasis: Sized {
+    fn look_at_basis_lh(eye: Self, target: Self, up: Self, fallback_forward: Self) -> (Self, Self, Self);
+}
+
+macro_rules! impl_look_at {
+    ($($T:ident)+) => {
+        $(
+            // Returns the (right, up, forward) world-space axes of a left-handed view.
+            // A right-handed view has the same up, but its right is flipped along with its forward, so
+            // that it stays a rotation.
+            impl LookAtBasis for Vec3<$T> {
+                fn look_at_basis_lh(eye: Self, target: Self, up: Self, fallback_forward: Self) -> (Self, Self, Self) {
+                    let d = target - eye;
+                    let f = if d.magnitude_squared() <= 1e-12 { fallback_forward } else { d.normalized() };
+                    let mut s = up.cross(f);
+                    if s.magnitude_squared() <= 1e-6 {
+                        let alt_up = if f.z.abs() < 0.9 { Vec3::unit_z() } else { Vec3::unit_x() };
+                        s = alt_up.cross(f);
+                    }
+                    let s = s.normalized();
+                    let u = f.cross(s);
+                    (s, u, f)
+                }
+            }
+            impl LookAt<$T> for Mat4<$T> {
+                fn look_at_view_lh(eye: Vec3<$T>, target: Vec3<$T>, up: Vec3<$T>) -> Self {
+                    let (s, u, f) = Vec3::look_at_basis_lh(eye, target, up, Vec3::forward_lh());
+                    Mat4::new(
+                        s.x, s.y, s.z, -s.dot(eye),
+                        u.x, u.y, u.z, -u.dot(eye),
+                        f.x, f.y, f.z, -f.dot(eye),
+                        0., 0., 0., 1.
+                    )
+                }
+                fn look_at_view_rh(eye: Vec3<$T>, target: Vec3<$T>, up: Vec3<$T>) -> Self {
+                    let (s, u, f) = Vec3::look_at_basis_lh(eye, target, up, Vec3::forward_rh());
+                    let (s, b) = (-s, -f);
+                    Mat4::new(
+                        s.x, s.y, s.z, -s.dot(eye),
+                        u.x, u.y, u.z, -u.dot(eye),
+                        b.x, b.y, b.z, -b.dot(eye),
+                        0., 0., 0., 1.
+                    )
+                }
+                fn look_at_model_lh(eye: Vec3<$T>, target: Vec3<$T>, up: Vec3<$T>) -> Self {
+                    let (s, u, f) = Vec3::look_at_basis_lh(eye, target, up, Vec3::forward_lh());
+                    Mat4::new(
+                        s.x, u.x, f.x, eye.x,
+                        s.y, u.y, f.y, eye.y,
+                        s.z, u.z, f.z, eye.z,
+                        0., 0., 0., 1.
+                    )
+                }
+                fn look_at_model_rh(eye: Vec3<$T>, target: Vec3<$T>, up: Vec3<$T>) -> Self {
+                    let (s, u, f) = Vec3::look_at_basis_lh(eye, target, up, Vec3::forward_rh());
+                    let (s, b) = (-s, -f);
+                    Mat4::new(
+                        s.x, u.x, b.x, eye.x,
+                        s.y, u.y, b.y, eye.y,
+                        s.z, u.z, b.z, eye.z,
+                        0., 0., 0., 1.
+                    )
+                }
+            }
+        )+
+    };
+}
+
+impl_look_at!{f32 f64}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Vec4;
+
+    fn assert_approx_eq(a: Vec3<f32>, b: Vec3<f32>) {
+        assert!((a - b).magnitude() <= 1e-5, "{:?} != {:?}", a, b);
+    }
+    fn transform_point(m: Mat4<f32>, p: Vec3<f32>) -> Vec3<f32> {
+        Vec3::from(m * Vec4::from_point(p))
+    }
+
+    #[test]
+    fn lh_view_looks_down_positive_z() {
+        let (eye, target) = (Vec3::new(1., 2., 3.), Vec3::new(4., 2., 7.));
+        let view = Mat4::look_at_view_lh(eye, target, Vec3::up());
+        assert_approx_eq(transform_point(view, eye), Vec3::zero());
+        assert_approx_eq(transform_point(view, target), Vec3::forward_lh() * 5.);
+        assert_approx_eq(transform_point(view, eye + Vec3::up()), Vec3::up());
+    }
+    #[test]
+    fn rh_view_looks_down_negative_z() {
+        let (eye, target) = (Vec3::new(1., 2., 3.), Vec3::new(4., 2., 7.));
+        let view = Mat4::look_at_view_rh(eye, target, Vec3::up());
+        assert_approx_eq(transform_point(view, eye), Vec3::zero());
+        assert_approx_eq(transform_point(view, target), Vec3::forward_rh() * 5.);
+        assert_approx_eq(transform_point(view, eye + Vec3::up()), Vec3::up());
+    }
+    #[test]
+    fn model_is_the_inverse_of_view() {
+        let (eye, target, up) = (Vec3::new(-3., 1., 0.5), Vec3::new(2., -1., 4.), Vec3::new(0.1, 1., 0.));
+        for &(view, model) in &[
+            (Mat4::look_at_view_lh(eye, target, up), Mat4::look_at_model_lh(eye, target, up)),
+            (Mat4::look_at_view_rh(eye, target, up), Mat4::look_at_model_rh(eye, target, up)),
+        ] {
+            for p in [Vec3::zero(), Vec3::new(1., 2., 3.), target].iter() {
+                assert_approx_eq(transform_point(model * view, *p), *p);
+            }
+        }
+    }
+    #[test]
+    fn looking_along_up_does_not_produce_nans() {
+        let eye = Vec3::new(0., 10., 0.);
+        for &target in &[Vec3::zero(), Vec3::new(0., 20., 0.)] {
+            let view = Mat4::look_at_view_lh(eye, target, Vec3::up());
+            assert!(view.into_col_array().iter().all(|x| x.is_finite()));
+            assert_approx_eq(transform_point(view, target), Vec3::forward_lh() * 10.);
+            let view = Mat4::look_at_view_rh(eye, target, Vec3::up());
+            assert!(view.into_col_array().iter().all(|x| x.is_finite()));
+            assert_approx_eq(transform_point(view, target), Vec3::forward_rh() * 10.);
+        }
+    }
+    #[test]
+    fn rh_view_of_positive_z_from_the_origin() {
+        let view = Mat4::look_at_view_rh(Vec3::zero(), Vec3::unit_z(), Vec3::unit_y());
+        let expected = Mat4::new(
+            -1., 0., 0., 0.,
+            0., 1., 0., 0.,
+            0., 0., -1., 0.,
+            0., 0., 0., 1.
+        );
+        for (a, b) in view.into_col_array().iter().zip(expected.into_col_array().iter()) {
+            assert!((a - b).abs() <= 1e-6, "{:?} != {:?}", view, expected);
+        }
+    }
+    #[test]
+    fn eye_at_target_does_not_produce_nans() {
+        let eye = Vec3::new(1., 2., 3.);
+        for &(view, forward) in &[
+            (Mat4::look_at_view_lh(eye, eye, Vec3::up()), Vec3::forward_lh()),
+            (Mat4::look_at_view_rh(eye, eye, Vec3::up()), Vec3::forward_rh()),
+        ] {
+            assert!(view.into_col_array().iter().all(|x| x.is_finite()));
+            // Just a translation
+            assert_approx_eq(transform_point(view, eye), Vec3::zero());
+            assert_approx_eq(transform_point(view, eye + forward), forward);
+            assert_approx_eq(transform_point(view, eye + Vec3::right()), Vec3::right());
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use super::Mat4;
+
+/// Perspective projections for reversed-Z depth buffers.
+///
+/// They map `near` to depth 1 and infinity to depth 0, and expect the GL clip space depth range to be [0, 1]
+/// (i.e `glClipControl(GL_LOWER_LEFT, GL_ZERO_TO_ONE)`), with the depth buffer cleared to 0 and the depth
+/// test set to `GL_GREATER`.
+/// Floating-point precision is highest close to zero, which is here spent on distant geometry, so
+/// depth precision ends up roughly uniform across the whole range.
+///
+/// As usual, `lh` looks down +Z and `rh` looks down -Z.
+pub trait ReversedZProjection<T> {
+    fn perspective_lh_reversed_z_infinite(fov_y_radians: T, aspect_ratio: T, near: T) -> Self;
+    fn perspective_rh_reversed_z_infinite(fov_y_radians: T, aspect_ratio: T, near: T) -> Self;
+}
+
+macro_rules! impl_reversed_z_projection {
+    ($($T:ident)+) => {
+        $(
+            impl ReversedZProjection<$T> for Mat4<$T> {
+                fn perspective_lh_reversed_z_infinite(fov_y_radians: $T, aspect_ratio: $T, near: $T) -> Self {
+                    assert!(near > 0.);
+                    assert!(aspect_ratio > 0.);
+                    let f = 1. / (fov_y_radians / 2.).tan();
+                    Mat4::new(
+                        f / aspect_ratio, 0., 0., 0.,
+                        0., f, 0., 0.,
+                        0., 0., 0., near,
+                        0., 0., 1., 0.
+                    )
+                }
+                fn perspective_rh_reversed_z_infinite(fov_y_radians: $T, aspect_ratio: $T, near: $T) -> Self {
+                    assert!(near > 0.);
+                    assert!(aspect_ratio > 0.);
+                    let f = 1. / (fov_y_radians / 2.).tan();
+                    Mat4::new(
+                        f / aspect_ratio, 0., 0., 0.,
+                        0., f, 0., 0.,
+                        0., 0., 0., near,
+                        0., 0., -1., 0.
+                    )
+                }
+            }
+        )+
+    };
+}
+
+impl_reversed_z_projection!{f32 f64}
+
+/// Perspective projections with the far plane at infinity, for the standard GL clip space depth range [-1, 1].
+///
+/// They map `near` to depth -1 and infinity to depth 1. This is what `vek`'s `perspective_*_no()` would give
+/// with an infinite `far`, except that these don't produce NaNs.
+pub trait InfinitePerspective<T> {
+    fn perspective_lh_no_infinite(fov_y_radians: T, aspect_ratio: T, near: T) -> Self;
+    fn perspective_rh_no_infinite(fov_y_radians: T, aspect_ratio: T, near: T) -> Self;
+}
+
+macro_rules! impl_infinite_perspective {
+    ($($T:ident)+) => {
+        $(
+            impl InfinitePerspective<$T> for Mat4<$T> {
+                fn perspective_lh_no_infinite(fov_y_radians: $T, aspect_ratio: $T, near: $T) -> Self {
+                    assert!(near > 0.);
+                    assert!(aspect_ratio > 0.);
+                    let f = 1. / (fov_y_radians / 2.).tan();
+                    Mat4::new(
+                        f / aspect_ratio, 0., 0., 0.,
+                        0., f, 0., 0.,
+                        0., 0., 1., -2. * near,
+                        0., 0., 1., 0.
+                    )
+                }
+                fn perspective_rh_no_infinite(fov_y_radians: $T, aspect_ratio: $T, near: $T) -> Self {
+                    assert!(near > 0.);
+                    assert!(aspect_ratio > 0.);
+                    let f = 1. / (fov_y_radians / 2.).tan();
+                    Mat4::new(
+                        f / aspect_ratio, 0., 0., 0.,
+                        0., f, 0., 0.,
+                        0., 0., -1., -2. * near,
+                        0., 0., -1., 0.
+                    )
+                }
+            }
+        )+
+    };
+}
+
+impl_infinite_perspective!{f32 f64}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Vec4;
+
+    fn depth(m: Mat4<f32>, p: Vec4<f32>) -> f32 {
+        let clip = m * p;
+        clip.z / clip.w
+    }
+    fn assert_approx_eq(a: f32, b: f32) {
+        assert!((a - b).abs() <= 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn near_maps_to_one_and_infinity_to_zero() {
+        let lh = Mat4::perspective_lh_reversed_z_infinite(1., 16. / 9., 0.01);
+        let rh = Mat4::perspective_rh_reversed_z_infinite(1., 16. / 9., 0.01);
+        assert_approx_eq(depth(lh, Vec4::new(0., 0., 0.01, 1.)), 1.);
+        assert_approx_eq(depth(rh, Vec4::new(0., 0., -0.01, 1.)), 1.);
+        // Points at infinity, straight ahead
+        assert_eq!(depth(lh, Vec4::new(0., 0., 1., 0.)), 0.);
+        assert_eq!(depth(rh, Vec4::new(0., 0., -1., 0.)), 0.);
+    }
+    #[test]
+    fn depth_decreases_with_distance() {
+        let m = Mat4::perspective_lh_reversed_z_infinite(1., 1., 0.1);
+        let mut prev = 1.;
+        for &z in &[0.2_f32, 1., 10., 1000., 1e6] {
+            let d = depth(m, Vec4::new(0., 0., z, 1.));
+            assert!(d < prev && d > 0.);
+            prev = d;
+        }
+        assert_approx_eq(depth(m, Vec4::new(0., 0., 10., 1.)), 0.01);
+    }
+    #[test]
+    fn infinite_perspective_matches_a_very_far_finite_one() {
+        let lh = Mat4::perspective_lh_no_infinite(1., 16. / 9., 0.01);
+        let rh = Mat4::perspective_rh_no_infinite(1., 16. / 9., 0.01);
+        assert_approx_eq(depth(lh, Vec4::new(0., 0., 0.01, 1.)), -1.);
+        assert_approx_eq(depth(rh, Vec4::new(0., 0., -0.01, 1.)), -1.);
+        assert_eq!(depth(lh, Vec4::new(0., 0., 1., 0.)), 1.);
+        assert_eq!(depth(rh, Vec4::new(0., 0., -1., 0.)), 1.);
+        let finite = Mat4::perspective_lh_no(1., 16. / 9., 0.01, 1e7);
+        for &z in &[0.5_f32, 10., 1000.] {
+            let p = Vec4::new(1., -2., z, 1.);
+            assert!((depth(lh, p) - depth(finite, p)).abs() <= 1e-5);
+        }
+    }
+}
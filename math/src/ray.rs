@@ -0,0 +1,180 @@
+use super::{Vec2, Vec3, Aabb};
+
+/// A half-line, for picking and visibility queries.
+///
+/// `direction` doesn't need to be normalized; intersection distances are then in units of its length.
+/// Intersections only report the nearest point at or after `origin` (i.e `t >= 0`).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray3<T> {
+    pub origin: Vec3<T>,
+    pub direction: Vec3<T>,
+}
+
+macro_rules! impl_ray3 {
+    ($($T:ident)+) => {
+        $(
+            impl Ray3<$T> {
+                pub fn new(origin: Vec3<$T>, direction: Vec3<$T>) -> Self {
+                    Self { origin, direction }
+                }
+                pub fn point_at(&self, t: $T) -> Vec3<$T> {
+                    self.origin + self.direction * t
+                }
+                /// If the ray starts inside the box, this is where it exits it.
+                pub fn intersect_aabb(&self, aabb: &Aabb<$T>) -> Option<$T> {
+                    let origin = self.origin.into_array();
+                    let direction = self.direction.into_array();
+                    let (min, max) = (aabb.min.into_array(), aabb.max.into_array());
+                    let mut t_enter = ::std::$T::NEG_INFINITY;
+                    let mut t_exit = ::std::$T::INFINITY;
+                    for i in 0..3 {
+                        if direction[i] == 0. {
+                            // Parallel to this slab; dividing would give 0 * inf = NaN when on its boundary.
+                            if origin[i] < min[i] || origin[i] > max[i] {
+                                return None;
+                            }
+                            continue;
+                        }
+                        let inv = 1. / direction[i];
+                        let (t0, t1) = ((min[i] - origin[i]) * inv, (max[i] - origin[i]) * inv);
+                        let (t0, t1) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+                        t_enter = t_enter.max(t0);
+                        t_exit = t_exit.min(t1);
+                    }
+                    if t_exit < t_enter || t_exit < 0. {
+                        return None;
+                    }
+                    Some(if t_enter >= 0. { t_enter } else { t_exit })
+                }
+                /// The plane is the set of points `p` such that `normal.dot(p) + d == 0`, like `CullingFrustum`'s planes.
+                /// `normal` doesn't need to be normalized. Both sides are hit.
+                pub fn intersect_plane(&self, normal: Vec3<$T>, d: $T) -> Option<$T> {
+                    let denom = normal.dot(self.direction);
+                    if denom.abs() <= ::std::$T::EPSILON {
+                        return None;
+                    }
+                    let t = -(normal.dot(self.origin) + d) / denom;
+                    if t < 0. {
+                        return None;
+                    }
+                    Some(t)
+                }
+                /// If the ray starts inside the sphere, this is where it exits it.
+                pub fn intersect_sphere(&self, center: Vec3<$T>, radius: $T) -> Option<$T> {
+                    let oc = self.origin - center;
+                    let a = self.direction.dot(self.direction);
+                    let b = oc.dot(self.direction);
+                    let c = oc.dot(oc) - radius * radius;
+                    let discriminant = b * b - a * c;
+                    if discriminant < 0. || a == 0. {
+                        return None;
+                    }
+                    let sqrt = discriminant.sqrt();
+                    let (t0, t1) = ((-b - sqrt) / a, (-b + sqrt) / a);
+                    if t0 >= 0. {
+                        Some(t0)
+                    } else if t1 >= 0. {
+                        Some(t1)
+                    } else {
+                        None
+                    }
+                }
+                /// Möller-Trumbore; both faces are hit.
+                /// Also returns the barycentric coordinates of `b` and `c` at the hit point,
+                /// i.e the point is `a * (1 - u - v) + b * u + c * v`.
+                pub fn intersect_triangle(&self, a: Vec3<$T>, b: Vec3<$T>, c: Vec3<$T>) -> Option<($T, Vec2<$T>)> {
+                    let epsilon = ::std::$T::EPSILON;
+                    let (e1, e2) = (b - a, c - a);
+                    let p = self.direction.cross(e2);
+                    let det = e1.dot(p);
+                    if det.abs() <= epsilon {
+                        return None; // Parallel to the triangle, or degenerate triangle
+                    }
+                    let inv_det = 1. / det;
+                    let s = self.origin - a;
+                    let u = s.dot(p) * inv_det;
+                    if u < 0. || u > 1. {
+                        return None;
+                    }
+                    let q = s.cross(e1);
+                    let v = self.direction.dot(q) * inv_det;
+                    if v < 0. || u + v > 1. {
+                        return None;
+                    }
+                    let t = e2.dot(q) * inv_det;
+                    if t < 0. {
+                        return None;
+                    }
+                    Some((t, Vec2::new(u, v)))
+                }
+            }
+        )+
+    };
+}
+
+impl_ray3!{f32 f64}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: f32, b: f32) {
+        assert!((a - b).abs() <= 1e-5, "{} != {}", a, b);
+    }
+    fn unit_box() -> Aabb<f32> {
+        Aabb { min: Vec3::new(-1., -1., -1.), max: Vec3::new(1., 1., 1.) }
+    }
+
+    #[test]
+    fn ray_vs_aabb() {
+        let ray = Ray3::<f32>::new(Vec3::new(-5., 0.5, 0.), Vec3::new(2., 0., 0.));
+        assert_approx_eq(ray.intersect_aabb(&unit_box()).unwrap(), 2.);
+        let miss = Ray3::<f32>::new(Vec3::new(-5., 1.5, 0.), Vec3::new(1., 0., 0.));
+        assert_eq!(miss.intersect_aabb(&unit_box()), None);
+        let away = Ray3::<f32>::new(Vec3::new(-5., 0., 0.), Vec3::new(-1., 0., 0.));
+        assert_eq!(away.intersect_aabb(&unit_box()), None);
+        let inside = Ray3::<f32>::new(Vec3::new(0., 0., 0.5), Vec3::new(0., 0., 1.));
+        assert_approx_eq(inside.intersect_aabb(&unit_box()).unwrap(), 0.5);
+        // Zero direction components, with the origin exactly on a slab's boundary
+        let grazing = Ray3::<f32>::new(Vec3::new(-5., 1., -1.), Vec3::new(1., 0., 0.));
+        assert_approx_eq(grazing.intersect_aabb(&unit_box()).unwrap(), 4.);
+    }
+    #[test]
+    fn ray_vs_plane() {
+        // y == 2
+        let (normal, d) = (Vec3::new(0., 1., 0.), -2.);
+        let ray = Ray3::<f32>::new(Vec3::new(1., -1., 0.), Vec3::new(0., 2., 0.));
+        assert_approx_eq(ray.intersect_plane(normal, d).unwrap(), 1.5);
+        assert_approx_eq(ray.intersect_plane(-normal, -d).unwrap(), 1.5);
+        let away = Ray3::<f32>::new(Vec3::new(1., -1., 0.), Vec3::new(0., -1., 0.));
+        assert_eq!(away.intersect_plane(normal, d), None);
+        let parallel = Ray3::<f32>::new(Vec3::new(1., -1., 0.), Vec3::new(1., 0., 0.));
+        assert_eq!(parallel.intersect_plane(normal, d), None);
+    }
+    #[test]
+    fn ray_vs_sphere() {
+        let ray = Ray3::<f32>::new(Vec3::new(0., 0., -5.), Vec3::new(0., 0., 1.));
+        assert_approx_eq(ray.intersect_sphere(Vec3::zero(), 1.).unwrap(), 4.);
+        assert_eq!(ray.intersect_sphere(Vec3::new(0., 2., 0.), 1.), None);
+        assert_eq!(ray.intersect_sphere(Vec3::new(0., 0., -10.), 1.), None);
+        let inside = Ray3::<f32>::new(Vec3::zero(), Vec3::new(0., 1., 0.));
+        assert_approx_eq(inside.intersect_sphere(Vec3::zero(), 2.).unwrap(), 2.);
+    }
+    #[test]
+    fn ray_vs_triangle() {
+        let (a, b, c) = (Vec3::new(0., 0., 0.), Vec3::new(1., 0., 0.), Vec3::new(0., 1., 0.));
+        let ray = Ray3::<f32>::new(Vec3::new(0.25, 0.5, 3.), Vec3::new(0., 0., -1.));
+        let (t, bary) = ray.intersect_triangle(a, b, c).unwrap();
+        assert_approx_eq(t, 3.);
+        assert_approx_eq(bary.x, 0.25);
+        assert_approx_eq(bary.y, 0.5);
+        // The back face is hit too
+        let back = Ray3::<f32>::new(Vec3::new(0.25, 0.5, -3.), Vec3::new(0., 0., 1.));
+        assert_approx_eq(back.intersect_triangle(a, b, c).unwrap().0, 3.);
+        let miss = Ray3::<f32>::new(Vec3::new(0.75, 0.75, 3.), Vec3::new(0., 0., -1.));
+        assert_eq!(miss.intersect_triangle(a, b, c), None);
+        let parallel = Ray3::<f32>::new(Vec3::new(-1., 0.25, 0.), Vec3::new(1., 0., 0.));
+        assert_eq!(parallel.intersect_triangle(a, b, c), None);
+    }
+}
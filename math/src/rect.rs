@@ -0,0 +1,96 @@
+use super::Rect;
+
+/// Set operations on rectangles, for hit-testing and computing scissor boxes.
+///
+/// Rectangles are half-open: they contain their left and bottom edges but not their right and top edges,
+/// so rectangles that merely touch don't overlap.
+///
+/// The names differ from the usual `intersection`/`union`/`contains_rect`, so that they aren't shadowed by
+/// `vek`'s inherent methods, which don't have the same semantics.
+pub trait RectSetOps: Sized {
+    /// The area shared by both rectangles, or `None` if it is empty (including when they only touch).
+    fn overlap(self, other: Self) -> Option<Self>;
+    /// The smallest rectangle that contains both rectangles.
+    fn bounding_union(self, other: Self) -> Self;
+    /// Does `other` fit entirely inside this rectangle? Edges may coincide.
+    fn contains_whole_rect(&self, other: &Self) -> bool;
+}
+
+fn partial_min<T: PartialOrd>(a: T, b: T) -> T { if b < a { b } else { a } }
+fn partial_max<T: PartialOrd>(a: T, b: T) -> T { if b > a { b } else { a } }
+
+macro_rules! impl_rect_set_ops {
+    ($($P:ident $E:ident)+) => {
+        $(
+            impl RectSetOps for Rect<$P, $E> {
+                fn overlap(self, other: Self) -> Option<Self> {
+                    let x = partial_max(self.x, other.x);
+                    let y = partial_max(self.y, other.y);
+                    let right = partial_min(self.x + self.w as $P, other.x + other.w as $P);
+                    let top = partial_min(self.y + self.h as $P, other.y + other.h as $P);
+                    if right <= x || top <= y {
+                        return None;
+                    }
+                    Some(Rect { x, y, w: (right - x) as $E, h: (top - y) as $E })
+                }
+                fn bounding_union(self, other: Self) -> Self {
+                    let x = partial_min(self.x, other.x);
+                    let y = partial_min(self.y, other.y);
+                    let right = partial_max(self.x + self.w as $P, other.x + other.w as $P);
+                    let top = partial_max(self.y + self.h as $P, other.y + other.h as $P);
+                    Rect { x, y, w: (right - x) as $E, h: (top - y) as $E }
+                }
+                fn contains_whole_rect(&self, other: &Self) -> bool {
+                    other.x >= self.x && other.y >= self.y
+                        && other.x + other.w as $P <= self.x + self.w as $P
+                        && other.y + other.h as $P <= self.y + self.h as $P
+                }
+            }
+        )+
+    };
+}
+
+impl_rect_set_ops!{
+    u32 u32
+    i32 i32
+    i32 u32
+    f32 f32
+    f64 f64
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disjoint_and_touching_rects_dont_overlap() {
+        let a = Rect::<u32, u32>::new(0, 0, 10, 10);
+        assert_eq!(a.overlap(Rect::new(20, 20, 5, 5)), None);
+        assert_eq!(a.overlap(Rect::new(10, 0, 5, 10)), None);
+        assert_eq!(a.overlap(Rect::new(0, 10, 10, 5)), None);
+        assert_eq!(a.overlap(Rect::new(10, 10, 5, 5)), None);
+        assert_eq!(a.bounding_union(Rect::new(20, 20, 5, 5)), Rect::new(0, 0, 25, 25));
+    }
+    #[test]
+    fn nested_rects() {
+        let outer = Rect::<i32, u32>::new(-10, -10, 20, 20);
+        let inner = Rect::new(-5, 0, 5, 10);
+        assert_eq!(outer.overlap(inner), Some(inner));
+        assert_eq!(inner.overlap(outer), Some(inner));
+        assert_eq!(outer.bounding_union(inner), outer);
+        assert!(outer.contains_whole_rect(&inner));
+        assert!(outer.contains_whole_rect(&outer));
+        assert!(!inner.contains_whole_rect(&outer));
+    }
+    #[test]
+    fn partially_overlapping_rects() {
+        let a = Rect::<f32, f32>::new(0., 0., 4., 3.);
+        let b = Rect::new(2., 1., 4., 4.);
+        assert_eq!(a.overlap(b), Some(Rect::new(2., 1., 2., 2.)));
+        assert_eq!(b.overlap(a), a.overlap(b));
+        assert_eq!(a.bounding_union(b), Rect::new(0., 0., 6., 5.));
+        assert!(!a.contains_whole_rect(&b));
+        assert!(!b.contains_whole_rect(&a));
+    }
+}
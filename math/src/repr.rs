@@ -0,0 +1,102 @@
+use vek::vec::{repr_c as c, repr_simd as simd};
+use vek::mat::repr_c::column_major::Mat4 as CMat4;
+use vek::mat::repr_simd::column_major::Mat4 as SimdMat4;
+use vek::quaternion::repr_c::Quaternion as CQuaternion;
+use vek::quaternion::repr_simd::Quaternion as SimdQuaternion;
+
+// `From` can't be implemented here: both sides are `vek` types, so that would break the orphan rule.
+// These traits are the next best thing.
+
+/// Converts a `repr_simd` value to its `repr_c` counterpart.
+pub trait IntoReprC {
+    type ReprC;
+    fn into_repr_c(self) -> Self::ReprC;
+}
+
+/// Converts a `repr_c` value to its `repr_simd` counterpart.
+pub trait IntoReprSimd {
+    type ReprSimd;
+    fn into_repr_simd(self) -> Self::ReprSimd;
+}
+
+macro_rules! impl_repr_conversions {
+    ($($Vec:ident { $($field:ident),+ })+) => {
+        $(
+            impl<T> IntoReprC for simd::$Vec<T> {
+                type ReprC = c::$Vec<T>;
+                fn into_repr_c(self) -> Self::ReprC {
+                    let simd::$Vec { $($field),+ } = self;
+                    c::$Vec { $($field),+ }
+                }
+            }
+            impl<T> IntoReprSimd for c::$Vec<T> {
+                type ReprSimd = simd::$Vec<T>;
+                fn into_repr_simd(self) -> Self::ReprSimd {
+                    let c::$Vec { $($field),+ } = self;
+                    simd::$Vec { $($field),+ }
+                }
+            }
+        )+
+    };
+}
+
+impl_repr_conversions!{
+    Vec2 { x, y }
+    Vec3 { x, y, z }
+    Vec4 { x, y, z, w }
+    Extent2 { w, h }
+    Extent3 { w, h, d }
+    Rgb { r, g, b }
+    Rgba { r, g, b, a }
+}
+
+impl<T> IntoReprC for SimdQuaternion<T> {
+    type ReprC = CQuaternion<T>;
+    fn into_repr_c(self) -> Self::ReprC {
+        let SimdQuaternion { x, y, z, w } = self;
+        CQuaternion { x, y, z, w }
+    }
+}
+impl<T> IntoReprSimd for CQuaternion<T> {
+    type ReprSimd = SimdQuaternion<T>;
+    fn into_repr_simd(self) -> Self::ReprSimd {
+        let CQuaternion { x, y, z, w } = self;
+        SimdQuaternion { x, y, z, w }
+    }
+}
+
+impl<T: Copy> IntoReprC for SimdMat4<T> {
+    type ReprC = CMat4<T>;
+    fn into_repr_c(self) -> Self::ReprC {
+        CMat4::from_col_arrays(self.into_col_arrays())
+    }
+}
+impl<T: Copy> IntoReprSimd for CMat4<T> {
+    type ReprSimd = SimdMat4<T>;
+    fn into_repr_simd(self) -> Self::ReprSimd {
+        SimdMat4::from_col_arrays(self.into_col_arrays())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simd_vec4_round_trips_through_repr_c() {
+        let s = simd::Vec4::new(1_f32, -2., 3.5, ::std::f32::MAX);
+        let v = s.into_repr_c();
+        assert_eq!((v.x, v.y, v.z, v.w), (1., -2., 3.5, ::std::f32::MAX));
+        assert_eq!(v.into_repr_simd(), s);
+    }
+    #[test]
+    fn mat4_and_quaternion_round_trip() {
+        let m = CMat4::<f32>::translation_3d(c::Vec3::new(1., 2., 3.)) * CMat4::scaling_3d(2.);
+        assert_eq!(m.into_repr_simd().into_repr_c(), m);
+        let q = CQuaternion::<f32>::rotation_y(1.5);
+        assert_eq!(q.into_repr_simd().into_repr_c(), q);
+        let color = c::Rgba::new(1_u8, 2, 3, 4);
+        assert_eq!(color.into_repr_simd().into_repr_c(), color);
+    }
+}
@@ -0,0 +1,397 @@
+use super::{Mat3, Mat4, Quaternion, Vec3};
+
+/// Converts rotation matrices back to quaternions.
+///
+/// The conversion uses Shepperd's method: it selects the largest of the trace and the
+/// three diagonal terms, so the square root is never taken of a value close to zero.
+/// This keeps it accurate near 180° rotations, where the naive trace-based formula breaks down.
+///
+/// The matrix is expected to be a pure rotation (orthonormal, no scale or shear).
+/// For a `Mat4`, only the upper-left 3x3 part is considered.
+pub trait QuaternionFromMatrix<T> {
+    fn from_mat3(m: Mat3<T>) -> Self;
+    fn from_mat4(m: Mat4<T>) -> Self;
+}
+
+macro_rules! impl_quaternion_from_matrix {
+    ($($T:ident)+) => {
+        $(
+            impl QuaternionFromMatrix<$T> for Quaternion<$T> {
+                fn from_mat3(m: Mat3<$T>) -> Self {
+                    // m_rc is the element at row r, column c.
+                    let (m00, m10, m20) = (m.cols.x.x, m.cols.x.y, m.cols.x.z);
+                    let (m01, m11, m21) = (m.cols.y.x, m.cols.y.y, m.cols.y.z);
+                    let (m02, m12, m22) = (m.cols.z.x, m.cols.z.y, m.cols.z.z);
+                    let trace = m00 + m11 + m22;
+
+                    if trace >= m00 && trace >= m11 && trace >= m22 {
+                        let s = (1. + trace).sqrt() * 2.; // s = 4w
+                        Quaternion::from_xyzw((m21 - m12) / s, (m02 - m20) / s, (m10 - m01) / s, s / 4.)
+                    } else if m00 >= m11 && m00 >= m22 {
+                        let s = (1. + m00 - m11 - m22).sqrt() * 2.; // s = 4x
+                        Quaternion::from_xyzw(s / 4., (m01 + m10) / s, (m02 + m20) / s, (m21 - m12) / s)
+                    } else if m11 >= m22 {
+                        let s = (1. + m11 - m00 - m22).sqrt() * 2.; // s = 4y
+                        Quaternion::from_xyzw((m01 + m10) / s, s / 4., (m12 + m21) / s, (m02 - m20) / s)
+                    } else {
+                        let s = (1. + m22 - m00 - m11).sqrt() * 2.; // s = 4z
+                        Quaternion::from_xyzw((m02 + m20) / s, (m12 + m21) / s, s / 4., (m10 - m01) / s)
+                    }
+                }
+                fn from_mat4(m: Mat4<$T>) -> Self {
+                    Self::from_mat3(Mat3::from(m))
+                }
+            }
+        )+
+    };
+}
+
+impl_quaternion_from_matrix!{f32 f64}
+
+/// Spherical linear interpolation which always takes the shortest arc.
+///
+/// `q` and `-q` are the same rotation, so when `self` and `to` are more than 90° apart in quaternion space,
+/// `to` is negated first; otherwise the interpolation would spin the long way around.
+/// When both are almost equal, this falls back to a normalized lerp, since dividing by `sin(theta)` would blow up.
+///
+/// The `nlerp` variants are always normalized lerps: cheaper, but the angular speed isn't constant.
+pub trait QuaternionSlerp<T> {
+    /// `factor` is clamped to [0, 1].
+    fn shortest_slerp(self, to: Self, factor: T) -> Self;
+    fn shortest_slerp_unclamped(self, to: Self, factor: T) -> Self;
+    /// `factor` is clamped to [0, 1].
+    fn shortest_nlerp(self, to: Self, factor: T) -> Self;
+    fn shortest_nlerp_unclamped(self, to: Self, factor: T) -> Self;
+}
+
+macro_rules! impl_quaternion_slerp {
+    ($($T:ident)+) => {
+        $(
+            impl QuaternionSlerp<$T> for Quaternion<$T> {
+                fn shortest_slerp(self, to: Self, factor: $T) -> Self {
+                    self.shortest_slerp_unclamped(to, factor.max(0.).min(1.))
+                }
+                fn shortest_slerp_unclamped(self, to: Self, factor: $T) -> Self {
+                    let from = self;
+                    let mut d = from.x*to.x + from.y*to.y + from.z*to.z + from.w*to.w;
+                    let to = if d < 0. {
+                        d = -d;
+                        Quaternion::from_xyzw(-to.x, -to.y, -to.z, -to.w)
+                    } else {
+                        to
+                    };
+                    let (a, b) = if d > 1. - 1e-4 {
+                        (1. - factor, factor)
+                    } else {
+                        let theta = d.acos();
+                        let sin_theta = theta.sin();
+                        (((1. - factor) * theta).sin() / sin_theta, (factor * theta).sin() / sin_theta)
+                    };
+                    let q = Quaternion::from_xyzw(a*from.x + b*to.x, a*from.y + b*to.y, a*from.z + b*to.z, a*from.w + b*to.w);
+                    if d > 1. - 1e-4 { q.normalized() } else { q }
+                }
+                fn shortest_nlerp(self, to: Self, factor: $T) -> Self {
+                    self.shortest_nlerp_unclamped(to, factor.max(0.).min(1.))
+                }
+                fn shortest_nlerp_unclamped(self, to: Self, factor: $T) -> Self {
+                    let from = self;
+                    let d = from.x*to.x + from.y*to.y + from.z*to.z + from.w*to.w;
+                    let (a, b) = (1. - factor, if d < 0. { -factor } else { factor });
+                    Quaternion::from_xyzw(a*from.x + b*to.x, a*from.y + b*to.y, a*from.z + b*to.z, a*from.w + b*to.w).normalized()
+                }
+            }
+        )+
+    };
+}
+
+impl_quaternion_slerp!{f32 f64}
+
+/// Conversions between quaternions and Euler angles, in radians.
+///
+/// The suffix is the order in which the rotations are applied, all around the fixed world axes.
+/// For instance with `xyz`, `angles.x` is applied first (around X), then `angles.y` (around Y), then `angles.z` (around Z),
+/// i.e the quaternion is `rz * ry * rx`. `angles` is always indexed by axis, whatever the order.
+///
+/// `to_euler_*()` returns angles in (-PI, PI] for the first and last axes, and [-PI/2, PI/2] for the middle one.
+/// When the middle angle is ±90° (gimbal lock), the first and last axes coincide, so the first angle is set to zero
+/// and the last one carries the whole rotation.
+pub trait QuaternionEuler<T> {
+    fn from_euler_xyz(angles: Vec3<T>) -> Self;
+    fn to_euler_xyz(self) -> Vec3<T>;
+    fn from_euler_zyx(angles: Vec3<T>) -> Self;
+    fn to_euler_zyx(self) -> Vec3<T>;
+    fn from_euler_yxz(angles: Vec3<T>) -> Self;
+    fn to_euler_yxz(self) -> Vec3<T>;
+}
+
+trait EulerAngles: Sized {
+    fn euler_angles(q: Quaternion<Self>, i: usize, j: usize, k: usize) -> Vec3<Self>;
+}
+
+macro_rules! impl_quaternion_euler {
+    ($($T:ident)+) => {
+        $(
+            // Angles of the rotation `rk * rj * ri`, where (i, j, k) is a permutation of the axes (0 is X, 1 is Y, 2 is Z),
+            // read from the rotation matrix of `q`.
+            impl EulerAngles for $T {
+                fn euler_angles(q: Quaternion<$T>, i: usize, j: usize, k: usize) -> Vec3<$T> {
+                    let Quaternion { x, y, z, w } = q;
+                    let m = [
+                        [1. - 2. * (y*y + z*z), 2. * (x*y - w*z), 2. * (x*z + w*y)],
+                        [2. * (x*y + w*z), 1. - 2. * (x*x + z*z), 2. * (y*z - w*x)],
+                        [2. * (x*z - w*y), 2. * (y*z + w*x), 1. - 2. * (x*x + y*y)],
+                    ];
+                    // Odd permutations (e.g ZYX) flip the signs of the off-diagonal terms
+                    let e = if (j + 3 - i) % 3 == 1 { 1. } else { -1. };
+                    let mut angles = [0.; 3];
+                    let sin_j = (-e * m[k][i]).max(-1.).min(1.);
+                    if sin_j.abs() >= 1. - 1e-6 {
+                        angles[j] = sin_j.signum() * ::std::$T::consts::PI / 2.;
+                        angles[k] = (-e * m[i][j]).atan2(m[j][j]);
+                    } else {
+                        angles[i] = (e * m[k][j]).atan2(m[k][k]);
+                        angles[j] = sin_j.asin();
+                        angles[k] = (e * m[j][i]).atan2(m[i][i]);
+                    }
+                    Vec3::new(angles[0], angles[1], angles[2])
+                }
+            }
+            impl QuaternionEuler<$T> for Quaternion<$T> {
+                fn from_euler_xyz(angles: Vec3<$T>) -> Self {
+                    Quaternion::rotation_z(angles.z) * Quaternion::rotation_y(angles.y) * Quaternion::rotation_x(angles.x)
+                }
+                fn to_euler_xyz(self) -> Vec3<$T> {
+                    $T::euler_angles(self, 0, 1, 2)
+                }
+                fn from_euler_zyx(angles: Vec3<$T>) -> Self {
+                    Quaternion::rotation_x(angles.x) * Quaternion::rotation_y(angles.y) * Quaternion::rotation_z(angles.z)
+                }
+                fn to_euler_zyx(self) -> Vec3<$T> {
+                    $T::euler_angles(self, 2, 1, 0)
+                }
+                fn from_euler_yxz(angles: Vec3<$T>) -> Self {
+                    Quaternion::rotation_z(angles.z) * Quaternion::rotation_x(angles.x) * Quaternion::rotation_y(angles.y)
+                }
+                fn to_euler_yxz(self) -> Vec3<$T> {
+                    $T::euler_angles(self, 1, 0, 2)
+                }
+            }
+        )+
+    };
+}
+
+impl_quaternion_euler!{f32 f64}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use super::super::Vec4;
+
+    // Deterministic xorshift, so that failures are reproducible.
+    struct Rng(u32);
+
+    impl Rng {
+        fn next_f32(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            (self.0 as f32 / ::std::u32::MAX as f32) * 2. - 1.
+        }
+        fn unit_quaternion(&mut self) -> Quaternion<f32> {
+            Quaternion::from_xyzw(self.next_f32(), self.next_f32(), self.next_f32(), self.next_f32()).normalized()
+        }
+    }
+
+    // q and -q represent the same rotation.
+    fn assert_same_rotation(a: Quaternion<f32>, b: Quaternion<f32>, epsilon: f32) {
+        let d = a.x*b.x + a.y*b.y + a.z*b.z + a.w*b.w;
+        let b = if d < 0. { Quaternion::from_xyzw(-b.x, -b.y, -b.z, -b.w) } else { b };
+        for &(x, y) in &[(a.x, b.x), (a.y, b.y), (a.z, b.z), (a.w, b.w)] {
+            assert!((x - y).abs() <= epsilon, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn mat4_from_quaternion_rotates_like_the_quaternion() {
+        let mut rng = Rng(0x2545F491);
+        for _ in 0..100 {
+            let q = rng.unit_quaternion();
+            let v = Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32());
+            let a = q * v;
+            let b = (Mat4::from(q) * Vec4::from_direction(v)).xyz();
+            assert!((a - b).magnitude() <= 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+    // `Mat3::from(Quaternion)` and `Mat4::from(Quaternion)` are provided by vek, and expect a normalized quaternion.
+    #[test]
+    fn quarter_turn_about_z_matrices() {
+        use super::super::{SimdMat4, IntoReprC, IntoReprSimd};
+        let q = Quaternion::rotation_z(PI / 2.);
+        assert!((q * Vec3::unit_x() - Vec3::unit_y()).magnitude() <= 1e-6);
+        assert!((Mat3::from(q) * Vec3::unit_x() - Vec3::unit_y()).magnitude() <= 1e-6);
+        assert!((Mat4::from(q) * Vec4::unit_x() - Vec4::unit_y()).magnitude() <= 1e-6);
+        let m = SimdMat4::from(q.into_repr_simd()).into_repr_c();
+        assert!((m * Vec4::unit_x() - Vec4::unit_y()).magnitude() <= 1e-6);
+    }
+    #[test]
+    fn random_quaternions_round_trip() {
+        let mut rng = Rng(0x9E3779B9);
+        for _ in 0..1000 {
+            let q = rng.unit_quaternion();
+            assert_same_rotation(q, Quaternion::from_mat4(Mat4::from(q)), 1e-5);
+            assert_same_rotation(q, Quaternion::from_mat3(Mat3::from(q)), 1e-5);
+        }
+    }
+    #[test]
+    fn near_half_turn_quaternions_round_trip() {
+        let axes = [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z(), Vec3::new(1., 1., 1.).normalized(), Vec3::new(-1., 0.5, 2.).normalized()];
+        for axis in axes.iter() {
+            for &angle in &[PI, PI - 1e-3, PI - 1e-6, PI + 1e-3] {
+                let q = Quaternion::rotation_3d(angle, *axis);
+                assert_same_rotation(q, Quaternion::from_mat4(Mat4::from(q)), 1e-5);
+            }
+        }
+    }
+    #[test]
+    fn slerp_between_equal_quaternions_is_identity() {
+        let mut rng = Rng(0x1B873593);
+        for _ in 0..100 {
+            let q = rng.unit_quaternion();
+            for &t in &[0., 0.25, 0.5, 1.] {
+                assert_same_rotation(q.shortest_slerp(q, t), q, 1e-6);
+            }
+        }
+    }
+    #[test]
+    fn slerp_midpoint_is_halfway_along_the_shortest_arc() {
+        let from = Quaternion::rotation_y(0_f32);
+        let to = Quaternion::rotation_y(PI / 2.);
+        let mid = from.shortest_slerp(to, 0.5);
+        assert_same_rotation(mid, Quaternion::rotation_y(PI / 4.), 1e-6);
+        // 45° from each end; the angle between unit quaternions is half the rotation angle.
+        let angle = |a: Quaternion<f32>, b: Quaternion<f32>| 2. * (a.x*b.x + a.y*b.y + a.z*b.z + a.w*b.w).abs().min(1.).acos();
+        assert!((angle(mid, from) - PI / 4.).abs() <= 1e-5);
+        assert!((angle(mid, to) - PI / 4.).abs() <= 1e-5);
+
+        // The same rotation with the opposite sign must not go the long way
+        let negated = Quaternion::from_xyzw(-to.x, -to.y, -to.z, -to.w);
+        assert_same_rotation(from.shortest_slerp(negated, 0.5), mid, 1e-6);
+
+        // Clamped vs. unclamped
+        assert_same_rotation(from.shortest_slerp(to, 2.), to, 1e-6);
+        assert_same_rotation(from.shortest_slerp_unclamped(to, 2.), Quaternion::rotation_y(PI), 1e-5);
+    }
+    #[test]
+    fn slerp_and_nlerp_endpoints() {
+        let mut rng = Rng(0x85EBCA6B);
+        for _ in 0..100 {
+            let (from, to) = (rng.unit_quaternion(), rng.unit_quaternion());
+            assert_same_rotation(from.shortest_slerp(to, 0.), from, 1e-5);
+            assert_same_rotation(from.shortest_slerp(to, 1.), to, 1e-5);
+            assert_same_rotation(from.shortest_nlerp(to, 0.), from, 1e-5);
+            assert_same_rotation(from.shortest_nlerp(to, 1.), to, 1e-5);
+            // Both agree on the midpoint, but not elsewhere
+            assert_same_rotation(from.shortest_nlerp(to, 0.5), from.shortest_slerp(to, 0.5), 1e-5);
+        }
+    }
+    #[test]
+    fn slerp_between_nearly_identical_quaternions_is_finite() {
+        let from = Quaternion::rotation_x(0.3_f32);
+        let to = Quaternion::rotation_x(0.3 + 1e-6);
+        for &t in &[0., 0.5, 1.] {
+            let q = from.shortest_slerp(to, t);
+            assert!(q.x.is_finite() && q.y.is_finite() && q.z.is_finite() && q.w.is_finite(), "{:?}", q);
+            assert_same_rotation(q, from, 1e-5);
+        }
+    }
+    // Compares rotations by what they do to the basis vectors.
+    fn assert_same_rotation_of_basis(a: Quaternion<f32>, b: Quaternion<f32>) {
+        for v in [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()].iter() {
+            assert!((a * *v - b * *v).magnitude() <= 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn euler_angles_apply_x_then_y_then_z() {
+        let q = Quaternion::from_euler_xyz(Vec3::new(PI / 2., PI / 2., 0.));
+        // X turns Y into Z, then Y turns Z into X
+        assert!((q * Vec3::unit_y() - Vec3::unit_x()).magnitude() <= 1e-6);
+        assert_same_rotation(Quaternion::from_euler_xyz(Vec3::new(0., 0., 1.)), Quaternion::rotation_z(1.), 1e-6);
+    }
+    #[test]
+    fn euler_angles_round_trip() {
+        let mut rng = Rng(0x85EBCA6B);
+        for _ in 0..1000 {
+            let angles = Vec3::new(rng.next_f32() * PI, rng.next_f32() * PI / 2., rng.next_f32() * PI) * 0.99;
+            let q = Quaternion::from_euler_xyz(angles);
+            let back = q.to_euler_xyz();
+            assert!((back - angles).magnitude() <= 1e-3, "{:?} != {:?}", back, angles);
+            assert_same_rotation_of_basis(Quaternion::from_euler_xyz(back), q);
+        }
+        for _ in 0..100 {
+            let q = rng.unit_quaternion();
+            assert_same_rotation_of_basis(Quaternion::from_euler_xyz(q.to_euler_xyz()), q);
+        }
+    }
+    #[test]
+    fn euler_angles_at_gimbal_lock() {
+        for &y in &[PI / 2., -PI / 2.] {
+            let q = Quaternion::from_euler_xyz(Vec3::new(0.3, y, 0.7));
+            let angles = q.to_euler_xyz();
+            assert!(angles.iter().all(|x| x.is_finite()));
+            assert_eq!(angles.x, 0.);
+            assert!((angles.y - y).abs() <= 1e-3);
+            assert_same_rotation_of_basis(Quaternion::from_euler_xyz(angles), q);
+        }
+    }
+    #[test]
+    fn other_euler_orders_round_trip() {
+        type Conversions = (fn(Vec3<f32>) -> Quaternion<f32>, fn(Quaternion<f32>) -> Vec3<f32>);
+        let orders: [Conversions; 2] = [
+            (Quaternion::from_euler_zyx, Quaternion::to_euler_zyx),
+            (Quaternion::from_euler_yxz, Quaternion::to_euler_yxz),
+        ];
+        let mut rng = Rng(0xCC9E2D51);
+        for &(from_euler, to_euler) in orders.iter() {
+            for _ in 0..1000 {
+                let angles = Vec3::new(rng.next_f32() * PI, rng.next_f32() * PI, rng.next_f32() * PI) * 0.99;
+                let q = from_euler(angles);
+                assert_same_rotation_of_basis(from_euler(to_euler(q)), q);
+            }
+        }
+        // The middle axis is the one limited to [-PI/2, PI/2]
+        let angles = Vec3::new(0.4, -0.2, 1.3);
+        let back = Quaternion::to_euler_zyx(Quaternion::from_euler_zyx(angles));
+        assert!((back - angles).magnitude() <= 1e-5, "{:?} != {:?}", back, angles);
+        let angles = Vec3::new(-1.1, 2.5, 0.6);
+        let back = Quaternion::to_euler_yxz(Quaternion::from_euler_yxz(angles));
+        assert!((back - angles).magnitude() <= 1e-5, "{:?} != {:?}", back, angles);
+        // ZYX applies Z first
+        let q = Quaternion::from_euler_zyx(Vec3::new(PI / 2., 0., PI / 2.));
+        assert!((q * Vec3::unit_x() - Vec3::unit_z()).magnitude() <= 1e-6);
+    }
+    #[test]
+    fn other_euler_orders_at_gimbal_lock() {
+        for &a in &[PI / 2., -PI / 2.] {
+            let q = Quaternion::from_euler_zyx(Vec3::new(0.3, a, 0.7));
+            let angles = q.to_euler_zyx();
+            assert_eq!(angles.z, 0.);
+            assert_same_rotation_of_basis(Quaternion::from_euler_zyx(angles), q);
+            let q = Quaternion::from_euler_yxz(Vec3::new(a, 0.3, 0.7));
+            let angles = q.to_euler_yxz();
+            assert!(angles.iter().all(|x| x.is_finite()));
+            assert_eq!(angles.y, 0.);
+            assert_same_rotation_of_basis(Quaternion::from_euler_yxz(angles), q);
+        }
+    }
+    #[test]
+    fn f64_round_trip() {
+        let q = Quaternion::<f64>::rotation_3d(3., Vec3::new(0.6, -0.8, 0.));
+        let r = Quaternion::from_mat4(Mat4::from(q));
+        for &(a, b) in &[(q.x, r.x), (q.y, r.y), (q.z, r.z), (q.w, r.w)] {
+            assert!((a - b).abs() <= 1e-12);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use super::{Rgb, Rgba};
+
+/// Conversions between linear and sRGB-encoded colors, with the exact piecewise IEC 61966-2-1 curve.
+///
+/// The `approx_` variants use a pure 2.2 gamma instead, which is cheaper but off in the darks.
+/// `Rgba` conversions leave alpha untouched, since alpha is always linear.
+pub trait SrgbConversion {
+    fn linear_to_srgb(self) -> Self;
+    fn srgb_to_linear(self) -> Self;
+    fn approx_linear_to_srgb(self) -> Self;
+    fn approx_srgb_to_linear(self) -> Self;
+}
+
+trait SrgbTransfer {
+    fn linear_to_srgb(self) -> Self;
+    fn srgb_to_linear(self) -> Self;
+}
+
+fn with_rgb<T, F: FnOnce(Rgb<T>) -> Rgb<T>>(c: Rgba<T>, f: F) -> Rgba<T> {
+    let Rgba { r, g, b, a } = c;
+    let Rgb { r, g, b } = f(Rgb { r, g, b });
+    Rgba { r, g, b, a }
+}
+
+macro_rules! impl_srgb_conversion {
+    ($($T:ident)+) => {
+        $(
+            impl SrgbTransfer for $T {
+                fn linear_to_srgb(self) -> $T {
+                    if self <= 0.0031308 {
+                        self * 12.92
+                    } else {
+                        1.055 * self.powf(1. / 2.4) - 0.055
+                    }
+                }
+                fn srgb_to_linear(self) -> $T {
+                    if self <= 0.04045 {
+                        self / 12.92
+                    } else {
+                        ((self + 0.055) / 1.055).powf(2.4)
+                    }
+                }
+            }
+            impl SrgbConversion for Rgb<$T> {
+                fn linear_to_srgb(self) -> Self { self.map(<$T as SrgbTransfer>::linear_to_srgb) }
+                fn srgb_to_linear(self) -> Self { self.map(<$T as SrgbTransfer>::srgb_to_linear) }
+                fn approx_linear_to_srgb(self) -> Self { self.map(|x: $T| x.powf(1. / 2.2)) }
+                fn approx_srgb_to_linear(self) -> Self { self.map(|x: $T| x.powf(2.2)) }
+            }
+            impl SrgbConversion for Rgba<$T> {
+                fn linear_to_srgb(self) -> Self { with_rgb(self, Rgb::linear_to_srgb) }
+                fn srgb_to_linear(self) -> Self { with_rgb(self, Rgb::srgb_to_linear) }
+                fn approx_linear_to_srgb(self) -> Self { with_rgb(self, Rgb::approx_linear_to_srgb) }
+                fn approx_srgb_to_linear(self) -> Self { with_rgb(self, Rgb::approx_srgb_to_linear) }
+            }
+        )+
+    };
+}
+
+impl_srgb_conversion!{f32 f64}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: f32, b: f32, epsilon: f32) {
+        assert!((a - b).abs() <= epsilon, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn breakpoints_are_continuous() {
+        let below = Rgb::broadcast(0.0031308_f32).linear_to_srgb();
+        let above = Rgb::broadcast(0.0031309_f32).linear_to_srgb();
+        assert_approx_eq(below.r, 0.04045, 1e-5);
+        assert_approx_eq(above.r, 0.04045, 1e-5);
+        let below = Rgb::broadcast(0.04045_f32).srgb_to_linear();
+        let above = Rgb::broadcast(0.04046_f32).srgb_to_linear();
+        assert_approx_eq(below.r, 0.0031308, 1e-6);
+        assert_approx_eq(above.r, 0.0031308, 1e-6);
+    }
+    #[test]
+    fn known_values_and_round_trips() {
+        let c = Rgb::new(0_f32, 0.5, 1.).srgb_to_linear();
+        assert_eq!((c.r, c.b), (0., 1.));
+        assert_approx_eq(c.g, 0.21404, 1e-5);
+        for i in 0..101 {
+            let x = i as f32 / 100.;
+            let c = Rgba::new(x, x / 2., 1. - x, 0.25);
+            let back = c.linear_to_srgb().srgb_to_linear();
+            assert_eq!(c.linear_to_srgb().a, 0.25);
+            for &(a, b) in &[(c.r, back.r), (c.g, back.g), (c.b, back.b), (c.a, back.a)] {
+                assert_approx_eq(a, b, 1e-6);
+            }
+        }
+        // The approximation is close in the mid-tones
+        assert_approx_eq(Rgb::broadcast(0.5_f32).approx_srgb_to_linear().r, 0.21404, 0.01);
+    }
+}